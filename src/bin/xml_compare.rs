@@ -0,0 +1,572 @@
+use clap::{Parser, ValueEnum};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use xml_compare_core::{
+    render_junit, render_markdown, render_markdown_batch, render_sarif, ComparisonOptions, DiffSeverity, DiffType, JunitCase, JunitOutcome, OutputFormat, SarifComparison,
+    XmlComparisonResponse, XmlComparisonService, XmlDiff,
+};
+
+/// Compares two XML files, or two directories of them, with the comparison options of the API.
+/// Exits with 0 when everything matches, 1 when something differs and 2 on errors.
+#[derive(Parser, Debug)]
+#[command(name = "xml-compare", version)]
+struct Cli {
+    /// Expected file or directory
+    left: PathBuf,
+    /// Actual file or directory
+    right: PathBuf,
+    /// JSON file of comparison options, shaped like the `options` object of API requests
+    #[arg(long, value_name = "FILE")]
+    options: Option<PathBuf>,
+    /// Path to skip, e.g. `/root/timestamp` or `//id`; repeatable
+    #[arg(long = "ignore-path", value_name = "PATH")]
+    ignore_paths: Vec<String>,
+    /// Attribute name to skip; repeatable
+    #[arg(long = "ignore-property", value_name = "NAME")]
+    ignore_properties: Vec<String>,
+    /// Match sibling elements regardless of their order
+    #[arg(long)]
+    ignore_order: bool,
+    /// Compare only the Body payloads of SOAP envelopes
+    #[arg(long)]
+    soap: bool,
+    #[arg(long, value_enum, default_value_t = Format::Summary)]
+    format: Format,
+    /// Extension of the files compared when the inputs are directories
+    #[arg(long, default_value = "xml", value_name = "EXT")]
+    extension: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// The comparison result as the API returns it
+    Json,
+    /// Unified diff of the canonical, pretty-printed documents
+    Diff,
+    /// One line per file and one per difference
+    Summary,
+    /// JUnit XML report with a test case per file pair, for CI test report pages
+    Junit,
+    /// SARIF log with a finding per difference, for code-scanning pages
+    Sarif,
+    /// Markdown summary with a table of differences, for pull-request comments
+    Markdown,
+}
+
+/// Outcome of one file pair in a directory comparison
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum FileOutcome {
+    Compared { result: Box<XmlComparisonResponse> },
+    OnlyLeft,
+    OnlyRight,
+    Error { error: String },
+}
+
+#[derive(Debug, Serialize)]
+struct FileComparison {
+    path: String,
+    #[serde(flatten)]
+    outcome: FileOutcome,
+}
+
+#[derive(Debug, Serialize)]
+struct DirectoryComparison {
+    matched: bool,
+    files: Vec<FileComparison>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verdict {
+    Matched,
+    Differs,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let mut stdout = io::stdout().lock();
+    match run(&cli, &mut stdout) {
+        Ok(Verdict::Matched) => ExitCode::SUCCESS,
+        Ok(Verdict::Differs) => ExitCode::from(1),
+        Err(e) => {
+            let _ = stdout.flush();
+            eprintln!("xml-compare: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(cli: &Cli, out: &mut impl Write) -> Result<Verdict, String> {
+    let options = load_options(cli)?;
+    let service = XmlComparisonService::new();
+    if cli.left.is_dir() && cli.right.is_dir() {
+        compare_directories(cli, &service, &options, out)
+    } else if cli.left.is_dir() || cli.right.is_dir() {
+        Err("cannot compare a file with a directory".to_string())
+    } else {
+        compare_single_files(cli, &service, &options, out)
+    }
+}
+
+// Flags add to the lists from the options file
+fn load_options(cli: &Cli) -> Result<ComparisonOptions, String> {
+    let mut options: ComparisonOptions = match &cli.options {
+        Some(path) => serde_json::from_str(&read(path)?)
+            .map_err(|e| format!("invalid options in {}: {}", path.display(), e))?,
+        None => ComparisonOptions::default(),
+    };
+    if !cli.ignore_paths.is_empty() {
+        options.ignore_paths.get_or_insert_with(Vec::new).extend(cli.ignore_paths.iter().cloned());
+    }
+    if !cli.ignore_properties.is_empty() {
+        options.ignore_properties.get_or_insert_with(Vec::new).extend(cli.ignore_properties.iter().cloned());
+    }
+    if cli.ignore_order {
+        options.ignore_element_order = Some(true);
+    }
+    if cli.soap {
+        options.soap = Some(true);
+    }
+    if cli.format == Format::Diff {
+        options.output_format = Some(OutputFormat::Unified);
+    }
+    Ok(options)
+}
+
+fn compare_single_files(
+    cli: &Cli,
+    service: &XmlComparisonService,
+    options: &ComparisonOptions,
+    out: &mut impl Write,
+) -> Result<Verdict, String> {
+    let result = compare_files(service, &cli.left, &cli.right, options)?;
+    let verdict = if result.matched { Verdict::Matched } else { Verdict::Differs };
+    let label = format!("{} vs {}", cli.left.display(), cli.right.display());
+    match cli.format {
+        Format::Json => write_json(out, &result)?,
+        Format::Diff => write_diff(out, &result, &cli.left.display().to_string(), &cli.right.display().to_string())?,
+        Format::Summary => write_summary(out, &label, &result)?,
+        Format::Junit => {
+            let case = JunitCase { name: label, outcome: JunitOutcome::Compared(&result), time: None };
+            write_junit(out, &[case])?
+        }
+        Format::Sarif => write_sarif(out, &[(cli.left.clone(), cli.right.clone(), &result)], &[])?,
+        Format::Markdown => write!(out, "{}", render_markdown(&label, &result)).map_err(|e| e.to_string())?,
+    }
+    Ok(verdict)
+}
+
+// Files are paired by their path relative to each directory
+fn compare_directories(
+    cli: &Cli,
+    service: &XmlComparisonService,
+    options: &ComparisonOptions,
+    out: &mut impl Write,
+) -> Result<Verdict, String> {
+    let left = list_files(&cli.left, &cli.extension)?;
+    let right = list_files(&cli.right, &cli.extension)?;
+    let files: Vec<FileComparison> = left
+        .union(&right)
+        .map(|path| {
+            let outcome = match (left.contains(path), right.contains(path)) {
+                (true, false) => FileOutcome::OnlyLeft,
+                (false, true) => FileOutcome::OnlyRight,
+                _ => match compare_files(service, &cli.left.join(path), &cli.right.join(path), options) {
+                    Ok(result) => FileOutcome::Compared { result: Box::new(result) },
+                    Err(error) => FileOutcome::Error { error },
+                },
+            };
+            FileComparison { path: path.to_string_lossy().replace('\\', "/"), outcome }
+        })
+        .collect();
+
+    let matched = files
+        .iter()
+        .all(|file| matches!(&file.outcome, FileOutcome::Compared { result } if result.matched));
+    let comparison = DirectoryComparison { matched, files };
+    match cli.format {
+        Format::Json => write_json(out, &comparison)?,
+        Format::Diff => write_directory_diff(out, cli, &comparison)?,
+        Format::Summary => write_directory_summary(out, &comparison)?,
+        Format::Junit => write_directory_junit(out, &comparison)?,
+        Format::Sarif => write_directory_sarif(out, cli, &comparison)?,
+        Format::Markdown => write_directory_markdown(out, cli, &comparison)?,
+    }
+
+    let errors: Vec<_> = comparison
+        .files
+        .iter()
+        .filter_map(|file| match &file.outcome {
+            FileOutcome::Error { error } => Some(format!("{}: {}", file.path, error)),
+            _ => None,
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    Ok(if comparison.matched { Verdict::Matched } else { Verdict::Differs })
+}
+
+fn compare_files(
+    service: &XmlComparisonService,
+    left: &Path,
+    right: &Path,
+    options: &ComparisonOptions,
+) -> Result<XmlComparisonResponse, String> {
+    service.compare_with_options(&read(left)?, &read(right)?, options).map_err(|e| e.to_string())
+}
+
+fn read(path: &Path) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+}
+
+// Relative paths of the files below `root` with the given extension, in sorted order
+fn list_files(root: &Path, extension: &str) -> Result<BTreeSet<PathBuf>, String> {
+    let mut files = BTreeSet::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = std::fs::read_dir(&dir).map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| format!("cannot read {}: {}", dir.display(), e))?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(extension))
+                && let Ok(relative) = path.strip_prefix(root)
+            {
+                files.insert(relative.to_path_buf());
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn write_json<T: Serialize>(out: &mut impl Write, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    writeln!(out, "{}", json).map_err(|e| e.to_string())
+}
+
+// The service labels the documents xml1/xml2; the CLI names the files instead
+fn write_diff(out: &mut impl Write, result: &XmlComparisonResponse, left: &str, right: &str) -> Result<(), String> {
+    let Some(diff) = result.unified_diff.as_deref() else { return Ok(()) };
+    let body = diff.strip_prefix("--- xml1\n+++ xml2\n").unwrap_or(diff);
+    if body.is_empty() {
+        return Ok(());
+    }
+    write!(out, "--- {}\n+++ {}\n{}", left, right, body).map_err(|e| e.to_string())
+}
+
+fn write_directory_diff(out: &mut impl Write, cli: &Cli, comparison: &DirectoryComparison) -> Result<(), String> {
+    for file in &comparison.files {
+        match &file.outcome {
+            FileOutcome::Compared { result } => write_diff(
+                out,
+                result,
+                &cli.left.join(&file.path).display().to_string(),
+                &cli.right.join(&file.path).display().to_string(),
+            )?,
+            FileOutcome::OnlyLeft => {
+                writeln!(out, "Only in {}: {}", cli.left.display(), file.path).map_err(|e| e.to_string())?
+            }
+            FileOutcome::OnlyRight => {
+                writeln!(out, "Only in {}: {}", cli.right.display(), file.path).map_err(|e| e.to_string())?
+            }
+            FileOutcome::Error { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+fn write_junit(out: &mut impl Write, cases: &[JunitCase]) -> Result<(), String> {
+    write!(out, "{}", render_junit("xml-compare", cases)).map_err(|e| e.to_string())
+}
+
+fn write_directory_junit(out: &mut impl Write, comparison: &DirectoryComparison) -> Result<(), String> {
+    let cases: Vec<_> = comparison
+        .files
+        .iter()
+        .map(|file| JunitCase {
+            name: file.path.clone(),
+            outcome: match &file.outcome {
+                FileOutcome::Compared { result } => JunitOutcome::Compared(result),
+                FileOutcome::OnlyLeft => JunitOutcome::Failed("Only in left".to_string()),
+                FileOutcome::OnlyRight => JunitOutcome::Failed("Only in right".to_string()),
+                FileOutcome::Error { error } => JunitOutcome::Error(error.clone()),
+            },
+            time: None,
+        })
+        .collect();
+    write_junit(out, &cases)
+}
+
+// Findings point at the files, with lines when they can be read again
+fn write_sarif(
+    out: &mut impl Write,
+    pairs: &[(PathBuf, PathBuf, &XmlComparisonResponse)],
+    errors: &[String],
+) -> Result<(), String> {
+    let documents: Vec<_> =
+        pairs.iter().map(|(left, right, _)| (std::fs::read_to_string(left).ok(), std::fs::read_to_string(right).ok())).collect();
+    let comparisons: Vec<_> = pairs
+        .iter()
+        .zip(&documents)
+        .map(|((left, right, result), (xml1, xml2))| SarifComparison {
+            result,
+            uri1: Some(left.to_string_lossy().replace('\\', "/")),
+            uri2: Some(right.to_string_lossy().replace('\\', "/")),
+            xml1: xml1.as_deref(),
+            xml2: xml2.as_deref(),
+        })
+        .collect();
+    writeln!(out, "{}", render_sarif(&comparisons, errors)).map_err(|e| e.to_string())
+}
+
+// A file on one side only is reported as its root element missing or extra
+fn unpaired_result(diff_type: DiffType, message: &str) -> XmlComparisonResponse {
+    XmlComparisonResponse {
+        matched: false,
+        diffs: vec![XmlDiff {
+            path: "/".to_string(),
+            diff_type,
+            expected: None,
+            actual: None,
+            message: message.to_string(),
+            similarity: None,
+            severity: DiffSeverity::Error,
+            tags: Vec::new(),
+            node: None,
+        }],
+        ..Default::default()
+    }
+}
+
+fn write_directory_sarif(out: &mut impl Write, cli: &Cli, comparison: &DirectoryComparison) -> Result<(), String> {
+    let only_left = unpaired_result(DiffType::ElementMissing, "File only in left");
+    let only_right = unpaired_result(DiffType::ElementExtra, "File only in right");
+    let mut pairs = Vec::new();
+    let mut errors = Vec::new();
+    for file in &comparison.files {
+        let result = match &file.outcome {
+            FileOutcome::Compared { result } => result.as_ref(),
+            FileOutcome::OnlyLeft => &only_left,
+            FileOutcome::OnlyRight => &only_right,
+            FileOutcome::Error { error } => {
+                errors.push(format!("{}: {}", file.path, error));
+                continue;
+            }
+        };
+        pairs.push((cli.left.join(&file.path), cli.right.join(&file.path), result));
+    }
+    write_sarif(out, &pairs, &errors)
+}
+
+fn write_directory_markdown(out: &mut impl Write, cli: &Cli, comparison: &DirectoryComparison) -> Result<(), String> {
+    let only_left = unpaired_result(DiffType::ElementMissing, "File only in left");
+    let only_right = unpaired_result(DiffType::ElementExtra, "File only in right");
+    let items: Vec<_> = comparison
+        .files
+        .iter()
+        .map(|file| {
+            let item = match &file.outcome {
+                FileOutcome::Compared { result } => Ok(result.as_ref()),
+                FileOutcome::OnlyLeft => Ok(&only_left),
+                FileOutcome::OnlyRight => Ok(&only_right),
+                FileOutcome::Error { error } => Err(error.clone()),
+            };
+            (file.path.clone(), item)
+        })
+        .collect();
+    let title = format!("{} vs {}", cli.left.display(), cli.right.display());
+    write!(out, "{}", render_markdown_batch(&title, &items)).map_err(|e| e.to_string())
+}
+
+fn write_summary(out: &mut impl Write, label: &str, result: &XmlComparisonResponse) -> Result<(), String> {
+    let verdict = if result.matched { "match" } else { "differ" };
+    writeln!(
+        out,
+        "{}: {} ({} diffs, {}/{} elements matched{})",
+        label,
+        verdict,
+        result.diffs.len(),
+        result.matched_elements,
+        result.total_elements,
+        if result.truncated { ", truncated" } else { "" }
+    )
+    .map_err(|e| e.to_string())?;
+    for diff in &result.diffs {
+        writeln!(out, "  {}: {}", diff.path, diff.message).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn write_directory_summary(out: &mut impl Write, comparison: &DirectoryComparison) -> Result<(), String> {
+    let (mut matched, mut differ, mut missing, mut errors) = (0, 0, 0, 0);
+    for file in &comparison.files {
+        match &file.outcome {
+            FileOutcome::Compared { result } => {
+                if result.matched {
+                    matched += 1;
+                } else {
+                    differ += 1;
+                    write_summary(out, &file.path, result)?;
+                }
+            }
+            FileOutcome::OnlyLeft => {
+                missing += 1;
+                writeln!(out, "{}: only in left", file.path).map_err(|e| e.to_string())?;
+            }
+            FileOutcome::OnlyRight => {
+                missing += 1;
+                writeln!(out, "{}: only in right", file.path).map_err(|e| e.to_string())?;
+            }
+            FileOutcome::Error { error } => {
+                errors += 1;
+                writeln!(out, "{}: error: {}", file.path, error).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    writeln!(
+        out,
+        "{} files: {} match, {} differ, {} unpaired, {} errors",
+        comparison.files.len(),
+        matched,
+        differ,
+        missing,
+        errors
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xml-compare-{}", uuid::Uuid::new_v4()));
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        dir
+    }
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::parse_from(std::iter::once("xml-compare").chain(args.iter().copied()))
+    }
+
+    fn run_to_string(cli: &Cli) -> (Result<Verdict, String>, String) {
+        let mut out = Vec::new();
+        let verdict = run(cli, &mut out);
+        (verdict, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_compare_files() {
+        let dir = temp_dir(&[
+            ("a.xml", "<root><id>1</id><v>x</v></root>"),
+            ("b.xml", "<root><id>2</id><v>y</v></root>"),
+        ]);
+        let (left, right) = (dir.join("a.xml"), dir.join("b.xml"));
+        let (left, right) = (left.to_str().unwrap(), right.to_str().unwrap());
+
+        let (verdict, output) = run_to_string(&cli(&[left, right]));
+        assert_eq!(verdict, Ok(Verdict::Differs));
+        assert!(output.starts_with(&format!("{} vs {}: differ (2 diffs", left, right)));
+
+        let (verdict, output) = run_to_string(&cli(&[left, right, "--ignore-path", "/root/id", "--format", "diff"]));
+        assert_eq!(verdict, Ok(Verdict::Differs));
+        assert!(output.starts_with(&format!("--- {}\n+++ {}\n@@", left, right)));
+        assert!(output.contains("\n-  <v>x</v>\n") && output.contains("\n+  <v>y</v>\n"));
+
+        let options = dir.join("options.json");
+        std::fs::write(&options, r#"{ "ignore_paths": ["/root/id", "/root/v"] }"#).unwrap();
+        let (verdict, output) = run_to_string(&cli(&[left, right, "--options", options.to_str().unwrap(), "--format", "json"]));
+        assert_eq!(verdict, Ok(Verdict::Matched));
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&output).unwrap()["matched"], true);
+
+        let soap = dir.join("soap.xml");
+        std::fs::write(&soap, r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body><root><id>1</id><v>x</v></root></s:Body></s:Envelope>"#).unwrap();
+        assert_eq!(run_to_string(&cli(&[soap.to_str().unwrap(), left, "--soap"])).0, Ok(Verdict::Matched));
+
+        let (verdict, _) = run_to_string(&cli(&[left, dir.join("missing.xml").to_str().unwrap()]));
+        assert!(verdict.unwrap_err().starts_with("cannot read"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_compare_directories() {
+        let left = temp_dir(&[
+            ("same.xml", "<a>1</a>"),
+            ("nested/changed.xml", "<a>1</a>"),
+            ("left-only.xml", "<a/>"),
+            ("notes.txt", "ignored"),
+        ]);
+        let right = temp_dir(&[("same.xml", "<a>1</a>"), ("nested/changed.xml", "<a>2</a>"), ("right-only.xml", "<a/>")]);
+        let (left_arg, right_arg) = (left.to_str().unwrap(), right.to_str().unwrap());
+
+        let (verdict, output) = run_to_string(&cli(&[left_arg, right_arg, "--format", "json"]));
+        assert_eq!(verdict, Ok(Verdict::Differs));
+        let json: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let statuses: Vec<_> = json["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|file| (file["path"].as_str().unwrap(), file["status"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("left-only.xml", "only_left"),
+                ("nested/changed.xml", "compared"),
+                ("right-only.xml", "only_right"),
+                ("same.xml", "compared"),
+            ]
+        );
+
+        let (_, output) = run_to_string(&cli(&[left_arg, right_arg]));
+        assert!(output.ends_with("4 files: 1 match, 1 differ, 2 unpaired, 0 errors\n"));
+
+        let (verdict, output) = run_to_string(&cli(&[left_arg, right_arg, "--format", "junit"]));
+        assert_eq!(verdict, Ok(Verdict::Differs));
+        assert!(output.contains("<testsuite name=\"xml-compare\" tests=\"4\" failures=\"3\" errors=\"0\" skipped=\"0\">"));
+        assert!(output.contains("<testcase classname=\"xml-compare\" name=\"left-only.xml\">\n      <failure message=\"Only in left\"/>"));
+        assert!(output.contains("<testcase classname=\"xml-compare\" name=\"same.xml\"/>"));
+
+        let (_, output) = run_to_string(&cli(&[left_arg, right_arg, "--format", "sarif"]));
+        let sarif: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let findings: Vec<_> = sarif["runs"][0]["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| {
+                let uri = result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"].as_str().unwrap();
+                (result["ruleId"].as_str().unwrap(), uri.rsplit('/').next().unwrap().to_string())
+            })
+            .collect();
+        assert_eq!(
+            findings,
+            [
+                ("ElementMissing", "left-only.xml".to_string()),
+                ("ContentDifferent", "changed.xml".to_string()),
+                ("ElementExtra", "right-only.xml".to_string()),
+            ]
+        );
+        assert_eq!(sarif["runs"][0]["results"][1]["locations"][0]["physicalLocation"]["region"]["startLine"], 1);
+
+        let (_, output) = run_to_string(&cli(&[left_arg, right_arg, "--format", "markdown"]));
+        assert!(output.starts_with(&format!("## {} vs {}: ❌ 3 of 4 differ\n", left_arg, right_arg)));
+        assert!(output.contains("| same.xml | ✅ match | 100.0% | 0 |\n"));
+        assert!(output.contains("\n### left-only.xml\n"));
+
+        std::fs::write(right.join("same.xml"), "<a></b>").unwrap();
+        let (verdict, _) = run_to_string(&cli(&[left_arg, right_arg]));
+        assert!(verdict.unwrap_err().starts_with("same.xml: "));
+
+        assert!(run_to_string(&cli(&[left.join("same.xml").to_str().unwrap(), right_arg])).0.is_err());
+        std::fs::remove_dir_all(left).unwrap();
+        std::fs::remove_dir_all(right).unwrap();
+    }
+}
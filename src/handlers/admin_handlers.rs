@@ -0,0 +1,71 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::models::{AppResult, JwtClaims, SessionListResponse, TenantUsage};
+
+/// List active upstream sessions, without their cookies
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/admin/sessions",
+    params(
+        ("X-Admin-Token" = String, Header, description = "Value of APP_ADMIN_TOKEN")
+    ),
+    responses(
+        (status = 200, description = "Session store contents", body = SessionListResponse),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "Admin endpoints are disabled"),
+        (status = 500, description = "Session store unavailable")
+    ),
+    tag = "Admin"
+)]
+pub async fn list_sessions(State(state): State<AppState>) -> AppResult<Json<SessionListResponse>> {
+    Ok(Json(state.auth_service.list_sessions().await?))
+}
+
+/// Force-expire an upstream session
+#[utoipa::path(
+    delete,
+    path = "/xml-compare-api/api/admin/sessions/{session_id}",
+    params(
+        ("session_id" = String, Path, description = "Session to expire"),
+        ("X-Admin-Token" = String, Header, description = "Value of APP_ADMIN_TOKEN")
+    ),
+    responses(
+        (status = 204, description = "Session removed"),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "Admin endpoints are disabled"),
+        (status = 404, description = "Session not found")
+    ),
+    tag = "Admin"
+)]
+pub async fn expire_session(
+    State(state): State<AppState>,
+    claims: Option<Extension<JwtClaims>>,
+    Path(session_id): Path<String>,
+) -> AppResult<StatusCode> {
+    state.auth_service.expire_session(&session_id).await?;
+    let subject = claims.as_ref().map_or("admin token", |Extension(claims)| claims.sub.as_str());
+    tracing::info!(session_id = %session_id, subject = %subject, "Session force-expired");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Limits of every tenant and what each has used since the server started
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/admin/tenants",
+    params(
+        ("X-Admin-Token" = String, Header, description = "Value of APP_ADMIN_TOKEN")
+    ),
+    responses(
+        (status = 200, description = "Tenants in the order of the tenants file; empty without one", body = Vec<TenantUsage>),
+        (status = 401, description = "Missing or wrong admin token"),
+        (status = 403, description = "Admin endpoints are disabled")
+    ),
+    tag = "Admin"
+)]
+pub async fn tenant_usage(State(state): State<AppState>) -> Json<Vec<TenantUsage>> {
+    Json(state.tenants.usage())
+}
@@ -46,4 +46,25 @@ pub async fn logout(
 ) -> AppResult<Json<()>> {
     state.auth_service.logout(&session_id).await?;
     Ok(Json(()))
-}
\ No newline at end of file
+}
+/// Extend a session, by its keep-alive URL or by logging in again
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/auth/refresh/{session_id}",
+    params(
+        ("session_id" = String, Path, description = "Session ID to refresh")
+    ),
+    responses(
+        (status = 200, description = "Session extended", body = LoginResponse),
+        (status = 401, description = "Logging in again failed"),
+        (status = 404, description = "Session not found"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Authentication"
+)]
+pub async fn refresh(
+    State(state): State<AppState>,
+    axum::extract::Path(session_id): axum::extract::Path<String>,
+) -> AppResult<Json<LoginResponse>> {
+    Ok(Json(state.auth_service.refresh(&session_id).await?))
+}
@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use crate::models::{AppResult, Baseline, BaselineComparisonRequest, BaselineComparisonResponse, XmlComparisonResponse};
+use crate::handlers::comparison_handlers::AppState;
+use crate::middleware::{record_comparison, ComparisonPermit, RecordedComparison};
+use crate::services::classify_diffs;
+
+/// List stored baselines
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/baselines",
+    responses(
+        (status = 200, description = "Stored baselines", body = Vec<Baseline>)
+    ),
+    tag = "Baselines"
+)]
+pub async fn list_baselines(State(state): State<AppState>) -> Json<Vec<Baseline>> {
+    Json(state.baseline_service.list().await)
+}
+
+/// Get a baseline by name
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/baselines/{name}",
+    params(
+        ("name" = String, Path, description = "Baseline name")
+    ),
+    responses(
+        (status = 200, description = "Baseline found", body = Baseline),
+        (status = 404, description = "Baseline not found")
+    ),
+    tag = "Baselines"
+)]
+pub async fn get_baseline(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> AppResult<Json<Baseline>> {
+    let baseline = state.baseline_service.get(&name).await?;
+    Ok(Json(baseline))
+}
+
+/// Store a baseline document
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/baselines",
+    request_body = Baseline,
+    responses(
+        (status = 200, description = "Baseline created", body = Baseline),
+        (status = 400, description = "Invalid baseline"),
+        (status = 409, description = "Baseline already exists")
+    ),
+    tag = "Baselines"
+)]
+pub async fn create_baseline(
+    State(state): State<AppState>,
+    Json(baseline): Json<Baseline>,
+) -> AppResult<Json<Baseline>> {
+    validate_baseline(&state, &baseline)?;
+    let baseline = state.baseline_service.create(baseline).await?;
+    Ok(Json(baseline))
+}
+
+/// Replace a baseline's document, options or accepted differences
+#[utoipa::path(
+    put,
+    path = "/xml-compare-api/api/baselines/{name}",
+    params(
+        ("name" = String, Path, description = "Baseline name")
+    ),
+    request_body = Baseline,
+    responses(
+        (status = 200, description = "Baseline updated", body = Baseline),
+        (status = 400, description = "Invalid baseline"),
+        (status = 404, description = "Baseline not found")
+    ),
+    tag = "Baselines"
+)]
+pub async fn update_baseline(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(baseline): Json<Baseline>,
+) -> AppResult<Json<Baseline>> {
+    validate_baseline(&state, &baseline)?;
+    let baseline = state.baseline_service.update(&name, baseline).await?;
+    Ok(Json(baseline))
+}
+
+/// Delete a baseline
+#[utoipa::path(
+    delete,
+    path = "/xml-compare-api/api/baselines/{name}",
+    params(
+        ("name" = String, Path, description = "Baseline name")
+    ),
+    responses(
+        (status = 200, description = "Baseline deleted"),
+        (status = 404, description = "Baseline not found")
+    ),
+    tag = "Baselines"
+)]
+pub async fn delete_baseline(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> AppResult<Json<()>> {
+    state.baseline_service.delete(&name).await?;
+    Ok(Json(()))
+}
+
+/// Compare a document against a baseline, separating new differences from accepted ones
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/baselines/{name}/compare",
+    params(
+        ("name" = String, Path, description = "Baseline name")
+    ),
+    request_body = BaselineComparisonRequest,
+    responses(
+        (status = 200, description = "New, known and resolved differences", body = BaselineComparisonResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Baseline not found")
+    ),
+    tag = "Baselines"
+)]
+pub async fn compare_baseline(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Path(name): Path<String>,
+    Json(request): Json<BaselineComparisonRequest>,
+) -> AppResult<Json<BaselineComparisonResponse>> {
+    let baseline = state.baseline_service.get(&name).await?;
+    let result = compare_to_baseline(&state, &baseline, request).await?;
+    let classified = classify_diffs(&baseline, result);
+    record_comparison(RecordedComparison {
+        matched: !classified.regression,
+        diffs: classified.new_diffs.len(),
+        match_ratio: classified.match_ratio,
+        urls: Vec::new(),
+        diff_paths: RecordedComparison::diff_paths(&classified.new_diffs),
+    });
+    Ok(Json(classified))
+}
+
+/// Accept every difference a document has from a baseline, replacing the accepted set
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/baselines/{name}/accept",
+    params(
+        ("name" = String, Path, description = "Baseline name")
+    ),
+    request_body = BaselineComparisonRequest,
+    responses(
+        (status = 200, description = "Baseline with the document's differences accepted", body = Baseline),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Baseline not found")
+    ),
+    tag = "Baselines"
+)]
+pub async fn accept_baseline_diffs(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Path(name): Path<String>,
+    Json(request): Json<BaselineComparisonRequest>,
+) -> AppResult<Json<Baseline>> {
+    let baseline = state.baseline_service.get(&name).await?;
+    let result = compare_to_baseline(&state, &baseline, request).await?;
+    let baseline = state.baseline_service.accept(&name, result.diffs).await?;
+    Ok(Json(baseline))
+}
+
+// Request options win over the profile's, which win over the baseline's. Every diff is needed to
+// tell new ones from known ones, so the response-shaping options are dropped.
+async fn compare_to_baseline(
+    state: &AppState,
+    baseline: &Baseline,
+    request: BaselineComparisonRequest,
+) -> AppResult<XmlComparisonResponse> {
+    let options = state
+        .profile_service
+        .resolve_options(request.options, request.profile.as_deref())
+        .await?
+        .or(baseline.options.clone())
+        .without_shaping();
+    Ok(state.xml_service.compare_with_options(&baseline.xml, &request.xml, &options)?)
+}
+
+// Rejects documents that do not parse and options that would fail every comparison
+fn validate_baseline(state: &AppState, baseline: &Baseline) -> AppResult<()> {
+    state.xml_service.compare_with_options(&baseline.xml, &baseline.xml, &baseline.options)?;
+    Ok(())
+}
@@ -1,14 +1,29 @@
 use axum::{
-    extract::State,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
     Json,
 };
 use crate::models::{
-    XmlComparisonRequest, XmlComparisonResponse, UrlComparisonRequest,
-    BatchXmlComparisonRequest, BatchUrlComparisonRequest, BatchComparisonResponse,
-    AppError, AppResult,
+    XmlComparisonRequest, XmlComparisonResponse, UrlComparisonRequest, UrlComparisonQuery,
+    BatchXmlComparisonRequest, BatchUrlComparisonRequest, BatchComparisonResponse, BatchCallbackPayload, BatchSummary,
+    XmlJsonComparisonRequest, JsonComparisonRequest, DocumentComparisonRequest, DocumentComparisonResponse,
+    GoldenComparisonRequest, GoldenComparisonResponse, CandidateResult, CandidateRank,
+    MixedComparisonRequest, DocumentInput, ManifestComparisonRequest, EnvironmentComparisonRequest, StoredBatchItem,
+    CacheStats, DocumentSource, DownloadRequest, FetchOptions, AppError, AppResult,
 };
-use crate::services::{XmlComparisonService, HttpClientService};
+use crate::services::{
+    XmlComparisonService, JsonComparisonService, HttpClientService, WebhookService, ResultCache, DiffEngine,
+    json_to_xml, compare_documents, result_cache_key, parse_manifest, environment_url, index_paths, JobGuard, AlertItem,
+};
+use xml_compare_core::{
+    render_junit, render_markdown, render_markdown_batch, render_sarif, JunitCase, JunitOutcome, OutputFormat, SarifComparison,
+};
+use crate::middleware::{in_tenant_scope, record_comparison, ComparisonPermit};
+use crate::utils::validation::validate_url;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 
 pub type AppState = Arc<AppStateInner>;
@@ -16,8 +31,30 @@ pub type AppState = Arc<AppStateInner>;
 #[derive(Clone)]
 pub struct AppStateInner {
     pub xml_service: XmlComparisonService,
+    pub json_service: JsonComparisonService,
     pub http_client: Arc<HttpClientService>,
     pub auth_service: Arc<crate::services::AuthService>,
+    pub profile_service: Arc<crate::services::ProfileService>,
+    pub baseline_service: Arc<crate::services::BaselineService>,
+    pub webhook_service: WebhookService,
+    /// Notifies chat and email when callback batches find differences
+    pub alerts: crate::services::AlertService,
+    pub result_cache: ResultCache,
+    /// Set when API routes require a bearer token
+    pub jwt_auth: Option<Arc<crate::services::JwtAuthService>>,
+    /// Token the admin routes require; they are disabled without one
+    pub admin_token: Option<String>,
+    /// Teams whose API keys the API routes require; empty when tenants are not configured
+    pub tenants: Arc<crate::services::TenantService>,
+    pub health_service: Arc<crate::services::HealthService>,
+    pub limits: crate::middleware::Limits,
+    /// Threads in-process batch items are compared on
+    pub batch_pool: crate::services::BatchPool,
+    pub jobs: crate::services::JobRegistry,
+    /// Where finished batches are kept for later retrieval
+    pub result_archive: crate::services::ResultArchive,
+    /// Where a summary of each comparison is kept for review
+    pub history: crate::services::HistoryService,
 }
 
 /// Compare two XML contents
@@ -25,8 +62,13 @@ pub struct AppStateInner {
     post,
     path = "/xml-compare-api/api/compare/xml",
     request_body = XmlComparisonRequest,
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response for the same request")
+    ),
     responses(
-        (status = 200, description = "XML comparison completed", body = XmlComparisonResponse),
+        (status = 200, description = "XML comparison completed", content(("application/json" = XmlComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String)),
+            headers(("ETag" = String), ("X-Cache" = String, description = "HIT or MISS"))),
+        (status = 304, description = "Same request as the given ETag"),
         (status = 400, description = "Invalid request"),
         (status = 500, description = "Internal server error")
     ),
@@ -34,9 +76,213 @@ pub struct AppStateInner {
 )]
 pub async fn compare_xmls(
     State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    headers: HeaderMap,
+    Json(request): Json<XmlComparisonRequest>,
+) -> AppResult<Response> {
+    let request = state.profile_service.resolve(request).await?;
+    let options = request.effective_options();
+    // The key covers everything the result depends on, so it doubles as the ETag
+    let etag = format!("\"{}\"", result_cache_key(&request.xml1, &request.xml2, &options));
+    if etag_matches(&headers, &etag) {
+        state.result_cache.record_not_modified();
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    let (result, cache_status) = match state.result_cache.get(&etag) {
+        Some(result) => (result, "HIT"),
+        None => {
+            let result = state.xml_service.compare_with_options(&request.xml1, &request.xml2, &options)?;
+            state.result_cache.insert(etag.clone(), result.clone());
+            (result, "MISS")
+        }
+    };
+    record_comparison(&result);
+    let headers = [(header::ETAG, etag), (header::HeaderName::from_static("x-cache"), cache_status.to_string())];
+    Ok((headers, comparison_response(result, Some((&request.xml1, &request.xml2)))).into_response())
+}
+
+// The result as JSON, or as the report `output_format` asks for; with the documents, SARIF
+// findings get their lines
+fn comparison_response(result: XmlComparisonResponse, documents: Option<(&str, &str)>) -> Response {
+    match result.output_format {
+        Some(OutputFormat::Junit) => {
+            let case = JunitCase { name: item_name(&result, 0), outcome: JunitOutcome::Compared(&result), time: None };
+            junit_response(render_junit("xml-compare", &[case]))
+        }
+        Some(OutputFormat::Sarif) => sarif_response(render_sarif(&[sarif_comparison(&result, documents)], &[])),
+        Some(OutputFormat::Markdown) => markdown_response(render_markdown("XML comparison", &result)),
+        _ => Json(result).into_response(),
+    }
+}
+
+fn junit_response(report: String) -> Response {
+    ([(header::CONTENT_TYPE, "application/xml; charset=utf-8")], report).into_response()
+}
+
+fn markdown_response(markdown: String) -> Response {
+    ([(header::CONTENT_TYPE, "text/markdown; charset=utf-8")], markdown).into_response()
+}
+
+fn sarif_response(log: String) -> Response {
+    ([(header::CONTENT_TYPE, "application/sarif+json")], log).into_response()
+}
+
+// Downloaded documents are located by their URLs
+fn sarif_comparison<'a>(result: &'a XmlComparisonResponse, documents: Option<(&'a str, &'a str)>) -> SarifComparison<'a> {
+    let (uri1, uri2) = match result.sources.as_slice() {
+        [source1, source2] => (Some(source1.url.clone()), Some(source2.url.clone())),
+        _ => (None, None),
+    };
+    SarifComparison {
+        result,
+        uri1,
+        uri2,
+        xml1: documents.map(|(xml1, _)| xml1),
+        xml2: documents.map(|(_, xml2)| xml2),
+    }
+}
+
+// The comparison's own ID, else its URLs, else its position
+fn item_name(result: &XmlComparisonResponse, index: usize) -> String {
+    match (&result.id, result.sources.as_slice()) {
+        (Some(id), _) => id.clone(),
+        (None, [source1, source2]) => format!("{} vs {}", source1.url, source2.url),
+        _ => format!("comparison {}", index + 1),
+    }
+}
+
+// `If-None-Match` holds `*` or a comma-separated list of (possibly weak) ETags
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Result cache counters
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/cache/stats",
+    responses(
+        (status = 200, description = "Cache statistics", body = CacheStats)
+    ),
+    tag = "XML Comparison"
+)]
+pub async fn cache_stats(State(state): State<AppState>) -> Json<CacheStats> {
+    Json(state.result_cache.stats())
+}
+
+/// Compare two XML contents and render the result as an HTML report
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/xml/report",
+    request_body = XmlComparisonRequest,
+    responses(
+        (status = 200, description = "Side-by-side HTML diff report", body = String, content_type = "text/html"),
+        (status = 400, description = "Invalid request"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "XML Comparison"
+)]
+pub async fn compare_xmls_report(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
     Json(request): Json<XmlComparisonRequest>,
+) -> AppResult<Html<String>> {
+    let request = state.profile_service.resolve(request).await?;
+    let report = state.xml_service.render_report(&request.xml1, &request.xml2, &request.effective_options())?;
+    Ok(Html(report))
+}
+
+/// Compare an XML document with a JSON document
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/xml-json",
+    request_body = XmlJsonComparisonRequest,
+    responses(
+        (status = 200, description = "XML/JSON comparison completed", body = XmlComparisonResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "XML Comparison"
+)]
+pub async fn compare_xml_json(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<XmlJsonComparisonRequest>,
+) -> AppResult<Json<XmlComparisonResponse>> {
+    let comparison_request = XmlComparisonRequest {
+        xml1: request.xml,
+        xml2: json_to_xml(&request.json, &request.conventions)?,
+        options: request.options,
+        profile: request.profile,
+        ..Default::default()
+    };
+    let comparison_request = state.profile_service.resolve(comparison_request).await?;
+
+    let result = state.xml_service.compare_with_options(
+        &comparison_request.xml1,
+        &comparison_request.xml2,
+        &comparison_request.effective_options(),
+    )?;
+    record_comparison(&result);
+    Ok(Json(result))
+}
+
+/// Compare two JSON documents
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/json",
+    request_body = JsonComparisonRequest,
+    responses(
+        (status = 200, description = "JSON comparison completed", body = XmlComparisonResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "JSON Comparison"
+)]
+pub async fn compare_json(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<JsonComparisonRequest>,
 ) -> AppResult<Json<XmlComparisonResponse>> {
-    let result = state.xml_service.compare_xmls(&request)?;
+    let options = state.profile_service.resolve_options(request.options, request.profile.as_deref()).await?;
+    let result = state.json_service.diff(&request.json1, &request.json2, &options)?;
+    record_comparison(&result);
+    Ok(Json(result))
+}
+
+/// Compare two documents of any supported format (XML, JSON, YAML)
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/document",
+    request_body = DocumentComparisonRequest,
+    responses(
+        (status = 200, description = "Comparison completed; includes the detected formats", body = DocumentComparisonResponse),
+        (status = 400, description = "Invalid request or unrecognized format"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Document Comparison"
+)]
+pub async fn compare_document(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<DocumentComparisonRequest>,
+) -> AppResult<Json<DocumentComparisonResponse>> {
+    let options = state.profile_service.resolve_options(request.options, request.profile.as_deref()).await?;
+    let result = compare_documents(
+        &state.xml_service,
+        &state.json_service,
+        &request.document1,
+        &request.document2,
+        &options,
+        &request.conventions,
+    )?;
+    record_comparison(&result.result);
     Ok(Json(result))
 }
 
@@ -46,7 +292,7 @@ pub async fn compare_xmls(
     path = "/xml-compare-api/api/compare/url",
     request_body = UrlComparisonRequest,
     responses(
-        (status = 200, description = "URL XML comparison completed", body = XmlComparisonResponse),
+        (status = 200, description = "URL XML comparison completed", content(("application/json" = XmlComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String))),
         (status = 400, description = "Invalid request"),
         (status = 401, description = "Authentication required"),
         (status = 500, description = "Internal server error")
@@ -55,8 +301,38 @@ pub async fn compare_xmls(
 )]
 pub async fn compare_urls(
     State(state): State<AppState>,
+    _permit: ComparisonPermit,
     Json(request): Json<UrlComparisonRequest>,
-) -> AppResult<Json<XmlComparisonResponse>> {
+) -> AppResult<Response> {
+    let result = run_url_comparison(&state, request).await?;
+    record_comparison(&result);
+    Ok(comparison_response(result, None))
+}
+
+/// Compare XMLs from two URLs given as query parameters
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/compare/url",
+    params(UrlComparisonQuery),
+    responses(
+        (status = 200, description = "URL XML comparison completed", content(("application/json" = XmlComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String))),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Comparison"
+)]
+pub async fn compare_urls_query(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Query(query): Query<UrlComparisonQuery>,
+) -> AppResult<Response> {
+    let result = run_url_comparison(&state, query.into()).await?;
+    record_comparison(&result);
+    Ok(comparison_response(result, None))
+}
+
+pub(crate) async fn run_url_comparison(state: &AppState, request: UrlComparisonRequest) -> AppResult<XmlComparisonResponse> {
     // Handle authentication - either use session_id or create new session from auth_credentials
     let session_id_string = if let Some(session_id) = &request.session_id {
         Some(session_id.clone())
@@ -66,6 +342,7 @@ pub async fn compare_urls(
             url: request.url1.clone(), // Use first URL as login URL
             username: auth_creds.username.clone(),
             password: auth_creds.password.clone(),
+            keep_alive_url: None,
         };
         let login_response = state.auth_service.login(&login_request).await?;
         Some(login_response.session_id)
@@ -76,24 +353,113 @@ pub async fn compare_urls(
     let session_id = session_id_string.as_deref();
 
     // Download XMLs from URLs
-    let xml1 = state.http_client
-        .download_xml(&request.url1, Some(&*state.auth_service), session_id)
+    let document1 = state.http_client
+        .fetch_xml(&request.url1, Some(&*state.auth_service), session_id, &request.headers1, &request.request1, &request.fetch)
         .await?;
     
-    let xml2 = state.http_client
-        .download_xml(&request.url2, Some(&*state.auth_service), session_id)
+    let document2 = state.http_client
+        .fetch_xml(&request.url2, Some(&*state.auth_service), session_id, &request.headers2, &request.request2, &request.fetch)
         .await?;
+    let _memory = state.limits.reserve_memory(document1.xml.len() + document2.xml.len())?;
+    let sources = vec![
+        document1.source,
+        document2.source,
+    ];
 
     // Create comparison request
+    let comparison_request = XmlComparisonRequest {
+        xml1: document1.xml,
+        xml2: document2.xml,
+        options: request.effective_options(),
+        profile: request.profile,
+        ..Default::default()
+    };
+    let comparison_request = state.profile_service.resolve(comparison_request).await?;
+
+    let mut result = state.xml_service.compare_with_options(
+        &comparison_request.xml1,
+        &comparison_request.xml2,
+        &comparison_request.effective_options(),
+    )?;
+    result.sources = sources;
+    Ok(result)
+}
+
+/// Compare two documents, each given inline or as a URL
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/mixed",
+    request_body = MixedComparisonRequest,
+    responses(
+        (status = 200, description = "Comparison completed", content(("application/json" = XmlComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String))),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "URL Comparison"
+)]
+pub async fn compare_mixed(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<MixedComparisonRequest>,
+) -> AppResult<Response> {
+    let first_url = [&request.document1, &request.document2].into_iter().find_map(|document| document.url.clone());
+    let session_id = match (&request.session_id, &request.auth_credentials, first_url) {
+        (Some(session_id), _, _) => Some(session_id.clone()),
+        (None, Some(auth_creds), Some(url)) => {
+            let login_request = crate::models::LoginRequest {
+                url,
+                username: auth_creds.username.clone(),
+                password: auth_creds.password.clone(),
+                keep_alive_url: None,
+            };
+            Some(state.auth_service.login(&login_request).await?.session_id)
+        }
+        _ => None,
+    };
+
+    let mut sources = Vec::new();
+    let xml1 = load_document(&state, request.document1, session_id.as_deref(), &request.fetch, &mut sources).await?;
+    let xml2 = load_document(&state, request.document2, session_id.as_deref(), &request.fetch, &mut sources).await?;
+    let _memory = state.limits.reserve_memory(xml1.len() + xml2.len())?;
+
     let comparison_request = XmlComparisonRequest {
         xml1,
         xml2,
-        ignore_paths: request.ignore_paths,
-        ignore_properties: request.ignore_properties,
+        options: request.options,
+        profile: request.profile,
+        ..Default::default()
     };
+    let comparison_request = state.profile_service.resolve(comparison_request).await?;
+    let mut result = state.xml_service.compare_with_options(
+        &comparison_request.xml1,
+        &comparison_request.xml2,
+        &comparison_request.effective_options(),
+    )?;
+    result.sources = sources;
+    record_comparison(&result);
+    Ok(comparison_response(result, Some((&comparison_request.xml1, &comparison_request.xml2))))
+}
 
-    let result = state.xml_service.compare_xmls(&comparison_request)?;
-    Ok(Json(result))
+// Inline documents are used as given; downloaded ones are recorded in `sources`
+async fn load_document(
+    state: &AppState,
+    document: DocumentInput,
+    session_id: Option<&str>,
+    fetch: &FetchOptions,
+    sources: &mut Vec<DocumentSource>,
+) -> AppResult<String> {
+    match (document.xml, document.url) {
+        (Some(xml), None) => Ok(xml),
+        (None, Some(url)) => {
+            let fetched = state.http_client
+                .fetch_xml(&url, Some(&*state.auth_service), session_id, &document.headers, &document.request, fetch)
+                .await?;
+            sources.push(fetched.source);
+            Ok(fetched.xml)
+        }
+        _ => Err(AppError::ValidationError("Give exactly one of xml or url for each document".to_string())),
+    }
 }
 
 /// Compare multiple XML pairs in batch
@@ -102,7 +468,7 @@ pub async fn compare_urls(
     path = "/xml-compare-api/api/compare/xml/batch",
     request_body = BatchXmlComparisonRequest,
     responses(
-        (status = 200, description = "Batch XML comparison completed", body = BatchComparisonResponse),
+        (status = 200, description = "Batch XML comparison completed", content(("application/json" = BatchComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String))),
         (status = 400, description = "Invalid request"),
         (status = 500, description = "Internal server error")
     ),
@@ -110,39 +476,190 @@ pub async fn compare_urls(
 )]
 pub async fn compare_xmls_batch(
     State(state): State<AppState>,
+    _permit: ComparisonPermit,
     Json(request): Json<BatchXmlComparisonRequest>,
-) -> AppResult<Json<BatchComparisonResponse>> {
-    let mut results = Vec::new();
-    let mut successful = 0;
-    let mut failed = 0;
+) -> AppResult<Response> {
+    state.limits.check_batch_size(request.comparisons.len())?;
+    if let Some(callback_url) = &request.callback_url {
+        validate_url(callback_url)?;
+    }
+    let mut batch = BatchResults::new(state.jobs.start(request.job_id.clone(), "xml_batch", request.comparisons.len())?);
+    let mut ids = Vec::with_capacity(request.comparisons.len());
+    let mut resolved = Vec::with_capacity(request.comparisons.len());
+    for mut comparison in request.comparisons {
+        ids.push(comparison.id.take());
+        resolved.push(state.profile_service.resolve(comparison).await);
+    }
+    // The comparisons themselves run on the batch workers, in parallel
+    let xml_service = state.xml_service.clone();
+    let job = batch.job.job();
+    let outcomes = state.batch_pool
+        .map(resolved, move |comparison: AppResult<XmlComparisonRequest>| {
+            // Once the job is cancelled, items not yet started are skipped
+            if job.is_cancelled() {
+                return (Err(job.cancelled_error()), Duration::ZERO);
+            }
+            let started = Instant::now();
+            let result = comparison.and_then(|comparison| {
+                let options = comparison.effective_options();
+                Ok(xml_service.compare_with_options(&comparison.xml1, &comparison.xml2, &options)?)
+            });
+            job.record(result.is_ok());
+            (result, started.elapsed())
+        })
+        .await?;
+    for ((result, duration), id) in outcomes.into_iter().zip(ids) {
+        batch.push(id, result, duration);
+    }
+
+    Ok(batch.respond(&state, request.callback_url.as_deref(), request.output_format).await)
+}
+
+// Results of a v1 batch in request order, with a placeholder for each comparison that failed or
+// was cancelled
+struct BatchResults {
+    job: JobGuard,
+    started: Instant,
+    results: Vec<XmlComparisonResponse>,
+    errors: Vec<Option<AppError>>,
+    durations: Vec<Duration>,
+}
+
+impl BatchResults {
+    fn new(job: JobGuard) -> Self {
+        let capacity = job.total;
+        Self {
+            job,
+            started: Instant::now(),
+            results: Vec::with_capacity(capacity),
+            errors: Vec::with_capacity(capacity),
+            durations: Vec::with_capacity(capacity),
+        }
+    }
 
-    let _total_comparisons = request.comparisons.len();
-    for comparison in &request.comparisons {
-        match state.xml_service.compare_xmls(&comparison) {
+    fn push(&mut self, id: Option<String>, result: AppResult<XmlComparisonResponse>, duration: Duration) {
+        match result {
             Ok(result) => {
-                results.push(result);
-                successful += 1;
+                record_comparison(&result);
+                self.results.push(XmlComparisonResponse { id, ..result });
+                self.errors.push(None);
             }
-            Err(_) => {
-                failed += 1;
-                // Add a failed result placeholder
-                results.push(XmlComparisonResponse {
+            Err(error) => {
+                self.results.push(XmlComparisonResponse {
+                    id,
                     matched: false,
                     match_ratio: 0.0,
                     diffs: vec![],
                     total_elements: 0,
                     matched_elements: 0,
+                    ..Default::default()
                 });
+                self.errors.push(Some(error));
             }
         }
+        self.durations.push(duration);
     }
 
-    Ok(Json(BatchComparisonResponse {
-        results,
-        total_comparisons: request.comparisons.len(),
-        successful_comparisons: successful,
-        failed_comparisons: failed,
-    }))
+    // The finished batch as JSON, or as the JUnit report, SARIF log or Markdown summary
+    // `output_format` asks for
+    async fn respond(self, state: &AppState, callback_url: Option<&str>, output_format: Option<OutputFormat>) -> Response {
+        let Some(format @ (OutputFormat::Junit | OutputFormat::Sarif | OutputFormat::Markdown)) = output_format else {
+            return Json(self.finish(state, callback_url).await).into_response();
+        };
+        let errors: Vec<_> = self.errors.iter().map(|error| error.as_ref().map(ToString::to_string)).collect();
+        let durations = self.durations.clone();
+        let response = self.finish(state, callback_url).await;
+        let items = response.results.iter().zip(errors).zip(durations).enumerate();
+        match format {
+            OutputFormat::Sarif => {
+                let mut comparisons = Vec::new();
+                let mut failures = Vec::new();
+                for (index, ((result, error), _)) in items {
+                    match error {
+                        Some(error) => failures.push(format!("{}: {}", item_name(result, index), error)),
+                        None => comparisons.push(sarif_comparison(result, None)),
+                    }
+                }
+                sarif_response(render_sarif(&comparisons, &failures))
+            }
+            OutputFormat::Markdown => {
+                let items: Vec<_> = items
+                    .map(|(index, ((result, error), _))| (item_name(result, index), error.map_or(Ok(result), Err)))
+                    .collect();
+                markdown_response(render_markdown_batch(&format!("Batch {}", response.job_id), &items))
+            }
+            _ => {
+                let cases: Vec<_> = items
+                    .map(|(index, ((result, error), duration))| JunitCase {
+                        name: item_name(result, index),
+                        outcome: match error {
+                            Some(error) => JunitOutcome::Error(error),
+                            None => JunitOutcome::Compared(result),
+                        },
+                        time: Some(duration),
+                    })
+                    .collect();
+                junit_response(render_junit(&response.job_id, &cases))
+            }
+        }
+    }
+
+    // Also archives the batch when results are kept, and notifies the callback and alert channels
+    async fn finish(self, state: &AppState, callback_url: Option<&str>) -> BatchComparisonResponse {
+        let succeeded: Vec<bool> = self.errors.iter().map(Option::is_none).collect();
+        let successful = succeeded.iter().filter(|succeeded| **succeeded).count();
+        let cancelled = self.errors.iter().filter(|error| matches!(error, Some(AppError::Cancelled(_)))).count();
+        let summary = BatchSummary::new(&self.results, &succeeded, &self.durations, self.started.elapsed());
+        if cancelled > 0 {
+            tracing::info!(job_id = %self.job.id, cancelled, "Batch cancelled");
+        }
+        if state.result_archive.is_enabled() {
+            let items = self
+                .results
+                .iter()
+                .zip(&self.errors)
+                .enumerate()
+                .map(|(index, (result, error))| StoredBatchItem {
+                    index,
+                    id: result.id.clone(),
+                    matched: error.is_none() && result.matched,
+                    error: error.as_ref().map(ToString::to_string),
+                    cancelled: matches!(error, Some(AppError::Cancelled(_))),
+                    result: error.is_none().then(|| serde_json::to_value(result).ok()).flatten(),
+                })
+                .collect();
+            state.result_archive.save(&self.job, items).await;
+        }
+        if callback_url.is_some() {
+            let items: Vec<_> = self
+                .results
+                .iter()
+                .zip(&self.errors)
+                .enumerate()
+                .map(|(index, (result, error))| AlertItem {
+                    index,
+                    id: result.id.as_deref(),
+                    failed: error.is_some(),
+                    matched: result.matched,
+                    diffs: &result.diffs,
+                })
+                .collect();
+            state.alerts.notify_batch(&self.job.id, &items);
+        }
+        let response = BatchComparisonResponse {
+            total_comparisons: self.results.len(),
+            successful_comparisons: successful,
+            failed_comparisons: self.results.len() - successful - cancelled,
+            cancelled_comparisons: cancelled,
+            job_id: self.job.id.clone(),
+            results: self.results,
+            summary,
+        };
+        if let Some(callback_url) = callback_url {
+            state.webhook_service.notify(callback_url, &BatchCallbackPayload::from(&response));
+        }
+        response
+    }
 }
 
 /// Compare XMLs from multiple URL pairs in batch
@@ -151,7 +668,7 @@ pub async fn compare_xmls_batch(
     path = "/xml-compare-api/api/compare/url/batch",
     request_body = BatchUrlComparisonRequest,
     responses(
-        (status = 200, description = "Batch URL comparison completed", body = BatchComparisonResponse),
+        (status = 200, description = "Batch URL comparison completed", content(("application/json" = BatchComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String))),
         (status = 400, description = "Invalid request"),
         (status = 401, description = "Authentication required"),
         (status = 500, description = "Internal server error")
@@ -160,90 +677,317 @@ pub async fn compare_xmls_batch(
 )]
 pub async fn compare_urls_batch(
     State(state): State<AppState>,
+    _permit: ComparisonPermit,
     Json(request): Json<BatchUrlComparisonRequest>,
-) -> AppResult<Json<BatchComparisonResponse>> {
-    let mut results = Vec::new();
-    let mut successful = 0;
-    let mut failed = 0;
+) -> AppResult<Response> {
+    state.limits.check_batch_size(request.comparisons.len())?;
+    if let Some(callback_url) = &request.callback_url {
+        validate_url(callback_url)?;
+    }
+    let job = state.jobs.start(request.job_id.clone(), "url_batch", request.comparisons.len())?;
+    Ok(run_url_batch(&state, job, request.comparisons, request.callback_url.as_deref(), request.output_format).await)
+}
+
+/// Download and compare the URL pairs listed in a CSV or JSON manifest
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/url/batch/manifest",
+    request_body = ManifestComparisonRequest,
+    responses(
+        (status = 200, description = "Batch URL comparison completed", content(("application/json" = BatchComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String))),
+        (status = 400, description = "Invalid request or manifest"),
+        (status = 401, description = "Authentication required"),
+        (status = 413, description = "Manifest lists more comparisons than a batch may hold"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Batch URL Comparison"
+)]
+pub async fn compare_manifest(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<ManifestComparisonRequest>,
+) -> AppResult<Response> {
+    if let Some(callback_url) = &request.callback_url {
+        validate_url(callback_url)?;
+    }
+    let manifest = match (request.manifest, &request.manifest_url) {
+        (Some(manifest), None) => manifest,
+        (None, Some(url)) => {
+            // Manifests are CSV or JSON, so any content type is accepted
+            let fetch = FetchOptions { content_types: Some(vec!["*/*".to_string()]), ..request.fetch.clone() };
+            let headers = BTreeMap::from([("Accept".to_string(), "text/csv, application/json, */*".to_string())]);
+            state.http_client
+                .download_xml(url, Some(&*state.auth_service), request.session_id.as_deref(), &headers, &DownloadRequest::default(), &fetch)
+                .await?
+        }
+        _ => return Err(AppError::ValidationError("Give exactly one of manifest and manifest_url".to_string())),
+    };
+    let mut comparisons = parse_manifest(&manifest)?;
+    state.limits.check_batch_size(comparisons.len())?;
+    for comparison in &mut comparisons {
+        comparison.options = comparison.effective_options().or(request.options.clone());
+        comparison.profile = comparison.profile.take().or_else(|| request.profile.clone());
+        comparison.session_id = comparison.session_id.take().or_else(|| request.session_id.clone());
+        comparison.auth_credentials = comparison.auth_credentials.take().or_else(|| request.auth_credentials.clone());
+        comparison.fetch = std::mem::take(&mut comparison.fetch).or(request.fetch.clone());
+    }
+    let job = state.jobs.start(request.job_id.clone(), "manifest_batch", comparisons.len())?;
+    let output_format = request.options.output_format;
+    Ok(run_url_batch(&state, job, comparisons, request.callback_url.as_deref(), output_format).await)
+}
 
-    // Process comparisons concurrently
+/// Compare the documents at the same paths under two base URLs, such as UAT against production
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/environments",
+    request_body = EnvironmentComparisonRequest,
+    responses(
+        (status = 200, description = "One result per path, with the path as its id", content(("application/json" = BatchComparisonResponse), ("application/xml" = String), ("application/sarif+json" = String), ("text/markdown" = String))),
+        (status = 400, description = "Invalid request, or an index without documents"),
+        (status = 401, description = "Authentication required"),
+        (status = 413, description = "More paths than a batch may hold"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Batch URL Comparison"
+)]
+pub async fn compare_environments(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<EnvironmentComparisonRequest>,
+) -> AppResult<Response> {
+    validate_url(&request.base_url1)?;
+    validate_url(&request.base_url2)?;
+    if let Some(callback_url) = &request.callback_url {
+        validate_url(callback_url)?;
+    }
+    let paths = match (request.paths, &request.index_url) {
+        (Some(paths), None) => paths,
+        (None, Some(index_url)) => {
+            let index_url = environment_url(&request.base_url1, index_url)?;
+            // Sitemaps, listings and plain lists all come with different content types
+            let fetch = FetchOptions { content_types: Some(vec!["*/*".to_string()]), ..request.fetch.clone() };
+            let index = state.http_client
+                .download_xml(&index_url, Some(&*state.auth_service), request.session_id.as_deref(), &BTreeMap::new(), &DownloadRequest::default(), &fetch)
+                .await?;
+            index_paths(&index, &index_url, &request.base_url1)?
+        }
+        _ => return Err(AppError::ValidationError("Give exactly one of paths and index_url".to_string())),
+    };
+    if paths.is_empty() {
+        return Err(AppError::ValidationError("No paths to compare".to_string()));
+    }
+    state.limits.check_batch_size(paths.len())?;
+    let comparisons = paths
+        .into_iter()
+        .map(|path| {
+            Ok(UrlComparisonRequest {
+                url1: environment_url(&request.base_url1, &path)?,
+                url2: environment_url(&request.base_url2, &path)?,
+                id: Some(path),
+                options: request.options.clone(),
+                profile: request.profile.clone(),
+                auth_credentials: request.auth_credentials.clone(),
+                session_id: request.session_id.clone(),
+                fetch: request.fetch.clone(),
+                ..Default::default()
+            })
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+    let job = state.jobs.start(request.job_id.clone(), "environment_batch", comparisons.len())?;
+    let output_format = request.options.output_format;
+    Ok(run_url_batch(&state, job, comparisons, request.callback_url.as_deref(), output_format).await)
+}
+
+async fn run_url_batch(
+    state: &AppState,
+    job: JobGuard,
+    comparisons: Vec<UrlComparisonRequest>,
+    callback_url: Option<&str>,
+    output_format: Option<OutputFormat>,
+) -> Response {
+    let mut batch = BatchResults::new(job);
+
+    // Process comparisons concurrently; cancelling the job drops their downloads
     let mut futures = Vec::new();
-    
-    let _total_comparisons = request.comparisons.len();
-    for comparison in request.comparisons.clone() {
+    for comparison in comparisons.clone() {
         let state = state.clone();
-        let future = tokio::spawn(async move {
-            // Handle authentication for this comparison
-            let session_id_string = if let Some(session_id) = &comparison.session_id {
-                Some(session_id.clone())
-            } else if let Some(auth_creds) = &comparison.auth_credentials {
-                // Create a temporary session for this request
-                let login_request = crate::models::LoginRequest {
-                    url: comparison.url1.clone(),
-                    username: auth_creds.username.clone(),
-                    password: auth_creds.password.clone(),
-                };
-                match state.auth_service.login(&login_request).await {
-                    Ok(login_response) => Some(login_response.session_id),
-                    Err(_) => None,
+        let job = batch.job.job();
+        let future = tokio::spawn(in_tenant_scope(async move {
+            let started = Instant::now();
+            let result = tokio::select! {
+                biased;
+                _ = job.cancelled() => Err(job.cancelled_error()),
+                result = compare_url_item(&state, comparison) => {
+                    job.record(result.is_ok());
+                    result
                 }
-            } else {
-                None
             };
-            
-            let session_id = session_id_string.as_deref();
-
-            // Download XMLs from URLs
-            let xml1_result = state.http_client
-                .download_xml(&comparison.url1, Some(&*state.auth_service), session_id)
-                .await;
-            
-            let xml2_result = state.http_client
-                .download_xml(&comparison.url2, Some(&*state.auth_service), session_id)
-                .await;
-
-            match (xml1_result, xml2_result) {
-                (Ok(xml1), Ok(xml2)) => {
-                            let comparison_request = XmlComparisonRequest {
-            xml1,
-            xml2,
-            ignore_paths: comparison.ignore_paths.clone(),
-            ignore_properties: comparison.ignore_properties.clone(),
-        };
-
-                    state.xml_service.compare_xmls(&comparison_request)
-                }
-                _ => Err(AppError::InternalError("Failed to download XML from URL".to_string())),
-            }
-        });
-        
+            (result, started.elapsed())
+        }));
         futures.push(future);
     }
 
     // Collect results
-    for future in futures {
+    for (future, comparison) in futures.into_iter().zip(&comparisons) {
         match future.await {
-            Ok(Ok(result)) => {
-                results.push(result);
-                successful += 1;
+            Ok((result, duration)) => batch.push(comparison.id.clone(), result, duration),
+            Err(e) => batch.push(comparison.id.clone(), Err(AppError::InternalError(e.to_string())), Duration::ZERO),
+        }
+    }
+
+    batch.respond(state, callback_url, output_format).await
+}
+
+async fn compare_url_item(state: &AppState, comparison: UrlComparisonRequest) -> AppResult<XmlComparisonResponse> {
+    // Handle authentication for this comparison
+    let session_id_string = if let Some(session_id) = &comparison.session_id {
+        Some(session_id.clone())
+    } else if let Some(auth_creds) = &comparison.auth_credentials {
+        // Create a temporary session for this request
+        let login_request = crate::models::LoginRequest {
+            url: comparison.url1.clone(),
+            username: auth_creds.username.clone(),
+            password: auth_creds.password.clone(),
+            keep_alive_url: None,
+        };
+        match state.auth_service.login(&login_request).await {
+            Ok(login_response) => Some(login_response.session_id),
+            Err(_) => None,
+        }
+    } else {
+        None
+    };
+    
+    let session_id = session_id_string.as_deref();
+
+    // Download XMLs from URLs
+    let xml1_result = state.http_client
+        .fetch_xml(&comparison.url1, Some(&*state.auth_service), session_id, &comparison.headers1, &comparison.request1, &comparison.fetch)
+        .await;
+    
+    let xml2_result = state.http_client
+        .fetch_xml(&comparison.url2, Some(&*state.auth_service), session_id, &comparison.headers2, &comparison.request2, &comparison.fetch)
+        .await;
+
+    match (xml1_result, xml2_result) {
+        (Ok(document1), Ok(document2)) => {
+            let _memory = state.limits.reserve_memory(document1.xml.len() + document2.xml.len())?;
+            let sources = vec![
+                document1.source,
+                document2.source,
+            ];
+            let comparison_request = XmlComparisonRequest {
+                xml1: document1.xml,
+                xml2: document2.xml,
+                options: comparison.effective_options(),
+                profile: comparison.profile.clone(),
+                ..Default::default()
+            };
+
+            state.profile_service
+                .resolve(comparison_request)
+                .await
+                .and_then(|comparison_request| {
+                    let options = comparison_request.effective_options();
+                    let mut result = state.xml_service.compare_with_options(
+                        &comparison_request.xml1,
+                        &comparison_request.xml2,
+                        &options,
+                    )?;
+                    result.sources = sources;
+                    Ok(result)
+                })
+        }
+        _ => Err(AppError::InternalError("Failed to download XML from URL".to_string())),
+    }
+}
+
+/// Compare one reference XML against many candidate XMLs or URLs
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/compare/golden",
+    request_body = GoldenComparisonRequest,
+    responses(
+        (status = 200, description = "Per-candidate results ranked by match ratio", body = GoldenComparisonResponse),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication required"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "Batch Comparison"
+)]
+pub async fn compare_golden(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<GoldenComparisonRequest>,
+) -> AppResult<Json<GoldenComparisonResponse>> {
+    state.limits.check_batch_size(request.candidates.len())?;
+    // Resolve the profile once; every candidate is compared with the same options
+    let options = state.profile_service.resolve_options(request.options, request.profile.as_deref()).await?;
+
+    let first_url = request.candidates.iter().find_map(|candidate| candidate.url.clone());
+    let session_id = match (&request.session_id, &request.auth_credentials, first_url) {
+        (Some(session_id), _, _) => Some(session_id.clone()),
+        (None, Some(auth_creds), Some(url)) => {
+            let login_request = crate::models::LoginRequest {
+                url,
+                username: auth_creds.username.clone(),
+                password: auth_creds.password.clone(),
+                keep_alive_url: None,
+            };
+            Some(state.auth_service.login(&login_request).await?.session_id)
+        }
+        _ => None,
+    };
+
+    let mut results = Vec::new();
+    for (i, candidate) in request.candidates.into_iter().enumerate() {
+        let name = candidate
+            .name
+            .or_else(|| candidate.url.clone())
+            .unwrap_or_else(|| format!("candidate {}", i + 1));
+        let xml = match (candidate.xml, candidate.url) {
+            (Some(xml), None) => Ok(xml),
+            (None, Some(url)) => {
+                state.http_client
+                    .download_xml(&url, Some(&*state.auth_service), session_id.as_deref(), &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default())
+                    .await
             }
-            _ => {
-                failed += 1;
-                results.push(XmlComparisonResponse {
-                    matched: false,
-                    match_ratio: 0.0,
-                    diffs: vec![],
-                    total_elements: 0,
-                    matched_elements: 0,
-                });
+            _ => Err(AppError::ValidationError("Give exactly one of xml or url".to_string())),
+        };
+        let outcome = xml.and_then(|xml| {
+            let _memory = state.limits.reserve_memory(request.reference.len() + xml.len())?;
+            Ok(state.xml_service.compare_with_options(&request.reference, &xml, &options)?)
+        });
+        results.push(match outcome {
+            Ok(result) => {
+                record_comparison(&result);
+                CandidateResult { name, result: Some(result), error: None }
             }
-        }
+            Err(error) => CandidateResult { name, result: None, error: Some(error.to_string()) },
+        });
     }
 
-    Ok(Json(BatchComparisonResponse {
-        results,
-        total_comparisons: request.comparisons.len(),
-        successful_comparisons: successful,
-        failed_comparisons: failed,
-    }))
-}
\ No newline at end of file
+    let ranking = rank_candidates(&results);
+    Ok(Json(GoldenComparisonResponse { results, ranking }))
+}
+
+// Highest match ratio first; ties go to the candidate with fewer diffs, then to request order
+fn rank_candidates(results: &[CandidateResult]) -> Vec<CandidateRank> {
+    let mut compared: Vec<_> = results
+        .iter()
+        .filter_map(|candidate| candidate.result.as_ref().map(|result| (candidate, result)))
+        .collect();
+    compared.sort_by(|(_, a), (_, b)| {
+        b.match_ratio.total_cmp(&a.match_ratio).then(a.diffs.len().cmp(&b.diffs.len()))
+    });
+    compared
+        .into_iter()
+        .enumerate()
+        .map(|(i, (candidate, result))| CandidateRank {
+            rank: i + 1,
+            name: candidate.name.clone(),
+            match_ratio: result.match_ratio,
+            diff_count: result.diffs.len(),
+        })
+        .collect()
+}
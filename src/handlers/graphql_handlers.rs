@@ -0,0 +1,316 @@
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, InputObject, Object, Schema};
+use axum::{extract::State, response::Html, Json};
+use crate::handlers::comparison_handlers::{AppState, run_url_comparison};
+use crate::middleware::{record_comparison, ComparisonPermit};
+use crate::services::XmlComparisonService;
+use crate::models::{ComparisonOptions, DocumentPairStats, DocumentStats, UrlComparisonRequest, XmlComparisonRequest, XmlComparisonResponse, XmlDiff, XmlNodeSnapshot};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+// Built once; the app state is attached to each request
+fn schema() -> &'static ApiSchema {
+    static SCHEMA: OnceLock<ApiSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| Schema::build(QueryRoot, EmptyMutation, EmptySubscription).limit_depth(10).finish())
+}
+
+/// Execute a GraphQL query against the comparison schema
+pub async fn graphql(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema().execute(request.data(state)).await)
+}
+
+/// GraphiQL page for exploring the schema
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/xml-compare-api/graphql").finish())
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Compare two XML documents; `options` takes the same object as the REST `options` field
+    async fn compare_xml(
+        &self,
+        ctx: &Context<'_>,
+        xml1: String,
+        xml2: String,
+        options: Option<async_graphql::Json<ComparisonOptions>>,
+        profile: Option<String>,
+    ) -> async_graphql::Result<ComparisonResult> {
+        let comparison = XmlComparisonInput { id: None, xml1, xml2, options, profile };
+        Ok(compare_pair(ctx.data::<AppState>()?, comparison).await?)
+    }
+
+    /// Compare many XML pairs; a failed comparison is reported on its item
+    async fn compare_xml_batch(&self, ctx: &Context<'_>, comparisons: Vec<XmlComparisonInput>) -> async_graphql::Result<BatchResult> {
+        let state = ctx.data::<AppState>()?;
+        state.limits.check_batch_size(comparisons.len())?;
+        let mut ids = Vec::with_capacity(comparisons.len());
+        let mut resolved = Vec::with_capacity(comparisons.len());
+        for mut comparison in comparisons {
+            ids.push(comparison.id.take());
+            resolved.push(resolve_pair(state, comparison).await);
+        }
+        let xml_service = state.xml_service.clone();
+        let outcomes = state.batch_pool
+            .map(resolved, move |request| request.and_then(|request| compare_resolved(&xml_service, request)))
+            .await?;
+        let items = outcomes
+            .into_iter()
+            .zip(ids)
+            .enumerate()
+            .map(|(index, (result, id))| BatchItem { index, id, result })
+            .collect();
+        Ok(BatchResult { items })
+    }
+
+    /// Download two documents and compare them
+    async fn compare_url(
+        &self,
+        ctx: &Context<'_>,
+        url1: String,
+        url2: String,
+        options: Option<async_graphql::Json<ComparisonOptions>>,
+        profile: Option<String>,
+        session_id: Option<String>,
+    ) -> async_graphql::Result<ComparisonResult> {
+        let request = UrlComparisonRequest {
+            url1,
+            url2,
+            options: options.map(|options| options.0).unwrap_or_default(),
+            profile,
+            session_id,
+            ..Default::default()
+        };
+        let result = run_url_comparison(ctx.data::<AppState>()?, request).await?;
+        record_comparison(&result);
+        Ok(ComparisonResult(result))
+    }
+}
+
+#[derive(InputObject)]
+pub struct XmlComparisonInput {
+    /// Client's own reference for this comparison, echoed on its batch item
+    id: Option<String>,
+    xml1: String,
+    xml2: String,
+    options: Option<async_graphql::Json<ComparisonOptions>>,
+    profile: Option<String>,
+}
+
+async fn compare_pair(state: &AppState, input: XmlComparisonInput) -> crate::models::AppResult<ComparisonResult> {
+    compare_resolved(&state.xml_service, resolve_pair(state, input).await?)
+}
+
+async fn resolve_pair(state: &AppState, input: XmlComparisonInput) -> crate::models::AppResult<XmlComparisonRequest> {
+    let request = XmlComparisonRequest {
+        xml1: input.xml1,
+        xml2: input.xml2,
+        options: input.options.map(|options| options.0).unwrap_or_default(),
+        profile: input.profile,
+        ..Default::default()
+    };
+    state.profile_service.resolve(request).await
+}
+
+fn compare_resolved(
+    xml_service: &XmlComparisonService,
+    request: XmlComparisonRequest,
+) -> crate::models::AppResult<ComparisonResult> {
+    let result = xml_service.compare_with_options(&request.xml1, &request.xml2, &request.effective_options())?;
+    record_comparison(&result);
+    Ok(ComparisonResult(result))
+}
+
+pub struct ComparisonResult(XmlComparisonResponse);
+
+#[Object]
+impl ComparisonResult {
+    async fn matched(&self) -> bool {
+        self.0.matched
+    }
+
+    async fn match_ratio(&self) -> f64 {
+        self.0.match_ratio
+    }
+
+    async fn total_elements(&self) -> usize {
+        self.0.total_elements
+    }
+
+    async fn matched_elements(&self) -> usize {
+        self.0.matched_elements
+    }
+
+    async fn diff_count(&self) -> usize {
+        self.0.diffs.len()
+    }
+
+    async fn truncated(&self) -> bool {
+        self.0.truncated
+    }
+
+    /// At most `limit` diffs, in report order
+    async fn diffs(&self, limit: Option<usize>) -> Vec<Diff<'_>> {
+        self.0.diffs.iter().take(limit.unwrap_or(usize::MAX)).map(Diff).collect()
+    }
+
+    async fn patch(&self) -> Option<&str> {
+        self.0.patch.as_deref()
+    }
+
+    async fn unified_diff(&self) -> Option<&str> {
+        self.0.unified_diff.as_deref()
+    }
+
+    /// Size and shape of xml1 and xml2
+    async fn document_stats(&self) -> Option<DocumentPairCounts> {
+        self.0.document_stats.map(DocumentPairCounts)
+    }
+}
+
+pub struct DocumentPairCounts(DocumentPairStats);
+
+#[Object(name = "DocumentPairStats")]
+impl DocumentPairCounts {
+    async fn xml1(&self) -> DocumentCounts {
+        DocumentCounts(self.0.xml1)
+    }
+
+    async fn xml2(&self) -> DocumentCounts {
+        DocumentCounts(self.0.xml2)
+    }
+}
+
+pub struct DocumentCounts(DocumentStats);
+
+#[Object(name = "DocumentStats")]
+impl DocumentCounts {
+    async fn elements(&self) -> usize {
+        self.0.elements
+    }
+
+    /// Attributes other than namespace declarations
+    async fn attributes(&self) -> usize {
+        self.0.attributes
+    }
+
+    /// Nesting depth of the deepest element; the root is at depth 1
+    async fn max_depth(&self) -> usize {
+        self.0.max_depth
+    }
+
+    /// Bytes of text and CDATA content, unescaped and trimmed
+    async fn text_bytes(&self) -> usize {
+        self.0.text_bytes
+    }
+}
+
+pub struct Diff<'a>(&'a XmlDiff);
+
+#[Object]
+impl Diff<'_> {
+    async fn path(&self) -> &str {
+        &self.0.path
+    }
+
+    /// As in the REST response, e.g. `ContentDifferent`
+    async fn diff_type(&self) -> String {
+        serde_name(&self.0.diff_type)
+    }
+
+    async fn expected(&self) -> Option<&str> {
+        self.0.expected.as_deref()
+    }
+
+    async fn actual(&self) -> Option<&str> {
+        self.0.actual.as_deref()
+    }
+
+    async fn message(&self) -> &str {
+        &self.0.message
+    }
+
+    async fn similarity(&self) -> Option<f64> {
+        self.0.similarity
+    }
+
+    async fn severity(&self) -> String {
+        serde_name(&self.0.severity)
+    }
+
+    async fn tags(&self) -> &[String] {
+        &self.0.tags
+    }
+
+    /// The missing or extra element: name, namespace, attributes, text and child names
+    async fn node(&self) -> Option<async_graphql::Json<XmlNodeSnapshot>> {
+        self.0.node.clone().map(async_graphql::Json)
+    }
+}
+
+pub struct BatchItem {
+    index: usize,
+    id: Option<String>,
+    result: crate::models::AppResult<ComparisonResult>,
+}
+
+#[Object]
+impl BatchItem {
+    async fn index(&self) -> usize {
+        self.index
+    }
+
+    async fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    async fn result(&self) -> Option<&ComparisonResult> {
+        self.result.as_ref().ok()
+    }
+
+    async fn error(&self) -> Option<String> {
+        self.result.as_ref().err().map(ToString::to_string)
+    }
+}
+
+pub struct BatchResult {
+    items: Vec<BatchItem>,
+}
+
+#[Object]
+impl BatchResult {
+    async fn total_comparisons(&self) -> usize {
+        self.items.len()
+    }
+
+    async fn successful_comparisons(&self) -> usize {
+        self.items.iter().filter(|item| item.result.is_ok()).count()
+    }
+
+    async fn failed_comparisons(&self) -> usize {
+        self.items.iter().filter(|item| item.result.is_err()).count()
+    }
+
+    async fn matched_comparisons(&self) -> usize {
+        self.items.iter().filter(|item| item.result.as_ref().is_ok_and(|result| result.0.matched)).count()
+    }
+
+    async fn items(&self) -> &[BatchItem] {
+        &self.items
+    }
+}
+
+// Name of a unit enum variant as serde writes it
+fn serde_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,56 @@
+use axum::{extract::State, http::StatusCode, Json};
+use crate::handlers::comparison_handlers::AppState;
+use crate::models::{HealthReport, HealthStatus, ReadinessReport};
+
+/// Service health, with connectivity probes to the configured upstream hosts
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/health",
+    responses(
+        (status = 200, description = "Health report; `status` is `degraded` when a probe failed", body = HealthReport)
+    ),
+    tag = "Health"
+)]
+pub async fn health(State(state): State<AppState>) -> Json<HealthReport> {
+    let checks = state.health_service.probe_upstreams().await;
+    let status = if checks.iter().all(|check| check.ok) { HealthStatus::Ok } else { HealthStatus::Degraded };
+    Json(HealthReport {
+        status,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        uptime_seconds: state.health_service.uptime().as_secs(),
+        active_sessions: state.auth_service.active_session_count().await,
+        queue_depth: state.webhook_service.pending_deliveries(),
+        checks,
+    })
+}
+
+/// Liveness: the process is up and serving requests
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/healthz/live",
+    responses(
+        (status = 200, description = "The service is alive")
+    ),
+    tag = "Health"
+)]
+pub async fn live() -> StatusCode {
+    StatusCode::OK
+}
+
+/// Readiness: every background worker and storage backend has started and the server is not
+/// shutting down
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/healthz/ready",
+    responses(
+        (status = 200, description = "Ready for traffic", body = ReadinessReport),
+        (status = 503, description = "Still starting up, or shutting down", body = ReadinessReport)
+    ),
+    tag = "Health"
+)]
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<ReadinessReport>) {
+    let (ready, components) = state.health_service.readiness();
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let shutting_down = state.health_service.is_shutting_down();
+    (status, Json(ReadinessReport { ready, shutting_down, components }))
+}
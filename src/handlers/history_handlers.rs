@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::middleware::current_tenant_name;
+use crate::models::{AppError, AppResult, HistoryEntry, HistoryQuery, TrendQuery, TrendResponse};
+
+/// List past comparisons, newest first
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/history",
+    params(HistoryQuery),
+    responses(
+        (status = 200, description = "Matching history entries", body = Vec<HistoryEntry>),
+        (status = 400, description = "Invalid filter"),
+        (status = 403, description = "The filter names another tenant"),
+        (status = 404, description = "History is not kept")
+    ),
+    tag = "History"
+)]
+pub async fn get_history(
+    State(state): State<AppState>,
+    Query(mut query): Query<HistoryQuery>,
+) -> AppResult<Json<Vec<HistoryEntry>>> {
+    query.tenant = own_tenant(query.tenant)?;
+    Ok(Json(state.history.query(&query).await?))
+}
+
+/// Match ratio trends, most frequently differing paths and diff types over time
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/history/trends",
+    params(TrendQuery),
+    responses(
+        (status = 200, description = "Trends per period", body = TrendResponse),
+        (status = 400, description = "Invalid filter, or only one of url1 and url2"),
+        (status = 403, description = "The filter names another tenant"),
+        (status = 404, description = "History is not kept")
+    ),
+    tag = "History"
+)]
+pub async fn get_trends(
+    State(state): State<AppState>,
+    Query(mut query): Query<TrendQuery>,
+) -> AppResult<Json<TrendResponse>> {
+    query.tenant = own_tenant(query.tenant)?;
+    Ok(Json(state.history.trends(&query).await?))
+}
+
+// Tenants only see their own comparisons
+fn own_tenant(asked: Option<String>) -> AppResult<Option<String>> {
+    let Some(tenant) = current_tenant_name() else {
+        return Ok(asked);
+    };
+    if asked.as_ref().is_some_and(|asked| *asked != tenant) {
+        return Err(AppError::Forbidden(format!("Tenant '{}' cannot read another tenant's history", tenant)));
+    }
+    Ok(Some(tenant))
+}
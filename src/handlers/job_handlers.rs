@@ -0,0 +1,125 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::models::{AppError, AppResult, JobInfo, JobListResponse, JobProgress, StoredBatch, StoredBatchItem, StoredItemsResponse};
+
+/// List the batches that are running, oldest first
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/jobs",
+    responses(
+        (status = 200, description = "Running batches", body = JobListResponse)
+    ),
+    tag = "Jobs"
+)]
+pub async fn list_jobs(State(state): State<AppState>) -> Json<JobListResponse> {
+    Json(JobListResponse { jobs: state.jobs.list() })
+}
+
+/// How far a running batch has got: comparisons finished and failed, throughput and an estimate of
+/// the time left
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/jobs/{job_id}/progress",
+    params(
+        ("job_id" = String, Path, description = "Job to follow")
+    ),
+    responses(
+        (status = 200, description = "Progress so far", body = JobProgress),
+        (status = 404, description = "No running job with this ID")
+    ),
+    tag = "Jobs"
+)]
+pub async fn job_progress(State(state): State<AppState>, Path(job_id): Path<String>) -> AppResult<Json<JobProgress>> {
+    Ok(Json(state.jobs.progress(&job_id)?))
+}
+
+/// Cancel a running batch. It stops starting comparisons, aborts its downloads and answers its
+/// own request with the results finished so far.
+#[utoipa::path(
+    delete,
+    path = "/xml-compare-api/api/jobs/{job_id}",
+    params(
+        ("job_id" = String, Path, description = "Job to cancel")
+    ),
+    responses(
+        (status = 202, description = "Cancellation requested", body = JobInfo),
+        (status = 404, description = "No running job with this ID")
+    ),
+    tag = "Jobs"
+)]
+pub async fn cancel_job(State(state): State<AppState>, Path(job_id): Path<String>) -> AppResult<(StatusCode, Json<JobInfo>)> {
+    let job = state.jobs.cancel(&job_id)?;
+    tracing::info!(job_id = %job_id, kind = %job.kind, "Job cancellation requested");
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// The results of a finished batch, kept for `APP_RESULT_TTL_HOURS` when `APP_RESULT_STORE` is set
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/jobs/{job_id}/results",
+    params(
+        ("job_id" = String, Path, description = "Job of the batch")
+    ),
+    responses(
+        (status = 200, description = "Stored results", body = StoredBatch),
+        (status = 404, description = "No stored results for this job")
+    ),
+    tag = "Jobs"
+)]
+pub async fn job_results(State(state): State<AppState>, Path(job_id): Path<String>) -> AppResult<Json<StoredBatch>> {
+    Ok(Json(state.result_archive.get(&job_id).await?))
+}
+
+/// The items of a finished batch whose documents differed or that could not be compared
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/jobs/{job_id}/results/failed",
+    params(
+        ("job_id" = String, Path, description = "Job of the batch")
+    ),
+    responses(
+        (status = 200, description = "Items that did not match", body = StoredItemsResponse),
+        (status = 404, description = "No stored results for this job")
+    ),
+    tag = "Jobs"
+)]
+pub async fn failed_job_results(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> AppResult<Json<StoredItemsResponse>> {
+    let batch = state.result_archive.get(&job_id).await?;
+    let items = batch.items.into_iter().filter(|item| !item.matched).collect();
+    Ok(Json(StoredItemsResponse { job_id: batch.job_id, items }))
+}
+
+/// One item of a finished batch, with its diffs
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/jobs/{job_id}/results/items/{index}",
+    params(
+        ("job_id" = String, Path, description = "Job of the batch"),
+        ("index" = usize, Path, description = "Position of the item in the batch request")
+    ),
+    responses(
+        (status = 200, description = "Stored item", body = StoredBatchItem),
+        (status = 404, description = "No stored results for this job, or no such item")
+    ),
+    tag = "Jobs"
+)]
+pub async fn job_result_item(
+    State(state): State<AppState>,
+    Path((job_id, index)): Path<(String, usize)>,
+) -> AppResult<Json<StoredBatchItem>> {
+    let batch = state.result_archive.get(&job_id).await?;
+    let total = batch.items.len();
+    batch
+        .items
+        .into_iter()
+        .nth(index)
+        .map(Json)
+        .ok_or_else(|| AppError::NotFound(format!("Job '{}' has {} items; there is no item {}", job_id, total, index)))
+}
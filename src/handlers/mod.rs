@@ -1,2 +1,12 @@
 pub mod comparison_handlers;
-pub mod auth_handlers;
\ No newline at end of file
+pub mod auth_handlers;
+pub mod xml_handlers;
+pub mod profile_handlers;
+pub mod baseline_handlers;
+pub mod v2_handlers;
+pub mod graphql_handlers;
+pub mod admin_handlers;
+pub mod health_handlers;
+pub mod job_handlers;
+pub mod history_handlers;
+pub mod viewer_handlers;
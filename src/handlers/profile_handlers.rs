@@ -0,0 +1,114 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use crate::models::{ComparisonProfile, AppResult};
+use crate::handlers::comparison_handlers::AppState;
+
+/// List stored comparison profiles
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/profiles",
+    responses(
+        (status = 200, description = "Stored profiles", body = Vec<ComparisonProfile>)
+    ),
+    tag = "Profiles"
+)]
+pub async fn list_profiles(State(state): State<AppState>) -> Json<Vec<ComparisonProfile>> {
+    Json(state.profile_service.list().await)
+}
+
+/// Get a comparison profile by name
+#[utoipa::path(
+    get,
+    path = "/xml-compare-api/api/profiles/{name}",
+    params(
+        ("name" = String, Path, description = "Profile name")
+    ),
+    responses(
+        (status = 200, description = "Profile found", body = ComparisonProfile),
+        (status = 404, description = "Profile not found")
+    ),
+    tag = "Profiles"
+)]
+pub async fn get_profile(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> AppResult<Json<ComparisonProfile>> {
+    let profile = state.profile_service.get(&name).await?;
+    Ok(Json(profile))
+}
+
+/// Create a comparison profile
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/profiles",
+    request_body = ComparisonProfile,
+    responses(
+        (status = 200, description = "Profile created", body = ComparisonProfile),
+        (status = 400, description = "Invalid profile"),
+        (status = 409, description = "Profile already exists")
+    ),
+    tag = "Profiles"
+)]
+pub async fn create_profile(
+    State(state): State<AppState>,
+    Json(profile): Json<ComparisonProfile>,
+) -> AppResult<Json<ComparisonProfile>> {
+    validate_profile(&state, &profile)?;
+    let profile = state.profile_service.create(profile).await?;
+    Ok(Json(profile))
+}
+
+/// Replace the options of an existing comparison profile
+#[utoipa::path(
+    put,
+    path = "/xml-compare-api/api/profiles/{name}",
+    params(
+        ("name" = String, Path, description = "Profile name")
+    ),
+    request_body = ComparisonProfile,
+    responses(
+        (status = 200, description = "Profile updated", body = ComparisonProfile),
+        (status = 400, description = "Invalid profile"),
+        (status = 404, description = "Profile not found")
+    ),
+    tag = "Profiles"
+)]
+pub async fn update_profile(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(profile): Json<ComparisonProfile>,
+) -> AppResult<Json<ComparisonProfile>> {
+    validate_profile(&state, &profile)?;
+    let profile = state.profile_service.update(&name, profile).await?;
+    Ok(Json(profile))
+}
+
+/// Delete a comparison profile
+#[utoipa::path(
+    delete,
+    path = "/xml-compare-api/api/profiles/{name}",
+    params(
+        ("name" = String, Path, description = "Profile name")
+    ),
+    responses(
+        (status = 200, description = "Profile deleted"),
+        (status = 404, description = "Profile not found")
+    ),
+    tag = "Profiles"
+)]
+pub async fn delete_profile(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> AppResult<Json<()>> {
+    state.profile_service.delete(&name).await?;
+    Ok(Json(()))
+}
+
+// Rejects options that would fail every comparison (invalid XPath, out-of-range threshold, ...)
+// by running them once on a trivial document
+fn validate_profile(state: &AppState, profile: &ComparisonProfile) -> AppResult<()> {
+    state.xml_service.compare_with_options("<probe/>", "<probe/>", &profile.options)?;
+    Ok(())
+}
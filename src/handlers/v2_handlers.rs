@@ -0,0 +1,240 @@
+use axum::{extract::State, Json};
+use crate::handlers::comparison_handlers::{AppState, run_url_comparison};
+use crate::models::{
+    AppError, AppResult, BatchCallbackPayload, BatchComparisonResponseV2, BatchItemV2, BatchUrlComparisonRequestV2,
+    BatchXmlComparisonRequestV2, ComparisonResultV2, StoredBatchItem, UrlComparisonRequest, UrlComparisonRequestV2,
+    XmlComparisonRequest, XmlComparisonRequestV2,
+};
+use crate::middleware::{in_tenant_scope, record_comparison, ComparisonPermit};
+use crate::services::{AlertItem, JobGuard, XmlComparisonService};
+use crate::utils::validation::validate_url;
+
+/// Compare two XML contents
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/v2/compare/xml",
+    operation_id = "compare_xmls_v2",
+    request_body = XmlComparisonRequestV2,
+    responses(
+        (status = 200, description = "XML comparison completed", body = ComparisonResultV2),
+        (status = 400, description = "Invalid request"),
+        (status = 422, description = "Unknown or mistyped request fields"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "v2"
+)]
+pub async fn compare_xmls(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<XmlComparisonRequestV2>,
+) -> AppResult<Json<ComparisonResultV2>> {
+    compare_xml_pair(&state, request).await.map(recorded).map(Json)
+}
+
+/// Compare XMLs from two URLs
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/v2/compare/url",
+    operation_id = "compare_urls_v2",
+    request_body = UrlComparisonRequestV2,
+    responses(
+        (status = 200, description = "URL XML comparison completed", body = ComparisonResultV2),
+        (status = 400, description = "Invalid request"),
+        (status = 401, description = "Authentication required"),
+        (status = 422, description = "Unknown or mistyped request fields"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "v2"
+)]
+pub async fn compare_urls(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<UrlComparisonRequestV2>,
+) -> AppResult<Json<ComparisonResultV2>> {
+    compare_url_pair(&state, request).await.map(recorded).map(Json)
+}
+
+/// Compare multiple XML pairs; each entry carries its result or its error
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/v2/compare/xml/batch",
+    operation_id = "compare_xmls_batch_v2",
+    request_body = BatchXmlComparisonRequestV2,
+    responses(
+        (status = 200, description = "Batch XML comparison completed", body = BatchComparisonResponseV2),
+        (status = 400, description = "Invalid request"),
+        (status = 422, description = "Unknown or mistyped request fields"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "v2"
+)]
+pub async fn compare_xmls_batch(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<BatchXmlComparisonRequestV2>,
+) -> AppResult<Json<BatchComparisonResponseV2>> {
+    state.limits.check_batch_size(request.comparisons.len())?;
+    if let Some(callback_url) = &request.callback_url {
+        validate_url(callback_url)?;
+    }
+    let job = state.jobs.start(request.job_id.clone(), "xml_batch", request.comparisons.len())?;
+    let ids: Vec<_> = request.comparisons.iter().map(|comparison| comparison.id.clone()).collect();
+    let mut resolved = Vec::with_capacity(request.comparisons.len());
+    for comparison in request.comparisons {
+        resolved.push(resolve_xml_pair(&state, comparison).await);
+    }
+    let xml_service = state.xml_service.clone();
+    let running = job.job();
+    let outcomes = state.batch_pool
+        .map(resolved, move |comparison| {
+            if running.is_cancelled() {
+                return Err(running.cancelled_error());
+            }
+            let result = comparison.and_then(|comparison| compare_resolved(&xml_service, comparison));
+            running.record(result.is_ok());
+            result
+        })
+        .await?;
+    let results = outcomes.into_iter().zip(ids).enumerate().map(|(index, (result, id))| batch_item(index, id, result)).collect();
+    Ok(Json(finish_batch(&state, &job, results, request.callback_url.as_deref()).await))
+}
+
+/// Download and compare multiple URL pairs concurrently; each entry carries its result or its error
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/v2/compare/url/batch",
+    operation_id = "compare_urls_batch_v2",
+    request_body = BatchUrlComparisonRequestV2,
+    responses(
+        (status = 200, description = "Batch URL comparison completed", body = BatchComparisonResponseV2),
+        (status = 400, description = "Invalid request"),
+        (status = 422, description = "Unknown or mistyped request fields"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "v2"
+)]
+pub async fn compare_urls_batch(
+    State(state): State<AppState>,
+    _permit: ComparisonPermit,
+    Json(request): Json<BatchUrlComparisonRequestV2>,
+) -> AppResult<Json<BatchComparisonResponseV2>> {
+    state.limits.check_batch_size(request.comparisons.len())?;
+    if let Some(callback_url) = &request.callback_url {
+        validate_url(callback_url)?;
+    }
+    let job = state.jobs.start(request.job_id.clone(), "url_batch", request.comparisons.len())?;
+    let ids: Vec<_> = request.comparisons.iter().map(|comparison| comparison.id.clone()).collect();
+    let tasks: Vec<_> = request
+        .comparisons
+        .into_iter()
+        .map(|comparison| {
+            let state = state.clone();
+            let job = job.job();
+            tokio::spawn(in_tenant_scope(async move {
+                tokio::select! {
+                    biased;
+                    _ = job.cancelled() => Err(job.cancelled_error()),
+                    result = compare_url_pair(&state, comparison) => {
+                        job.record(result.is_ok());
+                        result
+                    }
+                }
+            }))
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(tasks.len());
+    for (index, (task, id)) in tasks.into_iter().zip(ids).enumerate() {
+        let result = task.await.unwrap_or_else(|e| Err(AppError::InternalError(e.to_string())));
+        results.push(batch_item(index, id, result));
+    }
+    Ok(Json(finish_batch(&state, &job, results, request.callback_url.as_deref()).await))
+}
+
+async fn compare_xml_pair(state: &AppState, request: XmlComparisonRequestV2) -> AppResult<ComparisonResultV2> {
+    compare_resolved(&state.xml_service, resolve_xml_pair(state, request).await?)
+}
+
+async fn resolve_xml_pair(state: &AppState, request: XmlComparisonRequestV2) -> AppResult<XmlComparisonRequest> {
+    let comparison = XmlComparisonRequest {
+        xml1: request.xml1,
+        xml2: request.xml2,
+        options: request.options,
+        profile: request.profile,
+        ..Default::default()
+    };
+    state.profile_service.resolve(comparison).await
+}
+
+fn compare_resolved(xml_service: &XmlComparisonService, comparison: XmlComparisonRequest) -> AppResult<ComparisonResultV2> {
+    let options = comparison.effective_options();
+    // Shaping waits until the summary has counted every diff
+    let result = xml_service.compare_with_options(&comparison.xml1, &comparison.xml2, &options.without_shaping())?;
+    Ok(ComparisonResultV2::from(result).shaped(&options))
+}
+
+async fn compare_url_pair(state: &AppState, request: UrlComparisonRequestV2) -> AppResult<ComparisonResultV2> {
+    let options = state.profile_service.resolve_options(request.options, request.profile.as_deref()).await?;
+    let comparison = UrlComparisonRequest {
+        url1: request.url1,
+        url2: request.url2,
+        auth_credentials: request.auth_credentials,
+        session_id: request.session_id,
+        options: options.without_shaping(),
+        headers1: request.headers1,
+        headers2: request.headers2,
+        request1: request.request1,
+        request2: request.request2,
+        fetch: request.fetch,
+        ..Default::default()
+    };
+    let result = run_url_comparison(state, comparison).await?;
+    Ok(ComparisonResultV2::from(result).shaped(&options))
+}
+
+fn recorded(result: ComparisonResultV2) -> ComparisonResultV2 {
+    record_comparison(&result);
+    result
+}
+
+fn batch_item(index: usize, id: Option<String>, result: AppResult<ComparisonResultV2>) -> BatchItemV2 {
+    match result {
+        Ok(result) => BatchItemV2 { index, id, result: Some(recorded(result)), error: None, cancelled: false },
+        Err(e) => BatchItemV2 {
+            index,
+            id,
+            result: None,
+            cancelled: matches!(e, AppError::Cancelled(_)),
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+async fn finish_batch(
+    state: &AppState,
+    job: &JobGuard,
+    results: Vec<BatchItemV2>,
+    callback_url: Option<&str>,
+) -> BatchComparisonResponseV2 {
+    if state.result_archive.is_enabled() {
+        state.result_archive.save(job, results.iter().map(StoredBatchItem::from).collect()).await;
+    }
+    if callback_url.is_some() {
+        let items: Vec<_> = results
+            .iter()
+            .map(|item| AlertItem {
+                index: item.index,
+                id: item.id.as_deref(),
+                failed: item.result.is_none(),
+                matched: item.result.as_ref().is_some_and(|result| result.matched),
+                diffs: item.result.as_ref().map_or(&[], |result| &result.diffs),
+            })
+            .collect();
+        state.alerts.notify_batch(&job.id, &items);
+    }
+    let response = BatchComparisonResponseV2::new(job.id.clone(), results);
+    if let Some(callback_url) = callback_url {
+        state.webhook_service.notify(callback_url, &BatchCallbackPayload::from(&response));
+    }
+    response
+}
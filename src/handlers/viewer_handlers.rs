@@ -0,0 +1,391 @@
+use axum::response::Html;
+
+/// Side-by-side diff viewer: a page that posts two documents or two URLs to the comparison API
+/// and shows the result as collapsible trees with the differences highlighted
+pub async fn viewer_page() -> Html<&'static str> {
+    Html(VIEWER_PAGE)
+}
+
+const VIEWER_PAGE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<title>XML Compare API - Diff viewer</title>
+<style>
+    * { box-sizing: border-box; }
+    body { margin: 0; font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; color: #333; background: linear-gradient(135deg, #667eea 0%, #764ba2 100%); min-height: 100vh; }
+    .container { max-width: 1400px; margin: 0 auto; padding: 20px; }
+    .header { color: white; display: flex; justify-content: space-between; align-items: baseline; }
+    .header a { color: white; }
+    .content { background: white; border-radius: 15px; padding: 30px; box-shadow: 0 20px 40px rgba(0,0,0,0.1); margin-bottom: 20px; }
+    .tabs button { padding: 8px 16px; border: 1px solid #667eea; background: white; color: #667eea; border-radius: 8px; cursor: pointer; font-weight: bold; }
+    .tabs button.active { background: #667eea; color: white; }
+    .pair { display: grid; grid-template-columns: 1fr 1fr; gap: 20px; margin: 15px 0; }
+    label { display: block; font-weight: bold; margin-bottom: 5px; }
+    textarea, input[type=text], input[type=password], select { width: 100%; padding: 8px; border: 1px solid #dee2e6; border-radius: 6px; font-family: 'Monaco', 'Menlo', monospace; font-size: 0.85rem; }
+    textarea { min-height: 220px; resize: vertical; }
+    .rules { display: grid; grid-template-columns: repeat(auto-fit, minmax(250px, 1fr)); gap: 15px; margin: 15px 0; }
+    .rules textarea { min-height: 70px; }
+    .checkbox { display: flex; gap: 8px; align-items: center; font-weight: normal; }
+    .checkbox input { width: auto; }
+    details.settings { margin: 15px 0; }
+    .btn { padding: 12px 24px; background: #667eea; color: white; border: none; border-radius: 8px; font-weight: bold; cursor: pointer; }
+    .btn:hover { background: #5a6fd8; }
+    .btn:disabled { background: #a3acd9; cursor: wait; }
+    .btn.small { padding: 4px 10px; font-size: 0.8rem; }
+    .status { margin-left: 15px; }
+    .error { color: #dc3545; }
+    .summary .match { color: #28a745; }
+    .summary .mismatch { color: #dc3545; }
+    .legend span { padding: 2px 8px; border-radius: 4px; margin-right: 5px; }
+    .tree { background: #f8f9fa; border-radius: 8px; padding: 15px; overflow-x: auto; font-family: 'Monaco', 'Menlo', monospace; font-size: 0.85rem; min-width: 0; }
+    .tree details, .tree .leaf, .tree .close { margin-left: 18px; }
+    .tree > details, .tree > .leaf { margin-left: 0; }
+    .tree summary { cursor: pointer; }
+    .tree .text { color: #555; }
+    .removed { background: #f8d7da; }
+    .added { background: #d4edda; }
+    .moved { background: #d1ecf1; }
+    .changed { background: #fff3cd; }
+    .focus { outline: 2px solid #667eea; }
+    table { border-collapse: collapse; width: 100%; font-size: 0.85rem; margin-top: 15px; }
+    th, td { border: 1px solid #dee2e6; padding: 6px; text-align: left; vertical-align: top; word-break: break-word; }
+    th { background: #f8f9fa; }
+    tbody tr { cursor: pointer; }
+    tbody tr:hover { background: #f1f3ff; }
+    .hidden { display: none; }
+</style>
+</head>
+<body>
+<div class="container">
+    <div class="header">
+        <h1>🔍 XML diff viewer</h1>
+        <a href="/xml-compare-api/">← API home</a>
+    </div>
+    <form id="compare-form" class="content">
+        <div class="tabs">
+            <button type="button" id="tab-xml" class="active">Paste XML</button>
+            <button type="button" id="tab-url">Compare URLs</button>
+        </div>
+        <div id="mode-xml" class="pair">
+            <div><label for="xml1">xml1 (expected)</label><textarea id="xml1" spellcheck="false" placeholder="&lt;root&gt;…&lt;/root&gt;"></textarea></div>
+            <div><label for="xml2">xml2 (actual)</label><textarea id="xml2" spellcheck="false" placeholder="&lt;root&gt;…&lt;/root&gt;"></textarea></div>
+        </div>
+        <div id="mode-url" class="pair hidden">
+            <div><label for="url1">url1 (expected)</label><input type="text" id="url1" placeholder="https://api.example.com/expected.xml"></div>
+            <div><label for="url2">url2 (actual)</label><input type="text" id="url2" placeholder="https://api.example.com/actual.xml"></div>
+        </div>
+        <div class="rules">
+            <div><label for="ignore-paths">Ignore paths (one per line)</label><textarea id="ignore-paths" spellcheck="false" placeholder="/root/timestamp&#10;//id"></textarea></div>
+            <div><label for="ignore-properties">Ignore attributes (one per line or comma separated)</label><textarea id="ignore-properties" spellcheck="false" placeholder="id, version"></textarea></div>
+            <div>
+                <label for="namespace-mode">Namespaces</label>
+                <select id="namespace-mode">
+                    <option value="">uri (default)</option>
+                    <option value="strict">strict</option>
+                    <option value="qualified">qualified</option>
+                </select>
+                <label class="checkbox"><input type="checkbox" id="ignore-order"> Ignore element order</label>
+                <label for="profile">Profile</label><input type="text" id="profile" placeholder="stored profile name">
+            </div>
+        </div>
+        <details class="settings">
+            <summary>Credentials</summary>
+            <div class="pair">
+                <div><label for="api-key">Tenant API key (X-API-Key)</label><input type="password" id="api-key" autocomplete="off"></div>
+                <div><label for="token">Bearer token</label><input type="password" id="token" autocomplete="off"></div>
+            </div>
+        </details>
+        <button type="submit" class="btn" id="compare">Compare</button>
+        <span id="status" class="status"></span>
+    </form>
+    <div id="result" class="content hidden">
+        <p id="summary" class="summary"></p>
+        <p class="legend"><span class="removed">removed</span><span class="added">added</span><span class="moved">moved</span><span class="changed">changed</span>
+            <button type="button" class="btn small" id="expand">Expand all</button>
+            <button type="button" class="btn small" id="collapse">Collapse to differences</button></p>
+        <div class="pair">
+            <div><h3 id="title1">xml1</h3><div id="tree1" class="tree"></div></div>
+            <div><h3 id="title2">xml2</h3><div id="tree2" class="tree"></div></div>
+        </div>
+        <table id="diffs">
+            <thead><tr><th>Path</th><th>Type</th><th>Expected</th><th>Actual</th><th>Message</th></tr></thead>
+            <tbody></tbody>
+        </table>
+    </div>
+</div>
+<script>
+const $ = id => document.getElementById(id);
+let mode = 'xml';
+
+function setMode(next) {
+    mode = next;
+    $('tab-xml').classList.toggle('active', mode === 'xml');
+    $('tab-url').classList.toggle('active', mode === 'url');
+    $('mode-xml').classList.toggle('hidden', mode !== 'xml');
+    $('mode-url').classList.toggle('hidden', mode !== 'url');
+}
+$('tab-xml').onclick = () => setMode('xml');
+$('tab-url').onclick = () => setMode('url');
+
+for (const id of ['api-key', 'token']) {
+    $(id).value = sessionStorage.getItem('xml-compare-' + id) || '';
+    $(id).onchange = () => sessionStorage.setItem('xml-compare-' + id, $(id).value);
+}
+
+function list(text) {
+    return text.split(/[\n,]/).map(item => item.trim()).filter(Boolean);
+}
+
+function element(tag, className, text) {
+    const node = document.createElement(tag);
+    if (className) node.className = className;
+    if (text !== undefined) node.textContent = text;
+    return node;
+}
+
+$('compare-form').onsubmit = async event => {
+    event.preventDefault();
+    const options = {};
+    const ignorePaths = list($('ignore-paths').value);
+    const ignoreProperties = list($('ignore-properties').value);
+    if (ignorePaths.length) options.ignore_paths = ignorePaths;
+    if (ignoreProperties.length) options.ignore_properties = ignoreProperties;
+    if ($('ignore-order').checked) options.ignore_element_order = true;
+    if ($('namespace-mode').value) options.namespace_mode = $('namespace-mode').value;
+    const body = { options };
+    if ($('profile').value.trim()) body.profile = $('profile').value.trim();
+    if (mode === 'xml') {
+        body.xml1 = $('xml1').value;
+        body.xml2 = $('xml2').value;
+    } else {
+        body.url1 = $('url1').value.trim();
+        body.url2 = $('url2').value.trim();
+    }
+    const headers = { 'Content-Type': 'application/json' };
+    if ($('api-key').value) headers['X-API-Key'] = $('api-key').value;
+    if ($('token').value) headers['Authorization'] = 'Bearer ' + $('token').value;
+
+    $('compare').disabled = true;
+    $('status').className = 'status';
+    $('status').textContent = 'Comparing…';
+    try {
+        const response = await fetch('/xml-compare-api/api/compare/' + mode, { method: 'POST', headers, body: JSON.stringify(body) });
+        const result = await response.json().catch(() => ({}));
+        if (!response.ok) throw new Error(result.error || response.status + ' ' + response.statusText);
+        $('status').textContent = '';
+        render(result, mode === 'xml' ? [body.xml1, body.xml2] : null);
+    } catch (error) {
+        $('status').className = 'status error';
+        $('status').textContent = error.message;
+    } finally {
+        $('compare').disabled = false;
+    }
+};
+
+// Each element's path of local names with every position written out (`/a[1]/b[2]`), so that
+// diff paths, which only write positions when a name repeats, can be matched against it
+function diffSegments(path) {
+    const segments = [];
+    for (const segment of path.split('/').filter(Boolean)) {
+        // Attributes, text and keyed segments such as `trade[@id='2']` end the element path
+        const match = /^([^\[@]+?)(?:\[(\d+)\])?$/.exec(segment);
+        if (!match || match[1].endsWith('()')) break;
+        const name = match[1].replace(/^\{[^}]*\}/, '').split(':').pop();
+        segments.push('/' + name + '[' + (match[2] || 1) + ']');
+    }
+    return segments;
+}
+
+function reorderedPath(path, actual) {
+    const position = actual && actual.startsWith('position ') ? actual.slice(9) : null;
+    const base = path.lastIndexOf('[');
+    return position && base > 0 ? path.slice(0, base) + '[' + position + ']' : path;
+}
+
+const markOf = type => ({ ElementMissing: 'removed', ElementExtra: 'added', ElementMoved: 'moved', ElementReordered: 'moved' })[type] || 'changed';
+
+// A parsed document: its element tree, and every element's node by indexed path
+function buildTree(xml) {
+    const doc = new DOMParser().parseFromString(xml, 'application/xml');
+    if (!doc.documentElement || doc.getElementsByTagName('parsererror').length) return null;
+    const nodes = new Map();
+    const walk = (source, parentPath) => {
+        const counts = {};
+        const children = [];
+        for (const child of source.children) {
+            counts[child.localName] = (counts[child.localName] || 0) + 1;
+            const path = parentPath + '/' + child.localName + '[' + counts[child.localName] + ']';
+            const node = { source: child, path, marks: [], messages: [], children: [] };
+            nodes.set(path, node);
+            node.children = walk(child, path);
+            children.push(node);
+        }
+        return children;
+    };
+    return { roots: walk(doc, ''), nodes };
+}
+
+// Marks the diff's element, or its closest ancestor when the element is not in this document
+function mark(tree, path, markName, diff) {
+    if (!tree) return;
+    const segments = diffSegments(path);
+    for (let length = segments.length; length > 0; length--) {
+        const node = tree.nodes.get(segments.slice(0, length).join(''));
+        if (node) {
+            node.marks.push(length === segments.length ? markName : 'changed');
+            node.messages.push(diff.message);
+            return node;
+        }
+    }
+}
+
+function openTag(source) {
+    const attributes = Array.from(source.attributes).map(attribute => ' ' + attribute.name + '="' + attribute.value + '"').join('');
+    return '<' + source.nodeName + attributes;
+}
+
+function ownText(source) {
+    return Array.from(source.childNodes)
+        .filter(child => child.nodeType === Node.TEXT_NODE || child.nodeType === Node.CDATA_SECTION_NODE)
+        .map(child => child.nodeValue.trim())
+        .filter(Boolean)
+        .join(' ');
+}
+
+// A structural mark on the element wins over changes to its attributes or content
+function nodeClass(node) {
+    return node.marks.find(markName => markName !== 'changed') || node.marks[0] || '';
+}
+
+function renderNode(node, depth) {
+    const text = ownText(node.source);
+    const className = nodeClass(node);
+    let view;
+    if (!node.children.length) {
+        const content = text ? openTag(node.source) + '>' + text + '</' + node.source.nodeName + '>' : openTag(node.source) + '/>';
+        view = element('div', 'leaf ' + className, content);
+    } else {
+        view = element('details', className);
+        const summary = element('summary', '', openTag(node.source) + '>');
+        if (text) summary.append(' ', element('span', 'text', text));
+        view.append(summary);
+        let marked = node.marks.length > 0;
+        for (const child of node.children) {
+            const [childView, childMarked] = renderNode(child, depth + 1);
+            view.append(childView);
+            marked = marked || childMarked;
+        }
+        view.append(element('div', 'close', '</' + node.source.nodeName + '>'));
+        view.open = depth < 1 || marked;
+        view.dataset.marked = marked;
+        node.marked = marked;
+    }
+    if (node.messages.length) view.title = node.messages.join('\n');
+    node.view = view;
+    return [view, node.marks.length > 0 || !!node.marked];
+}
+
+// Without the documents' text, as for URLs downloaded by the server, the tree is built from the
+// diff paths alone
+function renderPathTree(container, diffs, side) {
+    const root = { children: new Map(), diffs: [] };
+    for (const diff of diffs) {
+        if (side === 1 && diff.diff_type === 'ElementExtra') continue;
+        if (side === 2 && diff.diff_type === 'ElementMissing') continue;
+        let node = root;
+        for (const segment of diff.path.split('/').filter(Boolean)) {
+            if (!node.children.has(segment)) node.children.set(segment, { children: new Map(), diffs: [] });
+            node = node.children.get(segment);
+        }
+        node.diffs.push(diff);
+    }
+    const render = (name, node) => {
+        const view = element('details');
+        view.open = true;
+        const summary = element('summary', node.diffs.length ? markOf(node.diffs[0].diff_type) : '', name);
+        view.append(summary);
+        for (const diff of node.diffs) {
+            const value = side === 1 ? diff.expected : diff.actual;
+            view.append(element('div', 'leaf ' + markOf(diff.diff_type), diff.message + (value != null ? ': ' + value : '')));
+        }
+        for (const [childName, child] of node.children) view.append(render(childName, child));
+        return view;
+    };
+    for (const [name, node] of root.children) container.append(render(name, node));
+}
+
+function setAll(open) {
+    for (const view of document.querySelectorAll('.tree details')) {
+        view.open = open || view.dataset.marked === 'true' || view.parentElement.classList.contains('tree');
+    }
+}
+$('expand').onclick = () => setAll(true);
+$('collapse').onclick = () => setAll(false);
+
+function reveal(nodes) {
+    for (const view of document.querySelectorAll('.focus')) view.classList.remove('focus');
+    for (const node of nodes.filter(Boolean)) {
+        for (let parent = node.view.parentElement; parent; parent = parent.parentElement) {
+            if (parent.tagName === 'DETAILS') parent.open = true;
+        }
+        node.view.classList.add('focus');
+        node.view.scrollIntoView({ block: 'nearest' });
+    }
+}
+
+function render(result, documents) {
+    $('result').classList.remove('hidden');
+    const summary = $('summary');
+    summary.replaceChildren(
+        element('strong', result.matched ? 'match' : 'mismatch', result.matched ? 'Documents match' : 'Documents differ'),
+        ' · match ratio ' + (result.match_ratio * 100).toFixed(1) + '% · ' + result.matched_elements + ' of ' +
+            result.total_elements + ' elements matched · ' + result.diffs.length + ' diff(s)' + (result.truncated ? ' (truncated)' : '')
+    );
+    const sources = result.sources || [];
+    $('title1').textContent = (sources[0] && sources[0].url) || 'xml1';
+    $('title2').textContent = (sources[1] && sources[1].url) || 'xml2';
+
+    const trees = documents ? documents.map(buildTree) : [null, null];
+    const targets = result.diffs.map(diff => {
+        const type = diff.diff_type;
+        if (type === 'ElementMissing') return [mark(trees[0], diff.path, 'removed', diff)];
+        if (type === 'ElementExtra') return [null, mark(trees[1], diff.path, 'added', diff)];
+        if (type === 'ElementMoved') {
+            return [mark(trees[0], diff.expected || diff.path, 'moved', diff), mark(trees[1], diff.actual || diff.path, 'moved', diff)];
+        }
+        if (type === 'ElementReordered') {
+            return [mark(trees[0], diff.path, 'moved', diff), mark(trees[1], reorderedPath(diff.path, diff.actual), 'moved', diff)];
+        }
+        return [mark(trees[0], diff.path, 'changed', diff), mark(trees[1], diff.path, 'changed', diff)];
+    });
+
+    [$('tree1'), $('tree2')].forEach((container, index) => {
+        container.replaceChildren();
+        const tree = trees[index];
+        if (tree) {
+            for (const node of tree.roots) container.append(renderNode(node, 0)[0]);
+        } else if (documents) {
+            container.append(element('p', 'error', 'The document could not be parsed in the browser'));
+        } else {
+            renderPathTree(container, result.diffs, index + 1);
+        }
+    });
+
+    const rows = $('diffs').tBodies[0];
+    rows.replaceChildren();
+    result.diffs.forEach((diff, index) => {
+        const row = element('tr', markOf(diff.diff_type));
+        const path = element('td');
+        path.append(element('code', '', diff.path));
+        row.append(path, element('td', '', diff.diff_type), element('td', '', diff.expected ?? ''), element('td', '', diff.actual ?? ''), element('td', '', diff.message));
+        row.onclick = () => reveal(targets[index]);
+        rows.append(row);
+    });
+    $('diffs').classList.toggle('hidden', result.diffs.length === 0);
+}
+</script>
+</body>
+</html>
+"##;
@@ -0,0 +1,74 @@
+use axum::{extract::State, Json};
+use crate::handlers::comparison_handlers::AppState;
+use crate::models::{
+    CanonicalizeRequest, CanonicalizeResponse, FormatRequest, FormatResponse, XmlValidationRequest, XmlValidationResponse, DownloadRequest, FetchOptions, AppError, AppResult,
+};
+use crate::services::{canonicalize, minify, pretty_print_with_indent, validate_against_xsd};
+use std::collections::BTreeMap;
+
+/// Convert an XML document to its Canonical XML (C14N) form
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/xml/canonicalize",
+    request_body = CanonicalizeRequest,
+    responses(
+        (status = 200, description = "Canonical form of the document", body = CanonicalizeResponse),
+        (status = 400, description = "Invalid XML"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "XML Tools"
+)]
+pub async fn canonicalize_xml(Json(request): Json<CanonicalizeRequest>) -> AppResult<Json<CanonicalizeResponse>> {
+    let canonical = canonicalize(&request.xml)?;
+    Ok(Json(CanonicalizeResponse { canonical }))
+}
+
+/// Pretty-print or minify an XML document
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/xml/format",
+    request_body = FormatRequest,
+    responses(
+        (status = 200, description = "Reformatted document", body = FormatResponse),
+        (status = 400, description = "Invalid XML or options"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "XML Tools"
+)]
+pub async fn format_xml(Json(request): Json<FormatRequest>) -> AppResult<Json<FormatResponse>> {
+    let xml = match (request.minify, request.indent) {
+        (true, Some(_)) => return Err(AppError::ValidationError("'indent' cannot be combined with 'minify'".to_string())),
+        (true, None) => minify(&request.xml)?,
+        (false, indent) => pretty_print_with_indent(&request.xml, indent.unwrap_or(2))?,
+    };
+    Ok(Json(FormatResponse { xml }))
+}
+
+/// Validate an XML document against an XSD given inline or by URL
+#[utoipa::path(
+    post,
+    path = "/xml-compare-api/api/xml/validate",
+    request_body = XmlValidationRequest,
+    responses(
+        (status = 200, description = "Validation result with any errors", body = XmlValidationResponse),
+        (status = 400, description = "Invalid request or schema"),
+        (status = 500, description = "Internal server error")
+    ),
+    tag = "XML Tools"
+)]
+pub async fn validate_xml(
+    State(state): State<AppState>,
+    Json(request): Json<XmlValidationRequest>,
+) -> AppResult<Json<XmlValidationResponse>> {
+    let xsd = match (request.xsd, request.xsd_url) {
+        (Some(xsd), None) => xsd,
+        (None, Some(url)) => {
+            state.http_client
+                .download_xml(&url, Some(&*state.auth_service), request.session_id.as_deref(), &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default())
+                .await?
+        }
+        _ => return Err(AppError::ValidationError("Provide exactly one of 'xsd' and 'xsd_url'".to_string())),
+    };
+    let errors = validate_against_xsd(&request.xml, &xsd)?;
+    Ok(Json(XmlValidationResponse { valid: errors.is_empty(), errors }))
+}
@@ -1,6 +1,8 @@
 // Library crate to expose modules for integration testing
 
+pub mod config;
 pub mod models;
 pub mod services;
 pub mod handlers;
+pub mod middleware;
 pub mod utils;
@@ -1,54 +1,204 @@
 use axum::{
-    routing::{post, get},
+    routing::{post, get, delete},
     Router,
     http::Method,
     extract::DefaultBodyLimit,
 };
 use tower_http::cors::{CorsLayer, Any};
+use tower_http::decompression::RequestDecompressionLayer;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+use axum::middleware::{from_fn, from_fn_with_state};
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-mod models;
-mod services;
-mod handlers;
-mod utils;
+use xml_compare_api::config::AppConfig;
+use xml_compare_api::models;
+use xml_compare_api::handlers::{
+    comparison_handlers, auth_handlers, xml_handlers, profile_handlers, baseline_handlers, v2_handlers, graphql_handlers,
+    admin_handlers, health_handlers, job_handlers, history_handlers, viewer_handlers,
+};
+use xml_compare_api::handlers::comparison_handlers::AppStateInner;
+use xml_compare_api::middleware::{require_jwt, require_admin, require_api_key, comparison_history, request_context, body_limit_errors, Limits};
+use xml_compare_api::services::{
+    XmlComparisonService, ParseLimits, JsonComparisonService, HttpClientService, PoolOptions, AuthService, ProfileService,
+    BaselineService, WebhookService,
+    ResultCache, JwtAuthService, JwtKeySource, HealthService, RedisSessionStore, SessionCipher, BatchPool,
+    JobRegistry, ResultArchive, AlertService, TenantService, HistoryService, open_result_store,
+};
 
-use handlers::{comparison_handlers, auth_handlers};
-use handlers::comparison_handlers::AppStateInner;
-use services::{XmlComparisonService, HttpClientService, AuthService};
+// Time batches cancelled at the shutdown deadline get to store their results and answer
+const JOB_PERSIST_GRACE: Duration = Duration::from_secs(10);
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         comparison_handlers::compare_xmls,
+        comparison_handlers::compare_xmls_report,
+        comparison_handlers::compare_xml_json,
+        comparison_handlers::compare_json,
+        comparison_handlers::compare_document,
         comparison_handlers::compare_urls,
+        comparison_handlers::compare_urls_query,
+        comparison_handlers::compare_mixed,
         comparison_handlers::compare_xmls_batch,
         comparison_handlers::compare_urls_batch,
+        comparison_handlers::compare_manifest,
+        comparison_handlers::compare_environments,
+        comparison_handlers::compare_golden,
+        comparison_handlers::cache_stats,
+        job_handlers::list_jobs,
+        job_handlers::job_progress,
+        job_handlers::cancel_job,
+        job_handlers::job_results,
+        job_handlers::failed_job_results,
+        job_handlers::job_result_item,
+        v2_handlers::compare_xmls,
+        v2_handlers::compare_urls,
+        v2_handlers::compare_xmls_batch,
+        v2_handlers::compare_urls_batch,
+        xml_handlers::canonicalize_xml,
+        xml_handlers::validate_xml,
+        xml_handlers::format_xml,
+        profile_handlers::list_profiles,
+        profile_handlers::get_profile,
+        profile_handlers::create_profile,
+        profile_handlers::update_profile,
+        profile_handlers::delete_profile,
+        baseline_handlers::list_baselines,
+        baseline_handlers::get_baseline,
+        baseline_handlers::create_baseline,
+        baseline_handlers::update_baseline,
+        baseline_handlers::delete_baseline,
+        baseline_handlers::compare_baseline,
+        baseline_handlers::accept_baseline_diffs,
         auth_handlers::login,
-        auth_handlers::logout
+        auth_handlers::logout,
+        auth_handlers::refresh,
+        admin_handlers::list_sessions,
+        admin_handlers::expire_session,
+        admin_handlers::tenant_usage,
+        history_handlers::get_history,
+        history_handlers::get_trends,
+        health_handlers::health,
+        health_handlers::live,
+        health_handlers::ready
     ),
     components(
         schemas(
             models::XmlComparisonRequest,
+            models::ComparisonOptions,
             models::XmlComparisonResponse,
             models::XmlDiff,
+            models::DocumentWarning,
             models::DiffType,
+            models::DiffSeverity,
+            models::NamespaceMode,
+            models::SimilarityAlgorithm,
+            models::ComparisonMode,
+            models::OutputFormat,
+            models::DiffField,
+            models::ChildCountMode,
+            models::NilMode,
+            models::ValueTransform,
+            models::TransformRule,
+            models::ScriptRule,
+            models::ClassificationRule,
+            models::XmlNodeSnapshot,
             models::UrlComparisonRequest,
             models::AuthCredentials,
+            models::FetchOptions,
+            models::ProxyOptions,
+            models::DownloadRequest,
+            models::OAuth2Credentials,
+            models::DocumentSource,
+            models::SourceCache,
+            models::ElementStats,
+            models::DocumentPairStats,
+            models::DocumentStats,
             models::BatchXmlComparisonRequest,
             models::BatchUrlComparisonRequest,
+            models::ManifestComparisonRequest,
+            models::EnvironmentComparisonRequest,
+            models::BatchCallbackPayload,
+            models::BatchStatus,
             models::BatchComparisonResponse,
+            models::JobInfo,
+            models::JobListResponse,
+            models::JobProgress,
+            models::StoredBatch,
+            models::StoredBatchItem,
+            models::StoredItemsResponse,
+            models::BatchSummary,
+            models::RatioBucket,
+            models::ItemTiming,
+            models::XmlJsonComparisonRequest,
+            models::JsonComparisonRequest,
+            models::DocumentComparisonRequest,
+            models::DocumentComparisonResponse,
+            models::DocumentFormat,
+            models::JsonConventions,
+            models::GoldenComparisonRequest,
+            models::GoldenCandidate,
+            models::MixedComparisonRequest,
+            models::DocumentInput,
+            models::GoldenComparisonResponse,
+            models::CandidateResult,
+            models::CandidateRank,
+            models::CacheStats,
+            models::Baseline,
+            models::BaselineComparisonRequest,
+            models::BaselineComparisonResponse,
+            models::XmlComparisonRequestV2,
+            models::UrlComparisonRequestV2,
+            models::BatchXmlComparisonRequestV2,
+            models::BatchUrlComparisonRequestV2,
+            models::ComparisonResultV2,
+            models::ComparisonSummary,
+            models::BatchItemV2,
+            models::BatchComparisonResponseV2,
+            models::CanonicalizeRequest,
+            models::CanonicalizeResponse,
+            models::FormatRequest,
+            models::FormatResponse,
+            models::XmlValidationRequest,
+            models::XmlValidationResponse,
+            models::ValidationIssue,
+            models::ComparisonProfile,
             models::LoginRequest,
             models::LoginResponse,
+            models::SessionSummary,
+            models::SessionListResponse,
+            models::TenantUsage,
+            models::HistoryEntry,
+            models::TrendBucket,
+            models::TrendPoint,
+            models::PathFrequency,
+            models::TrendResponse,
+            models::HealthReport,
+            models::HealthStatus,
+            models::ProbeResult,
+            models::ReadinessReport,
             models::AppError
         )
     ),
     tags(
         (name = "XML Comparison", description = "XML comparison endpoints"),
+        (name = "JSON Comparison", description = "JSON comparison endpoints"),
+        (name = "Document Comparison", description = "Format-detecting comparison endpoints"),
         (name = "URL Comparison", description = "URL-based XML comparison endpoints"),
         (name = "Batch Comparison", description = "Batch XML comparison endpoints"),
-        (name = "Authentication", description = "Authentication endpoints")
+        (name = "Jobs", description = "Running batches"),
+        (name = "v2", description = "Comparison endpoints taking nested options and returning summaries"),
+        (name = "XML Tools", description = "XML document utilities"),
+        (name = "Profiles", description = "Stored comparison profiles"),
+        (name = "Baselines", description = "Reference documents with accepted differences, for regression checks"),
+        (name = "History", description = "Past comparisons, kept when APP_HISTORY_DB is set"),
+        (name = "Authentication", description = "Authentication endpoints"),
+        (name = "Admin", description = "Operator endpoints, enabled by APP_ADMIN_TOKEN"),
+        (name = "Health", description = "Service health")
     ),
     servers(
         (url = "/xml-compare-api", description = "XML Compare API Server (Base Path)")
@@ -61,48 +211,262 @@ use services::{XmlComparisonService, HttpClientService, AuthService};
 )]
 struct ApiDoc;
 
+// Sessions are encrypted in Redis whenever a key is configured
+async fn redis_session_store(url: &str, config: &AppConfig) -> models::AppResult<RedisSessionStore> {
+    let key = match (&config.session_encryption_key, &config.session_encryption_key_file) {
+        (Some(key), _) => Some(key.clone()),
+        (None, Some(path)) => Some(std::fs::read_to_string(path).map_err(|e| {
+            models::AppError::ValidationError(format!("Cannot read session encryption key {}: {}", path, e))
+        })?),
+        (None, None) => None,
+    };
+    let cipher = key.as_deref().map(SessionCipher::from_base64).transpose()?;
+    let store = RedisSessionStore::connect(url).await?;
+    Ok(match cipher {
+        Some(cipher) => store.with_cipher(cipher),
+        None => {
+            tracing::warn!("Sessions are stored in Redis unencrypted; set APP_SESSION_ENCRYPTION_KEY");
+            store
+        }
+    })
+}
+
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt::init();
+    let config = AppConfig::from_env();
 
-    // Get port from environment variable or default to 3000
-    let port = std::env::var("APP_PORT")
-        .unwrap_or_else(|_| "3000".to_string())
-        .parse::<u16>()
-        .unwrap_or(3000);
+    // Initialize tracing
+    if config.log_json {
+        tracing_subscriber::fmt().json().init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
+    let port = config.port;
 
     // Create services
-    let xml_service = XmlComparisonService::new();
-    let http_client = Arc::new(HttpClientService::new());
-    let auth_service = Arc::new(AuthService::new(http_client.clone()));
+    let xml_service = XmlComparisonService::new().with_parse_cache(config.parse_cache_bytes).with_parse_limits(ParseLimits {
+        max_depth: config.max_xml_depth,
+        max_elements: config.max_xml_elements,
+        max_attributes: config.max_xml_attributes,
+    });
+    let json_service = JsonComparisonService::new();
+    let http_client = HttpClientService::with_defaults(models::FetchOptions {
+        connect_timeout_ms: Some(config.fetch_connect_timeout_secs * 1000),
+        read_timeout_ms: Some(config.fetch_read_timeout_secs * 1000),
+        timeout_ms: Some(config.fetch_timeout_secs * 1000),
+        max_bytes: Some(config.fetch_max_bytes),
+        proxy: config.fetch_proxy.clone().map(|url| models::ProxyOptions {
+            url,
+            username: config.fetch_proxy_username.clone(),
+            password: config.fetch_proxy_password.clone(),
+            no_proxy: config.fetch_no_proxy.clone(),
+        }),
+        max_redirects: Some(config.fetch_max_redirects),
+        cross_host_redirects: Some(config.fetch_cross_host_redirects),
+        oauth2: None,
+        bearer_token: None,
+        credential: None,
+        content_types: Some(config.fetch_content_types.clone()),
+    })
+    .with_bearer_tokens(&config.fetch_bearer_tokens)
+    .with_document_cache(config.fetch_cache_bytes)
+    .with_pool(PoolOptions {
+        max_idle_per_host: config.fetch_pool_max_idle,
+        idle_timeout: (config.fetch_pool_idle_secs > 0).then(|| Duration::from_secs(config.fetch_pool_idle_secs)),
+        tcp_keepalive: (config.fetch_tcp_keepalive_secs > 0).then(|| Duration::from_secs(config.fetch_tcp_keepalive_secs)),
+        http_version: config.fetch_http_version,
+    })
+    .and_then(|http_client| http_client.with_client_certificates(&config.fetch_client_certs));
+    let http_client = match http_client {
+        Ok(http_client) => Arc::new(http_client),
+        Err(error) => {
+            tracing::error!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let mut auth_service = AuthService::new(http_client.clone())
+        .with_refresh_margin(Duration::from_secs(config.session_refresh_minutes * 60));
+    if let Some(url) = &config.session_store_url {
+        match redis_session_store(url, &config).await {
+            Ok(store) => {
+                tracing::info!("Sessions are kept in Redis");
+                auth_service = auth_service.with_session_store(Arc::new(store));
+            }
+            Err(error) => {
+                tracing::error!("{}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+    let auth_service = Arc::new(auth_service);
+    let profile_service = Arc::new(ProfileService::new());
+    let webhook_service = WebhookService::new(config.webhook_secret.clone());
+    let alerts = match AlertService::from_config(&config) {
+        Ok(alerts) => alerts,
+        Err(error) => {
+            tracing::error!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let result_cache = ResultCache::new(Duration::from_secs(config.cache_ttl_secs), config.cache_max_entries);
+    let jwt_source = match (&config.jwt_jwks_url, &config.jwt_secret) {
+        (Some(url), _) => Some(JwtKeySource::JwksUrl(url.clone())),
+        (None, Some(secret)) => Some(JwtKeySource::Secret(secret.clone())),
+        (None, None) => None,
+    };
+    let jwt_auth = jwt_source
+        .map(|source| Arc::new(JwtAuthService::new(source, config.jwt_issuer.clone(), config.jwt_audience.clone())));
+    let batch_pool = match BatchPool::new(config.batch_parallelism) {
+        Ok(batch_pool) => batch_pool,
+        Err(error) => {
+            tracing::error!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let result_archive = match config.result_store.as_deref().map(open_result_store).transpose() {
+        Ok(Some(store)) => {
+            tracing::info!("Batch results are kept for {} hours", config.result_ttl_hours);
+            ResultArchive::new(store, Duration::from_secs(config.result_ttl_hours * 3600))
+        }
+        Ok(None) => ResultArchive::default(),
+        Err(error) => {
+            tracing::error!("{}", error);
+            std::process::exit(1);
+        }
+    };
+
+    let history = match config.history_db.as_deref() {
+        Some(path) => {
+            let retention = (config.history_retention_days > 0)
+                .then(|| Duration::from_secs(config.history_retention_days * 24 * 3600));
+            match HistoryService::open(path, config.history_flagged_only, retention) {
+                Ok(history) => history,
+                Err(error) => {
+                    tracing::error!("{}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => HistoryService::default(),
+    };
+    let tenants = match config.tenants_file.as_deref().map(TenantService::from_file).transpose() {
+        Ok(Some(tenants)) => {
+            tracing::info!("API routes require the API key of one of {} tenants", tenants.usage().len());
+            tenants
+        }
+        Ok(None) => TenantService::default(),
+        Err(error) => {
+            tracing::error!("{}", error);
+            std::process::exit(1);
+        }
+    };
 
     // Create app state
     let state = Arc::new(AppStateInner {
         xml_service,
+        json_service,
         http_client,
         auth_service,
+        profile_service,
+        baseline_service: Arc::new(BaselineService::new()),
+        webhook_service,
+        alerts,
+        result_cache,
+        jwt_auth,
+        admin_token: config.admin_token.clone(),
+        tenants: Arc::new(tenants),
+        health_service: Arc::new(HealthService::new(config.health_probe_urls.clone())),
+        limits: Limits::new(config.max_body_bytes, config.max_batch_items, config.max_concurrent_comparisons)
+            .with_memory(config.max_request_memory_bytes, config.memory_budget_bytes),
+        batch_pool,
+        jobs: JobRegistry::default().with_progress_log_interval(Duration::from_secs(config.job_progress_log_secs)),
+        result_archive,
+        history,
     });
 
     // Configure CORS
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_origin(Any);
 
+    // Admin routes additionally require the admin token
+    let admin_routes = Router::new()
+        .route("/xml-compare-api/api/admin/sessions", get(admin_handlers::list_sessions))
+        .route("/xml-compare-api/api/admin/sessions/:session_id", delete(admin_handlers::expire_session))
+        .route("/xml-compare-api/api/admin/tenants", get(admin_handlers::tenant_usage))
+        .route_layer(from_fn_with_state(state.clone(), require_admin));
+
     // Main app router - flattened for app-runner-router compatibility
     let app = Router::new()
-        // Landing page for base path (both with and without trailing slash)
-        .route("/xml-compare-api", get(landing_page))
-        .route("/xml-compare-api/", get(landing_page))
-        
         // API endpoints at base path level (flattened, no nesting)
         .route("/xml-compare-api/api/compare/xml", post(comparison_handlers::compare_xmls))
+        .route("/xml-compare-api/api/compare/xml/report", post(comparison_handlers::compare_xmls_report))
+        .route("/xml-compare-api/api/compare/xml-json", post(comparison_handlers::compare_xml_json))
+        .route("/xml-compare-api/api/compare/json", post(comparison_handlers::compare_json))
+        .route("/xml-compare-api/api/compare/document", post(comparison_handlers::compare_document))
         .route("/xml-compare-api/api/compare/xml/batch", post(comparison_handlers::compare_xmls_batch))
-        .route("/xml-compare-api/api/compare/url", post(comparison_handlers::compare_urls))
+        .route("/xml-compare-api/api/compare/url", post(comparison_handlers::compare_urls).get(comparison_handlers::compare_urls_query))
         .route("/xml-compare-api/api/compare/url/batch", post(comparison_handlers::compare_urls_batch))
+        .route("/xml-compare-api/api/compare/url/batch/manifest", post(comparison_handlers::compare_manifest))
+        .route("/xml-compare-api/api/compare/environments", post(comparison_handlers::compare_environments))
+        .route("/xml-compare-api/api/compare/mixed", post(comparison_handlers::compare_mixed))
+        .route("/xml-compare-api/api/compare/golden", post(comparison_handlers::compare_golden))
+        .route("/xml-compare-api/api/cache/stats", get(comparison_handlers::cache_stats))
+        .route("/xml-compare-api/api/jobs", get(job_handlers::list_jobs))
+        .route("/xml-compare-api/api/jobs/:job_id", delete(job_handlers::cancel_job))
+        .route("/xml-compare-api/api/jobs/:job_id/progress", get(job_handlers::job_progress))
+        .route("/xml-compare-api/api/jobs/:job_id/results", get(job_handlers::job_results))
+        .route("/xml-compare-api/api/jobs/:job_id/results/failed", get(job_handlers::failed_job_results))
+        .route("/xml-compare-api/api/jobs/:job_id/results/items/:index", get(job_handlers::job_result_item))
+        .route("/xml-compare-api/api/v2/compare/xml", post(v2_handlers::compare_xmls))
+        .route("/xml-compare-api/api/v2/compare/xml/batch", post(v2_handlers::compare_xmls_batch))
+        .route("/xml-compare-api/api/v2/compare/url", post(v2_handlers::compare_urls))
+        .route("/xml-compare-api/api/v2/compare/url/batch", post(v2_handlers::compare_urls_batch))
+        .route("/xml-compare-api/graphql", get(graphql_handlers::graphiql).post(graphql_handlers::graphql))
+        .route("/xml-compare-api/api/xml/canonicalize", post(xml_handlers::canonicalize_xml))
+        .route("/xml-compare-api/api/xml/validate", post(xml_handlers::validate_xml))
+        .route("/xml-compare-api/api/xml/format", post(xml_handlers::format_xml))
+        .route("/xml-compare-api/api/profiles", get(profile_handlers::list_profiles).post(profile_handlers::create_profile))
+        .route(
+            "/xml-compare-api/api/profiles/:name",
+            get(profile_handlers::get_profile)
+                .put(profile_handlers::update_profile)
+                .delete(profile_handlers::delete_profile),
+        )
+        .route("/xml-compare-api/api/baselines", get(baseline_handlers::list_baselines).post(baseline_handlers::create_baseline))
+        .route(
+            "/xml-compare-api/api/baselines/:name",
+            get(baseline_handlers::get_baseline)
+                .put(baseline_handlers::update_baseline)
+                .delete(baseline_handlers::delete_baseline),
+        )
+        .route("/xml-compare-api/api/baselines/:name/compare", post(baseline_handlers::compare_baseline))
+        .route("/xml-compare-api/api/baselines/:name/accept", post(baseline_handlers::accept_baseline_diffs))
         .route("/xml-compare-api/api/auth/login", post(auth_handlers::login))
         .route("/xml-compare-api/api/auth/logout/:session_id", post(auth_handlers::logout))
-        .route("/xml-compare-api/health", get(health_check))
+        .route("/xml-compare-api/api/auth/refresh/:session_id", post(auth_handlers::refresh))
+        .route("/xml-compare-api/api/history", get(history_handlers::get_history))
+        .route("/xml-compare-api/api/history/trends", get(history_handlers::get_trends))
+
+        // Comparisons run by the routes above are kept in the history when it is on
+        .route_layer(from_fn_with_state(state.clone(), comparison_history))
+
+        // Routes above require a tenant's API key when tenants are configured; admin routes use the
+        // admin token instead
+        .route_layer(from_fn_with_state(state.clone(), require_api_key))
+        .merge(admin_routes)
+
+        // Routes above require a bearer token when JWT authentication is configured
+        .route_layer(from_fn_with_state(state.clone(), require_jwt))
+
+        // Landing page for base path (both with and without trailing slash)
+        .route("/xml-compare-api", get(landing_page))
+        .route("/xml-compare-api/", get(landing_page))
+        // The viewer page itself is public; the comparisons it runs go through the routes above
+        .route("/xml-compare-api/viewer", get(viewer_handlers::viewer_page))
+        .route("/xml-compare-api/health", get(health_handlers::health))
+        .route("/xml-compare-api/healthz/live", get(health_handlers::live))
+        .route("/xml-compare-api/healthz/ready", get(health_handlers::ready))
         
         // Swagger UI at base path level only
         .merge(SwaggerUi::new("/xml-compare-api/swagger-ui").url("/xml-compare-api/api-docs/openapi.json", ApiDoc::openapi()))
@@ -110,19 +474,37 @@ async fn main() {
         // Apply state to all routes
         .with_state(state.clone())
         
-        // Configure body limits (500MB for large batch operations)
-        .layer(DefaultBodyLimit::max(500 * 1024 * 1024))
+        // Body limit (500MB by default for large batch operations) applies to the decompressed body
+        .layer(DefaultBodyLimit::max(config.max_body_bytes))
+        .layer(RequestDecompressionLayer::new())
+        .layer(from_fn_with_state(state.clone(), body_limit_errors))
+        .layer(from_fn(request_context))
         .layer(cors);
 
-        // Start background session cleanup task
+    // Flipped to true when SIGTERM/SIGINT arrives
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    // Start background session cleanup task
     let auth_service_cleanup = state.auth_service.clone();
-    tokio::spawn(async move {
+    let result_archive = state.result_archive.clone();
+    let history = state.history.clone();
+    let health_service = state.health_service.clone();
+    health_service.register_component("session_cleanup");
+    let mut cleanup_shutdown = shutdown_rx.clone();
+    let cleanup_task = tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300)); // Clean up every 5 minutes
         loop {
-            interval.tick().await;
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = cleanup_shutdown.wait_for(|stop| *stop) => break,
+            }
             auth_service_cleanup.cleanup_expired_sessions().await;
+            result_archive.remove_expired().await;
+            history.remove_expired().await;
+            health_service.mark_ready("session_cleanup");
             tracing::debug!("Cleaned up expired sessions");
         }
+        tracing::debug!("Session cleanup task stopped");
     });
 
     // Start server
@@ -136,14 +518,80 @@ async fn main() {
     tracing::info!("  - http://0.0.0.0:{}/xml-compare-api/swagger-ui/ (base path)", port);
     tracing::info!("Health check available at:");
     tracing::info!("  - http://0.0.0.0:{}/xml-compare-api/health (base path)", port);
+    tracing::info!("  - http://0.0.0.0:{}/xml-compare-api/healthz/live and /healthz/ready (probes)", port);
     tracing::info!("Base path (/) shows landing page");
     tracing::info!("Session cleanup task started (runs every 5 minutes)");
 
-    axum::serve(listener, app).await.unwrap();
+    let mut server_shutdown = shutdown_rx.clone();
+    let server = tokio::spawn(async move {
+        axum::serve(listener, app)
+            .with_graceful_shutdown(async move {
+                let _ = server_shutdown.wait_for(|stop| *stop).await;
+            })
+            .await
+    });
+
+    shutdown_signal().await;
+    // Readiness fails while connections are still accepted, so that probes see it and load
+    // balancers stop routing here before the listener closes
+    state.health_service.begin_shutdown();
+    let grace = Duration::from_secs(config.shutdown_ready_grace_secs);
+    tracing::info!("Shutting down: reporting not ready for {:?} before closing the listener", grace);
+    tokio::time::sleep(grace).await;
+
+    let timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    let mut deadline = Instant::now() + timeout;
+    tracing::info!("No longer accepting connections, waiting up to {:?} for in-flight work", timeout);
+    let _ = shutdown_tx.send(true);
+
+    // In-flight requests, including running batches, finish before the server future resolves.
+    // Batches still running at the deadline are cancelled: each stops, stores the results it has
+    // and marks the items it had not started as cancelled, then answers its caller.
+    let mut server = server;
+    let mut finished = tokio::time::timeout_at(deadline, &mut server).await;
+    if finished.is_err() {
+        let cancelled = state.jobs.cancel_all();
+        tracing::warn!("Shutdown timeout reached; cancelled {} running batches so that their results are kept", cancelled);
+        deadline += JOB_PERSIST_GRACE;
+        finished = tokio::time::timeout_at(deadline, &mut server).await;
+    }
+    match finished {
+        Ok(Ok(Ok(()))) => tracing::info!("In-flight requests finished"),
+        Ok(Ok(Err(e))) => tracing::error!("Server error: {}", e),
+        Ok(Err(e)) => tracing::error!("Server task failed: {}", e),
+        Err(_) => tracing::warn!("Exiting with requests still in flight"),
+    }
+    let undelivered = state.webhook_service.drain(deadline.saturating_duration_since(Instant::now())).await;
+    if undelivered > 0 {
+        tracing::warn!("Exiting with {} batch callbacks undelivered", undelivered);
+    }
+    let unsent = state.alerts.drain(deadline.saturating_duration_since(Instant::now())).await;
+    if unsent > 0 {
+        tracing::warn!("Exiting with {} mismatch alerts unsent", unsent);
+    }
+    let _ = cleanup_task.await;
+    tracing::info!("Shutdown complete");
 }
 
-async fn health_check() -> &'static str {
-    "OK"
+// Resolves on SIGINT (Ctrl+C) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("failed to install Ctrl+C handler");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
 
 async fn landing_page() -> axum::response::Html<&'static str> {
@@ -254,6 +702,8 @@ async fn landing_page() -> axum::response::Html<&'static str> {
         
         .method.post { background: #007bff; color: white; }
         .method.get { background: #28a745; color: white; }
+        .method.put { background: #fd7e14; color: white; }
+        .method.delete { background: #dc3545; color: white; }
         
         .quick-start {
             background: #e3f2fd;
@@ -376,6 +826,22 @@ async fn landing_page() -> axum::response::Html<&'static str> {
                     <span class="method post">POST</span>
                     <code>/xml-compare-api/api/compare/xml</code> - Compare two XML strings
                 </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/xml/report</code> - HTML side-by-side diff report
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/xml-json</code> - Compare XML with JSON
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/json</code> - Compare two JSON documents
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/document</code> - Compare XML/JSON/YAML, format detected
+                </div>
                 <div class="endpoint">
                     <span class="method post">POST</span>
                     <code>/xml-compare-api/api/compare/xml/batch</code> - Batch XML comparison
@@ -384,10 +850,116 @@ async fn landing_page() -> axum::response::Html<&'static str> {
                     <span class="method post">POST</span>
                     <code>/xml-compare-api/api/compare/url</code> - Compare XMLs from URLs
                 </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/compare/url?url1=…&amp;url2=…</code> - Same, from query parameters
+                </div>
                 <div class="endpoint">
                     <span class="method post">POST</span>
                     <code>/xml-compare-api/api/compare/url/batch</code> - Batch URL comparison
                 </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/url/batch/manifest</code> - Batch URL comparison from a CSV or JSON manifest
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/environments</code> - Compare the same paths under two base URLs
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/mixed</code> - Compare a URL with an inline XML (or any mix)
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/compare/golden</code> - Reference XML vs many candidates, ranked
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/v2/compare/{xml,url}[/batch]</code> - v2: nested options, summarized results
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/graphql</code> - GraphQL queries (GraphiQL on GET)
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/xml/canonicalize</code> - Canonical XML (C14N) form of a document
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/xml/validate</code> - Validate a document against an XSD
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/xml/format</code> - Pretty-print or minify a document
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/cache/stats</code> - Result cache hit statistics
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/jobs</code> - Running batches
+                </div>
+                <div class="endpoint">
+                    <span class="method delete">DELETE</span>
+                    <code>/xml-compare-api/api/jobs/{id}</code> - Cancel a running batch
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/jobs/{id}/progress</code> - Progress and ETA of a running batch
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/jobs/{id}/results</code> - Stored results of a finished batch
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/jobs/{id}/results/failed</code> - Stored items that did not match
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/jobs/{id}/results/items/{index}</code> - One stored item with its diffs
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/profiles</code> - List / create comparison profiles
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <span class="method put">PUT</span>
+                    <span class="method delete">DELETE</span>
+                    <code>/xml-compare-api/api/profiles/{name}</code> - Read / update / delete a profile
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/baselines</code> - List / store baseline documents
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <span class="method put">PUT</span>
+                    <span class="method delete">DELETE</span>
+                    <code>/xml-compare-api/api/baselines/{name}</code> - Read / update / delete a baseline
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/baselines/{name}/compare</code> - Report differences from a baseline that are not accepted
+                </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/baselines/{name}/accept</code> - Accept a document's differences from a baseline
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/history</code> - Past comparisons, filtered by date, outcome, URL or tenant
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/history/trends</code> - Match ratio, differing path and diff type trends over time
+                </div>
                 <div class="endpoint">
                     <span class="method post">POST</span>
                     <code>/xml-compare-api/api/auth/login</code> - Authenticate with URL
@@ -396,9 +968,37 @@ async fn landing_page() -> axum::response::Html<&'static str> {
                     <span class="method post">POST</span>
                     <code>/xml-compare-api/api/auth/logout/{session_id}</code> - Logout session
                 </div>
+                <div class="endpoint">
+                    <span class="method post">POST</span>
+                    <code>/xml-compare-api/api/auth/refresh/{session_id}</code> - Extend a session
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/admin/sessions</code> - List active sessions (admin token)
+                </div>
+                <div class="endpoint">
+                    <span class="method delete">DELETE</span>
+                    <code>/xml-compare-api/api/admin/sessions/{session_id}</code> - Force-expire a session (admin token)
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/api/admin/tenants</code> - Tenant limits and usage (admin token)
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/health</code> - Health report with upstream checks
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/healthz/live</code> - Liveness probe
+                </div>
+                <div class="endpoint">
+                    <span class="method get">GET</span>
+                    <code>/xml-compare-api/healthz/ready</code> - Readiness probe (503 while starting)
+                </div>
                 <div class="endpoint">
                     <span class="method get">GET</span>
-                    <code>/xml-compare-api/health</code> - Health check
+                    <code>/xml-compare-api/viewer</code> - Interactive side-by-side diff viewer
                 </div>
             </div>
             
@@ -448,6 +1048,7 @@ curl -X POST http://localhost:3000/xml-compare-api/api/compare/url \
             </div>
             
             <div style="text-align: center; margin: 40px 0;">
+                <a href="/xml-compare-api/viewer" class="btn">🔍 Diff Viewer</a>
                 <a href="/xml-compare-api/swagger-ui/" class="btn">📚 Interactive API Docs</a>
                 <a href="/xml-compare-api/health" class="btn secondary">🏥 Health Check</a>
             </div>
@@ -0,0 +1,27 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::models::{AppError, AppResult};
+
+pub const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// Lets requests through only with the configured admin token; without one the admin routes are
+/// disabled
+pub async fn require_admin(State(state): State<AppState>, request: Request, next: Next) -> AppResult<Response> {
+    let Some(expected) = &state.admin_token else {
+        return Err(AppError::Forbidden("Admin endpoints are disabled; set APP_ADMIN_TOKEN".to_string()));
+    };
+    let given = request.headers().get(ADMIN_TOKEN_HEADER).and_then(|value| value.to_str().ok());
+    if !given.is_some_and(|given| constant_time_eq(given.as_bytes(), expected.as_bytes())) {
+        return Err(AppError::AuthError("Missing or wrong admin token".to_string()));
+    }
+    Ok(next.run(request).await)
+}
+
+// Takes as long for a near miss as for a wrong first byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,44 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::middleware::{current_request_id, current_tenant_name, RecordedComparison};
+use std::sync::Mutex;
+
+/// Asks for the request's comparisons to be kept when `APP_HISTORY_FLAGGED_ONLY` is set
+pub const RECORD_HISTORY_HEADER: &str = "x-record-history";
+
+tokio::task_local! {
+    static RECORDED: Mutex<Vec<RecordedComparison>>;
+}
+
+/// Saves the comparisons a request ran to the history once it has been answered
+pub async fn comparison_history(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let flagged = request
+        .headers()
+        .get(RECORD_HISTORY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| matches!(value.trim().to_ascii_lowercase().as_str(), "true" | "1" | "yes"));
+    if !state.history.records(flagged) {
+        return next.run(request).await;
+    }
+    let endpoint = request.uri().path().to_string();
+    let (response, comparisons) = RECORDED
+        .scope(Mutex::default(), async move {
+            let response = next.run(request).await;
+            (response, RECORDED.with(|recorded| std::mem::take(&mut *recorded.lock().unwrap())))
+        })
+        .await;
+    state.history.save(current_request_id(), current_tenant_name(), endpoint, comparisons).await;
+    response
+}
+
+pub(crate) fn is_recording() -> bool {
+    RECORDED.try_with(|_| ()).is_ok()
+}
+
+pub(crate) fn remember(comparison: RecordedComparison) {
+    let _ = RECORDED.try_with(|recorded| recorded.lock().unwrap().push(comparison));
+}
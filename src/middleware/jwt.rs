@@ -0,0 +1,34 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::models::{AppError, AppResult};
+use tracing::Instrument;
+
+/// Requires a valid bearer token when JWT authentication is configured. The verified claims are
+/// added to the request extensions, and the subject to the request's log lines.
+pub async fn require_jwt(State(state): State<AppState>, mut request: Request, next: Next) -> AppResult<Response> {
+    let Some(jwt_auth) = &state.jwt_auth else {
+        return Ok(next.run(request).await);
+    };
+    let token = bearer_token(request.headers())
+        .ok_or_else(|| AppError::AuthError("Missing bearer token".to_string()))?
+        .to_string();
+    let claims = jwt_auth.verify(&token).await.inspect_err(|e| {
+        tracing::warn!(method = %request.method(), path = %request.uri().path(), "Rejected request: {}", e);
+    })?;
+
+    tracing::info!(subject = %claims.sub, method = %request.method(), path = %request.uri().path(), "Authenticated request");
+    let span = tracing::info_span!("auth", subject = %claims.sub);
+    request.extensions_mut().insert(claims);
+    Ok(next.run(request).instrument(span).await)
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+}
@@ -0,0 +1,209 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::middleware::tenants::current_tenant;
+use crate::models::{AppError, AppResult};
+use std::sync::Arc;
+use xml_compare_core::estimate_comparison_memory;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Size and concurrency limits shared by the comparison endpoints
+#[derive(Clone)]
+pub struct Limits {
+    pub max_body_bytes: usize,
+    pub max_batch_items: usize,
+    comparison_slots: Option<Arc<Semaphore>>,
+    max_request_memory: usize,
+    memory_budget_bytes: usize,
+    // Permits are KiB, so that budgets past 4 GiB fit the semaphore
+    memory_budget: Option<Arc<Semaphore>>,
+}
+
+const MB: usize = 1024 * 1024;
+
+impl Limits {
+    /// `max_concurrent_comparisons` of 0 leaves concurrency unlimited
+    pub fn new(max_body_bytes: usize, max_batch_items: usize, max_concurrent_comparisons: usize) -> Self {
+        Self {
+            max_body_bytes,
+            max_batch_items,
+            comparison_slots: (max_concurrent_comparisons > 0).then(|| Arc::new(Semaphore::new(max_concurrent_comparisons))),
+            max_request_memory: 0,
+            memory_budget_bytes: 0,
+            memory_budget: None,
+        }
+    }
+
+    /// Caps the estimated memory of one comparison request, and of all of them together; 0 leaves
+    /// either unlimited
+    pub fn with_memory(mut self, max_request_memory: usize, memory_budget: usize) -> Self {
+        self.max_request_memory = max_request_memory;
+        self.memory_budget_bytes = memory_budget.min(Semaphore::MAX_PERMITS.saturating_mul(1024));
+        self.memory_budget = (memory_budget > 0).then(|| Arc::new(Semaphore::new(self.memory_budget_bytes.div_ceil(1024))));
+        self
+    }
+
+    /// Applies the tenant's lower limit, if the request has a tenant with one
+    pub fn check_batch_size(&self, items: usize) -> AppResult<()> {
+        let limit = current_tenant()
+            .and_then(|tenant| tenant.max_batch_items)
+            .map_or(self.max_batch_items, |limit| limit.min(self.max_batch_items));
+        if items > limit {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Batch has {} items; the limit is {}",
+                items, limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Takes a comparison slot without waiting; a busy server answers 429 instead of queueing
+    pub fn try_acquire(&self) -> AppResult<ComparisonPermit> {
+        let Some(slots) = &self.comparison_slots else {
+            return Ok(ComparisonPermit { slot: None, memory: MemoryReservation(None) });
+        };
+        slots
+            .clone()
+            .try_acquire_owned()
+            .map(|permit| ComparisonPermit { slot: Some(permit), memory: MemoryReservation(None) })
+            .map_err(|_| AppError::TooManyRequests("Too many comparisons in progress; retry shortly".to_string()))
+    }
+
+    /// Sets aside the estimated memory of comparing `document_bytes` of XML. A request that could
+    /// never fit is refused with 413; one that only has to wait for others to finish gets 429
+    pub fn reserve_memory(&self, document_bytes: usize) -> AppResult<MemoryReservation> {
+        let estimate = estimate_comparison_memory(document_bytes);
+        let limit = match (self.max_request_memory, self.memory_budget_bytes) {
+            (0, budget) | (budget, 0) => budget,
+            (request, budget) => request.min(budget),
+        };
+        if limit > 0 && estimate > limit {
+            return Err(AppError::PayloadTooLarge(format!(
+                "Comparing {} bytes of XML needs an estimated {} MB; the limit is {} MB",
+                document_bytes,
+                estimate.div_ceil(MB),
+                limit / MB
+            )));
+        }
+        self.reserve(estimate)
+    }
+
+    // A body of unknown size, sent chunked or compressed, is taken to need the most one request
+    // may use
+    fn reserve_unknown(&self) -> AppResult<MemoryReservation> {
+        let estimate = estimate_comparison_memory(self.max_body_bytes);
+        self.reserve(match self.max_request_memory {
+            0 => estimate,
+            limit => estimate.min(limit),
+        }
+        .min(self.memory_budget_bytes))
+    }
+
+    fn reserve(&self, bytes: usize) -> AppResult<MemoryReservation> {
+        let Some(budget) = &self.memory_budget else {
+            return Ok(MemoryReservation(None));
+        };
+        let kib = bytes.div_ceil(1024).clamp(1, Semaphore::MAX_PERMITS) as u32;
+        budget
+            .clone()
+            .try_acquire_many_owned(kib)
+            .map(|permit| MemoryReservation(Some(permit)))
+            .map_err(|_| AppError::TooManyRequests("Not enough memory free for this comparison; retry shortly".to_string()))
+    }
+}
+
+/// Held by a comparison handler for as long as it runs
+#[allow(dead_code)]
+pub struct ComparisonPermit {
+    slot: Option<OwnedSemaphorePermit>,
+    memory: MemoryReservation,
+}
+
+/// Memory set aside from the global budget until dropped
+pub struct MemoryReservation(#[allow(dead_code)] Option<OwnedSemaphorePermit>);
+
+#[async_trait]
+impl FromRequestParts<AppState> for ComparisonPermit {
+    type Rejection = AppError;
+
+    // The memory is reserved from the body's declared size, before any of it is read
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let mut permit = state.limits.try_acquire()?;
+        let body_bytes = parts
+            .headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok()?.parse::<usize>().ok());
+        permit.memory = match body_bytes {
+            Some(bytes) => state.limits.reserve_memory(bytes)?,
+            None => state.limits.reserve_unknown()?,
+        };
+        Ok(permit)
+    }
+}
+
+/// Replaces the plain-text 413 of the body limit with the usual error JSON
+pub async fn body_limit_errors(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    if !is_plain_payload_too_large(&response) {
+        return response;
+    }
+    AppError::PayloadTooLarge(format!(
+        "Request body exceeds the {} byte limit",
+        state.limits.max_body_bytes
+    ))
+    .into_response()
+}
+
+pub(crate) fn is_plain_payload_too_large(response: &Response) -> bool {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value.as_bytes().starts_with(b"application/json"));
+    response.status() == StatusCode::PAYLOAD_TOO_LARGE && !is_json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits::new(1024, 2, 1);
+        assert!(limits.check_batch_size(2).is_ok());
+        assert!(matches!(limits.check_batch_size(3), Err(AppError::PayloadTooLarge(_))));
+
+        let permit = limits.try_acquire().unwrap();
+        assert!(matches!(limits.try_acquire(), Err(AppError::TooManyRequests(_))));
+        drop(permit);
+        assert!(limits.try_acquire().is_ok());
+
+        let unlimited = Limits::new(1024, 2, 0);
+        let _permits = [unlimited.try_acquire().unwrap(), unlimited.try_acquire().unwrap()];
+        assert!(unlimited.reserve_memory(usize::MAX).is_ok());
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mb = 1024 * 1024;
+        let limits = Limits::new(mb, 2, 0).with_memory(4 * mb, 6 * mb);
+        assert!(matches!(limits.reserve_memory(mb), Err(AppError::PayloadTooLarge(_))));
+
+        // Two requests fit the budget together; a third waits for one of them
+        let first = limits.reserve_memory(300 * 1024).unwrap();
+        let second = limits.reserve_memory(300 * 1024).unwrap();
+        assert!(matches!(limits.reserve_memory(300 * 1024), Err(AppError::TooManyRequests(_))));
+        drop(first);
+        assert!(limits.reserve_memory(300 * 1024).is_ok());
+        drop(second);
+
+        // A body of unknown size takes the per-request limit
+        let unknown = limits.reserve_unknown().unwrap();
+        assert!(matches!(limits.reserve_memory(300 * 1024), Err(AppError::TooManyRequests(_))));
+        drop(unknown);
+    }
+}
@@ -0,0 +1,13 @@
+pub mod jwt;
+pub mod request_context;
+pub mod admin;
+pub mod limits;
+pub mod tenants;
+pub mod history;
+
+pub use jwt::*;
+pub use request_context::*;
+pub use admin::*;
+pub use limits::*;
+pub use tenants::*;
+pub use history::*;
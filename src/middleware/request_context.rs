@@ -0,0 +1,141 @@
+use axum::{
+    extract::Request,
+    http::HeaderValue,
+    middleware::Next,
+    response::Response,
+};
+use crate::models::{ComparisonResultV2, DiffType, XmlComparisonResponse, XmlDiff};
+use crate::services::strip_path_indexes;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_CONTEXT: RequestContext;
+}
+
+struct RequestContext {
+    id: String,
+    stats: Mutex<ComparisonStats>,
+}
+
+/// Comparisons a request ran, summed for its log line
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ComparisonStats {
+    pub comparisons: usize,
+    pub matched: usize,
+    pub diffs: usize,
+}
+
+/// Takes the caller's `X-Request-Id` or generates one, echoes it on the response, runs the
+/// request inside a span carrying it, and logs one line per request with the outcome
+pub async fn request_context(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| valid_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let span = tracing::info_span!("request", request_id = %id, method = %request.method(), path = %request.uri().path());
+    let context = RequestContext { id: id.clone(), stats: Mutex::default() };
+
+    let started = Instant::now();
+    let (mut response, stats) = REQUEST_CONTEXT
+        .scope(context, async move {
+            let response = next.run(request).await;
+            (response, REQUEST_CONTEXT.with(|context| *context.stats.lock().unwrap()))
+        })
+        .instrument(span.clone())
+        .await;
+
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.status().as_u16(),
+            duration_ms = started.elapsed().as_secs_f64() * 1000.0,
+            comparisons = stats.comparisons,
+            matched = stats.matched,
+            diffs = stats.diffs,
+            "Request completed"
+        )
+    });
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// ID of the request being handled, outside of spawned tasks
+pub fn current_request_id() -> Option<String> {
+    REQUEST_CONTEXT.try_with(|context| context.id.clone()).ok()
+}
+
+/// A finished comparison, as the request's log line, its tenant's usage and the history see it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordedComparison {
+    pub matched: bool,
+    pub diffs: usize,
+    pub match_ratio: f64,
+    /// Where downloaded documents came from
+    pub urls: Vec<String>,
+    /// Path without positions and type of each diff, for the history's trends; only filled while
+    /// the history is recording
+    pub diff_paths: Vec<(String, DiffType)>,
+}
+
+impl RecordedComparison {
+    pub fn diff_paths(diffs: &[XmlDiff]) -> Vec<(String, DiffType)> {
+        if !crate::middleware::history::is_recording() {
+            return Vec::new();
+        }
+        diffs.iter().map(|diff| (strip_path_indexes(&diff.path), diff.diff_type)).collect()
+    }
+}
+
+impl From<&XmlComparisonResponse> for RecordedComparison {
+    fn from(result: &XmlComparisonResponse) -> Self {
+        Self {
+            matched: result.matched,
+            diffs: result.diffs.len(),
+            match_ratio: result.match_ratio,
+            urls: result.sources.iter().map(|source| source.url.clone()).collect(),
+            diff_paths: Self::diff_paths(&result.diffs),
+        }
+    }
+}
+
+impl From<&ComparisonResultV2> for RecordedComparison {
+    fn from(result: &ComparisonResultV2) -> Self {
+        Self {
+            matched: result.matched,
+            diffs: result.summary.diff_count,
+            match_ratio: result.match_ratio,
+            urls: result.sources.iter().map(|source| source.url.clone()).collect(),
+            diff_paths: Self::diff_paths(&result.diffs),
+        }
+    }
+}
+
+/// Counts one comparison toward the request's log line and its tenant's usage, and keeps it for
+/// the history
+pub fn record_comparison(comparison: impl Into<RecordedComparison>) {
+    let comparison = comparison.into();
+    if let Some(tenant) = crate::middleware::current_tenant() {
+        tenant.record_comparison(comparison.matched, comparison.diffs);
+    }
+    let _ = REQUEST_CONTEXT.try_with(|context| {
+        let mut stats = context.stats.lock().unwrap();
+        stats.comparisons += 1;
+        stats.matched += usize::from(comparison.matched);
+        stats.diffs += comparison.diffs;
+    });
+    crate::middleware::history::remember(comparison);
+}
+
+// Caller IDs end up in headers and logs, so they are kept short and printable
+fn valid_request_id(id: &str) -> bool {
+    (1..=128).contains(&id.len()) && id.bytes().all(|byte| byte.is_ascii_graphic())
+}
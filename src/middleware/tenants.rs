@@ -0,0 +1,85 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use crate::handlers::comparison_handlers::AppState;
+use crate::middleware::limits::is_plain_payload_too_large;
+use crate::models::{AppError, AppResult};
+use crate::services::Tenant;
+use http_body_util::Limited;
+use std::future::Future;
+use std::sync::Arc;
+use tracing::Instrument;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+tokio::task_local! {
+    static CURRENT_TENANT: Arc<Tenant>;
+}
+
+/// Requires a tenant's API key when tenants are configured, applies the tenant's rate and body
+/// limits, and runs the request as that tenant
+pub async fn require_api_key(State(state): State<AppState>, request: Request, next: Next) -> AppResult<Response> {
+    if !state.tenants.is_enabled() {
+        return Ok(next.run(request).await);
+    }
+    let tenant = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|key| state.tenants.authenticate(key.trim()))
+        .ok_or_else(|| AppError::AuthError("Missing or unknown API key".to_string()))?;
+    tenant.admit().inspect_err(|e| tracing::warn!(tenant = %tenant.name, "Rejected request: {}", e))?;
+
+    let request = match tenant.max_body_bytes {
+        Some(limit) => {
+            let length = request
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse::<usize>().ok());
+            if length.is_some_and(|length| length > limit) {
+                return Err(body_too_large(limit));
+            }
+            // Chunked and decompressed bodies have no usable length, so reading stops at the limit
+            request.map(|body| Body::new(Limited::new(body, limit)))
+        }
+        None => request,
+    };
+
+    let span = tracing::info_span!("tenant", tenant = %tenant.name);
+    let limit = tenant.max_body_bytes;
+    let response = CURRENT_TENANT.scope(tenant, next.run(request)).instrument(span).await;
+    match limit {
+        Some(limit) if is_plain_payload_too_large(&response) => Ok(body_too_large(limit).into_response()),
+        _ => Ok(response),
+    }
+}
+
+/// Tenant of the request being handled; spawned tasks see it only through [`in_tenant_scope`]
+pub fn current_tenant() -> Option<Arc<Tenant>> {
+    CURRENT_TENANT.try_with(Arc::clone).ok()
+}
+
+/// Name that the request's profiles, baselines and sessions are stored under
+pub fn current_tenant_name() -> Option<String> {
+    CURRENT_TENANT.try_with(|tenant| tenant.name.clone()).ok()
+}
+
+/// Carries the current tenant into a future that is about to be spawned
+pub fn in_tenant_scope<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let tenant = current_tenant();
+    async move {
+        match tenant {
+            Some(tenant) => CURRENT_TENANT.scope(tenant, future).await,
+            None => future.await,
+        }
+    }
+}
+
+fn body_too_large(limit: usize) -> AppError {
+    AppError::PayloadTooLarge(format!("Request body exceeds the tenant's {} byte limit", limit))
+}
@@ -1,22 +1,30 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::RwLock;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use utoipa::ToSchema;
+use super::AuthCredentials;
 
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+/// How long a session lasts after logging in or being refreshed
+pub const SESSION_LIFETIME_HOURS: i64 = 1;
+
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
 pub struct Session {
     pub id: String,
     pub url: String,
     pub cookies: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub expires_at: DateTime<Utc>,
+    /// What the session was opened with, so that refreshing it can log in again
+    #[serde(default)]
+    pub credentials: Option<AuthCredentials>,
+    /// Requested with the session cookies to extend the session instead of logging in again
+    #[serde(default)]
+    pub keep_alive_url: Option<String>,
+    /// Tenant that opened the session; other tenants cannot use it
+    #[serde(default)]
+    pub tenant: Option<String>,
 }
 
-pub type SessionStore = Arc<RwLock<HashMap<String, Session>>>;
-
 impl Session {
     pub fn new(url: String, cookies: Vec<String>) -> Self {
         let now = Utc::now();
@@ -25,11 +33,86 @@ impl Session {
             url,
             cookies,
             created_at: now,
-            expires_at: now + chrono::Duration::hours(1), // 1 hour expiry
+            expires_at: now + chrono::Duration::hours(SESSION_LIFETIME_HOURS),
+            credentials: None,
+            keep_alive_url: None,
+            tenant: None,
         }
     }
 
+    /// Starts a full lifetime from now
+    pub fn extend(&mut self) {
+        self.expires_at = Utc::now() + chrono::Duration::hours(SESSION_LIFETIME_HOURS);
+    }
+
     pub fn is_expired(&self) -> bool {
         Utc::now() > self.expires_at
     }
-}
\ No newline at end of file
+}
+
+// Cookie values and credentials stay out of logs
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session")
+            .field("id", &self.id)
+            .field("url", &self.url)
+            .field("cookies", &self.cookies.iter().map(|cookie| redact_cookie(cookie)).collect::<Vec<_>>())
+            .field("created_at", &self.created_at)
+            .field("expires_at", &self.expires_at)
+            .field("credentials", &self.credentials)
+            .field("keep_alive_url", &self.keep_alive_url)
+            .field("tenant", &self.tenant)
+            .finish()
+    }
+}
+
+/// A `Set-Cookie` value with the cookie's value hidden and its attributes kept, such as
+/// `sid=[redacted]; Path=/; HttpOnly`
+pub fn redact_cookie(cookie: &str) -> String {
+    let (pair, attributes) = cookie.split_once(';').unwrap_or((cookie, ""));
+    let name = pair.split('=').next().unwrap_or_default().trim();
+    if attributes.is_empty() {
+        format!("{}=[redacted]", name)
+    } else {
+        format!("{}=[redacted];{}", name, attributes)
+    }
+}
+
+/// What the admin endpoints show of a session; cookies stay private
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SessionSummary {
+    pub id: String,
+    pub url: String,
+    pub tenant: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<&Session> for SessionSummary {
+    fn from(session: &Session) -> Self {
+        Self {
+            id: session.id.clone(),
+            url: session.url.clone(),
+            tenant: session.tenant.clone(),
+            created_at: session.created_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionListResponse {
+    /// Sessions in the store, including expired ones not yet cleaned up
+    pub total: usize,
+    pub active: usize,
+    /// Active sessions, oldest first
+    pub sessions: Vec<SessionSummary>,
+}
+
+/// Claims of a verified bearer token, attached to the request for handlers and audit logs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JwtClaims {
+    pub sub: String,
+    #[serde(default)]
+    pub iss: Option<String>,
+}
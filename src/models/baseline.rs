@@ -0,0 +1,50 @@
+use crate::models::{ComparisonOptions, XmlDiff};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+/// A named reference document that new documents are compared against, with the differences
+/// already accepted
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Baseline {
+    pub name: String,
+    pub description: Option<String>,
+    /// The reference XML, compared as `xml1`
+    pub xml: String,
+    /// Options every comparison against this baseline uses
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    /// Known differences; a diff is known when its path, type and values match one of these
+    #[serde(default)]
+    pub accepted_diffs: Vec<XmlDiff>,
+}
+
+/// Baselines by tenant and name, like profiles
+pub type BaselineStore = Arc<RwLock<HashMap<(Option<String>, String), Baseline>>>;
+
+/// A document to compare against a baseline, or whose diffs to accept
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BaselineComparisonRequest {
+    pub xml: String,
+    /// Added to the baseline's options, and win over them
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BaselineComparisonResponse {
+    pub baseline: String,
+    /// The document has differences that were not accepted
+    pub regression: bool,
+    pub new_diffs: Vec<XmlDiff>,
+    /// Differences the document has that were accepted
+    pub known_diffs: Vec<XmlDiff>,
+    /// Accepted differences the document no longer has
+    pub resolved_diffs: Vec<XmlDiff>,
+    pub match_ratio: f64,
+    /// `max_diffs` cut the comparison short, so some new diffs may be missing
+    pub truncated: bool,
+}
@@ -1,65 +1,206 @@
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use crate::models::{DownloadRequest, FetchOptions};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use xml_compare_core::{ComparisonOptions, DiffType, OutputFormat, XmlComparisonResponse};
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub struct XmlComparisonRequest {
+    /// Client's own reference for this comparison, echoed on its result in a batch
+    pub id: Option<String>,
     pub xml1: String,
     pub xml2: String,
-    pub ignore_paths: Option<Vec<String>>,
-    pub ignore_properties: Option<Vec<String>>,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    /// Name of a stored comparison profile whose options apply to this request
+    pub profile: Option<String>,
+    /// Deprecated: options sent as top-level fields, as before `options` existed. Values set in
+    /// `options` take precedence.
+    #[serde(flatten)]
+    pub legacy_options: ComparisonOptions,
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct XmlComparisonResponse {
-    pub matched: bool,
-    pub match_ratio: f64,
-    pub diffs: Vec<XmlDiff>,
-    pub total_elements: usize,
-    pub matched_elements: usize,
+impl XmlComparisonRequest {
+    /// `options`, with fields it leaves unset taken from the deprecated top-level fields
+    pub fn effective_options(&self) -> ComparisonOptions {
+        self.options.clone().or(self.legacy_options.clone())
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub struct XmlDiff {
-    pub path: String,
-    pub diff_type: DiffType,
-    pub expected: Option<String>,
-    pub actual: Option<String>,
-    pub message: String,
-}
-
-#[derive(Debug, Serialize, Deserialize, ToSchema)]
-pub enum DiffType {
-    ElementMissing,
-    ElementExtra,
-    AttributeDifferent,
-    ContentDifferent,
-    StructureDifferent,
-}
-
-#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema, Clone)]
 pub struct UrlComparisonRequest {
+    /// Client's own reference for this comparison, echoed on its result in a batch
+    pub id: Option<String>,
     pub url1: String,
     pub url2: String,
     pub ignore_paths: Option<Vec<String>>,
     pub ignore_properties: Option<Vec<String>>,
     pub auth_credentials: Option<AuthCredentials>,
     pub session_id: Option<String>,
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    /// Extra HTTP headers sent when downloading `url1`
+    #[serde(default)]
+    pub headers1: BTreeMap<String, String>,
+    /// Extra HTTP headers sent when downloading `url2`
+    #[serde(default)]
+    pub headers2: BTreeMap<String, String>,
+    /// Method and body used to request `url1`
+    #[serde(default)]
+    pub request1: DownloadRequest,
+    /// Method and body used to request `url2`
+    #[serde(default)]
+    pub request2: DownloadRequest,
+    /// How both documents are downloaded: timeouts, proxy, redirects and tokens
+    #[serde(default)]
+    pub fetch: FetchOptions,
+}
+
+impl UrlComparisonRequest {
+    /// `options`, with the ignore lists taken from the top-level fields when it leaves them unset
+    pub fn effective_options(&self) -> ComparisonOptions {
+        self.options.clone().or(ComparisonOptions {
+            ignore_paths: self.ignore_paths.clone(),
+            ignore_properties: self.ignore_properties.clone(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Query string form of `UrlComparisonRequest`; list parameters are comma-separated
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UrlComparisonQuery {
+    pub url1: String,
+    pub url2: String,
+    pub ignore_paths: Option<String>,
+    pub ignore_properties: Option<String>,
+    pub session_id: Option<String>,
+    pub profile: Option<String>,
+}
+
+impl From<UrlComparisonQuery> for UrlComparisonRequest {
+    fn from(query: UrlComparisonQuery) -> Self {
+        let split = |list: Option<String>| {
+            list.map(|list| list.split(',').map(str::trim).filter(|item| !item.is_empty()).map(String::from).collect())
+        };
+        Self {
+            id: None,
+            url1: query.url1,
+            url2: query.url2,
+            ignore_paths: split(query.ignore_paths),
+            ignore_properties: split(query.ignore_properties),
+            auth_credentials: None,
+            session_id: query.session_id,
+            profile: query.profile,
+            options: ComparisonOptions::default(),
+            headers1: BTreeMap::new(),
+            headers2: BTreeMap::new(),
+            request1: DownloadRequest::default(),
+            request2: DownloadRequest::default(),
+            fetch: FetchOptions::default(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
 pub struct AuthCredentials {
     pub username: String,
     pub password: String,
 }
 
+impl std::fmt::Debug for AuthCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthCredentials").field("username", &self.username).field("password", &"[redacted]").finish()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct BatchXmlComparisonRequest {
     pub comparisons: Vec<XmlComparisonRequest>,
+    /// Receives a `BatchCallbackPayload` POST once the batch has been processed
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// ID to follow and cancel the batch by under `/api/jobs`; one is generated when unset
+    #[serde(default)]
+    pub job_id: Option<String>,
+    /// `junit` answers with a JUnit XML report, one test case per comparison, `sarif` with a SARIF
+    /// log, one finding per diff, and `markdown` with a summary for pull-request comments
+    #[serde(default)]
+    pub output_format: Option<OutputFormat>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct BatchUrlComparisonRequest {
     pub comparisons: Vec<UrlComparisonRequest>,
+    /// Receives a `BatchCallbackPayload` POST once the batch has been processed
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// ID to follow and cancel the batch by under `/api/jobs`; one is generated when unset
+    #[serde(default)]
+    pub job_id: Option<String>,
+    /// `junit` answers with a JUnit XML report, one test case per comparison, `sarif` with a SARIF
+    /// log, one finding per diff, and `markdown` with a summary for pull-request comments
+    #[serde(default)]
+    pub output_format: Option<OutputFormat>,
+}
+
+/// A URL batch given as a manifest, inline or by URL, instead of a list of comparisons
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ManifestComparisonRequest {
+    /// CSV with a header row (`url1`, `url2` and optionally `id`, `profile`, `ignore_paths`,
+    /// `ignore_properties`, lists separated by `;`), or a JSON array of URL comparisons; exactly
+    /// one of `manifest` and `manifest_url` is required
+    pub manifest: Option<String>,
+    pub manifest_url: Option<String>,
+    /// Options for every comparison, under the ones a row sets itself
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    /// Profile for rows that do not name one
+    pub profile: Option<String>,
+    /// Used to log in, at each row's first URL, unless the row has its own session
+    pub auth_credentials: Option<AuthCredentials>,
+    /// Session for rows without their own, also used to download `manifest_url`
+    pub session_id: Option<String>,
+    /// How the manifest and the documents are downloaded: timeouts, proxy, redirects and tokens
+    #[serde(default)]
+    pub fetch: FetchOptions,
+    /// Receives a `BatchCallbackPayload` POST once the batch has been processed
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// ID to follow and cancel the batch by under `/api/jobs`; one is generated when unset
+    #[serde(default)]
+    pub job_id: Option<String>,
+}
+
+/// Two deployments of the same documents, compared path by path
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct EnvironmentComparisonRequest {
+    /// Where the first environment serves the documents, such as `https://uat.example.com/trades/`
+    pub base_url1: String,
+    pub base_url2: String,
+    /// Paths relative to both base URLs; exactly one of `paths` and `index_url` is required
+    pub paths: Option<Vec<String>>,
+    /// Sitemap, HTML directory listing or plain list of paths, absolute or relative to
+    /// `base_url1`; every document it links to under `base_url1` is compared
+    pub index_url: Option<String>,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+    /// Used to log in at `base_url1` unless a session is given
+    pub auth_credentials: Option<AuthCredentials>,
+    pub session_id: Option<String>,
+    /// How the index and the documents are downloaded: timeouts, proxy, redirects and tokens
+    #[serde(default)]
+    pub fetch: FetchOptions,
+    /// Receives a `BatchCallbackPayload` POST once the batch has been processed
+    #[serde(default)]
+    pub callback_url: Option<String>,
+    /// ID to follow and cancel the batch by under `/api/jobs`; one is generated when unset
+    #[serde(default)]
+    pub job_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -68,18 +209,364 @@ pub struct BatchComparisonResponse {
     pub total_comparisons: usize,
     pub successful_comparisons: usize,
     pub failed_comparisons: usize,
+    /// Comparisons skipped or stopped because the job was cancelled; they have placeholder results
+    pub cancelled_comparisons: usize,
+    pub job_id: String,
+    pub summary: BatchSummary,
+}
+
+/// Headline numbers over a whole batch
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct BatchSummary {
+    pub matched_comparisons: usize,
+    /// Share of all comparisons that matched, in percent
+    pub match_percentage: f64,
+    /// Successful comparisons counted by `match_ratio`, in ten buckets of 0.1; a ratio of 1.0
+    /// counts in the last
+    pub match_ratio_histogram: Vec<RatioBucket>,
+    /// Diffs reported by every comparison, by type
+    #[schema(value_type = Object)]
+    pub diff_types: BTreeMap<DiffType, usize>,
+    /// The slowest comparisons, slowest first
+    pub slowest: Vec<ItemTiming>,
+    /// Time taken by the whole batch
+    pub wall_time_ms: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RatioBucket {
+    pub min: f64,
+    pub max: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ItemTiming {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub duration_ms: u64,
+}
+
+const SLOWEST_ITEMS: usize = 5;
+
+impl BatchSummary {
+    /// `results` hold a placeholder where `succeeded` is false; `durations` are per comparison
+    pub fn new(results: &[XmlComparisonResponse], succeeded: &[bool], durations: &[Duration], wall_time: Duration) -> Self {
+        let mut summary = Self {
+            match_ratio_histogram: (0..10)
+                .map(|bucket| RatioBucket { min: bucket as f64 / 10.0, max: (bucket + 1) as f64 / 10.0, count: 0 })
+                .collect(),
+            wall_time_ms: wall_time.as_millis() as u64,
+            ..Default::default()
+        };
+        for (result, _) in results.iter().zip(succeeded).filter(|(_, succeeded)| **succeeded) {
+            summary.matched_comparisons += usize::from(result.matched);
+            let bucket = ((result.match_ratio * 10.0) as usize).min(9);
+            summary.match_ratio_histogram[bucket].count += 1;
+            for diff in &result.diffs {
+                *summary.diff_types.entry(diff.diff_type).or_default() += 1;
+            }
+        }
+        if !results.is_empty() {
+            summary.match_percentage = summary.matched_comparisons as f64 * 100.0 / results.len() as f64;
+        }
+        let mut timings: Vec<_> = durations.iter().enumerate().collect();
+        timings.sort_by(|a, b| b.1.cmp(a.1));
+        summary.slowest = timings
+            .into_iter()
+            .take(SLOWEST_ITEMS)
+            .map(|(index, duration)| ItemTiming {
+                index,
+                id: results.get(index).and_then(|result| result.id.clone()),
+                duration_ms: duration.as_millis() as u64,
+            })
+            .collect();
+        summary
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// Every comparison ran
+    Completed,
+    /// At least one comparison could not run
+    Failed,
+    /// The job was cancelled before every comparison ran
+    Cancelled,
+}
+
+impl BatchStatus {
+    pub fn new(failed_comparisons: usize, cancelled_comparisons: usize) -> Self {
+        match (failed_comparisons, cancelled_comparisons) {
+            (_, 1..) => BatchStatus::Cancelled,
+            (1.., 0) => BatchStatus::Failed,
+            (0, 0) => BatchStatus::Completed,
+        }
+    }
+}
+
+/// Summary posted to a batch's `callback_url`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchCallbackPayload {
+    pub status: BatchStatus,
+    pub total_comparisons: usize,
+    pub successful_comparisons: usize,
+    pub failed_comparisons: usize,
+    /// Comparisons that ran and found the documents equal
+    pub matched_comparisons: usize,
+}
+
+impl From<&BatchComparisonResponse> for BatchCallbackPayload {
+    fn from(response: &BatchComparisonResponse) -> Self {
+        Self {
+            status: BatchStatus::new(response.failed_comparisons, response.cancelled_comparisons),
+            total_comparisons: response.total_comparisons,
+            successful_comparisons: response.successful_comparisons,
+            failed_comparisons: response.failed_comparisons,
+            matched_comparisons: response.summary.matched_comparisons,
+        }
+    }
 }
 
+/// An XML document compared with a JSON document converted to XML
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct XmlJsonComparisonRequest {
+    /// Compared as `xml1`
+    pub xml: String,
+    /// The JSON document itself (not a string containing it), compared as `xml2`
+    #[schema(value_type = Object)]
+    pub json: serde_json::Value,
+    #[serde(default)]
+    pub conventions: JsonConventions,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+}
+
+/// Two JSON documents; diff paths are JSON Pointers
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JsonComparisonRequest {
+    #[schema(value_type = Object)]
+    pub json1: serde_json::Value,
+    #[schema(value_type = Object)]
+    pub json2: serde_json::Value,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+}
+
+/// Two documents in any supported format, detected from their content
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DocumentComparisonRequest {
+    /// XML, JSON or YAML text
+    pub document1: String,
+    pub document2: String,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+    /// Used when one document is XML and the other JSON or YAML
+    #[serde(default)]
+    pub conventions: JsonConventions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentFormat {
+    Xml,
+    Json,
+    Yaml,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DocumentComparisonResponse {
+    pub format1: DocumentFormat,
+    pub format2: DocumentFormat,
+    #[serde(flatten)]
+    pub result: XmlComparisonResponse,
+}
+
+/// How JSON maps onto XML elements, attributes and text
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct JsonConventions {
+    /// Members whose name starts with this prefix are attributes
+    pub attribute_prefix: String,
+    /// Member holding an element's text when the element also has attributes or children
+    pub text_key: String,
+    /// Root element wrapping the whole document; the items of an array document become `item`
+    /// elements. Without it the document must be an object with a single member, the root.
+    pub root_name: Option<String>,
+}
+
+impl Default for JsonConventions {
+    fn default() -> Self {
+        Self {
+            attribute_prefix: "@".to_string(),
+            text_key: "#text".to_string(),
+            root_name: None,
+        }
+    }
+}
+
+/// Two documents, each given inline or as a URL, such as a live endpoint and a local golden copy
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct MixedComparisonRequest {
+    /// Compared as `xml1`
+    pub document1: DocumentInput,
+    /// Compared as `xml2`
+    pub document2: DocumentInput,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+    /// Used to log in, at the first URL, before downloading
+    pub auth_credentials: Option<AuthCredentials>,
+    pub session_id: Option<String>,
+    /// How the documents given as URLs are downloaded: timeouts, proxy, redirects and tokens
+    #[serde(default)]
+    pub fetch: FetchOptions,
+}
+
+/// A document given either inline (`xml`) or as a URL to download
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DocumentInput {
+    pub xml: Option<String>,
+    pub url: Option<String>,
+    /// Extra HTTP headers sent when downloading `url`
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Method and body used to request `url`
+    #[serde(default)]
+    pub request: DownloadRequest,
+}
+
+/// One reference document compared against many candidates
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GoldenComparisonRequest {
+    /// The reference ("golden") XML, compared as `xml1`
+    pub reference: String,
+    pub candidates: Vec<GoldenCandidate>,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+    /// Used to log in before downloading URL candidates
+    pub auth_credentials: Option<AuthCredentials>,
+    pub session_id: Option<String>,
+}
+
+/// A candidate given either inline (`xml`) or as a URL to download
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GoldenCandidate {
+    /// Label used in the results; defaults to the URL or `candidate <n>`
+    pub name: Option<String>,
+    pub xml: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GoldenComparisonResponse {
+    /// One entry per candidate, in request order
+    pub results: Vec<CandidateResult>,
+    /// Compared candidates, closest to the reference first
+    pub ranking: Vec<CandidateRank>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CandidateResult {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<XmlComparisonResponse>,
+    /// Why the candidate could not be compared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CandidateRank {
+    pub rank: usize,
+    pub name: String,
+    pub match_ratio: f64,
+    pub diff_count: usize,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct LoginRequest {
     pub url: String,
     pub username: String,
     pub password: String,
+    /// Requested with the session cookies when the session is refreshed; without it, or when it
+    /// fails, refreshing logs in again
+    #[serde(default)]
+    pub keep_alive_url: Option<String>,
+}
+
+impl std::fmt::Debug for LoginRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LoginRequest")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &"[redacted]")
+            .field("keep_alive_url", &self.keep_alive_url)
+            .finish()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct LoginResponse {
     pub session_id: String,
+    /// Cookies the login set, with their values redacted; they are only sent upstream
     pub cookies: Vec<String>,
     pub expires_at: String, // ISO 8601 formatted string
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xml_compare_core::XmlComparisonService;
+
+    #[test]
+    fn test_nested_options_override_legacy_fields() {
+        let service = XmlComparisonService::new();
+        let request: XmlComparisonRequest = serde_json::from_str(
+            r#"{
+                "xml1": "<a c=\"1\" d=\"1\"><b>x</b></a>",
+                "xml2": "<a c=\"2\" d=\"2\"><b>y</b></a>",
+                "ignore_properties": ["c"],
+                "ignore_paths": ["/a/b"],
+                "options": { "ignore_properties": ["d"] }
+            }"#,
+        )
+        .unwrap();
+
+        let options = request.effective_options();
+        assert_eq!(options.ignore_properties, Some(vec!["d".to_string()]));
+        assert_eq!(options.ignore_paths, Some(vec!["/a/b".to_string()]));
+
+        let result = service.compare_with_options(&request.xml1, &request.xml2, &options).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("c=1"));
+    }
+
+    #[test]
+    fn test_batch_summary() {
+        let service = XmlComparisonService::new();
+        let matched = service.compare_with_options("<a><b>1</b></a>", "<a><b>1</b></a>", &ComparisonOptions::default()).unwrap();
+        let differing = XmlComparisonResponse {
+            id: Some("T-2".to_string()),
+            ..service.compare_with_options("<a><b>1</b><c>1</c></a>", "<a><b>1</b><c>2</c></a>", &ComparisonOptions::default()).unwrap()
+        };
+        let results = [matched, differing, XmlComparisonResponse::default()];
+        let durations = [Duration::from_millis(5), Duration::from_millis(40), Duration::from_millis(1)];
+        let summary = BatchSummary::new(&results, &[true, true, false], &durations, Duration::from_millis(50));
+
+        assert_eq!(summary.matched_comparisons, 1);
+        assert!((summary.match_percentage - 100.0 / 3.0).abs() < 1e-9);
+        let counts: Vec<_> = summary.match_ratio_histogram.iter().map(|bucket| bucket.count).collect();
+        assert_eq!(counts, [0, 0, 0, 0, 0, 0, 1, 0, 0, 1]);
+        assert_eq!(summary.diff_types, BTreeMap::from([(DiffType::ContentDifferent, 1)]));
+        assert_eq!(summary.slowest[0], ItemTiming { index: 1, id: Some("T-2".to_string()), duration_ms: 40 });
+        assert_eq!(summary.slowest.len(), 3);
+        assert_eq!(summary.wall_time_ms, 50);
+    }
+}
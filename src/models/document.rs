@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CanonicalizeRequest {
+    pub xml: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CanonicalizeResponse {
+    pub canonical: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct FormatRequest {
+    pub xml: String,
+    /// Spaces per nesting level when pretty-printing (default 2)
+    pub indent: Option<usize>,
+    /// Write the document on one line instead, without whitespace between markup
+    #[serde(default)]
+    pub minify: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FormatResponse {
+    pub xml: String,
+}
+
+/// A document checked against an XML Schema given inline or by URL
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct XmlValidationRequest {
+    pub xml: String,
+    /// The schema document itself; exactly one of `xsd` and `xsd_url` is required
+    pub xsd: Option<String>,
+    pub xsd_url: Option<String>,
+    /// Session whose cookies are sent when downloading `xsd_url`
+    pub session_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct XmlValidationResponse {
+    pub valid: bool,
+    pub errors: Vec<ValidationIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ValidationIssue {
+    /// Path of the offending element, e.g. `/order/item[2]/price`
+    pub path: String,
+    /// 1-based position in the document
+    pub line: u32,
+    pub column: u32,
+    pub message: String,
+}
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -26,6 +26,30 @@ pub enum AppError {
     
     #[error("Validation error: {0}")]
     ValidationError(String),
+
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Unexpected content: {0}")]
+    UnexpectedContent(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
 }
 
 impl IntoResponse for AppError {
@@ -36,16 +60,39 @@ impl IntoResponse for AppError {
             AppError::AuthError(_) => (StatusCode::UNAUTHORIZED, self.to_string()),
             AppError::InvalidUrl(_) => (StatusCode::BAD_REQUEST, self.to_string()),
             AppError::ValidationError(_) => (StatusCode::BAD_REQUEST, self.to_string()),
+            AppError::NotFound(_) => (StatusCode::NOT_FOUND, self.to_string()),
+            AppError::Conflict(_) => (StatusCode::CONFLICT, self.to_string()),
+            AppError::Forbidden(_) => (StatusCode::FORBIDDEN, self.to_string()),
+            AppError::PayloadTooLarge(_) => (StatusCode::PAYLOAD_TOO_LARGE, self.to_string()),
+            AppError::TooManyRequests(_) => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, self.to_string()),
+            AppError::UnexpectedContent(_) => (StatusCode::BAD_GATEWAY, self.to_string()),
+            AppError::Cancelled(_) => (StatusCode::CONFLICT, self.to_string()),
             AppError::InternalError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string()),
         };
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": error_message,
             "status": status.as_u16()
-        }));
+        });
+        if let Some(request_id) = crate::middleware::current_request_id() {
+            body["request_id"] = request_id.into();
+        }
+        let body = Json(body);
 
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return (status, [(header::RETRY_AFTER, "1")], body).into_response();
+        }
         (status, body).into_response()
     }
 }
 
-pub type AppResult<T> = Result<T, AppError>;
\ No newline at end of file
+pub type AppResult<T> = Result<T, AppError>;
+impl From<xml_compare_core::CompareError> for AppError {
+    fn from(error: xml_compare_core::CompareError) -> Self {
+        match error {
+            xml_compare_core::CompareError::XmlParseError(message) => AppError::XmlParseError(message),
+            xml_compare_core::CompareError::ValidationError(message) => AppError::ValidationError(message),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Media types accepted as XML unless configured otherwise. `text/plain` is included because raw
+/// file hosts commonly serve XML with it.
+pub const DEFAULT_XML_CONTENT_TYPES: [&str; 4] = ["application/xml", "text/xml", "application/*+xml", "text/plain"];
+
+/// How source documents are downloaded. Fields left unset use the server's settings; `0` turns a
+/// timeout off.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FetchOptions {
+    /// Time allowed to open the connection
+    pub connect_timeout_ms: Option<u64>,
+    /// Longest wait for the next chunk of the response
+    pub read_timeout_ms: Option<u64>,
+    /// Limit on the whole download, from connecting to the last byte
+    pub timeout_ms: Option<u64>,
+    /// Largest document accepted, in bytes both as sent and once decompressed; `0` turns the limit
+    /// off
+    pub max_bytes: Option<u64>,
+    /// Proxy to download through; replaces the server's proxy settings as a whole
+    pub proxy: Option<ProxyOptions>,
+    /// Most redirects followed per download; `0` treats any redirect as an error
+    pub max_redirects: Option<usize>,
+    /// Whether redirects may lead to a host other than the one in the URL
+    pub cross_host_redirects: Option<bool>,
+    /// Client credentials whose access token is sent as a bearer token
+    pub oauth2: Option<OAuth2Credentials>,
+    /// Token sent as `Authorization: Bearer …`
+    pub bearer_token: Option<String>,
+    /// Name of a bearer token configured on the server, sent like `bearer_token`
+    pub credential: Option<String>,
+    /// Media types accepted as XML, where `*` matches any run of characters (`application/*+xml`);
+    /// an empty list or `*/*` accepts anything. Responses without a Content-Type are always read.
+    pub content_types: Option<Vec<String>>,
+}
+
+/// An OAuth2 client, authenticated at `token_url` with the client-credentials grant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct OAuth2Credentials {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Space-separated scopes to request
+    pub scope: Option<String>,
+}
+
+/// Successful answer of a token endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct OAuth2Token {
+    pub access_token: String,
+    /// Lifetime in seconds
+    pub expires_in: Option<u64>,
+}
+
+/// How a URL is requested: a plain GET by default, or another method sending a body such as a
+/// query XML
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DownloadRequest {
+    /// HTTP method, `GET` by default
+    pub method: Option<String>,
+    pub body: Option<String>,
+    /// Content-Type of `body`, `application/xml` by default
+    pub content_type: Option<String>,
+}
+
+/// An HTTP(S) or SOCKS5 proxy
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyOptions {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` (host names resolved by the proxy)
+    /// address of the proxy
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Hosts reached directly: domain names (matching subdomains too), IP addresses, CIDR ranges,
+    /// or `*` for every host
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+impl FetchOptions {
+    /// Fills every field left unset from `fallback`
+    pub fn or(self, fallback: FetchOptions) -> FetchOptions {
+        FetchOptions {
+            connect_timeout_ms: self.connect_timeout_ms.or(fallback.connect_timeout_ms),
+            read_timeout_ms: self.read_timeout_ms.or(fallback.read_timeout_ms),
+            timeout_ms: self.timeout_ms.or(fallback.timeout_ms),
+            max_bytes: self.max_bytes.or(fallback.max_bytes),
+            proxy: self.proxy.or(fallback.proxy),
+            max_redirects: self.max_redirects.or(fallback.max_redirects),
+            cross_host_redirects: self.cross_host_redirects.or(fallback.cross_host_redirects),
+            oauth2: self.oauth2.or(fallback.oauth2),
+            bearer_token: self.bearer_token.or(fallback.bearer_token),
+            credential: self.credential.or(fallback.credential),
+            content_types: self.content_types.or(fallback.content_types),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// The service runs but an upstream probe failed
+    Degraded,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    pub version: String,
+    pub uptime_seconds: u64,
+    pub active_sessions: usize,
+    /// Batch callbacks queued or being delivered
+    pub queue_depth: usize,
+    /// One entry per configured upstream probe
+    pub checks: Vec<ProbeResult>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProbeResult {
+    pub url: String,
+    pub ok: bool,
+    /// HTTP status of the probe response, when one arrived
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReadinessReport {
+    pub ready: bool,
+    /// Set once the server has started shutting down; it stays up only to finish in-flight work
+    pub shutting_down: bool,
+    /// Each background component and whether it has finished starting
+    pub components: BTreeMap<String, bool>,
+}
@@ -0,0 +1,103 @@
+use crate::models::DiffType;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+/// A comparison the server ran, as kept in the history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub compared_at: DateTime<Utc>,
+    pub request_id: Option<String>,
+    pub tenant: Option<String>,
+    /// Path of the request that ran the comparison
+    pub endpoint: String,
+    pub matched: bool,
+    pub diff_count: usize,
+    pub match_ratio: f64,
+    /// Where downloaded documents came from; empty for inline documents
+    pub urls: Vec<String>,
+}
+
+/// Filters for `GET /api/history`; entries come newest first
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// Compared at or after this time (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Compared before this time (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    pub matched: Option<bool>,
+    /// Part of a document URL
+    pub url: Option<String>,
+    pub tenant: Option<String>,
+    /// At most 1000; 100 when unset
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// Width of the periods trends are summed over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendBucket {
+    Hour,
+    #[default]
+    Day,
+    /// Weeks start on Monday, UTC
+    Week,
+}
+
+/// Filters for `GET /api/history/trends`
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TrendQuery {
+    /// Compared at or after this time (RFC 3339)
+    pub from: Option<DateTime<Utc>>,
+    /// Compared before this time (RFC 3339)
+    pub to: Option<DateTime<Utc>>,
+    /// Part of a document URL
+    pub url: Option<String>,
+    /// With `url2`, only comparisons of exactly this pair of URLs
+    pub url1: Option<String>,
+    pub url2: Option<String>,
+    pub tenant: Option<String>,
+    pub bucket: Option<TrendBucket>,
+    /// How many of the most frequently differing paths to list; 10 when unset
+    pub top: Option<usize>,
+}
+
+/// Comparisons of one period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TrendPoint {
+    pub start: DateTime<Utc>,
+    pub comparisons: u64,
+    pub matched: u64,
+    pub mean_match_ratio: f64,
+    pub min_match_ratio: f64,
+    pub diffs: u64,
+    /// Diffs by type
+    pub diff_types: BTreeMap<DiffType, u64>,
+}
+
+/// A path, with positions removed, and how often it differed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PathFrequency {
+    pub path: String,
+    pub diffs: u64,
+    /// Comparisons it differed in
+    pub comparisons: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TrendResponse {
+    pub bucket: TrendBucket,
+    /// Periods with comparisons, oldest first
+    pub points: Vec<TrendPoint>,
+    /// Most frequently differing paths, most diffs first
+    pub top_paths: Vec<PathFrequency>,
+    /// Diffs by type over the whole range
+    pub diff_types: BTreeMap<DiffType, u64>,
+    /// Mean match ratio of the last period minus that of the first; negative when drift grows
+    pub match_ratio_change: Option<f64>,
+}
@@ -0,0 +1,111 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A batch that is running
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct JobInfo {
+    pub id: String,
+    /// `xml_batch`, `url_batch`, `manifest_batch` or `environment_batch`
+    pub kind: String,
+    /// Comparisons in the batch
+    pub total: usize,
+    pub started_at: String, // ISO 8601 formatted string
+    /// Cancellation was requested; the batch stops and returns what it finished
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct JobListResponse {
+    pub jobs: Vec<JobInfo>,
+}
+
+/// How far a running batch has got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct JobProgress {
+    pub id: String,
+    pub total: usize,
+    /// Comparisons finished, successfully or not
+    pub completed: usize,
+    /// Comparisons among `completed` that could not run
+    pub failed: usize,
+    pub remaining: usize,
+    pub elapsed_ms: u64,
+    /// Average since the job started
+    pub items_per_second: f64,
+    /// Estimated time left at the current throughput; absent before the first comparison finishes
+    /// or once the job is cancelled
+    pub eta_seconds: Option<f64>,
+    pub cancelled: bool,
+}
+
+/// The results of a finished batch, kept for later retrieval
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StoredBatch {
+    pub job_id: String,
+    /// Tenant that ran the batch; only it can read the results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    /// `xml_batch`, `url_batch`, `manifest_batch` or `environment_batch`
+    pub kind: String,
+    #[schema(value_type = String)]
+    pub completed_at: DateTime<Utc>,
+    /// The results are removed after this
+    #[schema(value_type = String)]
+    pub expires_at: DateTime<Utc>,
+    pub total_comparisons: usize,
+    pub successful_comparisons: usize,
+    pub failed_comparisons: usize,
+    pub matched_comparisons: usize,
+    pub cancelled_comparisons: usize,
+    pub items: Vec<StoredBatchItem>,
+}
+
+impl StoredBatch {
+    pub fn new(job_id: String, kind: &str, items: Vec<StoredBatchItem>, ttl: chrono::Duration) -> Self {
+        let cancelled = items.iter().filter(|item| item.cancelled).count();
+        let failed = items.iter().filter(|item| item.error.is_some()).count() - cancelled;
+        let completed_at = Utc::now();
+        Self {
+            job_id,
+            tenant: None,
+            kind: kind.to_string(),
+            completed_at,
+            expires_at: completed_at + ttl,
+            total_comparisons: items.len(),
+            successful_comparisons: items.len() - failed - cancelled,
+            failed_comparisons: failed,
+            matched_comparisons: items.iter().filter(|item| item.matched).count(),
+            cancelled_comparisons: cancelled,
+            items,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+}
+
+/// One comparison of a stored batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct StoredBatchItem {
+    /// Position in the batch request
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub matched: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+    /// The result as the batch endpoint answered it, diffs included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StoredItemsResponse {
+    pub job_id: String,
+    pub items: Vec<StoredBatchItem>,
+}
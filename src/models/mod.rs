@@ -1,7 +1,28 @@
 pub mod comparison;
 pub mod auth;
 pub mod error;
+pub mod document;
+pub mod profile;
+pub mod v2;
+pub mod cache;
+pub mod health;
+pub mod fetch;
+pub mod job;
+pub mod baseline;
+pub mod tenant;
+pub mod history;
 
+pub use xml_compare_core::models::*;
 pub use comparison::*;
 pub use auth::*;
-pub use error::*;
\ No newline at end of file
+pub use error::*;
+pub use document::*;
+pub use profile::*;
+pub use v2::*;
+pub use cache::*;
+pub use health::*;
+pub use fetch::*;
+pub use job::*;
+pub use baseline::*;
+pub use tenant::*;
+pub use history::*;
@@ -0,0 +1,18 @@
+use crate::models::ComparisonOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use utoipa::ToSchema;
+
+/// Named set of comparison options that requests can reference with `profile`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct ComparisonProfile {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub options: ComparisonOptions,
+}
+
+/// Profiles by tenant and name; profiles of requests without a tenant have none
+pub type ProfileStore = Arc<RwLock<HashMap<(Option<String>, String), ComparisonProfile>>>;
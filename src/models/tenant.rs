@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// `APP_TENANTS_FILE`: the tenants sharing this instance
+#[derive(Debug, Clone, Deserialize)]
+pub struct TenantsFile {
+    pub tenants: Vec<TenantConfig>,
+}
+
+/// A team with its own API keys, limits, profiles, baselines and sessions
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    pub name: String,
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    /// Hex SHA-256 digests of keys, for files that should not hold the keys themselves
+    #[serde(default)]
+    pub api_key_hashes: Vec<String>,
+    /// Requests allowed per minute, in bursts of up to the same number; unlimited when unset
+    pub requests_per_minute: Option<u32>,
+    /// Lowers `APP_MAX_BATCH_ITEMS` for this tenant
+    pub max_batch_items: Option<usize>,
+    /// Lowers `APP_MAX_BODY_MB` for this tenant
+    pub max_body_mb: Option<usize>,
+}
+
+/// A tenant's limits and what it has used since the server started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TenantUsage {
+    pub name: String,
+    pub requests_per_minute: Option<u32>,
+    pub max_batch_items: Option<usize>,
+    pub max_body_bytes: Option<usize>,
+    /// Requests let through
+    pub requests: u64,
+    /// Requests refused with 429 for exceeding `requests_per_minute`
+    pub throttled: u64,
+    pub comparisons: u64,
+    pub matched: u64,
+    pub diffs: u64,
+}
@@ -0,0 +1,264 @@
+// Request and response shapes of the `/api/v2` routes. v2 takes options only in the nested
+// `options` object and rejects unknown fields; v1 shapes stay as they are for existing clients.
+
+use crate::models::{
+    AuthCredentials, BatchCallbackPayload, BatchStatus, ComparisonOptions, DiffField, DiffSeverity, DiffType,
+    DiffsView, DocumentPairStats, DocumentSource, DocumentWarning, DownloadRequest, ElementStats, FetchOptions, StoredBatchItem, XmlComparisonResponse,
+    XmlDiff,
+};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct XmlComparisonRequestV2 {
+    /// Client's own reference for this comparison, echoed on its batch entry
+    pub id: Option<String>,
+    pub xml1: String,
+    pub xml2: String,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UrlComparisonRequestV2 {
+    /// Client's own reference for this comparison, echoed on its batch entry
+    pub id: Option<String>,
+    pub url1: String,
+    pub url2: String,
+    #[serde(default)]
+    pub options: ComparisonOptions,
+    pub profile: Option<String>,
+    pub auth_credentials: Option<AuthCredentials>,
+    pub session_id: Option<String>,
+    /// Extra HTTP headers sent when downloading `url1`
+    #[serde(default)]
+    pub headers1: BTreeMap<String, String>,
+    /// Extra HTTP headers sent when downloading `url2`
+    #[serde(default)]
+    pub headers2: BTreeMap<String, String>,
+    /// Method and body used to request `url1`
+    #[serde(default)]
+    pub request1: DownloadRequest,
+    /// Method and body used to request `url2`
+    #[serde(default)]
+    pub request2: DownloadRequest,
+    /// How both documents are downloaded: timeouts, proxy, redirects and tokens
+    #[serde(default)]
+    pub fetch: FetchOptions,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BatchXmlComparisonRequestV2 {
+    pub comparisons: Vec<XmlComparisonRequestV2>,
+    pub callback_url: Option<String>,
+    /// ID to follow and cancel the batch by under `/api/jobs`; one is generated when unset
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BatchUrlComparisonRequestV2 {
+    pub comparisons: Vec<UrlComparisonRequestV2>,
+    pub callback_url: Option<String>,
+    /// ID to follow and cancel the batch by under `/api/jobs`; one is generated when unset
+    pub job_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ComparisonResultV2 {
+    pub matched: bool,
+    pub match_ratio: f64,
+    pub summary: ComparisonSummary,
+    pub diffs: Vec<XmlDiff>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unified_diff: Option<String>,
+    #[serde(default)]
+    pub warnings: Vec<DocumentWarning>,
+    /// Where downloaded documents came from; empty for inline documents
+    #[serde(default)]
+    pub sources: Vec<DocumentSource>,
+    /// Matched and differing instances per element name or pattern, when requested
+    #[serde(default)]
+    pub element_stats: Vec<ElementStats>,
+    /// The `diff_fields` option, applied when the result is serialized
+    #[serde(skip)]
+    pub diff_fields: Option<Vec<DiffField>>,
+}
+
+impl ComparisonResultV2 {
+    /// Applies the response-shaping options; the summary still counts every diff
+    pub fn shaped(mut self, options: &ComparisonOptions) -> Self {
+        if self.matched && options.exclude_matched_details.unwrap_or(false) {
+            self.diffs.clear();
+            self.warnings.clear();
+            self.patch = None;
+            self.unified_diff = None;
+        }
+        if !options.include_diffs.unwrap_or(true) {
+            self.diffs.clear();
+        }
+        self.diff_fields = options.diff_fields.clone();
+        self
+    }
+}
+
+// Written by hand so that `diff_fields` can trim each diff
+impl Serialize for ComparisonResultV2 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ComparisonResultV2", 9)?;
+        state.serialize_field("matched", &self.matched)?;
+        state.serialize_field("match_ratio", &self.match_ratio)?;
+        state.serialize_field("summary", &self.summary)?;
+        state.serialize_field("diffs", &DiffsView(&self.diffs, self.diff_fields.as_deref()))?;
+        match &self.patch {
+            Some(patch) => state.serialize_field("patch", patch)?,
+            None => state.skip_field("patch")?,
+        }
+        match &self.unified_diff {
+            Some(unified_diff) => state.serialize_field("unified_diff", unified_diff)?,
+            None => state.skip_field("unified_diff")?,
+        }
+        state.serialize_field("warnings", &self.warnings)?;
+        if self.sources.is_empty() {
+            state.skip_field("sources")?;
+        } else {
+            state.serialize_field("sources", &self.sources)?;
+        }
+        if self.element_stats.is_empty() {
+            state.skip_field("element_stats")?;
+        } else {
+            state.serialize_field("element_stats", &self.element_stats)?;
+        }
+        state.end()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct ComparisonSummary {
+    pub total_elements: usize,
+    pub matched_elements: usize,
+    pub diff_count: usize,
+    /// Diffs with `error` severity
+    pub error_count: usize,
+    /// Diffs with `info` severity, which only strict options report
+    pub info_count: usize,
+    #[schema(value_type = Object)]
+    pub by_type: BTreeMap<DiffType, usize>,
+    /// Diffs carrying each `classifications` tag
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub by_tag: BTreeMap<String, usize>,
+    /// True when comparison stopped early because `max_diffs` was reached
+    pub truncated: bool,
+    /// Size and shape of xml1 and xml2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_stats: Option<DocumentPairStats>,
+}
+
+impl From<XmlComparisonResponse> for ComparisonResultV2 {
+    fn from(response: XmlComparisonResponse) -> Self {
+        let mut summary = ComparisonSummary {
+            total_elements: response.total_elements,
+            matched_elements: response.matched_elements,
+            diff_count: response.diffs.len(),
+            truncated: response.truncated,
+            by_tag: response.tag_counts,
+            document_stats: response.document_stats,
+            ..Default::default()
+        };
+        for diff in &response.diffs {
+            *summary.by_type.entry(diff.diff_type).or_default() += 1;
+            match diff.severity {
+                DiffSeverity::Error => summary.error_count += 1,
+                DiffSeverity::Info => summary.info_count += 1,
+            }
+        }
+        Self {
+            matched: response.matched,
+            match_ratio: response.match_ratio,
+            summary,
+            diffs: response.diffs,
+            patch: response.patch,
+            unified_diff: response.unified_diff,
+            warnings: response.warnings,
+            sources: response.sources,
+            element_stats: response.element_stats,
+            diff_fields: response.diff_fields,
+        }
+    }
+}
+
+/// One batch entry: the result, or why the comparison could not run
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchItemV2 {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<ComparisonResultV2>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The comparison did not run, or was stopped, because the job was cancelled
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cancelled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct BatchComparisonResponseV2 {
+    pub results: Vec<BatchItemV2>,
+    pub total_comparisons: usize,
+    pub successful_comparisons: usize,
+    pub failed_comparisons: usize,
+    pub matched_comparisons: usize,
+    pub cancelled_comparisons: usize,
+    pub job_id: String,
+}
+
+impl BatchComparisonResponseV2 {
+    pub fn new(job_id: String, results: Vec<BatchItemV2>) -> Self {
+        let cancelled = results.iter().filter(|item| item.cancelled).count();
+        let failed = results.iter().filter(|item| item.error.is_some()).count() - cancelled;
+        let matched = results.iter().filter(|item| item.result.as_ref().is_some_and(|result| result.matched)).count();
+        Self {
+            total_comparisons: results.len(),
+            successful_comparisons: results.len() - failed - cancelled,
+            failed_comparisons: failed,
+            matched_comparisons: matched,
+            cancelled_comparisons: cancelled,
+            job_id,
+            results,
+        }
+    }
+}
+
+impl From<&BatchItemV2> for StoredBatchItem {
+    fn from(item: &BatchItemV2) -> Self {
+        Self {
+            index: item.index,
+            id: item.id.clone(),
+            matched: item.result.as_ref().is_some_and(|result| result.matched),
+            error: item.error.clone(),
+            cancelled: item.cancelled,
+            result: item.result.as_ref().and_then(|result| serde_json::to_value(result).ok()),
+        }
+    }
+}
+
+impl From<&BatchComparisonResponseV2> for BatchCallbackPayload {
+    fn from(response: &BatchComparisonResponseV2) -> Self {
+        Self {
+            status: BatchStatus::new(response.failed_comparisons, response.cancelled_comparisons),
+            total_comparisons: response.total_comparisons,
+            successful_comparisons: response.successful_comparisons,
+            failed_comparisons: response.failed_comparisons,
+            matched_comparisons: response.matched_comparisons,
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use crate::config::AppConfig;
+use crate::models::{AppError, AppResult, DiffSeverity, XmlDiff};
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use reqwest::Client;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const DEFAULT_TEMPLATE: &str =
+    "XML comparison job {job_id}: {mismatched} of {total} comparisons differ, {failed} failed\n{top_diffs}\n{report_url}";
+const DEFAULT_SUBJECT: &str = "XML comparison job {job_id}: {mismatched} of {total} comparisons differ";
+// Alerts are drained at shutdown like batch callbacks, so a channel that never answers must not
+// hold them forever
+const ALERT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const ALERT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One comparison of a finished batch, as alerts see it
+pub struct AlertItem<'a> {
+    pub index: usize,
+    pub id: Option<&'a str>,
+    /// The comparison could not run or was cancelled
+    pub failed: bool,
+    pub matched: bool,
+    pub diffs: &'a [XmlDiff],
+}
+
+struct EmailChannel {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+/// Posts a notification to Slack, Teams and email when a batch finds differences at or above the
+/// configured severity. Without a channel nothing is sent.
+#[derive(Clone)]
+pub struct AlertService {
+    client: Client,
+    pending: Arc<AtomicUsize>,
+    slack_url: Option<String>,
+    teams_url: Option<String>,
+    email: Option<Arc<EmailChannel>>,
+    min_severity: DiffSeverity,
+    template: Option<String>,
+    subject: Option<String>,
+    report_url: Option<String>,
+    top_diffs: usize,
+}
+
+impl Default for AlertService {
+    fn default() -> Self {
+        Self {
+            client: alert_client(),
+            pending: Arc::default(),
+            slack_url: None,
+            teams_url: None,
+            email: None,
+            min_severity: DiffSeverity::default(),
+            template: None,
+            subject: None,
+            report_url: None,
+            top_diffs: 0,
+        }
+    }
+}
+
+impl AlertService {
+    pub fn from_config(config: &AppConfig) -> AppResult<Self> {
+        let email = match &config.alert_smtp_url {
+            Some(url) => {
+                let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(url)
+                    .map_err(|e| AppError::ValidationError(format!("Invalid APP_ALERT_SMTP_URL: {}", e)))?
+                    .timeout(Some(ALERT_TIMEOUT))
+                    .build();
+                let from = config
+                    .alert_email_from
+                    .as_deref()
+                    .ok_or_else(|| AppError::ValidationError("APP_ALERT_SMTP_URL needs APP_ALERT_EMAIL_FROM".to_string()))
+                    .and_then(mailbox)?;
+                let to = config.alert_email_to.iter().map(|address| mailbox(address)).collect::<AppResult<Vec<_>>>()?;
+                if to.is_empty() {
+                    return Err(AppError::ValidationError("APP_ALERT_SMTP_URL needs APP_ALERT_EMAIL_TO".to_string()));
+                }
+                Some(Arc::new(EmailChannel { transport, from, to }))
+            }
+            None => None,
+        };
+        // Line breaks are hard to put in environment variables
+        let unescape = |template: &Option<String>| template.as_ref().map(|template| template.replace("\\n", "\n"));
+        Ok(Self {
+            slack_url: config.alert_slack_url.clone(),
+            teams_url: config.alert_teams_url.clone(),
+            email,
+            min_severity: config.alert_min_severity,
+            template: unescape(&config.alert_template),
+            subject: unescape(&config.alert_email_subject),
+            report_url: config.alert_report_url.clone(),
+            top_diffs: config.alert_top_diffs,
+            ..Self::default()
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.slack_url.is_some() || self.teams_url.is_some() || self.email.is_some()
+    }
+
+    /// Sends the batch's alert in the background, if it has differences worth one; failures are
+    /// logged, not reported to the caller
+    pub fn notify_batch(&self, job_id: &str, items: &[AlertItem]) {
+        if !self.is_enabled() {
+            return;
+        }
+        let Some((text, subject)) = self.render(job_id, items) else {
+            return;
+        };
+        tracing::info!(job_id, "Sending mismatch alert");
+        if let Some(url) = &self.slack_url {
+            self.post("Slack", url.clone(), serde_json::json!({ "text": text }));
+        }
+        if let Some(url) = &self.teams_url {
+            // Teams reads the text as Markdown, where single line breaks are ignored
+            self.post("Teams", url.clone(), serde_json::json!({ "text": text.replace('\n', "\n\n") }));
+        }
+        if let Some(email) = &self.email {
+            let email = email.clone();
+            self.spawn(async move {
+                if let Err(e) = send_email(&email, subject, text).await {
+                    tracing::warn!("Alert email failed: {}", e);
+                }
+            });
+        }
+    }
+
+    /// Alerts queued or being sent
+    pub fn pending_alerts(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Waits up to `timeout` for queued alerts to be sent; returns how many are left
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        while self.pending_alerts() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.pending_alerts()
+    }
+
+    fn spawn(&self, send: impl Future<Output = ()> + Send + 'static) {
+        let pending = self.pending.clone();
+        pending.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            send.await;
+            pending.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    // The alert's text and email subject, or None when no comparison has an alerting diff
+    fn render(&self, job_id: &str, items: &[AlertItem]) -> Option<(String, String)> {
+        let alerting = |diff: &&XmlDiff| diff.severity == DiffSeverity::Error || self.min_severity == DiffSeverity::Info;
+        // Items whose diffs were left out of the response only show that they did not match
+        let mismatched: Vec<_> = items
+            .iter()
+            .filter(|item| !item.failed && (item.diffs.iter().any(|diff| alerting(&diff)) || (!item.matched && item.diffs.is_empty())))
+            .collect();
+        if mismatched.is_empty() {
+            return None;
+        }
+        let top_diffs: Vec<String> = mismatched
+            .iter()
+            .flat_map(|item| item.diffs.iter().filter(alerting).map(move |diff| (item, diff)))
+            .take(self.top_diffs)
+            .map(|(item, diff)| {
+                let label = item.id.map_or_else(|| format!("#{}", item.index), str::to_string);
+                format!("- {} {}: {}", label, diff.path, diff.message)
+            })
+            .collect();
+        let report_url = self.report_url.as_deref().map_or_else(String::new, |url| {
+            url.replace("{job_id}", &utf8_percent_encode(job_id, NON_ALPHANUMERIC).to_string())
+        });
+        let fill = |template: &str| {
+            template
+                .replace("{job_id}", job_id)
+                .replace("{total}", &items.len().to_string())
+                .replace("{mismatched}", &mismatched.len().to_string())
+                .replace("{failed}", &items.iter().filter(|item| item.failed).count().to_string())
+                .replace("{top_diffs}", &top_diffs.join("\n"))
+                .replace("{report_url}", &report_url)
+                .trim_end()
+                .to_string()
+        };
+        Some((
+            fill(self.template.as_deref().unwrap_or(DEFAULT_TEMPLATE)),
+            fill(self.subject.as_deref().unwrap_or(DEFAULT_SUBJECT)),
+        ))
+    }
+
+    // Webhook URLs hold their credentials, so only the channel is logged
+    fn post(&self, channel: &'static str, url: String, payload: serde_json::Value) {
+        let client = self.client.clone();
+        self.spawn(async move {
+            let result = client.post(&url).json(&payload).send().await.and_then(|response| response.error_for_status());
+            if let Err(e) = result {
+                tracing::warn!("{} alert failed: {}", channel, e.without_url());
+            }
+        });
+    }
+}
+
+fn alert_client() -> Client {
+    Client::builder().connect_timeout(ALERT_CONNECT_TIMEOUT).timeout(ALERT_TIMEOUT).build().unwrap_or_default()
+}
+
+fn mailbox(address: &str) -> AppResult<Mailbox> {
+    address
+        .parse()
+        .map_err(|e| AppError::ValidationError(format!("Invalid alert email address '{}': {}", address, e)))
+}
+
+async fn send_email(email: &EmailChannel, subject: String, text: String) -> AppResult<()> {
+    let mut message = Message::builder().from(email.from.clone()).subject(subject).header(ContentType::TEXT_PLAIN);
+    for to in &email.to {
+        message = message.to(to.clone());
+    }
+    let message = message.body(text).map_err(|e| AppError::InternalError(e.to_string()))?;
+    email.transport.send(message).await.map_err(|e| AppError::HttpError(e.to_string()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DiffType;
+    use std::time::Duration;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn diff(path: &str, severity: DiffSeverity) -> XmlDiff {
+        XmlDiff {
+            path: path.to_string(),
+            diff_type: DiffType::ContentDifferent,
+            expected: None,
+            actual: None,
+            message: "Content differs".to_string(),
+            similarity: None,
+            severity,
+            tags: Vec::new(),
+            node: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_alerts() {
+        let mock_server = MockServer::start().await;
+        let text = "Job nightly/1: 1 of 3 differ\n- T-1 /trade/amount: Content differs\nhttps://compare.example.com/jobs/nightly%2F1";
+        Mock::given(method("POST"))
+            .and(path("/slack"))
+            .and(body_partial_json(serde_json::json!({ "text": text })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let config = AppConfig {
+            alert_slack_url: Some(format!("{}/slack", mock_server.uri())),
+            alert_template: Some("Job {job_id}: {mismatched} of {total} differ\\n{top_diffs}\\n{report_url}".to_string()),
+            alert_report_url: Some("https://compare.example.com/jobs/{job_id}".to_string()),
+            alert_top_diffs: 1,
+            ..Default::default()
+        };
+        let alerts = AlertService::from_config(&config).unwrap();
+        let (amounts, formatting) =
+            ([diff("/trade/amount", DiffSeverity::Error), diff("/trade/fee", DiffSeverity::Error)], [diff("/trade", DiffSeverity::Info)]);
+        let items = [
+            AlertItem { index: 0, id: Some("T-1"), failed: false, matched: false, diffs: &amounts },
+            AlertItem { index: 1, id: None, failed: false, matched: false, diffs: &formatting },
+            AlertItem { index: 2, id: None, failed: true, matched: false, diffs: &[] },
+        ];
+        alerts.notify_batch("nightly/1", &items);
+        // Info diffs alone do not alert, and neither do comparisons that failed
+        assert!(alerts.render("nightly/2", &items[1..]).is_none());
+
+        let info = AlertService { min_severity: DiffSeverity::Info, ..alerts.clone() };
+        let (text, subject) = info.render("nightly/3", &items[1..]).unwrap();
+        assert!(text.starts_with("Job nightly/3: 1 of 2 differ\n- #1 /trade: Content differs"), "{}", text);
+        assert_eq!(subject, "XML comparison job nightly/3: 1 of 2 comparisons differ");
+
+        assert_eq!(alerts.drain(Duration::from_secs(5)).await, 0);
+        mock_server.verify().await;
+
+        let email = AppConfig { alert_smtp_url: Some("smtp://localhost:2525".to_string()), ..Default::default() };
+        assert!(AlertService::from_config(&email).is_err());
+        assert!(!AlertService::default().is_enabled());
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_alerts() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(200)))
+            .mount(&mock_server)
+            .await;
+
+        let config = AppConfig { alert_teams_url: Some(format!("{}/teams", mock_server.uri())), ..Default::default() };
+        let alerts = AlertService::from_config(&config).unwrap();
+        let diffs = [diff("/trade/amount", DiffSeverity::Error)];
+        alerts.notify_batch("nightly/1", &[AlertItem { index: 0, id: None, failed: false, matched: false, diffs: &diffs }]);
+        assert_eq!(alerts.pending_alerts(), 1);
+        assert_eq!(alerts.drain(Duration::from_millis(10)).await, 1);
+        assert_eq!(alerts.drain(Duration::from_secs(5)).await, 0);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::models::{AppError, AppResult, Baseline, BaselineComparisonResponse, BaselineStore, XmlComparisonResponse, XmlDiff};
+use crate::middleware::current_tenant_name;
+use crate::services::profile_service::{tenant_key, validate_name};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub struct BaselineService {
+    baseline_store: BaselineStore,
+}
+
+impl Default for BaselineService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BaselineService {
+    pub fn new() -> Self {
+        Self {
+            baseline_store: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn list(&self) -> Vec<Baseline> {
+        let baselines = self.baseline_store.read().await;
+        let tenant = current_tenant_name();
+        let mut baselines: Vec<_> = baselines
+            .iter()
+            .filter(|((owner, _), _)| *owner == tenant)
+            .map(|(_, baseline)| baseline.clone())
+            .collect();
+        baselines.sort_by(|a, b| a.name.cmp(&b.name));
+        baselines
+    }
+
+    pub async fn get(&self, name: &str) -> AppResult<Baseline> {
+        let baselines = self.baseline_store.read().await;
+        baselines.get(&tenant_key(name)).cloned().ok_or_else(|| not_found(name))
+    }
+
+    pub async fn create(&self, baseline: Baseline) -> AppResult<Baseline> {
+        validate_name("baseline", &baseline.name)?;
+        let mut baselines = self.baseline_store.write().await;
+        let key = tenant_key(&baseline.name);
+        if baselines.contains_key(&key) {
+            return Err(AppError::Conflict(format!("Baseline '{}' already exists", baseline.name)));
+        }
+        baselines.insert(key, baseline.clone());
+        Ok(baseline)
+    }
+
+    pub async fn update(&self, name: &str, mut baseline: Baseline) -> AppResult<Baseline> {
+        baseline.name = name.to_string();
+        let mut baselines = self.baseline_store.write().await;
+        let existing = baselines.get_mut(&tenant_key(name)).ok_or_else(|| not_found(name))?;
+        *existing = baseline.clone();
+        Ok(baseline)
+    }
+
+    /// Replaces the baseline's accepted differences
+    pub async fn accept(&self, name: &str, diffs: Vec<XmlDiff>) -> AppResult<Baseline> {
+        let mut baselines = self.baseline_store.write().await;
+        let existing = baselines.get_mut(&tenant_key(name)).ok_or_else(|| not_found(name))?;
+        existing.accepted_diffs = diffs;
+        Ok(existing.clone())
+    }
+
+    pub async fn delete(&self, name: &str) -> AppResult<()> {
+        let mut baselines = self.baseline_store.write().await;
+        baselines.remove(&tenant_key(name)).map(|_| ()).ok_or_else(|| not_found(name))
+    }
+}
+
+/// Splits a comparison against `baseline` into new and known diffs. Each accepted diff accounts
+/// for one diff with the same path, type and values, so a difference that now occurs twice is new
+/// the second time.
+pub fn classify_diffs(baseline: &Baseline, result: XmlComparisonResponse) -> BaselineComparisonResponse {
+    let mut unmatched: Vec<Option<&XmlDiff>> = baseline.accepted_diffs.iter().map(Some).collect();
+    let (known_diffs, new_diffs): (Vec<_>, Vec<_>) = result.diffs.into_iter().partition(|diff| {
+        let known = unmatched.iter_mut().find(|accepted| accepted.is_some_and(|accepted| same_difference(accepted, diff)));
+        known.map(Option::take).is_some()
+    });
+    BaselineComparisonResponse {
+        baseline: baseline.name.clone(),
+        regression: !new_diffs.is_empty(),
+        new_diffs,
+        known_diffs,
+        resolved_diffs: unmatched.into_iter().flatten().cloned().collect(),
+        match_ratio: result.match_ratio,
+        truncated: result.truncated,
+    }
+}
+
+// Messages and severities can change with the service version, so they are not compared
+fn same_difference(a: &XmlDiff, b: &XmlDiff) -> bool {
+    a.path == b.path && a.diff_type == b.diff_type && a.expected == b.expected && a.actual == b.actual
+}
+
+fn not_found(name: &str) -> AppError {
+    AppError::NotFound(format!("Baseline '{}' does not exist", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ComparisonOptions, DiffSeverity, DiffType};
+
+    fn diff(path: &str, actual: &str) -> XmlDiff {
+        XmlDiff {
+            path: path.to_string(),
+            diff_type: DiffType::ContentDifferent,
+            expected: Some("1".to_string()),
+            actual: Some(actual.to_string()),
+            message: "Content differs".to_string(),
+            similarity: None,
+            severity: DiffSeverity::Error,
+            tags: Vec::new(),
+            node: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_classify_diffs() {
+        let service = BaselineService::new();
+        let baseline = Baseline {
+            name: "trade".to_string(),
+            description: None,
+            xml: "<trade/>".to_string(),
+            options: ComparisonOptions::default(),
+            accepted_diffs: Vec::new(),
+        };
+        service.create(baseline.clone()).await.unwrap();
+        assert!(matches!(service.create(baseline).await, Err(AppError::Conflict(_))));
+        let accepted = vec![diff("/trade/id", "2"), diff("/trade/leg", "2"), diff("/trade/fee", "2")];
+        let baseline = service.accept("trade", accepted).await.unwrap();
+
+        let result = XmlComparisonResponse {
+            diffs: vec![
+                XmlDiff { message: "Reworded".to_string(), ..diff("/trade/id", "2") },
+                diff("/trade/id", "2"),
+                diff("/trade/leg", "3"),
+            ],
+            ..Default::default()
+        };
+        let classified = classify_diffs(&baseline, result);
+        assert!(classified.regression);
+        let paths = |diffs: &[XmlDiff]| diffs.iter().map(|diff| diff.path.clone()).collect::<Vec<_>>();
+        assert_eq!(paths(&classified.known_diffs), vec!["/trade/id"]);
+        assert_eq!(paths(&classified.new_diffs), vec!["/trade/id", "/trade/leg"]);
+        assert_eq!(paths(&classified.resolved_diffs), vec!["/trade/leg", "/trade/fee"]);
+
+        service.delete("trade").await.unwrap();
+        assert!(matches!(service.accept("trade", Vec::new()).await, Err(AppError::NotFound(_))));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::models::{AppError, AppResult};
+use rayon::prelude::*;
+use std::sync::Arc;
+
+/// Worker threads that in-process batches are compared on, so that a large batch uses every core
+/// without holding up the async runtime
+#[derive(Clone)]
+pub struct BatchPool {
+    pool: Arc<rayon::ThreadPool>,
+}
+
+impl BatchPool {
+    /// `parallelism` of 0 starts one thread per CPU
+    pub fn new(parallelism: usize) -> AppResult<Self> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(parallelism)
+            .thread_name(|index| format!("batch-worker-{}", index))
+            .build()
+            .map_err(|e| AppError::InternalError(format!("Batch workers could not be started: {}", e)))?;
+        Ok(Self { pool: Arc::new(pool) })
+    }
+
+    pub fn parallelism(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Applies `f` to every item on the pool; results keep the order of `items`
+    pub async fn map<T, R, F>(&self, items: Vec<T>, f: F) -> AppResult<Vec<R>>
+    where
+        T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || pool.install(|| items.into_par_iter().map(f).collect()))
+            .await
+            .map_err(|e| AppError::InternalError(format!("Batch worker failed: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_map_keeps_order() {
+        let pool = BatchPool::new(4).unwrap();
+        assert_eq!(pool.parallelism(), 4);
+        let items: Vec<u64> = (0..1000).collect();
+        let squares = pool.map(items, |n| n * n).await.unwrap();
+        assert_eq!(squares, (0..1000).map(|n| n * n).collect::<Vec<_>>());
+
+        let failed = pool.map(vec![1, 0], |n: u32| 10 / n).await;
+        assert!(matches!(failed, Err(AppError::InternalError(_))));
+    }
+}
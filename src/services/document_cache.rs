@@ -0,0 +1,90 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Downloaded documents kept with their `ETag`/`Last-Modified` validators so that later downloads
+/// can be conditional. Holds at most `capacity_bytes` of text, evicting the least recently used
+/// documents first; a zero capacity turns caching off.
+#[derive(Default)]
+pub struct DocumentCache {
+    entries: Mutex<HashMap<String, CachedDocument>>,
+    capacity_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CachedDocument {
+    pub body: Arc<str>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    used_at: Instant,
+}
+
+impl CachedDocument {
+    pub fn new(body: &str, etag: Option<String>, last_modified: Option<String>) -> Self {
+        Self { body: Arc::from(body), etag, last_modified, used_at: Instant::now() }
+    }
+}
+
+impl DocumentCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self { entries: Mutex::default(), capacity_bytes }
+    }
+
+    /// Extra request headers can change the representation, so they are part of the key
+    pub fn key(url: &str, headers: &BTreeMap<String, String>) -> String {
+        let mut key = url.to_string();
+        for (name, value) in headers {
+            key.push_str(&format!("\n{}: {}", name.to_ascii_lowercase(), value));
+        }
+        key
+    }
+
+    pub fn get(&self, key: &str) -> Option<CachedDocument> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(key)?;
+        entry.used_at = Instant::now();
+        Some(entry.clone())
+    }
+
+    // Documents larger than the whole cache are not kept
+    pub fn insert(&self, key: String, document: CachedDocument) {
+        if self.capacity_bytes == 0 || document.body.len() > self.capacity_bytes {
+            self.entries.lock().unwrap().remove(&key);
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(&key);
+        let mut size: usize = entries.values().map(|entry| entry.body.len()).sum();
+        while size + document.body.len() > self.capacity_bytes {
+            let Some(oldest) = entries.iter().min_by_key(|(_, entry)| entry.used_at).map(|(key, _)| key.clone()) else {
+                break;
+            };
+            size -= entries.remove(&oldest).map_or(0, |entry| entry.body.len());
+        }
+        entries.insert(key, document);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let cache = DocumentCache::new(10);
+        cache.insert("a".to_string(), CachedDocument::new("aaaa", Some("\"1\"".to_string()), None));
+        cache.insert("b".to_string(), CachedDocument::new("bbbb", None, Some("Mon, 05 Oct 2026 10:00:00 GMT".to_string())));
+        assert_eq!(cache.get("a").unwrap().etag.as_deref(), Some("\"1\""));
+        cache.insert("c".to_string(), CachedDocument::new("cccc", None, None));
+        assert!(cache.get("b").is_none());
+        assert_eq!(&*cache.get("a").unwrap().body, "aaaa");
+        assert_eq!(&*cache.get("c").unwrap().body, "cccc");
+
+        cache.insert("d".to_string(), CachedDocument::new("too large!!", None, None));
+        assert!(cache.get("d").is_none());
+        assert!(DocumentCache::new(0).get("a").is_none());
+
+        let headers = BTreeMap::from([("Accept".to_string(), "application/xml".to_string())]);
+        assert_ne!(DocumentCache::key("http://a/", &headers), DocumentCache::key("http://a/", &BTreeMap::new()));
+    }
+}
@@ -0,0 +1,96 @@
+use crate::models::{AppError, AppResult, ComparisonOptions, DocumentComparisonResponse, DocumentFormat, JsonConventions};
+use crate::services::{DiffEngine, JsonComparisonService, XmlComparisonService, json_to_xml};
+use serde_json::Value;
+
+/// A payload after format detection: XML stays text for the XML engine, JSON and YAML are parsed
+/// into the same data model
+pub enum SniffedDocument<'a> {
+    Xml(&'a str),
+    Data(DocumentFormat, Value),
+}
+
+impl SniffedDocument<'_> {
+    pub fn format(&self) -> DocumentFormat {
+        match self {
+            SniffedDocument::Xml(_) => DocumentFormat::Xml,
+            SniffedDocument::Data(format, _) => *format,
+        }
+    }
+}
+
+/// Detects the format of a payload: markup is XML, text that parses as JSON is JSON, and any
+/// other text that parses as a YAML mapping or sequence is YAML
+pub fn sniff_document<'a>(payload: &'a str, label: &str) -> AppResult<SniffedDocument<'a>> {
+    let trimmed = payload.trim_start_matches('\u{feff}').trim_start();
+    if trimmed.starts_with('<') {
+        return Ok(SniffedDocument::Xml(payload));
+    }
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && let Ok(value) = serde_json::from_str(trimmed)
+    {
+        return Ok(SniffedDocument::Data(DocumentFormat::Json, value));
+    }
+    match serde_yaml::from_str::<Value>(trimmed) {
+        Ok(value @ (Value::Object(_) | Value::Array(_))) => Ok(SniffedDocument::Data(DocumentFormat::Yaml, value)),
+        _ => Err(AppError::ValidationError(format!("{} is not an XML, JSON or YAML document", label))),
+    }
+}
+
+/// Compares two payloads of any supported format. Two data documents use the JSON engine; when
+/// XML meets JSON or YAML, the data document is converted to XML under `conventions`.
+pub fn compare_documents(
+    xml_service: &XmlComparisonService,
+    json_service: &JsonComparisonService,
+    document1: &str,
+    document2: &str,
+    options: &ComparisonOptions,
+    conventions: &JsonConventions,
+) -> AppResult<DocumentComparisonResponse> {
+    let sniffed1 = sniff_document(document1, "document1")?;
+    let sniffed2 = sniff_document(document2, "document2")?;
+    let (format1, format2) = (sniffed1.format(), sniffed2.format());
+
+    let result = match (sniffed1, sniffed2) {
+        (SniffedDocument::Data(_, value1), SniffedDocument::Data(_, value2)) => json_service.diff(&value1, &value2, options)?,
+        (SniffedDocument::Xml(xml1), SniffedDocument::Xml(xml2)) => xml_service.diff(xml1, xml2, options)?,
+        (SniffedDocument::Xml(xml1), SniffedDocument::Data(_, value2)) => {
+            xml_service.diff(xml1, &json_to_xml(&value2, conventions)?, options)?
+        }
+        (SniffedDocument::Data(_, value1), SniffedDocument::Xml(xml2)) => {
+            xml_service.diff(&json_to_xml(&value1, conventions)?, xml2, options)?
+        }
+    };
+    Ok(DocumentComparisonResponse { format1, format2, result })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_document() {
+        assert_eq!(sniff_document("\u{feff} <a/>", "d").unwrap().format(), DocumentFormat::Xml);
+        assert_eq!(sniff_document("{\"a\": 1}", "d").unwrap().format(), DocumentFormat::Json);
+        assert_eq!(sniff_document("{a: 1}", "d").unwrap().format(), DocumentFormat::Yaml);
+        assert_eq!(sniff_document("a: 1\nb: [x, y]\n", "d").unwrap().format(), DocumentFormat::Yaml);
+        assert!(sniff_document("just text", "d").is_err());
+    }
+
+    #[test]
+    fn test_compare_mixed_documents() {
+        let (xml_service, json_service) = (XmlComparisonService::new(), JsonComparisonService::new());
+        let options = ComparisonOptions::default();
+        let conventions = JsonConventions::default();
+
+        let yaml = "order:\n  id: 7\n  item: [apple, pear]\n";
+        let result = compare_documents(&xml_service, &json_service, "{\"order\": {\"id\": 7, \"item\": [\"apple\", \"pear\"]}}", yaml, &options, &conventions).unwrap();
+        assert_eq!((result.format1, result.format2), (DocumentFormat::Json, DocumentFormat::Yaml));
+        assert!(result.result.matched);
+
+        let xml = "<order><id>7</id><item>apple</item><item>plum</item></order>";
+        let result = compare_documents(&xml_service, &json_service, xml, yaml, &options, &conventions).unwrap();
+        assert_eq!(result.format1, DocumentFormat::Xml);
+        assert_eq!(result.result.diffs.len(), 1);
+        assert_eq!(result.result.diffs[0].path, "/order/item[2]");
+    }
+}
@@ -0,0 +1,23 @@
+use crate::models::{AppResult, ComparisonOptions, XmlComparisonResponse};
+use crate::services::XmlComparisonService;
+
+/// A diff engine for one document format. Engines take the same options and report in the same
+/// response model; diff paths use the format's own path syntax.
+pub trait DiffEngine {
+    type Document: ?Sized;
+
+    fn diff(
+        &self,
+        document1: &Self::Document,
+        document2: &Self::Document,
+        options: &ComparisonOptions,
+    ) -> AppResult<XmlComparisonResponse>;
+}
+
+impl DiffEngine for XmlComparisonService {
+    type Document = str;
+
+    fn diff(&self, document1: &str, document2: &str, options: &ComparisonOptions) -> AppResult<XmlComparisonResponse> {
+        Ok(self.compare_with_options(document1, document2, options)?)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::models::{AppError, AppResult};
+use regex::Regex;
+use reqwest::Url;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+/// The URL of `path` under `base`, with `base` taken as a directory whether or not it ends in `/`
+pub fn environment_url(base: &str, path: &str) -> AppResult<String> {
+    let url = base_url(base)?
+        .join(path.trim_start_matches('/'))
+        .map_err(|e| AppError::ValidationError(format!("Invalid path '{}': {}", path, e)))?;
+    Ok(url.to_string())
+}
+
+/// The documents under `base` that an index lists, as paths relative to `base`, in the order they
+/// first appear. The index is a sitemap, an HTML page such as a directory listing, or a plain
+/// list with one path or URL per line; relative links are taken relative to `index_url`.
+/// Subdirectories, links with a query and links outside `base` are skipped.
+pub fn index_paths(index: &str, index_url: &str, base: &str) -> AppResult<Vec<String>> {
+    let base = base_url(base)?;
+    let index_url = Url::parse(index_url)
+        .map_err(|e| AppError::ValidationError(format!("Invalid index URL '{}': {}", index_url, e)))?;
+    let mut seen = HashSet::new();
+    let paths = index_links(index.trim_start_matches('\u{feff}'))?
+        .into_iter()
+        .filter_map(|link| index_url.join(&link).ok())
+        .filter(|url| url.query().is_none())
+        .filter_map(|mut url| {
+            url.set_fragment(None);
+            url.as_str().strip_prefix(base.as_str()).map(str::to_string)
+        })
+        .filter(|path| !path.is_empty() && !path.ends_with('/'))
+        .filter(|path| seen.insert(path.clone()))
+        .collect();
+    Ok(paths)
+}
+
+fn base_url(base: &str) -> AppResult<Url> {
+    let mut url = Url::parse(base).map_err(|e| AppError::ValidationError(format!("Invalid base URL '{}': {}", base, e)))?;
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    Ok(url)
+}
+
+fn index_links(index: &str) -> AppResult<Vec<String>> {
+    let trimmed = index.trim_start();
+    if !trimmed.starts_with('<') {
+        return Ok(trimmed
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect());
+    }
+    if let Ok(document) = roxmltree::Document::parse(trimmed) {
+        match document.root_element().tag_name().name() {
+            "urlset" => {
+                return Ok(document
+                    .descendants()
+                    .filter(|node| node.has_tag_name("loc"))
+                    .filter_map(|node| node.text())
+                    .map(|text| text.trim().to_string())
+                    .collect());
+            }
+            "sitemapindex" => {
+                return Err(AppError::ValidationError(
+                    "Sitemap indexes are not followed; give one of the sitemaps it lists".to_string(),
+                ));
+            }
+            _ => {}
+        }
+    }
+    // Anything else is read as HTML
+    static HREF: OnceLock<Regex> = OnceLock::new();
+    let href = HREF.get_or_init(|| Regex::new(r#"(?i)href\s*=\s*["']([^"']+)["']"#).unwrap());
+    Ok(href.captures_iter(trimmed).map(|captures| captures[1].replace("&amp;", "&")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_paths() {
+        assert_eq!(environment_url("https://uat.example.com/trades", "/2024/T-1.xml").unwrap(), "https://uat.example.com/trades/2024/T-1.xml");
+        assert_eq!(environment_url("https://prod.example.com/", "T-1.xml").unwrap(), "https://prod.example.com/T-1.xml");
+
+        let base = "https://uat.example.com/trades";
+        let listing = r#"<html><body><h1>Index of /trades</h1>
+            <a href="?C=N;O=D">Name</a> <a href="/">Parent Directory</a> <a href="2024/">2024/</a>
+            <a href="T-1.xml">T-1.xml</a> <A HREF='T-2.xml'>T-2.xml</A> <a href="T-1.xml">again</a>
+            <a href="https://elsewhere.example.com/T-3.xml">elsewhere</a></body></html>"#;
+        let paths = index_paths(listing, "https://uat.example.com/trades/", base).unwrap();
+        assert_eq!(paths, vec!["T-1.xml", "T-2.xml"]);
+
+        let sitemap = r#"<?xml version="1.0"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <url><loc>https://uat.example.com/trades/2024/T-1.xml</loc></url>
+              <url><loc> https://uat.example.com/trades/T-2.xml </loc></url>
+            </urlset>"#;
+        let paths = index_paths(sitemap, "https://uat.example.com/sitemap.xml", base).unwrap();
+        assert_eq!(paths, vec!["2024/T-1.xml", "T-2.xml"]);
+
+        let list = "# trades to check\n2024/T-1.xml\n\nhttps://uat.example.com/trades/T-2.xml\n";
+        let paths = index_paths(list, "https://uat.example.com/trades/list.txt", base).unwrap();
+        assert_eq!(paths, vec!["2024/T-1.xml", "T-2.xml"]);
+
+        let sitemap_index = r#"<sitemapindex><sitemap><loc>https://uat.example.com/a.xml</loc></sitemap></sitemapindex>"#;
+        assert!(index_paths(sitemap_index, "https://uat.example.com/", base).is_err());
+        assert!(environment_url("not a url", "T-1.xml").is_err());
+    }
+}
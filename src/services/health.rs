@@ -0,0 +1,134 @@
+use crate::models::ProbeResult;
+use reqwest::Client;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Uptime, connectivity probes, and the readiness of components that start in the background
+pub struct HealthService {
+    started_at: Instant,
+    probe_urls: Vec<String>,
+    client: Client,
+    components: Mutex<BTreeMap<String, bool>>,
+    shutting_down: AtomicBool,
+}
+
+impl HealthService {
+    pub fn new(probe_urls: Vec<String>) -> Self {
+        Self {
+            started_at: Instant::now(),
+            probe_urls,
+            client: Client::builder().timeout(PROBE_TIMEOUT).build().unwrap_or_default(),
+            components: Mutex::default(),
+            shutting_down: AtomicBool::new(false),
+        }
+    }
+
+    /// Declares a component readiness waits for, such as a background worker or a storage backend
+    pub fn register_component(&self, name: &str) {
+        self.components.lock().unwrap().entry(name.to_string()).or_insert(false);
+    }
+
+    pub fn mark_ready(&self, name: &str) {
+        self.components.lock().unwrap().insert(name.to_string(), true);
+    }
+
+    /// Takes the instance out of rotation while in-flight work drains
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    /// Whether every registered component is ready and the server is not shutting down, and each
+    /// component's state
+    pub fn readiness(&self) -> (bool, BTreeMap<String, bool>) {
+        let components = self.components.lock().unwrap().clone();
+        (!self.is_shutting_down() && components.values().all(|ready| *ready), components)
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// GETs every probe URL concurrently; an upstream that answers without a 5xx status is up
+    pub async fn probe_upstreams(&self) -> Vec<ProbeResult> {
+        let probes: Vec<_> = self
+            .probe_urls
+            .iter()
+            .map(|url| tokio::spawn(probe(self.client.clone(), url.clone())))
+            .collect();
+        let mut results = Vec::with_capacity(probes.len());
+        for (probe, url) in probes.into_iter().zip(&self.probe_urls) {
+            results.push(probe.await.unwrap_or_else(|e| ProbeResult {
+                url: url.clone(),
+                ok: false,
+                status: None,
+                latency_ms: 0,
+                error: Some(e.to_string()),
+            }));
+        }
+        results
+    }
+}
+
+async fn probe(client: Client, url: String) -> ProbeResult {
+    let started = Instant::now();
+    let outcome = client.get(&url).send().await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match outcome {
+        Ok(response) => ProbeResult {
+            ok: !response.status().is_server_error(),
+            status: Some(response.status().as_u16()),
+            url,
+            latency_ms,
+            error: None,
+        },
+        Err(e) => ProbeResult { url, ok: false, status: None, latency_ms, error: Some(e.to_string()) },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_probe_upstreams() {
+        let server = MockServer::start().await;
+        Mock::given(path("/up")).respond_with(ResponseTemplate::new(404)).mount(&server).await;
+        Mock::given(path("/down")).respond_with(ResponseTemplate::new(503)).mount(&server).await;
+
+        let service = HealthService::new(vec![
+            format!("{}/up", server.uri()),
+            format!("{}/down", server.uri()),
+            "http://127.0.0.1:9/".to_string(),
+        ]);
+        let results = service.probe_upstreams().await;
+        assert_eq!(results.iter().map(|result| result.ok).collect::<Vec<_>>(), [true, false, false]);
+        assert_eq!(results[1].status, Some(503));
+        assert!(results[2].error.is_some());
+    }
+
+    #[test]
+    fn test_readiness() {
+        let service = HealthService::new(Vec::new());
+        assert!(service.readiness().0);
+        service.register_component("session_cleanup");
+        service.register_component("history_db");
+        service.mark_ready("history_db");
+        let (ready, components) = service.readiness();
+        assert!(!ready);
+        assert!(components["history_db"]);
+        service.mark_ready("session_cleanup");
+        assert!(service.readiness().0);
+        service.begin_shutdown();
+        assert!(!service.readiness().0);
+    }
+}
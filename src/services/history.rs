@@ -0,0 +1,410 @@
+use crate::middleware::RecordedComparison;
+use crate::models::{
+    AppError, AppResult, DiffType, HistoryEntry, HistoryQuery, PathFrequency, TrendBucket, TrendPoint, TrendQuery,
+    TrendResponse,
+};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+const DEFAULT_TOP_PATHS: usize = 10;
+// Diffs kept per comparison for trends; a document that differs everywhere says little more
+const MAX_DIFF_PATHS: usize = 1000;
+// The Unix epoch was a Thursday; weeks are counted from the Monday after it
+const WEEK_OFFSET_MS: i64 = 4 * 24 * 3600 * 1000;
+
+// Conditions shared by the history and its trends, over `comparison_history h` and parameters
+// ?1 to ?6: from, to, matched, URL part, tenant and exact URL pair
+const FILTER: &str = "(?1 IS NULL OR h.compared_at >= ?1)
+    AND (?2 IS NULL OR h.compared_at < ?2)
+    AND (?3 IS NULL OR h.matched = ?3)
+    AND (?4 IS NULL OR instr(h.urls, ?4) > 0)
+    AND (?5 IS NULL OR h.tenant = ?5)
+    AND (?6 IS NULL OR h.urls = ?6)";
+
+/// Keeps a summary of comparisons in SQLite: when, for whom, on which URLs and with what outcome.
+/// Without a database nothing is kept.
+#[derive(Clone, Default)]
+pub struct HistoryService {
+    connection: Option<Arc<Mutex<rusqlite::Connection>>>,
+    flagged_only: bool,
+    retention: Option<Duration>,
+}
+
+impl HistoryService {
+    /// Opens or creates the database; `:memory:` keeps it in memory. With `flagged_only` only
+    /// requests that ask for it are kept, and a `retention` of `None` keeps entries forever.
+    pub fn open(path: &str, flagged_only: bool, retention: Option<Duration>) -> AppResult<Self> {
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS comparison_history (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    compared_at INTEGER NOT NULL,
+                    request_id TEXT,
+                    tenant TEXT,
+                    endpoint TEXT NOT NULL,
+                    matched INTEGER NOT NULL,
+                    diff_count INTEGER NOT NULL,
+                    match_ratio REAL NOT NULL,
+                    urls TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS comparison_history_compared_at ON comparison_history (compared_at);
+                CREATE TABLE IF NOT EXISTS comparison_history_diffs (
+                    entry_id INTEGER NOT NULL REFERENCES comparison_history (id) ON DELETE CASCADE,
+                    path TEXT NOT NULL,
+                    diff_type TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS comparison_history_diffs_entry ON comparison_history_diffs (entry_id);
+                PRAGMA foreign_keys = ON;",
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self { connection: Some(Arc::new(Mutex::new(connection))), flagged_only, retention })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Whether a request's comparisons are kept, given whether it asked for that
+    pub fn records(&self, flagged: bool) -> bool {
+        self.is_enabled() && (flagged || !self.flagged_only)
+    }
+
+    /// Keeping history is best effort: the comparisons have already been answered, so failures
+    /// are only logged
+    pub async fn save(
+        &self,
+        request_id: Option<String>,
+        tenant: Option<String>,
+        endpoint: String,
+        comparisons: Vec<RecordedComparison>,
+    ) {
+        if comparisons.is_empty() {
+            return;
+        }
+        let now = Utc::now().timestamp_millis();
+        let result = self
+            .run(move |connection| {
+                let transaction = connection.unchecked_transaction()?;
+                {
+                    let mut statement = transaction.prepare_cached(
+                        "INSERT INTO comparison_history
+                            (compared_at, request_id, tenant, endpoint, matched, diff_count, match_ratio, urls)
+                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    )?;
+                    let mut diff_statement = transaction.prepare_cached(
+                        "INSERT INTO comparison_history_diffs (entry_id, path, diff_type) VALUES (?1, ?2, ?3)",
+                    )?;
+                    for comparison in comparisons {
+                        statement.execute(rusqlite::params![
+                            now,
+                            request_id,
+                            tenant,
+                            endpoint,
+                            comparison.matched,
+                            comparison.diffs as i64,
+                            comparison.match_ratio,
+                            comparison.urls.join("\n"),
+                        ])?;
+                        let entry_id = transaction.last_insert_rowid();
+                        for (path, diff_type) in comparison.diff_paths.iter().take(MAX_DIFF_PATHS) {
+                            diff_statement.execute(rusqlite::params![entry_id, path, diff_type_name(*diff_type)])?;
+                        }
+                    }
+                }
+                transaction.commit()
+            })
+            .await;
+        if let Err(error) = result {
+            tracing::warn!("Comparison history could not be saved: {}", error);
+        }
+    }
+
+    /// Entries matching the query, newest first
+    pub async fn query(&self, query: &HistoryQuery) -> AppResult<Vec<HistoryEntry>> {
+        if !self.is_enabled() {
+            return Err(AppError::NotFound("Comparison history is not kept; set APP_HISTORY_DB".to_string()));
+        }
+        let from = query.from.map(|from| from.timestamp_millis());
+        let to = query.to.map(|to| to.timestamp_millis());
+        let (matched, url, tenant) = (query.matched, query.url.clone(), query.tenant.clone());
+        let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as i64;
+        let offset = query.offset.unwrap_or(0) as i64;
+        self.run(move |connection| {
+            let mut statement = connection.prepare_cached(&format!(
+                "SELECT id, compared_at, request_id, tenant, endpoint, matched, diff_count, match_ratio, urls
+                 FROM comparison_history h
+                 WHERE {}
+                 ORDER BY id DESC
+                 LIMIT ?7 OFFSET ?8",
+                FILTER
+            ))?;
+            let pair: Option<String> = None;
+            let rows = statement.query_map(rusqlite::params![from, to, matched, url, tenant, pair, limit, offset], |row| {
+                let urls: String = row.get(8)?;
+                Ok(HistoryEntry {
+                    id: row.get(0)?,
+                    compared_at: DateTime::from_timestamp_millis(row.get(1)?).unwrap_or_default(),
+                    request_id: row.get(2)?,
+                    tenant: row.get(3)?,
+                    endpoint: row.get(4)?,
+                    matched: row.get(5)?,
+                    diff_count: row.get::<_, i64>(6)? as usize,
+                    match_ratio: row.get(7)?,
+                    urls: urls.split('\n').filter(|url| !url.is_empty()).map(str::to_string).collect(),
+                })
+            })?;
+            rows.collect()
+        })
+        .await
+    }
+
+    /// Match ratios, diff types and differing paths per period of `bucket`
+    pub async fn trends(&self, query: &TrendQuery) -> AppResult<TrendResponse> {
+        if !self.is_enabled() {
+            return Err(AppError::NotFound("Comparison history is not kept; set APP_HISTORY_DB".to_string()));
+        }
+        let pair = match (&query.url1, &query.url2) {
+            (Some(url1), Some(url2)) => Some(format!("{}\n{}", url1, url2)),
+            (None, None) => None,
+            _ => return Err(AppError::ValidationError("Give both url1 and url2, or neither".to_string())),
+        };
+        let bucket = query.bucket.unwrap_or_default();
+        let (width, offset) = match bucket {
+            TrendBucket::Hour => (3600 * 1000, 0),
+            TrendBucket::Day => (24 * 3600 * 1000, 0),
+            TrendBucket::Week => (7 * 24 * 3600 * 1000, WEEK_OFFSET_MS),
+        };
+        let from = query.from.map(|from| from.timestamp_millis());
+        let to = query.to.map(|to| to.timestamp_millis());
+        let (url, tenant) = (query.url.clone(), query.tenant.clone());
+        let top = query.top.unwrap_or(DEFAULT_TOP_PATHS).min(MAX_LIMIT) as i64;
+        let (mut points, diff_types, top_paths) = self
+            .run(move |connection| {
+                let params = rusqlite::params![from, to, None::<bool>, url, tenant, pair, width, offset];
+                let mut statement = connection.prepare_cached(&format!(
+                    "SELECT (h.compared_at - ?8) / ?7 AS bucket, COUNT(*), SUM(h.matched), AVG(h.match_ratio),
+                            MIN(h.match_ratio), SUM(h.diff_count)
+                     FROM comparison_history h
+                     WHERE {}
+                     GROUP BY bucket
+                     ORDER BY bucket",
+                    FILTER
+                ))?;
+                let points = statement
+                    .query_map(params, |row| {
+                        let bucket: i64 = row.get(0)?;
+                        Ok(TrendPoint {
+                            start: DateTime::from_timestamp_millis(bucket * width + offset).unwrap_or_default(),
+                            comparisons: row.get(1)?,
+                            matched: row.get(2)?,
+                            mean_match_ratio: row.get(3)?,
+                            min_match_ratio: row.get(4)?,
+                            diffs: row.get(5)?,
+                            diff_types: BTreeMap::new(),
+                        })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+
+                let mut statement = connection.prepare_cached(&format!(
+                    "SELECT (h.compared_at - ?8) / ?7 AS bucket, d.diff_type, COUNT(*)
+                     FROM comparison_history h JOIN comparison_history_diffs d ON d.entry_id = h.id
+                     WHERE {}
+                     GROUP BY bucket, d.diff_type",
+                    FILTER
+                ))?;
+                let diff_types = statement
+                    .query_map(params, |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, u64>(2)?)))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+
+                let mut statement = connection.prepare_cached(&format!(
+                    "SELECT d.path, COUNT(*) AS diffs, COUNT(DISTINCT h.id)
+                     FROM comparison_history h JOIN comparison_history_diffs d ON d.entry_id = h.id
+                     WHERE {}
+                     GROUP BY d.path
+                     ORDER BY diffs DESC, d.path
+                     LIMIT ?7",
+                    FILTER
+                ))?;
+                let top_paths = statement
+                    .query_map(rusqlite::params![from, to, None::<bool>, url, tenant, pair, top], |row| {
+                        Ok(PathFrequency { path: row.get(0)?, diffs: row.get(1)?, comparisons: row.get(2)? })
+                    })?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok((points, diff_types, top_paths))
+            })
+            .await?;
+
+        let mut totals = BTreeMap::new();
+        for (bucket, name, count) in diff_types {
+            let Some(diff_type) = parse_diff_type(&name) else {
+                continue;
+            };
+            *totals.entry(diff_type).or_insert(0) += count;
+            let start = DateTime::from_timestamp_millis(bucket * width + offset).unwrap_or_default();
+            if let Some(point) = points.iter_mut().find(|point| point.start == start) {
+                point.diff_types.insert(diff_type, count);
+            }
+        }
+        let match_ratio_change = match (points.first(), points.last()) {
+            (Some(first), Some(last)) if points.len() > 1 => Some(last.mean_match_ratio - first.mean_match_ratio),
+            _ => None,
+        };
+        Ok(TrendResponse { bucket, points, top_paths, diff_types: totals, match_ratio_change })
+    }
+
+    pub async fn remove_expired(&self) {
+        let Some(retention) = self.retention.filter(|_| self.is_enabled()) else {
+            return;
+        };
+        let cutoff = Utc::now().timestamp_millis().saturating_sub(retention.as_millis() as i64);
+        let removed = self
+            .run(move |connection| {
+                connection.execute("DELETE FROM comparison_history WHERE compared_at < ?1", rusqlite::params![cutoff])
+            })
+            .await;
+        match removed {
+            Ok(0) => {}
+            Ok(removed) => tracing::debug!(removed, "Removed expired history entries"),
+            Err(error) => tracing::warn!("Expired history entries could not be removed: {}", error),
+        }
+    }
+
+    // SQLite blocks, so statements run off the async runtime
+    async fn run<T, F>(&self, f: F) -> AppResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&rusqlite::Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let Some(connection) = self.connection.clone() else {
+            return Err(history_error("history is not kept".to_string()));
+        };
+        tokio::task::spawn_blocking(move || f(&connection.lock().unwrap()))
+            .await
+            .map_err(|e| history_error(e.to_string()))?
+            .map_err(sqlite_error)
+    }
+}
+
+// Diff types are stored under their serialized names
+fn diff_type_name(diff_type: DiffType) -> String {
+    serde_json::to_value(diff_type).ok().and_then(|value| value.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+fn parse_diff_type(name: &str) -> Option<DiffType> {
+    serde_json::from_value(serde_json::Value::String(name.to_string())).ok()
+}
+
+fn sqlite_error(error: rusqlite::Error) -> AppError {
+    history_error(error.to_string())
+}
+
+fn history_error(message: String) -> AppError {
+    AppError::InternalError(format!("History store error: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comparison(matched: bool, urls: &[&str]) -> RecordedComparison {
+        RecordedComparison {
+            matched,
+            diffs: usize::from(!matched),
+            match_ratio: if matched { 1.0 } else { 0.5 },
+            urls: urls.iter().map(|url| url.to_string()).collect(),
+            diff_paths: if matched { Vec::new() } else { vec![("/trade/price".to_string(), DiffType::ContentDifferent)] },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let history = HistoryService::open(":memory:", false, Some(Duration::from_secs(3600))).unwrap();
+        assert!(history.records(false));
+        let started = Utc::now();
+        history
+            .save(
+                Some("req-1".to_string()),
+                Some("pricing".to_string()),
+                "/api/compare/url/batch".to_string(),
+                vec![
+                    comparison(true, &["https://uat.example.com/trades/1", "https://prod.example.com/trades/1"]),
+                    comparison(false, &["https://uat.example.com/trades/2", "https://prod.example.com/trades/2"]),
+                ],
+            )
+            .await;
+        history.save(None, None, "/api/compare/xml".to_string(), vec![comparison(true, &[])]).await;
+
+        let all = history.query(&HistoryQuery::default()).await.unwrap();
+        assert_eq!(all.iter().map(|entry| entry.id).collect::<Vec<_>>(), vec![3, 2, 1]);
+        assert!(all[0].compared_at >= started - chrono::Duration::seconds(1));
+        assert_eq!(all[1].urls, vec!["https://uat.example.com/trades/2", "https://prod.example.com/trades/2"]);
+
+        let query = |query: HistoryQuery| {
+            let history = history.clone();
+            async move { history.query(&query).await.unwrap().iter().map(|entry| entry.id).collect::<Vec<_>>() }
+        };
+        assert_eq!(query(HistoryQuery { matched: Some(false), ..Default::default() }).await, vec![2]);
+        assert_eq!(query(HistoryQuery { url: Some("trades/1".to_string()), ..Default::default() }).await, vec![1]);
+        assert_eq!(query(HistoryQuery { tenant: Some("pricing".to_string()), ..Default::default() }).await, vec![2, 1]);
+        assert_eq!(query(HistoryQuery { limit: Some(1), offset: Some(1), ..Default::default() }).await, vec![2]);
+        let later = HistoryQuery { from: Some(Utc::now() + chrono::Duration::minutes(1)), ..Default::default() };
+        assert!(query(later).await.is_empty());
+
+        history.remove_expired().await;
+        assert_eq!(query(HistoryQuery::default()).await.len(), 3);
+
+        let flagged = HistoryService::open(":memory:", true, None).unwrap();
+        assert!(!flagged.records(false) && flagged.records(true));
+        assert!(!HistoryService::default().records(true));
+        assert!(matches!(HistoryService::default().query(&HistoryQuery::default()).await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_trends() {
+        let history = HistoryService::open(":memory:", false, None).unwrap();
+        let pair = ["https://uat.example.com/trades/1", "https://prod.example.com/trades/1"];
+        let mut drifted = comparison(false, &pair);
+        drifted.diff_paths.push(("/trade/book".to_string(), DiffType::ElementMissing));
+        history
+            .save(None, Some("pricing".to_string()), "/api/compare/url".to_string(), vec![comparison(true, &pair), drifted])
+            .await;
+        history.save(None, None, "/api/compare/xml".to_string(), vec![comparison(false, &[])]).await;
+
+        let trends = history.trends(&TrendQuery::default()).await.unwrap();
+        assert_eq!(trends.bucket, TrendBucket::Day);
+        assert_eq!(trends.points.len(), 1);
+        let point = &trends.points[0];
+        assert_eq!((point.comparisons, point.matched, point.diffs), (3, 1, 2));
+        assert!((point.mean_match_ratio - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(point.min_match_ratio, 0.5);
+        assert_eq!(point.start, DateTime::from_timestamp(Utc::now().timestamp() / 86400 * 86400, 0).unwrap());
+        assert_eq!(trends.diff_types, BTreeMap::from([(DiffType::ContentDifferent, 2), (DiffType::ElementMissing, 1)]));
+        assert_eq!(point.diff_types, trends.diff_types);
+        assert_eq!(trends.top_paths[0].path, "/trade/price");
+        assert_eq!((trends.top_paths[0].diffs, trends.top_paths[0].comparisons), (2, 2));
+        assert_eq!(trends.match_ratio_change, None);
+
+        let for_pair = TrendQuery {
+            url1: Some(pair[0].to_string()),
+            url2: Some(pair[1].to_string()),
+            bucket: Some(TrendBucket::Week),
+            top: Some(1),
+            ..Default::default()
+        };
+        let trends = history.trends(&for_pair).await.unwrap();
+        assert_eq!(trends.points[0].comparisons, 2);
+        assert_eq!(trends.points[0].start.format("%A").to_string(), "Monday");
+        assert_eq!(trends.top_paths.len(), 1);
+        let reversed = TrendQuery { url1: Some(pair[1].to_string()), url2: Some(pair[0].to_string()), ..Default::default() };
+        assert!(history.trends(&reversed).await.unwrap().points.is_empty());
+
+        let half_pair = TrendQuery { url1: Some(pair[0].to_string()), ..Default::default() };
+        assert!(matches!(history.trends(&half_pair).await, Err(AppError::ValidationError(_))));
+        assert!(matches!(HistoryService::default().trends(&TrendQuery::default()).await, Err(AppError::NotFound(_))));
+    }
+}
@@ -1,45 +1,390 @@
-use crate::models::{AppError, AppResult, Session};
-use reqwest::Client;
+use crate::models::{AppError, AppResult, DEFAULT_XML_CONTENT_TYPES, DownloadRequest, FetchOptions, OAuth2Credentials, OAuth2Token, ProxyOptions, Session, SourceCache, DocumentSource};
+use crate::services::{CachedDocument, DocumentCache};
+use reqwest::{header, Client, ClientBuilder, Identity, Method, Response, StatusCode};
 use base64::{Engine as _, engine::general_purpose};
+use hashlink::LruCache;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::Duration;
+
+// reqwest's own default
+const DEFAULT_MAX_REDIRECTS: usize = 10;
+// Requests choose their own timeouts, proxies and redirect limits, so the clients built for them
+// are bounded; the least recently used one is dropped
+const DOWNLOAD_CLIENT_CAPACITY: usize = 32;
 
 pub struct HttpClientService {
     client: Client,
+    defaults: FetchOptions,
+    pool: PoolOptions,
+    // Settings reqwest only takes per client get one client each, reused across requests
+    download_clients: Mutex<LruCache<ClientSettings, Client>>,
+    documents: DocumentCache,
+    // Client certificates by host name
+    identities: HashMap<String, Identity>,
+    // Named bearer tokens that requests refer to with `credential`
+    bearer_tokens: HashMap<String, String>,
+}
+
+impl Default for HttpClientService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How the clients keep and reuse connections. Every download client gets the same settings, so a
+/// batch reuses its connections to each host from one item to the next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolOptions {
+    /// Idle connections kept open per host
+    pub max_idle_per_host: usize,
+    /// How long an idle connection is kept; `None` keeps it until the server closes it
+    pub idle_timeout: Option<Duration>,
+    /// Interval of TCP keepalive probes on open connections; `None` sends none
+    pub tcp_keepalive: Option<Duration>,
+    pub http_version: HttpVersion,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: 32,
+            idle_timeout: Some(Duration::from_secs(90)),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            http_version: HttpVersion::Auto,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/2 where the server offers it during the TLS handshake, HTTP/1.1 otherwise
+    #[default]
+    Auto,
+    Http1,
+    /// HTTP/2 only, also over plain `http://` URLs; servers that only speak HTTP/1.1 fail
+    Http2,
+}
+
+/// A downloaded document, with where it came from and how it was transferred
+#[derive(Debug)]
+pub struct FetchedDocument {
+    pub xml: String,
+    pub source: DocumentSource,
+}
+
+// A response body after undoing its compression
+struct DecodedBody {
+    text: String,
+    bytes: u64,
+    compressed_bytes: Option<u64>,
+    compression: Vec<String>,
+    // A `.gz` file was unpacked, so the Content-Type described the archive rather than the XML
+    unpacked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ClientSettings {
+    connect_timeout: Option<Duration>,
+    proxy: Option<ProxyOptions>,
+    max_redirects: usize,
+    cross_host_redirects: bool,
+    // Host whose client certificate is presented
+    identity: Option<String>,
 }
 
 impl HttpClientService {
     pub fn new() -> Self {
+        Self::with_defaults(FetchOptions::default())
+    }
+
+    /// Downloads use `defaults` for the fetch options a request leaves unset
+    pub fn with_defaults(defaults: FetchOptions) -> Self {
         Self {
             client: Client::new(),
+            defaults,
+            pool: PoolOptions::default(),
+            download_clients: Mutex::new(LruCache::new(DOWNLOAD_CLIENT_CAPACITY)),
+            documents: DocumentCache::default(),
+            identities: HashMap::new(),
+            bearer_tokens: HashMap::new(),
+        }
+    }
+
+    /// Presents a client certificate to each of the hosts; `certificates` pairs a host name with a
+    /// PEM file holding the certificate chain and private key
+    pub fn with_client_certificates(mut self, certificates: &[(String, String)]) -> AppResult<Self> {
+        for (host, path) in certificates {
+            let pem = std::fs::read(path).map_err(|e| {
+                AppError::ValidationError(format!("Cannot read client certificate {} for {}: {}", path, host, e))
+            })?;
+            let identity = Identity::from_pem(&pem).map_err(|e| {
+                AppError::ValidationError(format!("Invalid client certificate {} for {}: {}", path, host, e))
+            })?;
+            self.identities.insert(host.to_ascii_lowercase(), identity);
         }
+        Ok(self)
+    }
+
+    /// Tokens that requests may use by name instead of sending the token itself
+    pub fn with_bearer_tokens(mut self, tokens: &[(String, String)]) -> Self {
+        self.bearer_tokens.extend(tokens.iter().cloned());
+        self
+    }
+
+    /// Keeps up to `capacity_bytes` of downloaded documents for conditional GETs
+    pub fn with_document_cache(mut self, capacity_bytes: usize) -> Self {
+        self.documents = DocumentCache::new(capacity_bytes);
+        self
+    }
+
+    /// Keeps and reuses connections as `pool` says, for downloads as well as logins and token
+    /// requests
+    pub fn with_pool(mut self, pool: PoolOptions) -> AppResult<Self> {
+        self.pool = pool;
+        self.client = self.client_builder().build().map_err(|e| AppError::InternalError(format!("Failed to build HTTP client: {}", e)))?;
+        self.download_clients.lock().unwrap().clear();
+        Ok(self)
+    }
+
+    fn client_builder(&self) -> ClientBuilder {
+        let builder = Client::builder()
+            .pool_max_idle_per_host(self.pool.max_idle_per_host)
+            .pool_idle_timeout(self.pool.idle_timeout)
+            .tcp_keepalive(self.pool.tcp_keepalive);
+        match self.pool.http_version {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1 => builder.http1_only(),
+            HttpVersion::Http2 => builder.http2_prior_knowledge(),
+        }
+    }
+
+    fn download_client(&self, settings: ClientSettings) -> AppResult<Client> {
+        let mut clients = self.download_clients.lock().unwrap();
+        if let Some(client) = clients.get_mut(&settings) {
+            return Ok(client.clone());
+        }
+        let mut builder = self.client_builder().redirect(redirect_policy(settings.max_redirects, settings.cross_host_redirects));
+        if let Some(connect_timeout) = settings.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(identity) = settings.identity.as_ref().and_then(|host| self.identities.get(host)) {
+            builder = builder.identity(identity.clone());
+        }
+        if let Some(options) = &settings.proxy {
+            builder = builder.proxy(proxy(options)?);
+        }
+        let client = builder.build().map_err(|e| AppError::InternalError(format!("Failed to build HTTP client: {}", e)))?;
+        clients.insert(settings, client.clone());
+        Ok(client)
     }
 
     pub async fn download_xml(
         &self, 
         url: &str, 
         auth_service: Option<&crate::services::AuthService>,
-        session_id: Option<&str>
+        session_id: Option<&str>,
+        headers: &BTreeMap<String, String>,
+        request: &DownloadRequest,
+        fetch: &FetchOptions,
     ) -> AppResult<String> {
-        let mut request = self.client.get(url);
+        Ok(self.fetch_xml(url, auth_service, session_id, headers, request, fetch).await?.xml)
+    }
+
+    /// Like `download_xml`, also telling whether the cached copy was reused
+    pub async fn fetch_xml(
+        &self,
+        url: &str,
+        auth_service: Option<&crate::services::AuthService>,
+        session_id: Option<&str>,
+        headers: &BTreeMap<String, String>,
+        request: &DownloadRequest,
+        fetch: &FetchOptions,
+    ) -> AppResult<FetchedDocument> {
+        let fetch = fetch.clone().or(self.defaults.clone());
+        let download = self.download(url, auth_service, session_id, headers, request, &fetch);
+        match timeout(fetch.timeout_ms) {
+            Some(limit) => tokio::time::timeout(limit, download).await.map_err(|_| {
+                AppError::Timeout(format!("Downloading {} took longer than {} ms", url, limit.as_millis()))
+            })?,
+            None => download.await,
+        }
+    }
+
+    async fn download(
+        &self,
+        url: &str,
+        auth_service: Option<&crate::services::AuthService>,
+        session_id: Option<&str>,
+        headers: &BTreeMap<String, String>,
+        download: &DownloadRequest,
+        fetch: &FetchOptions,
+    ) -> AppResult<FetchedDocument> {
+        let client = self.download_client(ClientSettings {
+            connect_timeout: timeout(fetch.connect_timeout_ms),
+            proxy: fetch.proxy.clone(),
+            max_redirects: fetch.max_redirects.unwrap_or(DEFAULT_MAX_REDIRECTS),
+            cross_host_redirects: fetch.cross_host_redirects.unwrap_or(true),
+            identity: reqwest::Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+                .filter(|host| self.identities.contains_key(host)),
+        })?;
+        let method = match &download.method {
+            Some(method) => Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                .map_err(|_| AppError::ValidationError(format!("Invalid HTTP method '{}'", method)))?,
+            None => Method::GET,
+        };
+        let mut request = client.request(method.clone(), url);
+        if let Some(body) = &download.body {
+            let content_type = download.content_type.as_deref().unwrap_or("application/xml");
+            request = request.header(header::CONTENT_TYPE, content_type).body(body.clone());
+        }
+        if !headers.keys().any(|name| name.eq_ignore_ascii_case("accept")) {
+            request = request.header(header::ACCEPT, "application/xml, text/xml");
+        }
+
+        for (name, value) in headers {
+            let name = header::HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| AppError::ValidationError(format!("Invalid header name '{}'", name)))?;
+            let value = header::HeaderValue::from_str(value)
+                .map_err(|_| AppError::ValidationError(format!("Invalid value for header '{}'", name)))?;
+            request = request.header(name, value);
+        }
+
+        let bearer_token = match (&fetch.bearer_token, &fetch.credential, &fetch.oauth2) {
+            (None, None, None) => None,
+            (Some(token), None, None) => Some(token.clone()),
+            (None, Some(name), None) => Some(
+                self.bearer_tokens
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| AppError::ValidationError(format!("Unknown credential '{}'", name)))?,
+            ),
+            (None, None, Some(credentials)) => match auth_service {
+                Some(auth_service) => Some(auth_service.oauth2_token(credentials).await?),
+                None => None,
+            },
+            _ => {
+                return Err(AppError::ValidationError(
+                    "Give at most one of bearer_token, credential and oauth2".to_string(),
+                ));
+            }
+        };
+        if let Some(token) = bearer_token {
+            request = request.bearer_auth(token);
+        }
 
         // Add cookies if session exists
-        if let (Some(auth_service), Some(session_id)) = (auth_service, session_id) {
-            if let Some(session) = auth_service.get_session(session_id).await? {
-                for cookie in &session.cookies {
-                    request = request.header("Cookie", cookie);
-                }
+        if let (Some(auth_service), Some(session_id)) = (auth_service, session_id)
+            && let Some(session) = auth_service.session_for_download(session_id).await?
+        {
+            for cookie in &session.cookies {
+                request = request.header("Cookie", cookie);
             }
         }
 
-        let response = request.send().await.map_err(|e| AppError::HttpError(e.to_string()))?;
-        
+        // Revalidate a cached copy instead of downloading it again. Only plain GETs are cached, as
+        // the answer to a query body depends on more than the URL.
+        let key = DocumentCache::key(url, headers);
+        let cacheable = method == Method::GET && download.body.is_none();
+        let cached = if cacheable { self.documents.get(&key) } else { None };
+        if !headers.keys().any(|name| name.eq_ignore_ascii_case("accept-encoding")) {
+            request = request.header(header::ACCEPT_ENCODING, "gzip, deflate, br");
+        }
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await.map_err(|e| fetch_error(url, e))?;
+        let final_url = response.url().to_string();
+
+        if response.status() == StatusCode::NOT_MODIFIED
+            && let Some(cached) = cached
+        {
+            let source = DocumentSource {
+                url: url.to_string(),
+                final_url,
+                cache: SourceCache::Hit,
+                bytes: cached.body.len() as u64,
+                compressed_bytes: None,
+                compression: Vec::new(),
+            };
+            return Ok(FetchedDocument { xml: cached.body.to_string(), source });
+        }
         if !response.status().is_success() {
             return Err(AppError::InternalError(
                 format!("HTTP request failed with status: {}", response.status())
             ));
         }
 
-        let content = response.text().await.map_err(|e| AppError::HttpError(e.to_string()))?;
-        Ok(content)
+        let validator = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string);
+        let etag = validator(header::ETAG);
+        let last_modified = validator(header::LAST_MODIFIED);
+        let no_store = validator(header::CACHE_CONTROL).is_some_and(|value| value.to_ascii_lowercase().contains("no-store"));
+        let content_type = validator(header::CONTENT_TYPE);
+
+        let max_bytes = fetch.max_bytes.filter(|max_bytes| *max_bytes > 0);
+        let body = read_text(url, response, timeout(fetch.read_timeout_ms), max_bytes).await?;
+        let xml = body.text;
+
+        // HTML error and login pages would otherwise reach the parser and produce confusing diffs
+        if let Some(content_type) = content_type.filter(|_| !body.unpacked) {
+            let default_types = DEFAULT_XML_CONTENT_TYPES.map(str::to_string);
+            let accepted = fetch.content_types.as_deref().unwrap_or(&default_types);
+            if !is_accepted_content_type(accepted, &content_type) {
+                return Err(AppError::UnexpectedContent(format!(
+                    "{} returned {} instead of XML: {}",
+                    final_url, content_type, preview(&xml)
+                )));
+            }
+        }
+        if cacheable && (etag.is_some() || last_modified.is_some()) && !no_store {
+            self.documents.insert(key, CachedDocument::new(&xml, etag, last_modified));
+        }
+        let source = DocumentSource {
+            url: url.to_string(),
+            final_url,
+            cache: SourceCache::Miss,
+            bytes: body.bytes,
+            compressed_bytes: body.compressed_bytes,
+            compression: body.compression,
+        };
+        Ok(FetchedDocument { xml, source })
+    }
+
+    /// Runs the OAuth2 client-credentials grant, authenticating the client with HTTP Basic
+    pub async fn request_oauth2_token(&self, credentials: &OAuth2Credentials) -> AppResult<OAuth2Token> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &credentials.scope {
+            form.push(("scope", scope));
+        }
+        let response = self.client
+            .post(&credentials.token_url)
+            .basic_auth(&credentials.client_id, Some(&credentials.client_secret))
+            .form(&form)
+            .send()
+            .await
+            .map_err(|e| AppError::HttpError(format!("Token request failed: {} (URL: {})", e, credentials.token_url)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_body = response.text().await.unwrap_or_default();
+            return Err(AppError::AuthError(format!(
+                "Token endpoint {} refused client '{}' (status: {}): {}",
+                credentials.token_url, credentials.client_id, status, error_body
+            )));
+        }
+        response
+            .json()
+            .await
+            .map_err(|e| AppError::AuthError(format!("Invalid token response from {}: {}", credentials.token_url, e)))
     }
 
     // Note: batch download method removed as it's not used and would need significant refactoring
@@ -88,7 +433,7 @@ impl HttpClientService {
             .map_err(|e| {
                 AppError::HttpError(format!(
                     "{} request failed: {} (URL: {})", 
-                    method, e.to_string(), url
+                    method, e, url
                 ))
             })?;
 
@@ -109,24 +454,220 @@ impl HttpClientService {
             return Err(AppError::AuthError(error_message));
         }
 
-        // Extract cookies from successful response
-        let cookies: Vec<String> = response
-            .headers()
-            .get_all("set-cookie")
-            .iter()
-            .filter_map(|header| header.to_str().ok().map(|s| s.to_string()))
-            .collect();
-
-        let session = Session::new(url.to_string(), cookies);
+        let session = Session::new(url.to_string(), set_cookies(&response));
         Ok(session)
     }
+
+    /// Requests `url` with a session's cookies to keep the session alive, returning the cookies the
+    /// server set in its answer
+    pub async fn keep_alive(&self, url: &str, cookies: &[String]) -> AppResult<Vec<String>> {
+        let mut request = self.client.get(url);
+        for cookie in cookies {
+            request = request.header("Cookie", cookie);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| AppError::HttpError(format!("Keep-alive request failed: {} (URL: {})", e, url)))?;
+        if !response.status().is_success() {
+            return Err(AppError::AuthError(format!("Keep-alive URL {} answered {}", url, response.status())));
+        }
+        Ok(set_cookies(&response))
+    }
+}
+
+fn set_cookies(response: &Response) -> Vec<String> {
+    response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok().map(|s| s.to_string()))
+        .collect()
+}
+
+fn timeout(ms: Option<u64>) -> Option<Duration> {
+    ms.filter(|ms| *ms > 0).map(Duration::from_millis)
+}
+
+fn redirect_policy(max_redirects: usize, cross_host: bool) -> reqwest::redirect::Policy {
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() > max_redirects {
+            let error = format!("more than {} redirects", max_redirects);
+            return attempt.error(error);
+        }
+        let origin = attempt.previous().first().and_then(|url| url.host_str().map(str::to_string));
+        if !cross_host && attempt.url().host_str().map(str::to_string) != origin {
+            let error = format!("redirect to another host ({}) is not allowed", attempt.url());
+            return attempt.error(error);
+        }
+        attempt.follow()
+    })
+}
+
+fn proxy(options: &ProxyOptions) -> AppResult<reqwest::Proxy> {
+    let mut proxy = reqwest::Proxy::all(&options.url)
+        .map_err(|e| AppError::ValidationError(format!("Invalid proxy URL '{}': {}", options.url, e)))?;
+    if let Some(username) = &options.username {
+        proxy = proxy.basic_auth(username, options.password.as_deref().unwrap_or_default());
+    }
+    Ok(proxy.no_proxy(reqwest::NoProxy::from_string(&options.no_proxy.join(","))))
+}
+
+fn fetch_error(url: &str, error: reqwest::Error) -> AppError {
+    if error.is_timeout() {
+        AppError::Timeout(format!("Connecting to {} timed out", url))
+    } else {
+        AppError::HttpError(error.to_string())
+    }
+}
+
+// An empty allowlist accepts anything
+fn is_accepted_content_type(accepted: &[String], content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    accepted.is_empty() || accepted.iter().any(|pattern| matches_glob(&pattern.trim().to_ascii_lowercase(), &essence))
+}
+
+// `*` matches any run of characters, including none
+fn matches_glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+// The start of a body with its whitespace collapsed, for error messages
+fn preview(text: &str) -> String {
+    const PREVIEW_CHARS: usize = 200;
+    let start: String = text.chars().take(PREVIEW_CHARS).collect();
+    let mut preview = start.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().nth(PREVIEW_CHARS).is_some() {
+        preview.push_str("...");
+    }
+    preview
+}
+
+// Reads the body chunk by chunk so a stalled upstream trips `read_timeout` and an oversized one is
+// dropped as soon as it passes `max_bytes`, then decodes it with the charset of the Content-Type,
+// as `Response::text` does
+async fn read_text(
+    url: &str,
+    mut response: Response,
+    read_timeout: Option<Duration>,
+    max_bytes: Option<u64>,
+) -> AppResult<DecodedBody> {
+    let too_large = |max_bytes| AppError::PayloadTooLarge(format!("{} is larger than {} bytes", url, max_bytes));
+    if let Some(max_bytes) = max_bytes
+        && response.content_length().is_some_and(|length| length > max_bytes)
+    {
+        return Err(too_large(max_bytes));
+    }
+    let encoding = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .and_then(|mime| mime.get_param("charset").and_then(|charset| encoding_rs::Encoding::for_label(charset.as_str().as_bytes())))
+        .unwrap_or(encoding_rs::UTF_8);
+    let header_value = |name| response.headers().get(name).and_then(|value| value.to_str().ok()).unwrap_or_default().to_ascii_lowercase();
+    let content_encoding = header_value(header::CONTENT_ENCODING);
+    let content_type = header_value(header::CONTENT_TYPE);
+    let gzipped_at_rest = response.url().path().to_ascii_lowercase().ends_with(".gz")
+        || content_type.starts_with("application/gzip")
+        || content_type.starts_with("application/x-gzip");
+
+    let mut body = Vec::new();
+    loop {
+        let chunk = match read_timeout {
+            Some(limit) => tokio::time::timeout(limit, response.chunk()).await.map_err(|_| {
+                AppError::Timeout(format!("No data from {} for {} ms", url, limit.as_millis()))
+            })?,
+            None => response.chunk().await,
+        };
+        match chunk.map_err(|e| AppError::HttpError(e.to_string()))? {
+            Some(chunk) => {
+                if let Some(max_bytes) = max_bytes
+                    && (body.len() + chunk.len()) as u64 > max_bytes
+                {
+                    return Err(too_large(max_bytes));
+                }
+                body.extend_from_slice(&chunk)
+            }
+            None => break,
+        }
+    }
+    let compressed_bytes = body.len() as u64;
+
+    // Content-Encoding lists the codings in the order they were applied
+    let mut compression = Vec::new();
+    for coding in content_encoding.split(',').map(str::trim).rev() {
+        match coding {
+            "" | "identity" => {}
+            "gzip" | "x-gzip" | "deflate" | "br" => {
+                body = decompress(url, coding, &body, max_bytes)?;
+                compression.insert(0, coding.trim_start_matches("x-").to_string());
+            }
+            other => {
+                return Err(AppError::UnexpectedContent(format!("{} is encoded with unsupported {}", url, other)));
+            }
+        }
+    }
+    let unpacked = gzipped_at_rest && body.starts_with(&[0x1f, 0x8b]);
+    if unpacked {
+        body = decompress(url, "gzip", &body, max_bytes)?;
+        compression.insert(0, "gzip".to_string());
+    }
+
+    let (text, _, _) = encoding.decode(&body);
+    Ok(DecodedBody {
+        text: text.into_owned(),
+        bytes: body.len() as u64,
+        compressed_bytes: (!compression.is_empty()).then_some(compressed_bytes),
+        compression,
+        unpacked,
+    })
+}
+
+// `max_bytes` also bounds the decompressed size, so a small archive cannot expand without limit
+fn decompress(url: &str, coding: &str, body: &[u8], max_bytes: Option<u64>) -> AppResult<Vec<u8>> {
+    let reader: Box<dyn Read + '_> = match coding {
+        "br" => Box::new(brotli::Decompressor::new(body, 4096)),
+        // Servers disagree on whether deflate is zlib-wrapped, so accept both
+        "deflate" if body.len() >= 2 && body[0] & 0x0f == 8 && u16::from_be_bytes([body[0], body[1]]).is_multiple_of(31) => {
+            Box::new(flate2::read::ZlibDecoder::new(body))
+        }
+        "deflate" => Box::new(flate2::read::DeflateDecoder::new(body)),
+        _ => Box::new(flate2::read::MultiGzDecoder::new(body)),
+    };
+    let mut decompressed = Vec::new();
+    reader
+        .take(max_bytes.map_or(u64::MAX, |max_bytes| max_bytes + 1))
+        .read_to_end(&mut decompressed)
+        .map_err(|e| AppError::UnexpectedContent(format!("{} could not be decompressed from {}: {}", url, coding, e)))?;
+    if let Some(max_bytes) = max_bytes
+        && decompressed.len() as u64 > max_bytes
+    {
+        return Err(AppError::PayloadTooLarge(format!("{} is larger than {} bytes once decompressed", url, max_bytes)));
+    }
+    Ok(decompressed)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use wiremock::{MockServer, Mock, ResponseTemplate};
-    use wiremock::matchers::{method, path, header};
+    use wiremock::matchers::{body_string, method, path, header, headers};
 
     #[tokio::test]
     async fn test_download_xml_success() {
@@ -141,7 +682,7 @@ mod tests {
         let service = HttpClientService::new();
         let url = format!("{}/test.xml", mock_server.uri());
         
-        let result = service.download_xml(&url, None, None).await;
+        let result = service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default()).await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "<test>content</test>");
     }
@@ -159,10 +700,486 @@ mod tests {
         let service = HttpClientService::new();
         let url = format!("{}/notfound.xml", mock_server.uri());
         
-        let result = service.download_xml(&url, None, None).await;
+        let result = service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default()).await;
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_download_xml_sends_headers() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/trade.xml"))
+            .and(header("Accept", "application/fpml+xml"))
+            .and(header("X-Tenant", "emea"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<trade/>"))
+            .mount(&mock_server)
+            .await;
+
+        let service = HttpClientService::new();
+        let url = format!("{}/trade.xml", mock_server.uri());
+        let headers = BTreeMap::from([
+            ("Accept".to_string(), "application/fpml+xml".to_string()),
+            ("X-Tenant".to_string(), "emea".to_string()),
+        ]);
+        assert_eq!(service.download_xml(&url, None, None, &headers, &DownloadRequest::default(), &FetchOptions::default()).await.unwrap(), "<trade/>");
+
+        let headers = BTreeMap::from([("Bad Name".to_string(), "x".to_string())]);
+        let result = service.download_xml(&url, None, None, &headers, &DownloadRequest::default(), &FetchOptions::default()).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_with_bearer_token() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/trade.xml"))
+            .and(header("Authorization", "Bearer t0ken"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<trade/>"))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let service = HttpClientService::new().with_bearer_tokens(&[("trades".to_string(), "t0ken".to_string())]);
+        let url = format!("{}/trade.xml", mock_server.uri());
+        let download = |fetch: FetchOptions| {
+            let (service, url) = (&service, &url);
+            async move { service.download_xml(url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch).await }
+        };
+        assert_eq!(download(FetchOptions { bearer_token: Some("t0ken".to_string()), ..Default::default() }).await.unwrap(), "<trade/>");
+        assert_eq!(download(FetchOptions { credential: Some("trades".to_string()), ..Default::default() }).await.unwrap(), "<trade/>");
+
+        let unknown = download(FetchOptions { credential: Some("fpml".to_string()), ..Default::default() }).await;
+        assert!(matches!(unknown, Err(AppError::ValidationError(message)) if message.contains("'fpml'")));
+        let both = FetchOptions { bearer_token: Some("t0ken".to_string()), credential: Some("trades".to_string()), ..Default::default() };
+        assert!(matches!(download(both).await, Err(AppError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_with_post_body() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/query"))
+            .and(header("Content-Type", "text/xml"))
+            .and(body_string("<query id=\"42\"/>"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<trade id=\"42\"/>").insert_header("ETag", "\"v1\""))
+            .mount(&mock_server)
+            .await;
+
+        let service = HttpClientService::new().with_document_cache(1024);
+        let url = format!("{}/query", mock_server.uri());
+        let request = DownloadRequest {
+            method: Some("post".to_string()),
+            body: Some("<query id=\"42\"/>".to_string()),
+            content_type: Some("text/xml".to_string()),
+        };
+        for _ in 0..2 {
+            let document = service
+                .fetch_xml(&url, None, None, &BTreeMap::new(), &request, &FetchOptions::default())
+                .await
+                .unwrap();
+            assert_eq!((document.xml.as_str(), document.source.cache), ("<trade id=\"42\"/>", SourceCache::Miss));
+        }
+
+        let request = DownloadRequest { method: Some("NOT A METHOD".to_string()), ..Default::default() };
+        let result = service.download_xml(&url, None, None, &BTreeMap::new(), &request, &FetchOptions::default()).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_client_certificates() {
+        let certificate = rcgen::generate_simple_self_signed(vec!["comparer".to_string()]).unwrap();
+        let directory = std::env::temp_dir().join(format!("xml-compare-certs-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let identity = directory.join("client.pem");
+        let pem = certificate.serialize_pem().unwrap() + certificate.serialize_private_key_pem().as_str();
+        std::fs::write(&identity, pem).unwrap();
+        let certificate_only = directory.join("certificate.pem");
+        std::fs::write(&certificate_only, certificate.serialize_pem().unwrap()).unwrap();
+        let entry = |path: &std::path::Path| vec![("127.0.0.1".to_string(), path.display().to_string())];
+
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/trade.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<trade/>"))
+            .mount(&mock_server)
+            .await;
+        let service = HttpClientService::new().with_client_certificates(&entry(&identity)).unwrap();
+        let url = format!("http://127.0.0.1:{}/trade.xml", mock_server.address().port());
+        let xml = service
+            .download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default())
+            .await;
+        assert_eq!(xml.unwrap(), "<trade/>");
+
+        for path in [certificate_only, directory.join("missing.pem")] {
+            let result = HttpClientService::new().with_client_certificates(&entry(&path));
+            assert!(matches!(result, Err(AppError::ValidationError(_))));
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_through_proxy() {
+        let proxy_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/trade.xml"))
+            .and(header("Proxy-Authorization", "Basic dXNlcjpwYXNz"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<proxied/>"))
+            .mount(&proxy_server)
+            .await;
+
+        let service = HttpClientService::with_defaults(FetchOptions {
+            proxy: Some(ProxyOptions {
+                url: proxy_server.uri(),
+                username: Some("user".to_string()),
+                password: Some("pass".to_string()),
+                no_proxy: Vec::new(),
+            }),
+            ..Default::default()
+        });
+        let result = service
+            .download_xml("http://upstream.invalid/trade.xml", None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default())
+            .await;
+        assert_eq!(result.unwrap(), "<proxied/>");
+
+        // Hosts in no_proxy are fetched directly
+        let direct_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/trade.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<direct/>"))
+            .mount(&direct_server)
+            .await;
+        let fetch = FetchOptions {
+            proxy: Some(ProxyOptions {
+                url: proxy_server.uri(),
+                username: None,
+                password: None,
+                no_proxy: vec!["127.0.0.1".to_string()],
+            }),
+            ..Default::default()
+        };
+        let url = format!("{}/trade.xml", direct_server.uri());
+        assert_eq!(service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch).await.unwrap(), "<direct/>");
+
+        let fetch = FetchOptions {
+            proxy: Some(ProxyOptions { url: "not a proxy".to_string(), username: None, password: None, no_proxy: Vec::new() }),
+            ..Default::default()
+        };
+        let result = service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_xml_revalidates_cached_documents() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/trade.xml"))
+            .and(header("If-None-Match", "\"v1\""))
+            .respond_with(ResponseTemplate::new(304))
+            .with_priority(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/trade.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<trade/>").insert_header("ETag", "\"v1\""))
+            .mount(&mock_server)
+            .await;
+
+        let url = format!("{}/trade.xml", mock_server.uri());
+        async fn fetch(service: &HttpClientService, url: &str) -> FetchedDocument {
+            service.fetch_xml(url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default()).await.unwrap()
+        }
+        let service = HttpClientService::new().with_document_cache(1024);
+        let first = fetch(&service, &url).await;
+        assert_eq!((first.xml.as_str(), first.source.cache), ("<trade/>", SourceCache::Miss));
+        let second = fetch(&service, &url).await;
+        assert_eq!((second.xml.as_str(), second.source.cache), ("<trade/>", SourceCache::Hit));
+
+        // Without a cache every download is unconditional
+        let service = HttpClientService::new();
+        fetch(&service, &url).await;
+        assert_eq!(fetch(&service, &url).await.source.cache, SourceCache::Miss);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_xml_redirect_policy() {
+        let mock_server = MockServer::start().await;
+        let port = mock_server.address().port();
+        Mock::given(method("GET"))
+            .and(path("/old.xml"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", "/new.xml"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/elsewhere.xml"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", format!("http://localhost:{}/new.xml", port).as_str()))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/new.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<new/>"))
+            .mount(&mock_server)
+            .await;
+
+        let service = HttpClientService::new();
+        let fetch = |max_redirects, cross_host_redirects| FetchOptions { max_redirects, cross_host_redirects, ..Default::default() };
+        let old = format!("http://127.0.0.1:{}/old.xml", port);
+        let document = service.fetch_xml(&old, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch(None, None)).await.unwrap();
+        assert_eq!((document.xml.as_str(), document.source.final_url), ("<new/>", format!("http://127.0.0.1:{}/new.xml", port)));
+        let result = service.fetch_xml(&old, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch(Some(0), None)).await;
+        assert!(matches!(result, Err(AppError::HttpError(_))));
+
+        let elsewhere = format!("http://127.0.0.1:{}/elsewhere.xml", port);
+        let document = service.fetch_xml(&elsewhere, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch(None, None)).await.unwrap();
+        assert_eq!(document.source.final_url, format!("http://localhost:{}/new.xml", port));
+        let result = service.fetch_xml(&elsewhere, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch(None, Some(false))).await;
+        assert!(matches!(result, Err(AppError::HttpError(message)) if message.contains("another host")));
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_timeouts() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/slow.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("<a/>").set_delay(Duration::from_millis(500)))
+            .mount(&mock_server)
+            .await;
+        let url = format!("{}/slow.xml", mock_server.uri());
+
+        let service = HttpClientService::with_defaults(FetchOptions { timeout_ms: Some(100), ..Default::default() });
+        let result = service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default()).await;
+        assert!(matches!(result, Err(AppError::Timeout(_))));
+        // The request's own setting wins over the server's; 0 lifts the limit
+        let fetch = FetchOptions { timeout_ms: Some(0), ..Default::default() };
+        assert_eq!(service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch).await.unwrap(), "<a/>");
+
+        // Headers arrive at once, then the body stalls
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n<a>").await.unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        });
+        let fetch = FetchOptions { read_timeout_ms: Some(100), ..Default::default() };
+        let result = HttpClientService::new().download_xml(&format!("http://{}/", address), None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch).await;
+        assert!(matches!(result, Err(AppError::Timeout(message)) if message.starts_with("No data from")));
+    }
+
+    #[tokio::test]
+    async fn test_downloads_reuse_pooled_connections() {
+        // Answers every request on a connection and counts the connections opened
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let connections = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let opened = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                opened.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::spawn(async move {
+                    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    while let Ok(read @ 1..) = socket.read(&mut buffer).await {
+                        request.extend_from_slice(&buffer[..read]);
+                        if request.windows(4).any(|window| window == b"\r\n\r\n") {
+                            request.clear();
+                            socket.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n<a/>").await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        let url = format!("http://{}/", address);
+        let download_three_times = |service: HttpClientService| {
+            let url = url.clone();
+            async move {
+                let (headers, request, fetch) = (BTreeMap::new(), DownloadRequest::default(), FetchOptions::default());
+                for _ in 0..3 {
+                    assert_eq!(service.download_xml(&url, None, None, &headers, &request, &fetch).await.unwrap(), "<a/>");
+                }
+            }
+        };
+
+        download_three_times(HttpClientService::new().with_pool(PoolOptions::default()).unwrap()).await;
+        assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let unpooled = PoolOptions { max_idle_per_host: 0, http_version: HttpVersion::Http1, ..Default::default() };
+        download_three_times(HttpClientService::new().with_pool(unpooled).unwrap()).await;
+        assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_download_clients_are_bounded() {
+        let service = HttpClientService::new();
+        for connect_timeout_ms in 1..=100 {
+            let settings = ClientSettings {
+                connect_timeout: Some(Duration::from_millis(connect_timeout_ms)),
+                proxy: None,
+                max_redirects: DEFAULT_MAX_REDIRECTS,
+                cross_host_redirects: true,
+                identity: None,
+            };
+            service.download_client(settings).unwrap();
+        }
+        let clients = service.download_clients.lock().unwrap();
+        assert_eq!(clients.len(), DOWNLOAD_CLIENT_CAPACITY);
+        assert!(clients.iter().any(|(settings, _)| settings.connect_timeout == Some(Duration::from_millis(100))));
+        assert!(!clients.iter().any(|(settings, _)| settings.connect_timeout == Some(Duration::from_millis(1))));
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_size_limit() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/large.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(format!("<a>{}</a>", "x".repeat(100))))
+            .mount(&mock_server)
+            .await;
+        let url = format!("{}/large.xml", mock_server.uri());
+        let download = |max_bytes| {
+            let fetch = FetchOptions { max_bytes, ..Default::default() };
+            let service = HttpClientService::with_defaults(FetchOptions { max_bytes: Some(50), ..Default::default() });
+            let url = url.clone();
+            async move { service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch).await }
+        };
+
+        assert!(matches!(download(None).await, Err(AppError::PayloadTooLarge(_))));
+        assert_eq!(download(Some(107)).await.unwrap().len(), 107);
+        assert!(download(Some(0)).await.is_ok());
+
+        // Without a Content-Length the limit applies while streaming
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            use tokio::io::AsyncWriteExt;
+            let (mut socket, _) = listener.accept().await.unwrap();
+            socket.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n").await.unwrap();
+            for _ in 0..100 {
+                if socket.write_all(b"10\r\n<a>xxxxxxxxx</a>\r\n").await.is_err() {
+                    break;
+                }
+            }
+            let _ = socket.write_all(b"0\r\n\r\n").await;
+        });
+        let fetch = FetchOptions { max_bytes: Some(64), ..Default::default() };
+        let url = format!("http://{}/", address);
+        let result = HttpClientService::new()
+            .download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &fetch)
+            .await;
+        assert!(matches!(result, Err(AppError::PayloadTooLarge(_))));
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_decodes_charset() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/latin1.xml"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(b"<name>Jos\xe9</name>".to_vec(), "application/xml; charset=ISO-8859-1"))
+            .mount(&mock_server)
+            .await;
+
+        let service = HttpClientService::new();
+        let url = format!("{}/latin1.xml", mock_server.uri());
+        assert_eq!(service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default()).await.unwrap(), "<name>José</name>");
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_checks_content_type() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/report.xml"))
+            .and(headers("Accept", vec!["application/xml", "text/xml"]))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(b"<report/>".to_vec(), "application/vnd.acme+xml"))
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                b"<!DOCTYPE html>\n<html>\n  <head><title>Sign in</title></head>\n</html>".to_vec(),
+                "text/html; charset=utf-8",
+            ))
+            .mount(&mock_server)
+            .await;
+
+        let service = HttpClientService::new();
+        let no_headers = BTreeMap::new();
+        let url = format!("{}/report.xml", mock_server.uri());
+        assert_eq!(service.download_xml(&url, None, None, &no_headers, &DownloadRequest::default(), &FetchOptions::default()).await.unwrap(), "<report/>");
+
+        let url = format!("{}/login", mock_server.uri());
+        let error = service.download_xml(&url, None, None, &no_headers, &DownloadRequest::default(), &FetchOptions::default()).await.unwrap_err();
+        assert!(matches!(error, AppError::UnexpectedContent(_)));
+        assert!(error.to_string().contains("returned text/html;charset=utf-8 instead of XML: <!DOCTYPE html> <html> <head><title>Sign in"), "{}", error);
+
+        let fetch = FetchOptions { content_types: Some(vec!["*/*".to_string()]), ..Default::default() };
+        assert!(service.download_xml(&url, None, None, &no_headers, &DownloadRequest::default(), &fetch).await.unwrap().starts_with("<!DOCTYPE html>"));
+        let fetch = FetchOptions { content_types: Some(vec!["application/xml".to_string()]), ..Default::default() };
+        let url = format!("{}/report.xml", mock_server.uri());
+        assert!(service.download_xml(&url, None, None, &no_headers, &DownloadRequest::default(), &fetch).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_download_xml_decompresses() {
+        use std::io::Write;
+        let xml = format!("<trades>{}</trades>", "<trade id=\"1\"/>".repeat(100));
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(xml.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut deflate = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        deflate.write_all(xml.as_bytes()).unwrap();
+        let deflate = deflate.finish().unwrap();
+        let mut br = Vec::new();
+        brotli::BrotliCompress(&mut xml.as_bytes(), &mut br, &Default::default()).unwrap();
+
+        let mock_server = MockServer::start().await;
+        for (route, encoding, body) in [("/gzip", "gzip", &gzip), ("/deflate", "deflate", &deflate), ("/br", "br", &br)] {
+            Mock::given(method("GET"))
+                .and(path(route))
+                .and(headers("Accept-Encoding", vec!["gzip", "deflate", "br"]))
+                .respond_with(ResponseTemplate::new(200).set_body_raw(body.clone(), "application/xml").insert_header("Content-Encoding", encoding))
+                .mount(&mock_server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/trades.xml.gz"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(gzip.clone(), "application/gzip"))
+            .mount(&mock_server)
+            .await;
+
+        async fn fetch(service: &HttpClientService, url: String) -> FetchedDocument {
+            service.fetch_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &FetchOptions::default()).await.unwrap()
+        }
+        let service = HttpClientService::new();
+        for (route, body) in [("gzip", &gzip), ("deflate", &deflate), ("br", &br)] {
+            let document = fetch(&service, format!("{}/{}", mock_server.uri(), route)).await;
+            assert_eq!(document.xml, xml);
+            assert_eq!(document.source.compression, vec![route.to_string()]);
+            assert_eq!((document.source.bytes, document.source.compressed_bytes), (xml.len() as u64, Some(body.len() as u64)));
+        }
+        let document = fetch(&service, format!("{}/trades.xml.gz", mock_server.uri())).await;
+        assert_eq!((document.xml.as_str(), document.source.compression), (xml.as_str(), vec!["gzip".to_string()]));
+
+        // The limit applies to the decompressed document too
+        let url = format!("{}/gzip", mock_server.uri());
+        let limited = FetchOptions { max_bytes: Some(1000), ..Default::default() };
+        let result = service.download_xml(&url, None, None, &BTreeMap::new(), &DownloadRequest::default(), &limited).await;
+        assert!(matches!(result, Err(AppError::PayloadTooLarge(message)) if message.contains("once decompressed")));
+    }
+
+    #[test]
+    fn test_content_type_patterns() {
+        let accepted = DEFAULT_XML_CONTENT_TYPES.map(str::to_string);
+        assert!(is_accepted_content_type(&accepted, "Application/XML; charset=UTF-8"));
+        assert!(is_accepted_content_type(&accepted, "application/atom+xml"));
+        assert!(!is_accepted_content_type(&accepted, "application/json"));
+        assert!(!is_accepted_content_type(&accepted, "application/xml-dtd"));
+        assert!(is_accepted_content_type(&[], "text/html"));
+        assert!(matches_glob("a*b*c", "abbc"));
+        assert!(!matches_glob("a*bc", "abc*"));
+    }
+
     #[tokio::test]
     async fn test_authenticate_success_with_post() {
         let mock_server = MockServer::start().await;
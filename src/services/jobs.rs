@@ -0,0 +1,249 @@
+use crate::middleware::current_tenant_name;
+use crate::models::{AppError, AppResult, JobInfo, JobProgress};
+use crate::services::profile_service::tenant_key;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+/// Batches that are running, by tenant and job ID, so that they can be listed, followed and
+/// cancelled by the tenant that started them
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<JobKey, Arc<Job>>>>,
+    progress_log_interval: Option<Duration>,
+}
+
+// Tenant and job ID
+type JobKey = (Option<String>, String);
+
+pub struct Job {
+    pub id: String,
+    pub tenant: Option<String>,
+    pub kind: &'static str,
+    pub total: usize,
+    pub started_at: DateTime<Utc>,
+    started: Instant,
+    completed: AtomicUsize,
+    failed: AtomicUsize,
+    cancel: watch::Sender<bool>,
+}
+
+impl Job {
+    /// Counts a comparison that finished, successfully or not
+    pub fn record(&self, succeeded: bool) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        if !succeeded {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn progress(&self) -> JobProgress {
+        let completed = self.completed.load(Ordering::Relaxed);
+        let elapsed = self.started.elapsed();
+        let items_per_second = if elapsed.is_zero() { 0.0 } else { completed as f64 / elapsed.as_secs_f64() };
+        let remaining = self.total.saturating_sub(completed);
+        JobProgress {
+            id: self.id.clone(),
+            total: self.total,
+            completed,
+            failed: self.failed.load(Ordering::Relaxed),
+            remaining,
+            elapsed_ms: elapsed.as_millis() as u64,
+            items_per_second,
+            eta_seconds: (items_per_second > 0.0 && !self.is_cancelled()).then(|| remaining as f64 / items_per_second),
+            cancelled: self.is_cancelled(),
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel.borrow()
+    }
+
+    /// Resolves once the job is cancelled
+    pub async fn cancelled(&self) {
+        let mut cancelled = self.cancel.subscribe();
+        // The sender lives as long as the job, so waiting cannot fail
+        let _ = cancelled.wait_for(|cancelled| *cancelled).await;
+    }
+
+    pub fn cancelled_error(&self) -> AppError {
+        AppError::Cancelled(format!("Job '{}' was cancelled", self.id))
+    }
+
+    pub fn info(&self) -> JobInfo {
+        JobInfo {
+            id: self.id.clone(),
+            kind: self.kind.to_string(),
+            total: self.total,
+            started_at: self.started_at.to_rfc3339(),
+            cancelled: self.is_cancelled(),
+        }
+    }
+}
+
+impl JobRegistry {
+    /// Logs the progress of each job this often while it runs
+    pub fn with_progress_log_interval(mut self, interval: Duration) -> Self {
+        self.progress_log_interval = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// Registers a batch of `total` items under `id`, or a new ID; it stays listed until the
+    /// returned guard is dropped
+    pub fn start(&self, id: Option<String>, kind: &'static str, total: usize) -> AppResult<JobGuard> {
+        let id = id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let key = tenant_key(&id);
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.contains_key(&key) {
+            return Err(AppError::Conflict(format!("Job '{}' is already running", id)));
+        }
+        let job = Arc::new(Job {
+            id,
+            tenant: key.0.clone(),
+            kind,
+            total,
+            started_at: Utc::now(),
+            started: Instant::now(),
+            completed: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            cancel: watch::Sender::new(false),
+        });
+        jobs.insert(key, job.clone());
+        if let Some(interval) = self.progress_log_interval {
+            tokio::spawn(log_progress(Arc::downgrade(&job), interval));
+        }
+        Ok(JobGuard { registry: self.clone(), job })
+    }
+
+    pub fn progress(&self, id: &str) -> AppResult<JobProgress> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(&tenant_key(id))
+            .map(|job| job.progress())
+            .ok_or_else(|| AppError::NotFound(format!("No running job '{}'", id)))
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let tenant = current_tenant_name();
+        let mut jobs: Vec<_> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|job| job.tenant == tenant)
+            .map(|job| job.info())
+            .collect();
+        jobs.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        jobs
+    }
+
+    /// Asks the job to stop; it returns what it has finished so far
+    pub fn cancel(&self, id: &str) -> AppResult<JobInfo> {
+        let job = self
+            .jobs
+            .lock()
+            .unwrap()
+            .get(&tenant_key(id))
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("No running job '{}'", id)))?;
+        job.cancel.send_replace(true);
+        Ok(job.info())
+    }
+
+    /// Cancels every running job, as on shutdown, and returns how many there were
+    pub fn cancel_all(&self) -> usize {
+        let jobs = self.jobs.lock().unwrap();
+        for job in jobs.values() {
+            job.cancel.send_replace(true);
+        }
+        jobs.len()
+    }
+}
+
+// Stops once every holder of the job, the request and its workers, has let go of it
+async fn log_progress(job: std::sync::Weak<Job>, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let Some(job) = job.upgrade() else {
+            return;
+        };
+        let progress = job.progress();
+        tracing::info!(
+            job_id = %progress.id,
+            completed = progress.completed,
+            failed = progress.failed,
+            total = progress.total,
+            items_per_second = format!("{:.1}", progress.items_per_second),
+            eta_seconds = progress.eta_seconds.map(|eta| eta.round() as u64),
+            "Job progress"
+        );
+    }
+}
+
+/// A running job; dropping it removes the job from the registry
+pub struct JobGuard {
+    registry: JobRegistry,
+    job: Arc<Job>,
+}
+
+impl JobGuard {
+    pub fn job(&self) -> Arc<Job> {
+        self.job.clone()
+    }
+}
+
+impl std::ops::Deref for JobGuard {
+    type Target = Job;
+
+    fn deref(&self) -> &Job {
+        &self.job
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        let key = (self.job.tenant.clone(), self.job.id.clone());
+        self.registry.jobs.lock().unwrap().remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancel_job() {
+        let registry = JobRegistry::default();
+        let guard = registry.start(Some("nightly".to_string()), "url_batch", 3).unwrap();
+        assert!(matches!(registry.start(Some("nightly".to_string()), "url_batch", 1), Err(AppError::Conflict(_))));
+        assert_eq!(registry.list().len(), 1);
+
+        guard.record(true);
+        guard.record(false);
+        let progress = registry.progress("nightly").unwrap();
+        assert_eq!((progress.completed, progress.failed, progress.remaining), (2, 1, 1));
+        assert!(progress.items_per_second > 0.0 && progress.eta_seconds.is_some());
+
+        let job = guard.job();
+        let waiter = tokio::spawn(async move { job.cancelled().await });
+        let info = registry.cancel("nightly").unwrap();
+        assert!(info.cancelled && guard.is_cancelled());
+        waiter.await.unwrap();
+
+        let other = registry.start(None, "url_batch", 2).unwrap();
+        assert_eq!(registry.cancel_all(), 2);
+        assert!(other.is_cancelled());
+
+        drop((guard, other));
+        assert!(registry.list().is_empty());
+        assert!(matches!(registry.cancel("nightly"), Err(AppError::NotFound(_))));
+        assert!(matches!(registry.progress("nightly"), Err(AppError::NotFound(_))));
+        assert_ne!(registry.start(None, "xml_batch", 1).unwrap().id, "");
+    }
+}
@@ -0,0 +1,265 @@
+use crate::models::{AppResult, ComparisonOptions, DiffSeverity, DiffType, XmlComparisonResponse, XmlDiff};
+use crate::services::{DiffClassifier, DiffEngine};
+use crate::services::xml_comparison::{path_pattern_matches, property_matches};
+use serde_json::Value;
+
+/// Compares JSON documents. Values are addressed by JSON Pointer (`/order/items/0/sku`), which
+/// `ignore_paths` patterns match with the same exact, prefix and glob rules as XML paths;
+/// `ignore_properties` names object members to skip at any depth. `ignore_element_order` pairs
+/// equal array items regardless of position, and `max_diffs` and `classifications` apply as for XML.
+#[derive(Clone, Default)]
+pub struct JsonComparisonService;
+
+struct JsonContext<'a> {
+    ignore_paths: &'a [String],
+    ignore_properties: &'a [String],
+    ignore_element_order: bool,
+}
+
+#[derive(Default)]
+struct JsonOutcome {
+    diffs: Vec<XmlDiff>,
+    matched: usize,
+    max_diffs: Option<usize>,
+    truncated: bool,
+}
+
+impl JsonOutcome {
+    fn push(&mut self, path: &str, diff_type: DiffType, expected: Option<String>, actual: Option<String>, message: String) {
+        if self.max_diffs.is_some_and(|max| self.diffs.len() >= max) {
+            self.truncated = true;
+            return;
+        }
+        self.diffs.push(XmlDiff {
+            path: path.to_string(),
+            diff_type,
+            expected,
+            actual,
+            message,
+            similarity: None,
+            severity: DiffSeverity::Error,
+            tags: Vec::new(),
+            node: None,
+        });
+    }
+}
+
+impl JsonComparisonService {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn compare_values(&self, path: &str, value1: &Value, value2: &Value, context: &JsonContext, outcome: &mut JsonOutcome) {
+        // Ignored values count as matched, like ignored XML elements
+        if context.ignore_paths.iter().any(|pattern| path_pattern_matches(path, pattern)) {
+            outcome.matched += count(value1);
+            return;
+        }
+
+        match (value1, value2) {
+            (Value::Object(members1), Value::Object(members2)) => {
+                outcome.matched += 1;
+                for (key, member1) in members1 {
+                    let member_path = child_pointer(path, key);
+                    if self.property_ignored(key, context) {
+                        outcome.matched += count(member1);
+                        continue;
+                    }
+                    match members2.get(key) {
+                        Some(member2) => self.compare_values(&member_path, member1, member2, context, outcome),
+                        None => self.report_missing(&member_path, member1, context, outcome),
+                    }
+                }
+                for (key, member2) in members2 {
+                    if !members1.contains_key(key) && !self.property_ignored(key, context) {
+                        self.report_extra(&child_pointer(path, key), member2, context, outcome);
+                    }
+                }
+            }
+            (Value::Array(items1), Value::Array(items2)) => {
+                outcome.matched += 1;
+                let pairs = pair_items(items1, items2, context.ignore_element_order);
+                for (i, item1) in items1.iter().enumerate() {
+                    let item_path = child_pointer(path, &i.to_string());
+                    match pairs[i] {
+                        Some(j) => self.compare_values(&item_path, item1, &items2[j], context, outcome),
+                        None => self.report_missing(&item_path, item1, context, outcome),
+                    }
+                }
+                for (j, item2) in items2.iter().enumerate() {
+                    if !pairs.contains(&Some(j)) {
+                        self.report_extra(&child_pointer(path, &j.to_string()), item2, context, outcome);
+                    }
+                }
+            }
+            _ if value1 == value2 => outcome.matched += 1,
+            _ if kind(value1) == kind(value2) || (is_scalar(value1) && is_scalar(value2)) => outcome.push(
+                path,
+                DiffType::ContentDifferent,
+                Some(display(value1)),
+                Some(display(value2)),
+                "Value differs".to_string(),
+            ),
+            _ => outcome.push(
+                path,
+                DiffType::StructureDifferent,
+                Some(kind(value1).to_string()),
+                Some(kind(value2).to_string()),
+                format!("Value is {} in first JSON but {} in second JSON", kind(value1), kind(value2)),
+            ),
+        }
+    }
+
+    fn report_missing(&self, path: &str, value: &Value, context: &JsonContext, outcome: &mut JsonOutcome) {
+        if !context.ignore_paths.iter().any(|pattern| path_pattern_matches(path, pattern)) {
+            outcome.push(path, DiffType::ElementMissing, Some(display(value)), None, "Value missing in second JSON".to_string());
+        }
+    }
+
+    fn report_extra(&self, path: &str, value: &Value, context: &JsonContext, outcome: &mut JsonOutcome) {
+        if !context.ignore_paths.iter().any(|pattern| path_pattern_matches(path, pattern)) {
+            outcome.push(path, DiffType::ElementExtra, None, Some(display(value)), "Extra value in second JSON".to_string());
+        }
+    }
+
+    fn property_ignored(&self, key: &str, context: &JsonContext) -> bool {
+        context.ignore_properties.iter().any(|prop| property_matches(key, None, prop))
+    }
+}
+
+impl DiffEngine for JsonComparisonService {
+    type Document = Value;
+
+    fn diff(&self, document1: &Value, document2: &Value, options: &ComparisonOptions) -> AppResult<XmlComparisonResponse> {
+        let context = JsonContext {
+            ignore_paths: options.ignore_paths.as_deref().unwrap_or_default(),
+            ignore_properties: options.ignore_properties.as_deref().unwrap_or_default(),
+            ignore_element_order: options.ignore_element_order.unwrap_or(false),
+        };
+        let classifier = DiffClassifier::compile(options.classifications.as_deref().unwrap_or_default())?;
+        let mut outcome = JsonOutcome { max_diffs: options.max_diffs, ..Default::default() };
+        self.compare_values("", document1, document2, &context, &mut outcome);
+        let tag_counts = classifier.classify(&mut outcome.diffs);
+
+        let total_elements = count(document1).max(count(document2));
+        let matched_elements = outcome.matched.min(total_elements);
+        let mut result = XmlComparisonResponse {
+            matched: outcome.diffs.is_empty() && !outcome.truncated,
+            match_ratio: if total_elements > 0 { matched_elements as f64 / total_elements as f64 } else { 1.0 },
+            diffs: outcome.diffs,
+            total_elements,
+            matched_elements,
+            truncated: outcome.truncated,
+            tag_counts,
+            ..Default::default()
+        };
+        result.shape(options);
+        Ok(result)
+    }
+}
+
+// Pairs each item of the first array with an item of the second: by position, or with
+// `ignore_element_order` first with an equal unused item and then with the next unused one
+fn pair_items(items1: &[Value], items2: &[Value], ignore_order: bool) -> Vec<Option<usize>> {
+    if !ignore_order {
+        return (0..items1.len()).map(|i| (i < items2.len()).then_some(i)).collect();
+    }
+    let mut used = vec![false; items2.len()];
+    let mut pairs: Vec<Option<usize>> = items1
+        .iter()
+        .map(|item1| {
+            let j = (0..items2.len()).find(|&j| !used[j] && items2[j] == *item1)?;
+            used[j] = true;
+            Some(j)
+        })
+        .collect();
+    for pair in pairs.iter_mut().filter(|pair| pair.is_none()) {
+        if let Some(j) = used.iter().position(|used| !used) {
+            used[j] = true;
+            *pair = Some(j);
+        }
+    }
+    pairs
+}
+
+// RFC 6901 escaping of one reference token
+fn child_pointer(path: &str, token: &str) -> String {
+    format!("{}/{}", path, token.replace('~', "~0").replace('/', "~1"))
+}
+
+fn count(value: &Value) -> usize {
+    1 + match value {
+        Value::Object(members) => members.values().map(count).sum(),
+        Value::Array(items) => items.iter().map(count).sum(),
+        _ => 0,
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn is_scalar(value: &Value) -> bool {
+    !matches!(value, Value::Array(_) | Value::Object(_))
+}
+
+// Strings without their quotes, everything else as compact JSON
+fn display(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_diffs() {
+        let service = JsonComparisonService::new();
+        let json1 = json!({ "id": 1, "name": "a", "tags": ["x", "y"], "meta": { "at": "t1" }, "a/b": true });
+        let json2 = json!({ "id": 2, "name": ["a"], "tags": ["x"], "meta": { "at": "t2" }, "a/b": true, "new": null });
+        let options = ComparisonOptions { ignore_properties: Some(vec!["at".to_string()]), ..Default::default() };
+
+        let result = service.diff(&json1, &json2, &options).unwrap();
+        let diffs: Vec<_> = result.diffs.iter().map(|diff| (diff.path.as_str(), diff.diff_type)).collect();
+        assert_eq!(
+            diffs,
+            vec![
+                ("/id", DiffType::ContentDifferent),
+                ("/name", DiffType::StructureDifferent),
+                ("/tags/1", DiffType::ElementMissing),
+                ("/new", DiffType::ElementExtra),
+            ]
+        );
+        assert_eq!(child_pointer("", "a/b~c"), "/a~1b~0c");
+    }
+
+    #[test]
+    fn test_json_ignore_paths_and_order() {
+        let service = JsonComparisonService::new();
+        let json1 = json!({ "items": [{ "sku": "A", "at": 1 }, { "sku": "B", "at": 2 }] });
+        let json2 = json!({ "items": [{ "sku": "B", "at": 3 }, { "sku": "A", "at": 4 }] });
+
+        let options = ComparisonOptions {
+            ignore_paths: Some(vec!["/items/*/at".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(service.diff(&json1, &json2, &options).unwrap().diffs.len(), 2);
+
+        let json2 = json!({ "items": [{ "sku": "B", "at": 2 }, { "sku": "A", "at": 1 }] });
+        let options = ComparisonOptions { ignore_element_order: Some(true), ..Default::default() };
+        let result = service.diff(&json1, &json2, &options).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.match_ratio, 1.0);
+    }
+}
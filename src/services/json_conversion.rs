@@ -0,0 +1,153 @@
+use crate::models::{AppError, AppResult, JsonConventions};
+use quick_xml::escape::escape;
+use serde_json::Value;
+
+/// Converts a JSON document into the XML it stands for under `conventions`, so it can be
+/// compared with an XML document. Object members become child elements, array members repeat
+/// their element, members starting with the attribute prefix become attributes and the text key
+/// holds the element's text.
+pub fn json_to_xml(json: &Value, conventions: &JsonConventions) -> AppResult<String> {
+    let mut xml = String::new();
+    match (&conventions.root_name, json) {
+        (Some(root), Value::Array(items)) => {
+            xml.push_str(&format!("<{}>", checked_name(root)?));
+            for item in items {
+                write_element(&mut xml, "item", item, conventions)?;
+            }
+            xml.push_str(&format!("</{}>", root));
+        }
+        (Some(root), value) => write_element(&mut xml, root, value, conventions)?,
+        (None, Value::Object(members)) if members.len() == 1 => {
+            let (name, value) = members.iter().next().unwrap();
+            if value.is_array() {
+                return Err(root_error());
+            }
+            write_element(&mut xml, name, value, conventions)?;
+        }
+        (None, _) => return Err(root_error()),
+    }
+    Ok(xml)
+}
+
+fn write_element(xml: &mut String, name: &str, value: &Value, conventions: &JsonConventions) -> AppResult<()> {
+    let name = checked_name(name)?;
+    let Value::Object(members) = value else {
+        match scalar_text(value) {
+            Some(text) if !text.is_empty() => xml.push_str(&format!("<{0}>{1}</{0}>", name, escape(&text))),
+            _ => xml.push_str(&format!("<{}/>", name)),
+        }
+        return Ok(());
+    };
+
+    xml.push_str(&format!("<{}", name));
+    let mut text = None;
+    let mut children = Vec::new();
+    for (key, member) in members {
+        if key == &conventions.text_key {
+            text = Some(scalar_text(member).ok_or_else(|| {
+                AppError::ValidationError(format!("'{}' of element '{}' must be a scalar", key, name))
+            })?);
+        } else if let Some(attribute) = key.strip_prefix(conventions.attribute_prefix.as_str())
+            && !conventions.attribute_prefix.is_empty()
+        {
+            let value = scalar_text(member).ok_or_else(|| {
+                AppError::ValidationError(format!("Attribute '{}' of element '{}' must be a scalar", key, name))
+            })?;
+            xml.push_str(&format!(" {}=\"{}\"", checked_name(attribute)?, escape(&value)));
+        } else {
+            children.push((key, member));
+        }
+    }
+
+    let text = text.unwrap_or_default();
+    if children.is_empty() && text.is_empty() {
+        xml.push_str("/>");
+        return Ok(());
+    }
+    xml.push('>');
+    xml.push_str(&escape(&text));
+    for (key, member) in children {
+        match member {
+            Value::Array(items) => {
+                for item in items {
+                    if item.is_array() {
+                        return Err(AppError::ValidationError(format!("Nested arrays are not supported ('{}')", key)));
+                    }
+                    write_element(xml, key, item, conventions)?;
+                }
+            }
+            member => write_element(xml, key, member, conventions)?,
+        }
+    }
+    xml.push_str(&format!("</{}>", name));
+    Ok(())
+}
+
+// Text of a scalar as written in JSON; null is empty. None for objects and arrays.
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some(String::new()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        Value::Number(number) => Some(number.to_string()),
+        Value::String(text) => Some(text.clone()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn checked_name(name: &str) -> AppResult<&str> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+    if valid {
+        Ok(name)
+    } else {
+        Err(AppError::ValidationError(format!("'{}' is not a valid XML name", name)))
+    }
+}
+
+fn root_error() -> AppError {
+    AppError::ValidationError(
+        "The JSON document must be an object with a single non-array member, or conventions.root_name must be set"
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_json_to_xml() {
+        let json = json!({
+            "trade": {
+                "@id": "T1",
+                "amount": 10.5,
+                "active": true,
+                "note": null,
+                "legs": { "leg": [{ "#text": "fixed", "@n": 1 }, { "#text": "float", "@n": 2 }] }
+            }
+        });
+        assert_eq!(
+            json_to_xml(&json, &JsonConventions::default()).unwrap(),
+            "<trade id=\"T1\"><amount>10.5</amount><active>true</active><note/><legs><leg n=\"1\">fixed</leg><leg n=\"2\">float</leg></legs></trade>"
+        );
+    }
+
+    #[test]
+    fn test_json_conventions() {
+        let conventions = JsonConventions {
+            attribute_prefix: "_".to_string(),
+            text_key: "value".to_string(),
+            root_name: Some("items".to_string()),
+        };
+        let json = json!([{ "_code": "a&b", "value": "x" }, "y"]);
+        assert_eq!(
+            json_to_xml(&json, &conventions).unwrap(),
+            "<items><item code=\"a&amp;b\">x</item><item>y</item></items>"
+        );
+
+        assert!(json_to_xml(&json!({ "a": 1, "b": 2 }), &JsonConventions::default()).is_err());
+        assert!(json_to_xml(&json!({ "a": { "bad name": 1 } }), &JsonConventions::default()).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use crate::models::{AppError, AppResult, UrlComparisonRequest};
+
+/// Expands a batch manifest into URL comparisons. A manifest starting with `[` is a JSON array of
+/// URL comparisons; anything else is CSV with a header row naming the columns `url1`, `url2` and
+/// optionally `id`, `profile`, `ignore_paths` and `ignore_properties`. List cells separate their
+/// entries with `;`.
+pub fn parse_manifest(manifest: &str) -> AppResult<Vec<UrlComparisonRequest>> {
+    let manifest = manifest.trim_start_matches('\u{feff}');
+    if manifest.trim_start().starts_with('[') {
+        return serde_json::from_str(manifest)
+            .map_err(|e| AppError::ValidationError(format!("Invalid JSON manifest: {}", e)));
+    }
+    parse_csv(manifest)
+}
+
+#[derive(Clone, Copy)]
+enum Column {
+    Id,
+    Url1,
+    Url2,
+    Profile,
+    IgnorePaths,
+    IgnoreProperties,
+}
+
+fn parse_csv(manifest: &str) -> AppResult<Vec<UrlComparisonRequest>> {
+    let invalid = |e: csv::Error| AppError::ValidationError(format!("Invalid CSV manifest: {}", e));
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(manifest.as_bytes());
+    let columns = reader
+        .headers()
+        .map_err(invalid)?
+        .iter()
+        .map(|name| match name.to_ascii_lowercase().as_str() {
+            "id" => Ok(Column::Id),
+            "url1" => Ok(Column::Url1),
+            "url2" => Ok(Column::Url2),
+            "profile" => Ok(Column::Profile),
+            "ignore_paths" => Ok(Column::IgnorePaths),
+            "ignore_properties" => Ok(Column::IgnoreProperties),
+            _ => Err(AppError::ValidationError(format!("Unknown manifest column '{}'", name))),
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let mut comparisons = Vec::new();
+    for (row, record) in reader.records().enumerate() {
+        let record = record.map_err(invalid)?;
+        let mut comparison = UrlComparisonRequest::default();
+        for (column, value) in columns.iter().zip(record.iter()).filter(|(_, value)| !value.is_empty()) {
+            match column {
+                Column::Id => comparison.id = Some(value.to_string()),
+                Column::Url1 => comparison.url1 = value.to_string(),
+                Column::Url2 => comparison.url2 = value.to_string(),
+                Column::Profile => comparison.profile = Some(value.to_string()),
+                Column::IgnorePaths => comparison.ignore_paths = Some(split_list(value)),
+                Column::IgnoreProperties => comparison.ignore_properties = Some(split_list(value)),
+            }
+        }
+        // Rows are numbered as in a spreadsheet, where the header is row 1
+        if comparison.url1.is_empty() || comparison.url2.is_empty() {
+            return Err(AppError::ValidationError(format!("Manifest row {} needs both url1 and url2", row + 2)));
+        }
+        comparisons.push(comparison);
+    }
+    Ok(comparisons)
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(';').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let csv = "\u{feff}id,url1,url2,ignore_paths\n\
+                   T-1, https://a/1.xml, https://b/1.xml, //timestamp; //audit\n\
+                   ,https://a/2.xml,https://b/2.xml,\n\
+                   \"T,3\",https://a/3.xml,https://b/3.xml,\n";
+        let comparisons = parse_manifest(csv).unwrap();
+        assert_eq!(comparisons.len(), 3);
+        assert_eq!(comparisons[0].id.as_deref(), Some("T-1"));
+        assert_eq!(comparisons[0].url2, "https://b/1.xml");
+        assert_eq!(comparisons[0].ignore_paths, Some(vec!["//timestamp".to_string(), "//audit".to_string()]));
+        assert_eq!((comparisons[1].id.as_ref(), comparisons[1].ignore_paths.as_ref()), (None, None));
+        assert_eq!(comparisons[2].id.as_deref(), Some("T,3"));
+
+        let json = r#"[{ "id": "T-1", "url1": "https://a/1.xml", "url2": "https://b/1.xml", "options": { "ignore_paths": ["//x"] } }]"#;
+        let comparisons = parse_manifest(json).unwrap();
+        assert_eq!(comparisons[0].effective_options().ignore_paths, Some(vec!["//x".to_string()]));
+
+        let error = |manifest| parse_manifest(manifest).unwrap_err().to_string();
+        assert_eq!(error("url1,url2,owner\n"), "Validation error: Unknown manifest column 'owner'");
+        assert_eq!(error("url1,url2\nhttps://a,https://b\nhttps://a,\n"), "Validation error: Manifest row 3 needs both url1 and url2");
+        assert!(error("url1,url2\nhttps://a,https://b,extra\n").starts_with("Validation error: Invalid CSV manifest"));
+        assert!(error("[{ \"url1\": 1 }]").starts_with("Validation error: Invalid JSON manifest"));
+    }
+}
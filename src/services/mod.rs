@@ -1,7 +1,62 @@
-pub mod xml_comparison;
 pub mod http_client;
 pub mod auth_service;
+pub mod profile_service;
+pub mod baseline_service;
+pub mod json_conversion;
+pub mod engine;
+pub mod json_comparison;
+pub mod documents;
+pub mod webhook;
+pub mod alerts;
+pub mod xsd_validation;
+pub mod result_cache;
+pub mod jwt_auth;
+pub mod health;
+pub mod document_cache;
+pub mod session_store;
+pub mod batch_pool;
+pub mod manifest;
+pub mod environments;
+pub mod jobs;
+pub mod result_store;
+pub mod tenant_service;
+pub mod history;
+
+// The comparison engine lives in the xml-compare-core crate
+pub use xml_compare_core::{
+    canonicalization, classification, entities, formatting, normalization, patch, report, transforms, xml_comparison, xpath,
+};
 
 pub use xml_comparison::*;
 pub use http_client::*;
-pub use auth_service::*;
\ No newline at end of file
+pub use auth_service::*;
+pub use xpath::*;
+pub use normalization::*;
+pub use canonicalization::*;
+pub use classification::*;
+pub use entities::*;
+pub use patch::*;
+pub use report::*;
+pub use formatting::*;
+pub use profile_service::*;
+pub use baseline_service::*;
+pub use transforms::*;
+pub use json_conversion::*;
+pub use engine::*;
+pub use json_comparison::*;
+pub use documents::*;
+pub use webhook::*;
+pub use alerts::*;
+pub use xsd_validation::*;
+pub use result_cache::*;
+pub use jwt_auth::*;
+pub use health::*;
+pub use document_cache::*;
+pub use session_store::*;
+pub use batch_pool::*;
+pub use manifest::*;
+pub use environments::*;
+pub use jobs::*;
+pub use result_store::*;
+pub use tenant_service::*;
+pub use history::*;
\ No newline at end of file
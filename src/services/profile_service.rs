@@ -0,0 +1,215 @@
+use crate::middleware::current_tenant_name;
+use crate::models::{AppError, AppResult, ComparisonOptions, ComparisonProfile, ProfileStore, XmlComparisonRequest};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use std::collections::HashMap;
+
+pub struct ProfileService {
+    profile_store: ProfileStore,
+}
+
+impl Default for ProfileService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProfileService {
+    pub fn new() -> Self {
+        Self {
+            profile_store: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn list(&self) -> Vec<ComparisonProfile> {
+        let profiles = self.profile_store.read().await;
+        let tenant = current_tenant_name();
+        let mut profiles: Vec<_> = profiles
+            .iter()
+            .filter(|((owner, _), _)| *owner == tenant)
+            .map(|(_, profile)| profile.clone())
+            .collect();
+        profiles.sort_by(|a, b| a.name.cmp(&b.name));
+        profiles
+    }
+
+    pub async fn get(&self, name: &str) -> AppResult<ComparisonProfile> {
+        let profiles = self.profile_store.read().await;
+        profiles
+            .get(&tenant_key(name))
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Profile '{}' does not exist", name)))
+    }
+
+    pub async fn create(&self, profile: ComparisonProfile) -> AppResult<ComparisonProfile> {
+        validate_name("profile", &profile.name)?;
+        let mut profiles = self.profile_store.write().await;
+        let key = tenant_key(&profile.name);
+        if profiles.contains_key(&key) {
+            return Err(AppError::Conflict(format!("Profile '{}' already exists", profile.name)));
+        }
+        profiles.insert(key, profile.clone());
+        Ok(profile)
+    }
+
+    pub async fn update(&self, name: &str, mut profile: ComparisonProfile) -> AppResult<ComparisonProfile> {
+        profile.name = name.to_string();
+        let mut profiles = self.profile_store.write().await;
+        match profiles.get_mut(&tenant_key(name)) {
+            Some(existing) => {
+                *existing = profile.clone();
+                Ok(profile)
+            }
+            None => Err(AppError::NotFound(format!("Profile '{}' does not exist", name))),
+        }
+    }
+
+    pub async fn delete(&self, name: &str) -> AppResult<()> {
+        let mut profiles = self.profile_store.write().await;
+        profiles
+            .remove(&tenant_key(name))
+            .map(|_| ())
+            .ok_or_else(|| AppError::NotFound(format!("Profile '{}' does not exist", name)))
+    }
+
+    /// Fills the request's options from the profile it references
+    pub async fn resolve(&self, mut request: XmlComparisonRequest) -> AppResult<XmlComparisonRequest> {
+        if request.profile.is_none() {
+            return Ok(request);
+        }
+        request.options = self.resolve_options(request.effective_options(), request.profile.as_deref()).await?;
+        request.legacy_options = ComparisonOptions::default();
+        Ok(request)
+    }
+
+    /// Merges `options` with the named profile's, for requests that carry options without a pair
+    /// of XML documents
+    pub async fn resolve_options(&self, options: ComparisonOptions, profile: Option<&str>) -> AppResult<ComparisonOptions> {
+        let Some(name) = profile else {
+            return Ok(options);
+        };
+        let profile = {
+            let profiles = self.profile_store.read().await;
+            profiles
+                .get(&tenant_key(name))
+                .cloned()
+                .ok_or_else(|| AppError::ValidationError(format!("Unknown profile '{}'", name)))?
+        };
+        Ok(apply_profile(profile.options, options))
+    }
+}
+
+/// Options set on the request take precedence over the profile's; list options are appended to
+/// the profile's and match keys are merged
+pub fn apply_profile(mut profile: ComparisonOptions, mut options: ComparisonOptions) -> ComparisonOptions {
+    options.ignore_paths = merge_lists(profile.ignore_paths.take(), options.ignore_paths);
+    options.ignore_properties = merge_lists(profile.ignore_properties.take(), options.ignore_properties);
+    options.date_paths = merge_lists(profile.date_paths.take(), options.date_paths);
+    options.date_formats = merge_lists(profile.date_formats.take(), options.date_formats);
+    options.literal_paths = merge_lists(profile.literal_paths.take(), options.literal_paths);
+    options.transforms = merge_lists(profile.transforms.take(), options.transforms);
+    options.scripts = merge_lists(profile.scripts.take(), options.scripts);
+    options.base64_paths = merge_lists(profile.base64_paths.take(), options.base64_paths);
+    options.classifications = merge_lists(profile.classifications.take(), options.classifications);
+    options.match_keys = match (profile.match_keys.take(), options.match_keys) {
+        (Some(mut keys), Some(overrides)) => {
+            keys.extend(overrides);
+            Some(keys)
+        }
+        (keys, overrides) => overrides.or(keys),
+    };
+    options.or(profile)
+}
+
+fn merge_lists<T>(profile: Option<Vec<T>>, request: Option<Vec<T>>) -> Option<Vec<T>> {
+    match (profile, request) {
+        (Some(mut values), Some(extra)) => {
+            values.extend(extra);
+            Some(values)
+        }
+        (values, extra) => extra.or(values),
+    }
+}
+
+/// Key of the named profile, baseline or job of the request's tenant; tenants cannot see each other's
+pub(crate) fn tenant_key(name: &str) -> (Option<String>, String) {
+    (current_tenant_name(), name.to_string())
+}
+
+// Names appear in URLs, so they are limited to a URL-safe character set
+pub(crate) fn validate_name(kind: &str, name: &str) -> AppResult<()> {
+    let valid = !name.is_empty()
+        && name.len() <= 100
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::ValidationError(format!(
+            "Invalid {} name '{}': use 1-100 letters, digits, '-', '_' or '.'",
+            kind, name
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_profile_crud() {
+        let service = ProfileService::new();
+        let profile = ComparisonProfile { name: "fpml-v5".to_string(), ..Default::default() };
+
+        service.create(profile.clone()).await.unwrap();
+        assert!(matches!(service.create(profile.clone()).await, Err(AppError::Conflict(_))));
+        assert!(matches!(
+            service.create(ComparisonProfile { name: "bad name".to_string(), ..Default::default() }).await,
+            Err(AppError::ValidationError(_))
+        ));
+
+        let options = ComparisonOptions { max_diffs: Some(5), ..Default::default() };
+        let updated = ComparisonProfile { options, ..Default::default() };
+        assert_eq!(service.update("fpml-v5", updated).await.unwrap().name, "fpml-v5");
+        assert_eq!(service.get("fpml-v5").await.unwrap().options.max_diffs, Some(5));
+        assert_eq!(service.list().await.len(), 1);
+
+        service.delete("fpml-v5").await.unwrap();
+        assert!(matches!(service.get("fpml-v5").await, Err(AppError::NotFound(_))));
+        assert!(matches!(service.delete("fpml-v5").await, Err(AppError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_merges_profile_options() {
+        let service = ProfileService::new();
+        service
+            .create(ComparisonProfile {
+                name: "trades".to_string(),
+                options: ComparisonOptions {
+                    ignore_paths: Some(vec!["//timestamp".to_string()]),
+                    match_keys: Some(HashMap::from([("/trades/trade".to_string(), "@id".to_string())])),
+                    ignore_element_order: Some(true),
+                    max_diffs: Some(10),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let request = XmlComparisonRequest {
+            options: ComparisonOptions { max_diffs: Some(3), ..Default::default() },
+            legacy_options: ComparisonOptions { ignore_paths: Some(vec!["//audit".to_string()]), ..Default::default() },
+            profile: Some("trades".to_string()),
+            ..Default::default()
+        };
+        let resolved = service.resolve(request).await.unwrap();
+        assert_eq!(resolved.options.ignore_paths, Some(vec!["//timestamp".to_string(), "//audit".to_string()]));
+        assert_eq!(resolved.options.match_keys.unwrap()["/trades/trade"], "@id");
+        assert_eq!(resolved.options.ignore_element_order, Some(true));
+        assert_eq!(resolved.options.max_diffs, Some(3));
+        assert!(resolved.legacy_options.ignore_paths.is_none());
+
+        let unknown = XmlComparisonRequest { profile: Some("missing".to_string()), ..Default::default() };
+        assert!(matches!(service.resolve(unknown).await, Err(AppError::ValidationError(_))));
+    }
+}
@@ -0,0 +1,152 @@
+use crate::models::{CacheStats, ComparisonOptions, XmlComparisonResponse};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Comparison results keyed by a hash of both documents and the resolved options. A zero TTL or
+/// capacity turns caching off.
+#[derive(Clone)]
+pub struct ResultCache {
+    entries: Arc<Mutex<HashMap<String, CachedResult>>>,
+    counters: Arc<Counters>,
+    ttl: Duration,
+    capacity: usize,
+}
+
+struct CachedResult {
+    result: XmlComparisonResponse,
+    stored_at: Instant,
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    not_modified: AtomicU64,
+}
+
+impl ResultCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Arc::new(Mutex::new(HashMap::new())),
+            counters: Arc::new(Counters::default()),
+            ttl,
+            capacity,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    pub fn get(&self, key: &str) -> Option<XmlComparisonResponse> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let cached = match entries.get(key) {
+            Some(entry) if entry.stored_at.elapsed() < self.ttl => Some(entry.result.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if cached.is_some() { &self.counters.hits } else { &self.counters.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        cached
+    }
+
+    // When full, expired entries go first and then the oldest one
+    pub fn insert(&self, key: String, result: XmlComparisonResponse) {
+        if !self.enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, entry| entry.stored_at.elapsed() < self.ttl);
+            if entries.len() >= self.capacity
+                && let Some(oldest) = entries.iter().min_by_key(|(_, entry)| entry.stored_at).map(|(key, _)| key.clone())
+            {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, CachedResult { result, stored_at: Instant::now() });
+    }
+
+    pub fn record_not_modified(&self) {
+        self.counters.not_modified.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            capacity: self.capacity,
+            ttl_seconds: self.ttl.as_secs(),
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            not_modified: self.counters.not_modified.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Hex SHA-256 of both documents and the options; option maps are hashed with sorted keys so
+/// equal options always give the same key
+pub fn result_cache_key(xml1: &str, xml2: &str, options: &ComparisonOptions) -> String {
+    let options = serde_json::to_value(options).map(sort_keys).unwrap_or_default();
+    let mut hasher = Sha256::new();
+    for part in [xml1, xml2, &options.to_string()] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part.as_bytes());
+    }
+    hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(members) => {
+            let mut members: Vec<_> = members.into_iter().collect();
+            members.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+            Value::Object(members.into_iter().map(|(key, value)| (key, sort_keys(value))).collect())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        value => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_ignores_map_order() {
+        let mut options = ComparisonOptions {
+            match_keys: Some(HashMap::from([("/a/b".to_string(), "@id".to_string()), ("/a/c".to_string(), "@k".to_string())])),
+            ..Default::default()
+        };
+        let key = result_cache_key("<a/>", "<a/>", &options);
+        options.match_keys = Some(HashMap::from([("/a/c".to_string(), "@k".to_string()), ("/a/b".to_string(), "@id".to_string())]));
+        assert_eq!(result_cache_key("<a/>", "<a/>", &options), key);
+        assert_ne!(result_cache_key("<a/><a/>", "", &options), result_cache_key("<a/>", "<a/>", &options));
+    }
+
+    #[test]
+    fn test_cache_expiry_and_capacity() {
+        let cache = ResultCache::new(Duration::from_secs(60), 1);
+        assert!(cache.get("a").is_none());
+        cache.insert("a".to_string(), XmlComparisonResponse { matched: true, ..Default::default() });
+        assert!(cache.get("a").unwrap().matched);
+        cache.insert("b".to_string(), XmlComparisonResponse::default());
+        assert!(cache.get("a").is_none());
+        let stats = cache.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 2));
+
+        let disabled = ResultCache::new(Duration::ZERO, 10);
+        disabled.insert("a".to_string(), XmlComparisonResponse::default());
+        assert!(disabled.get("a").is_none());
+        assert_eq!(disabled.stats().entries, 0);
+    }
+}
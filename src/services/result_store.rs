@@ -0,0 +1,287 @@
+use axum::async_trait;
+use crate::middleware::current_tenant_name;
+use crate::models::{AppError, AppResult, StoredBatch, StoredBatchItem};
+use crate::services::Job;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Where the results of finished batches are kept, by a key made of the tenant and job ID
+#[async_trait]
+pub trait ResultStore: Send + Sync {
+    /// Adds the batch, or replaces an earlier one with the same key
+    async fn save(&self, key: &str, batch: &StoredBatch) -> AppResult<()>;
+
+    /// The batch, unless it has expired
+    async fn get(&self, key: &str) -> AppResult<Option<StoredBatch>>;
+
+    /// How many expired batches were removed
+    async fn remove_expired(&self) -> AppResult<usize>;
+}
+
+/// Opens `sqlite:<file>` or `dir:<directory>`
+pub fn open_result_store(location: &str) -> AppResult<Arc<dyn ResultStore>> {
+    match location.split_once(':') {
+        Some(("sqlite", path)) => Ok(Arc::new(SqliteResultStore::open(path)?)),
+        Some(("dir", path)) => Ok(Arc::new(DiskResultStore::open(path)?)),
+        _ => Err(AppError::ValidationError(format!(
+            "Result store '{}' must be sqlite:<file> or dir:<directory>",
+            location
+        ))),
+    }
+}
+
+/// One JSON file per batch, named after a hash of the key so that any job ID is a safe file name
+pub struct DiskResultStore {
+    dir: PathBuf,
+}
+
+impl DiskResultStore {
+    pub fn open(dir: impl Into<PathBuf>) -> AppResult<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| store_error(format!("cannot create {}: {}", dir.display(), e)))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:x}.json", Sha256::digest(key.as_bytes())))
+    }
+}
+
+#[async_trait]
+impl ResultStore for DiskResultStore {
+    async fn save(&self, key: &str, batch: &StoredBatch) -> AppResult<()> {
+        let json = serde_json::to_vec(batch).map_err(|e| store_error(e.to_string()))?;
+        // Written aside and renamed, so that readers never see half a file
+        let path = self.path(key);
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, json).await.map_err(|e| store_error(e.to_string()))?;
+        tokio::fs::rename(&partial, &path).await.map_err(|e| store_error(e.to_string()))
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Option<StoredBatch>> {
+        let json = match tokio::fs::read(self.path(key)).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(store_error(e.to_string())),
+        };
+        let batch: StoredBatch = serde_json::from_slice(&json).map_err(|e| store_error(e.to_string()))?;
+        Ok(Some(batch).filter(|batch| !batch.is_expired()))
+    }
+
+    async fn remove_expired(&self) -> AppResult<usize> {
+        #[derive(serde::Deserialize)]
+        struct Expiry {
+            expires_at: chrono::DateTime<chrono::Utc>,
+        }
+
+        let mut entries = tokio::fs::read_dir(&self.dir).await.map_err(|e| store_error(e.to_string()))?;
+        let mut removed = 0;
+        while let Some(entry) = entries.next_entry().await.map_err(|e| store_error(e.to_string()))? {
+            let path = entry.path();
+            if path.extension().is_none_or(|extension| extension != "json") {
+                continue;
+            }
+            // Files that cannot be read are left for someone to look at
+            let Ok(json) = tokio::fs::read(&path).await else {
+                continue;
+            };
+            let expired = serde_json::from_slice::<Expiry>(&json)
+                .is_ok_and(|expiry| expiry.expires_at <= chrono::Utc::now());
+            if expired && tokio::fs::remove_file(&path).await.is_ok() {
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+}
+
+/// Batches kept as JSON in an SQLite table
+pub struct SqliteResultStore {
+    connection: Arc<Mutex<rusqlite::Connection>>,
+}
+
+impl SqliteResultStore {
+    /// Opens or creates the database; `:memory:` keeps it in memory
+    pub fn open(path: &str) -> AppResult<Self> {
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS batch_results (
+                    job_id TEXT PRIMARY KEY,
+                    expires_at INTEGER NOT NULL,
+                    batch TEXT NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS batch_results_expires_at ON batch_results (expires_at);",
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    // SQLite blocks, so statements run off the async runtime
+    async fn run<T, F>(&self, f: F) -> AppResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&rusqlite::Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || f(&connection.lock().unwrap()))
+            .await
+            .map_err(|e| store_error(e.to_string()))?
+            .map_err(sqlite_error)
+    }
+}
+
+#[async_trait]
+impl ResultStore for SqliteResultStore {
+    async fn save(&self, key: &str, batch: &StoredBatch) -> AppResult<()> {
+        let json = serde_json::to_string(batch).map_err(|e| store_error(e.to_string()))?;
+        let (key, expires_at) = (key.to_string(), batch.expires_at.timestamp_millis());
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO batch_results (job_id, expires_at, batch) VALUES (?1, ?2, ?3)",
+                rusqlite::params![key, expires_at, json],
+            )
+        })
+        .await
+        .map(|_| ())
+    }
+
+    async fn get(&self, key: &str) -> AppResult<Option<StoredBatch>> {
+        let key = key.to_string();
+        let now = chrono::Utc::now().timestamp_millis();
+        let json: Option<String> = self
+            .run(move |connection| {
+                let mut statement =
+                    connection.prepare("SELECT batch FROM batch_results WHERE job_id = ?1 AND expires_at > ?2")?;
+                let mut rows = statement.query(rusqlite::params![key, now])?;
+                rows.next()?.map(|row| row.get(0)).transpose()
+            })
+            .await?;
+        json.map(|json| serde_json::from_str(&json).map_err(|e| store_error(e.to_string())))
+            .transpose()
+    }
+
+    async fn remove_expired(&self) -> AppResult<usize> {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.run(move |connection| {
+            connection.execute("DELETE FROM batch_results WHERE expires_at <= ?1", rusqlite::params![now])
+        })
+        .await
+    }
+}
+
+/// Keeps finished batches in a store for `ttl`. Without a store nothing is kept.
+#[derive(Clone, Default)]
+pub struct ResultArchive {
+    store: Option<Arc<dyn ResultStore>>,
+    ttl: Duration,
+}
+
+impl ResultArchive {
+    pub fn new(store: Arc<dyn ResultStore>, ttl: Duration) -> Self {
+        Self { store: Some(store), ttl }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Storing is best effort: the batch has already been answered, so failures are only logged
+    pub async fn save(&self, job: &Job, items: Vec<StoredBatchItem>) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        let ttl = chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX);
+        let batch = StoredBatch { tenant: job.tenant.clone(), ..StoredBatch::new(job.id.clone(), job.kind, items, ttl) };
+        if let Err(error) = store.save(&store_key(job.tenant.as_deref(), &job.id), &batch).await {
+            tracing::warn!(job_id = %job.id, "Batch results could not be stored: {}", error);
+        }
+    }
+
+    /// The batch the request's tenant ran under `job_id`; other tenants' batches are not found
+    pub async fn get(&self, job_id: &str) -> AppResult<StoredBatch> {
+        let Some(store) = &self.store else {
+            return Err(AppError::NotFound("Batch results are not kept; set APP_RESULT_STORE".to_string()));
+        };
+        let tenant = current_tenant_name();
+        store
+            .get(&store_key(tenant.as_deref(), job_id))
+            .await?
+            .filter(|batch| batch.tenant == tenant)
+            .ok_or_else(|| AppError::NotFound(format!("No stored results for job '{}'", job_id)))
+    }
+
+    pub async fn remove_expired(&self) {
+        let Some(store) = &self.store else {
+            return;
+        };
+        match store.remove_expired().await {
+            Ok(0) => {}
+            Ok(removed) => tracing::debug!(removed, "Removed expired batch results"),
+            Err(error) => tracing::warn!("Expired batch results could not be removed: {}", error),
+        }
+    }
+}
+
+// Without tenants the key is the job ID. A tenant's name is prefixed with its length, so that no
+// tenant and job ID can make the key of another's.
+fn store_key(tenant: Option<&str>, job_id: &str) -> String {
+    match tenant {
+        Some(tenant) => format!("{}:{}:{}", tenant.len(), tenant, job_id),
+        None => job_id.to_string(),
+    }
+}
+
+fn sqlite_error(error: rusqlite::Error) -> AppError {
+    store_error(error.to_string())
+}
+
+fn store_error(message: String) -> AppError {
+    AppError::InternalError(format!("Result store error: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch(job_id: &str, ttl: chrono::Duration) -> StoredBatch {
+        let items = vec![
+            StoredBatchItem { index: 0, id: Some("T-1".to_string()), matched: true, error: None, cancelled: false, result: None },
+            StoredBatchItem {
+                index: 1,
+                id: None,
+                matched: false,
+                error: Some("HTTP error".to_string()),
+                cancelled: false,
+                result: None,
+            },
+        ];
+        StoredBatch::new(job_id.to_string(), "xml_batch", items, ttl)
+    }
+
+    async fn check_store(store: &dyn ResultStore) {
+        store.save("nightly", &batch("nightly", chrono::Duration::hours(1))).await.unwrap();
+        store.save("../stale", &batch("../stale", chrono::Duration::seconds(-1))).await.unwrap();
+        let stored = store.get("nightly").await.unwrap().unwrap();
+        assert_eq!((stored.total_comparisons, stored.failed_comparisons, stored.matched_comparisons), (2, 1, 1));
+        assert_eq!(stored.items[0].id.as_deref(), Some("T-1"));
+        assert!(store.get("../stale").await.unwrap().is_none());
+        assert!(store.get("unknown").await.unwrap().is_none());
+
+        assert_eq!(store.remove_expired().await.unwrap(), 1);
+        assert!(store.get("nightly").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_result_stores() {
+        check_store(&SqliteResultStore::open(":memory:").unwrap()).await;
+
+        let dir = std::env::temp_dir().join(format!("xml-compare-results-{}", uuid::Uuid::new_v4()));
+        check_store(&DiskResultStore::open(&dir).unwrap()).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(open_result_store("redis://cache").is_err());
+    }
+}
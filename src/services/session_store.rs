@@ -0,0 +1,257 @@
+use axum::async_trait;
+use base64::{Engine as _, engine::general_purpose};
+use crate::models::{AppError, AppResult, Session};
+use redis::AsyncCommands;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use tokio::sync::RwLock;
+
+/// Where login sessions are kept. The in-memory store is the default; a shared store such as Redis
+/// lets sessions survive restarts and be used by every replica.
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn get(&self, session_id: &str) -> AppResult<Option<Session>>;
+
+    /// Adds the session, or replaces the one with the same ID
+    async fn insert(&self, session: Session) -> AppResult<()>;
+
+    /// Whether there was a session to remove
+    async fn remove(&self, session_id: &str) -> AppResult<bool>;
+
+    /// Every stored session, including expired ones not yet removed
+    async fn list(&self) -> AppResult<Vec<Session>>;
+
+    async fn remove_expired(&self) -> AppResult<()>;
+}
+
+#[derive(Default)]
+pub struct MemorySessionStore {
+    sessions: RwLock<HashMap<String, Session>>,
+}
+
+#[async_trait]
+impl SessionStore for MemorySessionStore {
+    async fn get(&self, session_id: &str) -> AppResult<Option<Session>> {
+        Ok(self.sessions.read().await.get(session_id).cloned())
+    }
+
+    async fn insert(&self, session: Session) -> AppResult<()> {
+        self.sessions.write().await.insert(session.id.clone(), session);
+        Ok(())
+    }
+
+    async fn remove(&self, session_id: &str) -> AppResult<bool> {
+        Ok(self.sessions.write().await.remove(session_id).is_some())
+    }
+
+    async fn list(&self) -> AppResult<Vec<Session>> {
+        Ok(self.sessions.read().await.values().cloned().collect())
+    }
+
+    async fn remove_expired(&self) -> AppResult<()> {
+        self.sessions.write().await.retain(|_, session| !session.is_expired());
+        Ok(())
+    }
+}
+
+/// Encrypts stored sessions with AES-256-GCM. Each value is base64 of a random nonce followed by
+/// the ciphertext, so the same session never encrypts the same way twice. The session ID is
+/// authenticated with it, so a value moved under another session's key does not decrypt.
+pub struct SessionCipher {
+    key: LessSafeKey,
+    random: SystemRandom,
+}
+
+impl SessionCipher {
+    /// `key` is 32 bytes, base64-encoded
+    pub fn from_base64(key: &str) -> AppResult<Self> {
+        let invalid = || AppError::ValidationError("The session encryption key must be 32 bytes, base64-encoded".to_string());
+        let key = general_purpose::STANDARD.decode(key.trim()).map_err(|_| invalid())?;
+        let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| invalid())?;
+        Ok(Self { key: LessSafeKey::new(key), random: SystemRandom::new() })
+    }
+
+    fn seal(&self, session_id: &str, plaintext: &[u8]) -> AppResult<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        self.random
+            .fill(&mut nonce)
+            .map_err(|_| AppError::InternalError("No randomness for a session nonce".to_string()))?;
+        let mut sealed = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(session_id), &mut sealed)
+            .map_err(|_| AppError::InternalError("Session could not be encrypted".to_string()))?;
+        Ok(general_purpose::STANDARD.encode([nonce.as_slice(), &sealed].concat()))
+    }
+
+    fn open(&self, session_id: &str, value: &str) -> Option<Vec<u8>> {
+        let sealed = general_purpose::STANDARD.decode(value).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let mut ciphertext = ciphertext.to_vec();
+        let plaintext = self.key.open_in_place(nonce, Aad::from(session_id), &mut ciphertext).ok()?;
+        Some(plaintext.to_vec())
+    }
+}
+
+/// Sessions kept in Redis as JSON under `<prefix><session ID>`, each expiring with its session.
+/// With a cipher the JSON is encrypted, cookies and credentials included.
+pub struct RedisSessionStore {
+    connection: redis::aio::ConnectionManager,
+    prefix: String,
+    cipher: Option<SessionCipher>,
+}
+
+const REDIS_KEY_PREFIX: &str = "xml-compare:session:";
+
+impl RedisSessionStore {
+    /// Connects to `url`, such as `redis://cache.internal:6379/0`
+    pub async fn connect(url: &str) -> AppResult<Self> {
+        let client = redis::Client::open(url).map_err(store_error)?;
+        let connection = redis::aio::ConnectionManager::new(client).await.map_err(store_error)?;
+        Ok(Self { connection, prefix: REDIS_KEY_PREFIX.to_string(), cipher: None })
+    }
+
+    pub fn with_cipher(mut self, cipher: SessionCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    fn key(&self, session_id: &str) -> String {
+        format!("{}{}", self.prefix, session_id)
+    }
+
+    fn encode(&self, session: &Session) -> AppResult<String> {
+        encode(session, self.cipher.as_ref())
+    }
+
+    fn decode(&self, session_id: &str, value: &str) -> Option<Session> {
+        decode(session_id, value, self.cipher.as_ref())
+    }
+}
+
+#[async_trait]
+impl SessionStore for RedisSessionStore {
+    async fn get(&self, session_id: &str) -> AppResult<Option<Session>> {
+        let value: Option<String> = self.connection.clone().get(self.key(session_id)).await.map_err(store_error)?;
+        Ok(value.and_then(|value| self.decode(session_id, &value)))
+    }
+
+    async fn insert(&self, session: Session) -> AppResult<()> {
+        let value = self.encode(&session)?;
+        let ttl_ms = (session.expires_at - chrono::Utc::now()).num_milliseconds().max(1);
+        redis::cmd("SET")
+            .arg(self.key(&session.id))
+            .arg(value)
+            .arg("PX")
+            .arg(ttl_ms)
+            .query_async(&mut self.connection.clone())
+            .await
+            .map_err(store_error)
+    }
+
+    async fn remove(&self, session_id: &str) -> AppResult<bool> {
+        let removed: usize = self.connection.clone().del(self.key(session_id)).await.map_err(store_error)?;
+        Ok(removed > 0)
+    }
+
+    async fn list(&self) -> AppResult<Vec<Session>> {
+        let mut connection = self.connection.clone();
+        let keys: Vec<String> = {
+            let mut scan = connection.scan_match::<_, String>(format!("{}*", self.prefix)).await.map_err(store_error)?;
+            let mut keys = Vec::new();
+            while let Some(key) = scan.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        // Sessions that expired between the scan and the read come back empty
+        let values: Vec<Option<String>> =
+            redis::cmd("MGET").arg(&keys).query_async(&mut connection).await.map_err(store_error)?;
+        Ok(keys
+            .iter()
+            .zip(values)
+            .filter_map(|(key, value)| self.decode(key.strip_prefix(&self.prefix)?, &value?))
+            .collect())
+    }
+
+    // Redis removes sessions itself when they expire
+    async fn remove_expired(&self) -> AppResult<()> {
+        Ok(())
+    }
+}
+
+fn encode(session: &Session, cipher: Option<&SessionCipher>) -> AppResult<String> {
+    let json = serde_json::to_string(session)
+        .map_err(|e| AppError::InternalError(format!("Session could not be encoded: {}", e)))?;
+    match cipher {
+        Some(cipher) => cipher.seal(&session.id, json.as_bytes()),
+        None => Ok(json),
+    }
+}
+
+// Sessions that cannot be read, such as ones stored before the key changed, count as logged out
+fn decode(session_id: &str, value: &str, cipher: Option<&SessionCipher>) -> Option<Session> {
+    let json = match cipher {
+        Some(cipher) => cipher.open(session_id, value),
+        None => Some(value.as_bytes().to_vec()),
+    };
+    let session = json.and_then(|json| serde_json::from_slice(&json).ok());
+    if session.is_none() {
+        tracing::warn!("Ignoring a stored session that could not be decrypted or decoded");
+    }
+    session
+}
+
+fn store_error(error: redis::RedisError) -> AppError {
+    AppError::InternalError(format!("Session store error: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemorySessionStore::default();
+        let active = Session::new("https://a.example.com/login".to_string(), vec!["sid=1".to_string()]);
+        let mut expired = Session::new("https://b.example.com/login".to_string(), vec![]);
+        expired.expires_at = expired.created_at - chrono::Duration::seconds(1);
+        store.insert(active.clone()).await.unwrap();
+        store.insert(expired.clone()).await.unwrap();
+        assert_eq!(store.get(&active.id).await.unwrap().unwrap().cookies, active.cookies);
+        assert_eq!(store.list().await.unwrap().len(), 2);
+
+        store.remove_expired().await.unwrap();
+        assert!(store.get(&expired.id).await.unwrap().is_none());
+        assert!(store.remove(&active.id).await.unwrap());
+        assert!(!store.remove(&active.id).await.unwrap());
+    }
+
+    #[test]
+    fn test_session_encryption() {
+        let mut session = Session::new("https://a.example.com/login".to_string(), vec!["sid=s3cret".to_string()]);
+        session.credentials = Some(crate::models::AuthCredentials { username: "svc".to_string(), password: "pa55".to_string() });
+        let cipher = SessionCipher::from_base64(&general_purpose::STANDARD.encode([7u8; 32])).unwrap();
+
+        let stored = encode(&session, Some(&cipher)).unwrap();
+        assert!(!stored.contains("s3cret") && !stored.contains("pa55"));
+        assert_ne!(stored, encode(&session, Some(&cipher)).unwrap());
+        let decoded = decode(&session.id, &stored, Some(&cipher)).unwrap();
+        assert_eq!((decoded.id, decoded.cookies), (session.id.clone(), session.cookies.clone()));
+
+        // Another key, another session's ID, or a session stored unencrypted, reads as no session
+        let other = SessionCipher::from_base64(&general_purpose::STANDARD.encode([8u8; 32])).unwrap();
+        assert!(decode(&session.id, &stored, Some(&other)).is_none());
+        assert!(decode("another-session", &stored, Some(&cipher)).is_none());
+        assert!(decode(&session.id, &encode(&session, None).unwrap(), Some(&cipher)).is_none());
+        assert!(SessionCipher::from_base64("c2hvcnQ=").is_err());
+        assert!(!format!("{:?}", session).contains("s3cret"));
+    }
+}
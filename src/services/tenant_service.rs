@@ -0,0 +1,200 @@
+use crate::models::{AppError, AppResult, TenantConfig, TenantUsage, TenantsFile};
+use crate::services::profile_service::validate_name;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A tenant with its limits and usage counters
+pub struct Tenant {
+    pub name: String,
+    pub requests_per_minute: Option<u32>,
+    pub max_batch_items: Option<usize>,
+    pub max_body_bytes: Option<usize>,
+    // Tokens left and when they were last topped up; a token comes back every 60s / rate
+    bucket: Mutex<(f64, Instant)>,
+    requests: AtomicU64,
+    throttled: AtomicU64,
+    comparisons: AtomicU64,
+    matched: AtomicU64,
+    diffs: AtomicU64,
+}
+
+impl Tenant {
+    fn new(config: &TenantConfig) -> Self {
+        Self {
+            name: config.name.clone(),
+            requests_per_minute: config.requests_per_minute,
+            max_batch_items: config.max_batch_items,
+            max_body_bytes: config.max_body_mb.map(|mb| mb * 1024 * 1024),
+            bucket: Mutex::new((f64::from(config.requests_per_minute.unwrap_or(0)), Instant::now())),
+            requests: AtomicU64::new(0),
+            throttled: AtomicU64::new(0),
+            comparisons: AtomicU64::new(0),
+            matched: AtomicU64::new(0),
+            diffs: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a request against the tenant's rate, refusing it once the rate is used up
+    pub fn admit(&self) -> AppResult<()> {
+        if let Some(per_minute) = self.requests_per_minute {
+            let per_minute = f64::from(per_minute);
+            let mut bucket = self.bucket.lock().unwrap();
+            let (tokens, updated) = &mut *bucket;
+            let now = Instant::now();
+            *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * per_minute / 60.0).min(per_minute);
+            *updated = now;
+            if *tokens < 1.0 {
+                self.throttled.fetch_add(1, Ordering::Relaxed);
+                let wait = Duration::from_secs_f64((1.0 - *tokens) * 60.0 / per_minute);
+                return Err(AppError::TooManyRequests(format!(
+                    "Tenant '{}' is limited to {} requests per minute; retry in {}s",
+                    self.name,
+                    per_minute,
+                    wait.as_secs().max(1)
+                )));
+            }
+            *tokens -= 1.0;
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    pub fn record_comparison(&self, matched: bool, diffs: usize) {
+        self.comparisons.fetch_add(1, Ordering::Relaxed);
+        self.matched.fetch_add(u64::from(matched), Ordering::Relaxed);
+        self.diffs.fetch_add(diffs as u64, Ordering::Relaxed);
+    }
+
+    pub fn usage(&self) -> TenantUsage {
+        TenantUsage {
+            name: self.name.clone(),
+            requests_per_minute: self.requests_per_minute,
+            max_batch_items: self.max_batch_items,
+            max_body_bytes: self.max_body_bytes,
+            requests: self.requests.load(Ordering::Relaxed),
+            throttled: self.throttled.load(Ordering::Relaxed),
+            comparisons: self.comparisons.load(Ordering::Relaxed),
+            matched: self.matched.load(Ordering::Relaxed),
+            diffs: self.diffs.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Tenants and the API keys that identify them. Without tenants every request is let through
+/// without a key.
+#[derive(Default)]
+pub struct TenantService {
+    tenants: Vec<Arc<Tenant>>,
+    // Keys are looked up by digest, so neither the file nor memory needs the keys themselves
+    by_key_hash: HashMap<String, Arc<Tenant>>,
+}
+
+impl TenantService {
+    pub fn from_file(path: &str) -> AppResult<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| AppError::ValidationError(format!("Cannot read tenants file {}: {}", path, e)))?;
+        let file: TenantsFile = serde_yaml::from_str(&contents)
+            .map_err(|e| AppError::ValidationError(format!("Invalid tenants file {}: {}", path, e)))?;
+        Self::new(file.tenants)
+    }
+
+    pub fn new(configs: Vec<TenantConfig>) -> AppResult<Self> {
+        let mut service = Self::default();
+        for config in configs {
+            validate_name("tenant", &config.name)?;
+            if service.tenants.iter().any(|tenant| tenant.name == config.name) {
+                return Err(AppError::ValidationError(format!("Tenant '{}' is defined twice", config.name)));
+            }
+            if config.api_keys.is_empty() && config.api_key_hashes.is_empty() {
+                return Err(AppError::ValidationError(format!("Tenant '{}' has no API keys", config.name)));
+            }
+            let tenant = Arc::new(Tenant::new(&config));
+            let hashes = config.api_keys.iter().map(|key| key_hash(key)).chain(config.api_key_hashes.iter().map(|hash| {
+                let hash = hash.trim().to_ascii_lowercase();
+                let valid = hash.len() == 64 && hash.bytes().all(|byte| byte.is_ascii_hexdigit());
+                valid.then_some(hash).ok_or_else(|| {
+                    AppError::ValidationError(format!("Tenant '{}' has a key hash that is not hex SHA-256", config.name))
+                })
+            }).collect::<AppResult<Vec<_>>>()?);
+            for hash in hashes {
+                if service.by_key_hash.insert(hash, tenant.clone()).is_some() {
+                    return Err(AppError::ValidationError(format!("Tenant '{}' reuses another tenant's API key", config.name)));
+                }
+            }
+            service.tenants.push(tenant);
+        }
+        Ok(service)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.tenants.is_empty()
+    }
+
+    pub fn authenticate(&self, api_key: &str) -> Option<Arc<Tenant>> {
+        self.by_key_hash.get(&key_hash(api_key)).cloned()
+    }
+
+    /// Usage of every tenant, in the order of the tenants file
+    pub fn usage(&self) -> Vec<TenantUsage> {
+        self.tenants.iter().map(|tenant| tenant.usage()).collect()
+    }
+}
+
+fn key_hash(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tenant(name: &str, api_keys: &[&str]) -> TenantConfig {
+        TenantConfig {
+            name: name.to_string(),
+            api_keys: api_keys.iter().map(|key| key.to_string()).collect(),
+            api_key_hashes: Vec::new(),
+            requests_per_minute: None,
+            max_batch_items: None,
+            max_body_mb: None,
+        }
+    }
+
+    #[test]
+    fn test_tenants() {
+        let yaml = r#"
+tenants:
+  - name: pricing
+    api_keys: [pricing-key]
+    requests_per_minute: 2
+    max_body_mb: 1
+  - name: risk
+    api_key_hashes: ["0A3E1A4C7E4F0E1D8B1D1B0B5DE67A2D4B5C50A7B5E0E1C1A9D8B6A7C5E4F3D2"]
+"#;
+        let file: TenantsFile = serde_yaml::from_str(yaml).unwrap();
+        let service = TenantService::new(file.tenants).unwrap();
+        assert!(service.is_enabled());
+        let pricing = service.authenticate("pricing-key").unwrap();
+        assert_eq!(pricing.max_body_bytes, Some(1024 * 1024));
+        assert!(service.authenticate("Pricing-key").is_none());
+
+        let risk = TenantService::new(vec![TenantConfig { api_key_hashes: vec![key_hash("risk-key")], ..tenant("risk", &[]) }]).unwrap();
+        assert_eq!(risk.authenticate("risk-key").unwrap().name, "risk");
+
+        // Two requests fit the burst, the third has to wait for a token
+        assert!(pricing.admit().is_ok());
+        assert!(pricing.admit().is_ok());
+        assert!(matches!(pricing.admit(), Err(AppError::TooManyRequests(_))));
+        pricing.record_comparison(false, 3);
+        let usage = &service.usage()[0];
+        assert_eq!((usage.requests, usage.throttled, usage.comparisons, usage.matched, usage.diffs), (2, 1, 1, 0, 3));
+
+        assert!(TenantService::new(vec![tenant("a", &["key"]), tenant("b", &["key"])]).is_err());
+        assert!(TenantService::new(vec![tenant("a", &["key"]), tenant("a", &["other"])]).is_err());
+        assert!(TenantService::new(vec![tenant("idle", &[])]).is_err());
+        assert!(TenantService::new(vec![TenantConfig { api_key_hashes: vec!["abc".to_string()], ..tenant("a", &[]) }]).is_err());
+        assert!(!TenantService::default().is_enabled());
+    }
+}
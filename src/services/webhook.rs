@@ -0,0 +1,148 @@
+use crate::models::{AppError, AppResult};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::Serialize;
+use sha2::Sha256;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+// A callback target that never answers would otherwise hold its delivery, and shutdown, forever
+const CALLBACK_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const CALLBACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Posts completion payloads to client-supplied callback URLs
+#[derive(Clone)]
+pub struct WebhookService {
+    client: Client,
+    secret: Option<String>,
+    pending: Arc<AtomicUsize>,
+}
+
+impl Default for WebhookService {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl WebhookService {
+    /// Payloads are signed when a secret is configured
+    pub fn new(secret: Option<String>) -> Self {
+        Self {
+            client: Client::builder()
+                .connect_timeout(CALLBACK_CONNECT_TIMEOUT)
+                .timeout(CALLBACK_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            secret,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Delivers the payload in the background; failures are logged, not reported to the caller
+    pub fn notify<T: Serialize>(&self, url: &str, payload: &T) {
+        let body = match serde_json::to_vec(payload) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!("Failed to serialize callback payload for {}: {}", url, e);
+                return;
+            }
+        };
+        let service = self.clone();
+        let url = url.to_string();
+        self.pending.fetch_add(1, Ordering::Relaxed);
+        tokio::spawn(async move {
+            if let Err(e) = service.deliver(&url, body).await {
+                tracing::warn!("Callback to {} failed: {}", url, e);
+            }
+            service.pending.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+
+    /// Callbacks queued or being delivered
+    pub fn pending_deliveries(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
+    /// Waits up to `timeout` for queued callbacks to be delivered; returns how many are left
+    pub async fn drain(&self, timeout: Duration) -> usize {
+        let deadline = Instant::now() + timeout;
+        while self.pending_deliveries() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        self.pending_deliveries()
+    }
+
+    pub async fn deliver(&self, url: &str, body: Vec<u8>) -> AppResult<()> {
+        let mut request = self.client.post(url).header("Content-Type", "application/json");
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &body));
+        }
+        let response = request.body(body).send().await.map_err(|e| AppError::HttpError(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(AppError::HttpError(format!("Callback returned status: {}", response.status())));
+        }
+        Ok(())
+    }
+}
+
+/// `sha256=<hex>` HMAC of the request body, as sent in the signature header
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let hex: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{MockServer, Mock, ResponseTemplate};
+    use wiremock::matchers::{method, path, header};
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deliver_signs_payload() {
+        let mock_server = MockServer::start().await;
+        let body = br#"{"status":"completed"}"#.to_vec();
+
+        Mock::given(method("POST"))
+            .and(path("/hook"))
+            .and(header(SIGNATURE_HEADER, sign("secret", &body).as_str()))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let service = WebhookService::new(Some("secret".to_string()));
+        let url = format!("{}/hook", mock_server.uri());
+        service.deliver(&url, body).await.unwrap();
+        assert!(service.deliver(&format!("{}/missing", mock_server.uri()), Vec::new()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drain_waits_for_deliveries() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(204).set_delay(Duration::from_millis(200)))
+            .mount(&mock_server)
+            .await;
+
+        let service = WebhookService::new(None);
+        service.notify(&format!("{}/hook", mock_server.uri()), &"done");
+        assert_eq!(service.pending_deliveries(), 1);
+        assert_eq!(service.drain(Duration::from_millis(10)).await, 1);
+        assert_eq!(service.drain(Duration::from_secs(5)).await, 0);
+    }
+}
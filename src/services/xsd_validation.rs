@@ -0,0 +1,1095 @@
+use crate::models::{AppError, AppResult, ValidationIssue};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::HashMap;
+
+const XS_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema";
+const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+// Longest chain of named type, group or attribute group references that validation follows
+const MAX_REFERENCE_DEPTH: usize = 64;
+
+/// Validates a document against an XML Schema. Malformed documents and schema violations are
+/// returned as issues; a malformed or unsupported schema is an error.
+///
+/// Supported: global and local element, attribute, complexType and simpleType declarations,
+/// sequence/choice/all with occurrence bounds, named groups and attribute groups, any and
+/// anyAttribute, simple and complex content extension, restriction facets, lists and unions.
+/// Names are matched by local name; include, import and identity constraints are not supported.
+pub fn validate_against_xsd(xml: &str, xsd: &str) -> AppResult<Vec<ValidationIssue>> {
+    let schema_doc = Document::parse_with_options(xsd, parsing_options())
+        .map_err(|e| AppError::XmlParseError(format!("Invalid XSD: {}", e)))?;
+    let schema = Schema::parse(schema_doc.root_element())?;
+
+    let doc = match Document::parse_with_options(xml, parsing_options()) {
+        Ok(doc) => doc,
+        Err(e) => {
+            let pos = e.pos();
+            return Ok(vec![ValidationIssue { path: String::new(), line: pos.row, column: pos.col, message: e.to_string() }]);
+        }
+    };
+
+    let mut validator = Validator { schema: &schema, doc: &doc, issues: Vec::new(), schema_error: None, furthest: None };
+    let root = doc.root_element();
+    let path = format!("/{}", root.tag_name().name());
+    match schema.elements.get(root.tag_name().name()) {
+        Some(decl) => validator.validate_element(root, &path, &decl.kind),
+        None => validator.issue(root, &path, format!("No global element declaration for '{}'", root.tag_name().name())),
+    }
+
+    match validator.schema_error {
+        Some(message) => Err(AppError::ValidationError(message)),
+        None => Ok(validator.issues),
+    }
+}
+
+fn parsing_options() -> ParsingOptions {
+    ParsingOptions { allow_dtd: true, ..ParsingOptions::default() }
+}
+
+#[derive(Default)]
+struct Schema {
+    elements: HashMap<String, ElementDecl>,
+    attributes: HashMap<String, AttributeDecl>,
+    complex_types: HashMap<String, ComplexType>,
+    simple_types: HashMap<String, SimpleType>,
+    groups: HashMap<String, Particle>,
+    attribute_groups: HashMap<String, Vec<AttributeUse>>,
+}
+
+struct ElementDecl {
+    name: String,
+    kind: TypeDef,
+}
+
+enum TypeDef {
+    Any,
+    /// Built-in XSD type, by local name
+    Builtin(String),
+    /// Type declared in the schema, by local name
+    Named(String),
+    Complex(Box<ComplexType>),
+    Simple(Box<SimpleType>),
+}
+
+#[derive(Clone, Copy)]
+struct Occurs {
+    min: usize,
+    max: Option<usize>,
+}
+
+enum Particle {
+    Element(ElementDecl, Occurs),
+    ElementRef(String, Occurs),
+    Sequence(Vec<Particle>, Occurs),
+    Choice(Vec<Particle>, Occurs),
+    All(Vec<Particle>, Occurs),
+    GroupRef(String, Occurs),
+    Any(Occurs),
+}
+
+#[derive(Default)]
+struct ComplexType {
+    content: Option<Particle>,
+    attributes: Vec<AttributeUse>,
+    any_attribute: bool,
+    mixed: bool,
+    /// Base type of a simpleContent or complexContent derivation
+    base: Option<TypeDef>,
+    simple_content: bool,
+}
+
+struct AttributeDecl {
+    name: String,
+    kind: TypeDef,
+    required: bool,
+}
+
+enum AttributeUse {
+    Decl(AttributeDecl),
+    Ref(String, bool),
+    GroupRef(String),
+}
+
+enum SimpleType {
+    Restriction(TypeDef, Facets),
+    List(TypeDef),
+    Union(Vec<TypeDef>),
+}
+
+#[derive(Default)]
+struct Facets {
+    enumeration: Vec<String>,
+    patterns: Vec<Regex>,
+    length: Option<usize>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    min_inclusive: Option<f64>,
+    max_inclusive: Option<f64>,
+    min_exclusive: Option<f64>,
+    max_exclusive: Option<f64>,
+}
+
+impl Schema {
+    fn parse(root: Node) -> AppResult<Self> {
+        if !is_xs(root, "schema") {
+            return Err(AppError::ValidationError("XSD root element must be xs:schema".to_string()));
+        }
+        let mut schema = Schema::default();
+        for node in xs_children(root) {
+            match node.tag_name().name() {
+                "element" => {
+                    let decl = parse_element(node)?;
+                    schema.elements.insert(decl.name.clone(), decl);
+                }
+                "attribute" => {
+                    let decl = parse_attribute(node)?;
+                    schema.attributes.insert(decl.name.clone(), decl);
+                }
+                "complexType" => {
+                    schema.complex_types.insert(required_attr(node, "name")?.to_string(), parse_complex_type(node)?);
+                }
+                "simpleType" => {
+                    schema.simple_types.insert(required_attr(node, "name")?.to_string(), parse_simple_type(node)?);
+                }
+                "group" => {
+                    let particle = xs_children(node)
+                        .find(|child| matches!(child.tag_name().name(), "sequence" | "choice" | "all"))
+                        .map(parse_particle)
+                        .transpose()?
+                        .ok_or_else(|| AppError::ValidationError("xs:group needs a sequence, choice or all".to_string()))?;
+                    schema.groups.insert(required_attr(node, "name")?.to_string(), particle);
+                }
+                "attributeGroup" => {
+                    let (attributes, _) = parse_attributes(node)?;
+                    schema.attribute_groups.insert(required_attr(node, "name")?.to_string(), attributes);
+                }
+                "notation" => {}
+                other => {
+                    return Err(AppError::ValidationError(format!("xs:{} is not supported", other)));
+                }
+            }
+        }
+        schema.check_references()?;
+        Ok(schema)
+    }
+
+    // Validation follows type derivations and group references without any input to stop it, so
+    // the schema comes with the XSD and a cycle in it would never end. Element declarations are
+    // not followed: their nesting is bounded by the document.
+    fn check_references(&self) -> AppResult<()> {
+        let type_edges = |name: &str| match self.complex_types.get(name) {
+            Some(complex) => complex.base.iter().flat_map(type_refs).collect(),
+            None => self.simple_types.get(name).map(simple_refs).unwrap_or_default(),
+        };
+        let group_edges = |name: &str| {
+            let mut refs = Vec::new();
+            if let Some(particle) = self.groups.get(name) {
+                group_refs(particle, &mut refs);
+            }
+            refs
+        };
+        let attribute_group_edges = |name: &str| {
+            self.attribute_groups
+                .get(name)
+                .into_iter()
+                .flatten()
+                .filter_map(|attribute| match attribute {
+                    AttributeUse::GroupRef(name) => Some(name.as_str()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let types = self.complex_types.keys().chain(self.simple_types.keys());
+        check_reference_graph("type", types, type_edges)?;
+        check_reference_graph("group", self.groups.keys(), group_edges)?;
+        check_reference_graph("attribute group", self.attribute_groups.keys(), attribute_group_edges)
+    }
+}
+
+fn check_reference_graph<'a>(
+    kind: &str,
+    names: impl Iterator<Item = &'a String>,
+    edges: impl Fn(&'a str) -> Vec<&'a str>,
+) -> AppResult<()> {
+    // Depth of each name once its references are checked; None while they are being checked
+    let mut depths = HashMap::new();
+    for name in names {
+        reference_depth(kind, name, &edges, &mut depths, &mut Vec::new())?;
+    }
+    Ok(())
+}
+
+fn reference_depth<'a>(
+    kind: &str,
+    name: &'a str,
+    edges: &impl Fn(&'a str) -> Vec<&'a str>,
+    depths: &mut HashMap<&'a str, Option<usize>>,
+    path: &mut Vec<&'a str>,
+) -> AppResult<usize> {
+    match depths.get(name) {
+        Some(Some(depth)) => return Ok(*depth),
+        Some(None) => {
+            let start = path.iter().position(|seen| *seen == name).unwrap_or(0);
+            let cycle: Vec<&str> = path[start..].iter().copied().chain([name]).collect();
+            return Err(AppError::ValidationError(format!("Schema has a circular {} reference: {}", kind, cycle.join(" -> "))));
+        }
+        None => {}
+    }
+    let too_deep = || AppError::ValidationError(format!("Schema nests {} references more than {} deep", kind, MAX_REFERENCE_DEPTH));
+    if path.len() >= MAX_REFERENCE_DEPTH {
+        return Err(too_deep());
+    }
+
+    depths.insert(name, None);
+    path.push(name);
+    let mut depth = 1;
+    for next in edges(name) {
+        depth = depth.max(1 + reference_depth(kind, next, edges, depths, path)?);
+    }
+    path.pop();
+    if depth > MAX_REFERENCE_DEPTH {
+        return Err(too_deep());
+    }
+    depths.insert(name, Some(depth));
+    Ok(depth)
+}
+
+// Named types that a type definition derives from or is made of
+fn type_refs(kind: &TypeDef) -> Vec<&str> {
+    match kind {
+        TypeDef::Named(name) => vec![name],
+        TypeDef::Simple(simple) => simple_refs(simple),
+        TypeDef::Complex(complex) => complex.base.iter().flat_map(type_refs).collect(),
+        TypeDef::Any | TypeDef::Builtin(_) => Vec::new(),
+    }
+}
+
+fn simple_refs(simple: &SimpleType) -> Vec<&str> {
+    match simple {
+        SimpleType::Restriction(base, _) => type_refs(base),
+        SimpleType::List(item) => type_refs(item),
+        SimpleType::Union(members) => members.iter().flat_map(type_refs).collect(),
+    }
+}
+
+fn group_refs<'a>(particle: &'a Particle, refs: &mut Vec<&'a str>) {
+    match particle {
+        Particle::GroupRef(name, _) => refs.push(name),
+        Particle::Sequence(particles, _) | Particle::Choice(particles, _) | Particle::All(particles, _) => {
+            particles.iter().for_each(|particle| group_refs(particle, refs));
+        }
+        Particle::Element(..) | Particle::ElementRef(..) | Particle::Any(_) => {}
+    }
+}
+
+fn is_xs(node: Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().namespace() == Some(XS_NAMESPACE) && node.tag_name().name() == name
+}
+
+// Schema children, without annotations
+fn xs_children<'a, 'input>(node: Node<'a, 'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(|child| {
+        child.is_element() && child.tag_name().namespace() == Some(XS_NAMESPACE) && child.tag_name().name() != "annotation"
+    })
+}
+
+fn required_attr<'a>(node: Node<'a, '_>, name: &str) -> AppResult<&'a str> {
+    node.attribute(name)
+        .ok_or_else(|| AppError::ValidationError(format!("xs:{} needs a '{}' attribute", node.tag_name().name(), name)))
+}
+
+// Type references are QNames; only the XSD namespace is told apart, as names are matched locally
+fn type_ref(node: Node, value: &str) -> TypeDef {
+    let (prefix, local) = match value.split_once(':') {
+        Some((prefix, local)) => (Some(prefix), local),
+        None => (None, value),
+    };
+    if node.lookup_namespace_uri(prefix) == Some(XS_NAMESPACE) {
+        match local {
+            "anyType" | "anySimpleType" => TypeDef::Any,
+            _ => TypeDef::Builtin(local.to_string()),
+        }
+    } else {
+        TypeDef::Named(local.to_string())
+    }
+}
+
+fn local_name(value: &str) -> String {
+    value.rsplit(':').next().unwrap_or(value).to_string()
+}
+
+fn parse_occurs(node: Node) -> AppResult<Occurs> {
+    let invalid = |name: &str, value: &str| AppError::ValidationError(format!("Invalid {} '{}'", name, value));
+    let min = match node.attribute("minOccurs") {
+        Some(value) => value.trim().parse().map_err(|_| invalid("minOccurs", value))?,
+        None => 1,
+    };
+    let max = match node.attribute("maxOccurs") {
+        Some("unbounded") => None,
+        Some(value) => Some(value.trim().parse().map_err(|_| invalid("maxOccurs", value))?),
+        None => Some(1),
+    };
+    Ok(Occurs { min, max })
+}
+
+fn parse_element(node: Node) -> AppResult<ElementDecl> {
+    let name = required_attr(node, "name")?.to_string();
+    let kind = match node.attribute("type") {
+        Some(value) => type_ref(node, value),
+        None => inline_type(node)?.unwrap_or(TypeDef::Any),
+    };
+    Ok(ElementDecl { name, kind })
+}
+
+fn inline_type(node: Node) -> AppResult<Option<TypeDef>> {
+    for child in xs_children(node) {
+        match child.tag_name().name() {
+            "complexType" => return Ok(Some(TypeDef::Complex(Box::new(parse_complex_type(child)?)))),
+            "simpleType" => return Ok(Some(TypeDef::Simple(Box::new(parse_simple_type(child)?)))),
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+fn parse_particle(node: Node) -> AppResult<Particle> {
+    let occurs = parse_occurs(node)?;
+    let particles = || xs_children(node).map(parse_particle).collect::<AppResult<Vec<_>>>();
+    Ok(match node.tag_name().name() {
+        "element" => match node.attribute("ref") {
+            Some(name) => Particle::ElementRef(local_name(name), occurs),
+            None => Particle::Element(parse_element(node)?, occurs),
+        },
+        "sequence" => Particle::Sequence(particles()?, occurs),
+        "choice" => Particle::Choice(particles()?, occurs),
+        "all" => Particle::All(particles()?, occurs),
+        "group" => Particle::GroupRef(local_name(required_attr(node, "ref")?), occurs),
+        "any" => Particle::Any(occurs),
+        other => return Err(AppError::ValidationError(format!("xs:{} is not supported in a content model", other))),
+    })
+}
+
+fn parse_attribute(node: Node) -> AppResult<AttributeDecl> {
+    let kind = match node.attribute("type") {
+        Some(value) => type_ref(node, value),
+        None => inline_type(node)?.unwrap_or(TypeDef::Any),
+    };
+    Ok(AttributeDecl {
+        name: required_attr(node, "name")?.to_string(),
+        kind,
+        required: node.attribute("use") == Some("required"),
+    })
+}
+
+// Attribute uses declared directly under `node`, and whether it allows any attribute
+fn parse_attributes(node: Node) -> AppResult<(Vec<AttributeUse>, bool)> {
+    let mut attributes = Vec::new();
+    let mut any_attribute = false;
+    for child in xs_children(node) {
+        match child.tag_name().name() {
+            "attribute" => attributes.push(match child.attribute("ref") {
+                Some(name) => AttributeUse::Ref(local_name(name), child.attribute("use") == Some("required")),
+                None => AttributeUse::Decl(parse_attribute(child)?),
+            }),
+            "attributeGroup" => attributes.push(AttributeUse::GroupRef(local_name(required_attr(child, "ref")?))),
+            "anyAttribute" => any_attribute = true,
+            _ => {}
+        }
+    }
+    Ok((attributes, any_attribute))
+}
+
+fn parse_complex_type(node: Node) -> AppResult<ComplexType> {
+    let mut complex = ComplexType { mixed: node.attribute("mixed") == Some("true"), ..Default::default() };
+    let mut body = node;
+    if let Some(content) = xs_children(node).find(|child| matches!(child.tag_name().name(), "simpleContent" | "complexContent")) {
+        complex.simple_content = content.tag_name().name() == "simpleContent";
+        complex.mixed |= content.attribute("mixed") == Some("true");
+        let derivation = xs_children(content)
+            .find(|child| matches!(child.tag_name().name(), "extension" | "restriction"))
+            .ok_or_else(|| AppError::ValidationError("Derived content needs an extension or restriction".to_string()))?;
+        complex.base = Some(type_ref(derivation, required_attr(derivation, "base")?));
+        body = derivation;
+    }
+    complex.content = xs_children(body)
+        .find(|child| matches!(child.tag_name().name(), "sequence" | "choice" | "all" | "group"))
+        .map(parse_particle)
+        .transpose()?;
+    (complex.attributes, complex.any_attribute) = parse_attributes(body)?;
+    Ok(complex)
+}
+
+fn parse_simple_type(node: Node) -> AppResult<SimpleType> {
+    let invalid = || AppError::ValidationError("xs:simpleType needs a restriction, list or union".to_string());
+    let derivation = xs_children(node).next().ok_or_else(invalid)?;
+    match derivation.tag_name().name() {
+        "restriction" => {
+            let base = match derivation.attribute("base") {
+                Some(value) => type_ref(derivation, value),
+                None => inline_type(derivation)?.unwrap_or(TypeDef::Any),
+            };
+            Ok(SimpleType::Restriction(base, parse_facets(derivation)?))
+        }
+        "list" => {
+            let item = match derivation.attribute("itemType") {
+                Some(value) => type_ref(derivation, value),
+                None => inline_type(derivation)?.unwrap_or(TypeDef::Any),
+            };
+            Ok(SimpleType::List(item))
+        }
+        "union" => {
+            let mut members: Vec<TypeDef> = derivation
+                .attribute("memberTypes")
+                .unwrap_or("")
+                .split_whitespace()
+                .map(|value| type_ref(derivation, value))
+                .collect();
+            for child in xs_children(derivation) {
+                members.push(TypeDef::Simple(Box::new(parse_simple_type(child)?)));
+            }
+            Ok(SimpleType::Union(members))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_facets(node: Node) -> AppResult<Facets> {
+    let mut facets = Facets::default();
+    for child in xs_children(node) {
+        let value = child.attribute("value").unwrap_or("");
+        let invalid = || AppError::ValidationError(format!("Invalid xs:{} value '{}'", child.tag_name().name(), value));
+        let number = || value.trim().parse::<f64>().map_err(|_| invalid());
+        let length = || value.trim().parse::<usize>().map_err(|_| invalid());
+        match child.tag_name().name() {
+            "enumeration" => facets.enumeration.push(value.to_string()),
+            // XSD patterns always match the whole value
+            "pattern" => facets.patterns.push(Regex::new(&format!("^(?:{})$", value)).map_err(|_| invalid())?),
+            "length" => facets.length = Some(length()?),
+            "minLength" => facets.min_length = Some(length()?),
+            "maxLength" => facets.max_length = Some(length()?),
+            "minInclusive" => facets.min_inclusive = Some(number()?),
+            "maxInclusive" => facets.max_inclusive = Some(number()?),
+            "minExclusive" => facets.min_exclusive = Some(number()?),
+            "maxExclusive" => facets.max_exclusive = Some(number()?),
+            _ => {}
+        }
+    }
+    Ok(facets)
+}
+
+impl Facets {
+    fn check(&self, value: &str) -> Result<(), String> {
+        let trimmed = value.trim();
+        if !self.enumeration.is_empty() && !self.enumeration.iter().any(|allowed| allowed == trimmed) {
+            return Err(format!("'{}' is not one of: {}", trimmed, self.enumeration.join(", ")));
+        }
+        if let Some(pattern) = self.patterns.iter().find(|pattern| !pattern.is_match(trimmed)) {
+            let pattern = pattern.as_str();
+            return Err(format!("'{}' does not match pattern '{}'", trimmed, &pattern[4..pattern.len() - 2]));
+        }
+        let length = value.chars().count();
+        if self.length.is_some_and(|expected| length != expected)
+            || self.min_length.is_some_and(|min| length < min)
+            || self.max_length.is_some_and(|max| length > max)
+        {
+            return Err(format!("'{}' has length {}, outside the allowed length", value, length));
+        }
+        let bounded = self.min_inclusive.is_some()
+            || self.max_inclusive.is_some()
+            || self.min_exclusive.is_some()
+            || self.max_exclusive.is_some();
+        if bounded {
+            let number: f64 = trimmed.parse().map_err(|_| format!("'{}' is not a number", trimmed))?;
+            if self.min_inclusive.is_some_and(|min| number < min)
+                || self.max_inclusive.is_some_and(|max| number > max)
+                || self.min_exclusive.is_some_and(|min| number <= min)
+                || self.max_exclusive.is_some_and(|max| number >= max)
+            {
+                return Err(format!("{} is outside the allowed range", trimmed));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_builtin(name: &str, value: &str) -> Result<(), String> {
+    let value = value.trim();
+    let integer = |min: i128, max: i128| value.parse::<i128>().is_ok_and(|n| (min..=max).contains(&n));
+    let valid = match name {
+        "boolean" => matches!(value, "true" | "false" | "1" | "0"),
+        "decimal" => {
+            value.parse::<f64>().is_ok() && value.chars().all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.'))
+        }
+        "float" | "double" => {
+            matches!(value, "INF" | "-INF" | "NaN")
+                || (value.parse::<f64>().is_ok() && value.chars().all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E')))
+        }
+        "integer" => integer(i128::MIN, i128::MAX),
+        "long" => integer(i64::MIN.into(), i64::MAX.into()),
+        "int" => integer(i32::MIN.into(), i32::MAX.into()),
+        "short" => integer(i16::MIN.into(), i16::MAX.into()),
+        "byte" => integer(i8::MIN.into(), i8::MAX.into()),
+        "nonNegativeInteger" => integer(0, i128::MAX),
+        "positiveInteger" => integer(1, i128::MAX),
+        "nonPositiveInteger" => integer(i128::MIN, 0),
+        "negativeInteger" => integer(i128::MIN, -1),
+        "unsignedLong" => integer(0, u64::MAX.into()),
+        "unsignedInt" => integer(0, u32::MAX.into()),
+        "unsignedShort" => integer(0, u16::MAX.into()),
+        "unsignedByte" => integer(0, u8::MAX.into()),
+        "date" => NaiveDate::parse_from_str(strip_timezone(value), "%Y-%m-%d").is_ok(),
+        "time" => NaiveTime::parse_from_str(strip_timezone(value), "%H:%M:%S%.f").is_ok(),
+        "dateTime" => {
+            DateTime::parse_from_rfc3339(value).is_ok()
+                || NaiveDateTime::parse_from_str(strip_timezone(value), "%Y-%m-%dT%H:%M:%S%.f").is_ok()
+        }
+        _ => true,
+    };
+    if valid { Ok(()) } else { Err(format!("'{}' is not a valid xs:{}", value, name)) }
+}
+
+// Drops a trailing `Z` or `±hh:mm` timezone
+fn strip_timezone(value: &str) -> &str {
+    if let Some(stripped) = value.strip_suffix('Z') {
+        return stripped;
+    }
+    let bytes = value.as_bytes();
+    if bytes.len() > 6 && matches!(bytes[bytes.len() - 6], b'+' | b'-') && bytes[bytes.len() - 3] == b':' {
+        return &value[..value.len() - 6];
+    }
+    value
+}
+
+enum Resolved<'s> {
+    Any,
+    Builtin(&'s str),
+    Simple(&'s SimpleType),
+    Complex(&'s ComplexType),
+}
+
+struct Validator<'s, 'd> {
+    schema: &'s Schema,
+    doc: &'d Document<'d>,
+    issues: Vec<ValidationIssue>,
+    /// First reference to an undeclared type, group or element found in the schema
+    schema_error: Option<String>,
+    /// Furthest child position at which an element was expected, with the names expected there
+    furthest: Option<(usize, Vec<String>)>,
+}
+
+impl<'s> Validator<'s, '_> {
+    fn issue(&mut self, node: Node, path: &str, message: String) {
+        let pos = self.doc.text_pos_at(node.range().start);
+        self.issues.push(ValidationIssue { path: path.to_string(), line: pos.row, column: pos.col, message });
+    }
+
+    fn undeclared(&mut self, kind: &str, name: &str) {
+        self.schema_error.get_or_insert_with(|| format!("Schema references undeclared {} '{}'", kind, name));
+    }
+
+    fn resolve(&mut self, kind: &'s TypeDef) -> Resolved<'s> {
+        match kind {
+            TypeDef::Any => Resolved::Any,
+            TypeDef::Builtin(name) => Resolved::Builtin(name),
+            TypeDef::Complex(complex) => Resolved::Complex(complex),
+            TypeDef::Simple(simple) => Resolved::Simple(simple),
+            TypeDef::Named(name) => {
+                if let Some(complex) = self.schema.complex_types.get(name) {
+                    Resolved::Complex(complex)
+                } else if let Some(simple) = self.schema.simple_types.get(name) {
+                    Resolved::Simple(simple)
+                } else {
+                    self.undeclared("type", name);
+                    Resolved::Any
+                }
+            }
+        }
+    }
+
+    fn check_value(&mut self, kind: &'s TypeDef, value: &str) -> Result<(), String> {
+        match self.resolve(kind) {
+            Resolved::Any | Resolved::Complex(_) => Ok(()),
+            Resolved::Builtin(name) => check_builtin(name, value),
+            Resolved::Simple(SimpleType::Restriction(base, facets)) => {
+                self.check_value(base, value)?;
+                facets.check(value)
+            }
+            Resolved::Simple(SimpleType::List(item)) => {
+                value.split_whitespace().try_for_each(|token| self.check_value(item, token))
+            }
+            Resolved::Simple(SimpleType::Union(members)) => {
+                if members.iter().any(|member| self.check_value(member, value).is_ok()) {
+                    Ok(())
+                } else {
+                    Err(format!("'{}' matches none of the union's member types", value.trim()))
+                }
+            }
+        }
+    }
+
+    fn validate_element(&mut self, node: Node, path: &str, kind: &'s TypeDef) {
+        let name = node.tag_name().name();
+        match self.resolve(kind) {
+            Resolved::Any => {}
+            Resolved::Complex(complex) => self.validate_complex(node, path, complex),
+            Resolved::Builtin(_) | Resolved::Simple(_) => {
+                if let Some(child) = node.children().find(|child| child.is_element()) {
+                    self.issue(child, path, format!("Element '{}' must not contain child elements", name));
+                }
+                if let Err(message) = self.check_value(kind, &text_of(node)) {
+                    self.issue(node, path, format!("Element '{}': {}", name, message));
+                }
+            }
+        }
+    }
+
+    fn validate_complex(&mut self, node: Node, path: &str, complex: &'s ComplexType) {
+        let name = node.tag_name().name();
+
+        // Base types contribute their content first and their attributes, most derived last
+        let mut chain = vec![complex];
+        let mut text_type = None;
+        let mut current = complex;
+        while let Some(base) = &current.base {
+            match self.resolve(base) {
+                Resolved::Complex(base) => {
+                    chain.push(base);
+                    current = base;
+                }
+                Resolved::Any => break,
+                Resolved::Builtin(_) | Resolved::Simple(_) => {
+                    text_type = Some(base);
+                    break;
+                }
+            }
+        }
+        chain.reverse();
+        let simple_content = chain.iter().any(|complex| complex.simple_content);
+        let mixed = chain.iter().any(|complex| complex.mixed);
+        let any_attribute = chain.iter().any(|complex| complex.any_attribute);
+
+        self.validate_attributes(node, path, &chain, any_attribute);
+
+        let elements: Vec<Node> = node.children().filter(|child| child.is_element()).collect();
+        if simple_content {
+            if let Some(child) = elements.first() {
+                self.issue(*child, path, format!("Element '{}' must not contain child elements", name));
+            }
+            if let Some(text_type) = text_type
+                && let Err(message) = self.check_value(text_type, &text_of(node))
+            {
+                self.issue(node, path, format!("Element '{}': {}", name, message));
+            }
+            return;
+        }
+        if !mixed && !text_of(node).trim().is_empty() {
+            self.issue(node, path, format!("Element '{}' must not contain text", name));
+        }
+
+        let content: Vec<&'s Particle> = chain.iter().filter_map(|complex| complex.content.as_ref()).collect();
+        self.furthest = None;
+        let mut pos = Some(0);
+        for particle in &content {
+            pos = pos.and_then(|pos| self.match_particle(particle, &elements, pos));
+        }
+        if pos != Some(elements.len()) {
+            let (at, expected) = match (self.furthest.take(), pos) {
+                (Some((at, expected)), pos) if pos.is_none_or(|pos| at >= pos) => (at, expected),
+                (_, pos) => (pos.unwrap_or(0), Vec::new()),
+            };
+            let expected = match expected.as_slice() {
+                [] => String::new(),
+                names => format!("; expected {}", names.iter().map(|name| format!("'{}'", name)).collect::<Vec<_>>().join(" or ")),
+            };
+            match elements.get(at) {
+                Some(child) => {
+                    let message = format!("Unexpected element '{}'{}", child.tag_name().name(), expected);
+                    self.issue(*child, &child_path(path, &elements, at), message);
+                }
+                None => self.issue(node, path, format!("Element '{}' is incomplete{}", name, expected)),
+            }
+        }
+
+        let mut declarations = HashMap::new();
+        for particle in &content {
+            self.collect_declarations(particle, &mut declarations);
+        }
+        for (i, child) in elements.iter().enumerate() {
+            if let Some(kind) = declarations.get(child.tag_name().name()) {
+                self.validate_element(*child, &child_path(path, &elements, i), kind);
+            }
+        }
+    }
+
+    fn validate_attributes(&mut self, node: Node, path: &str, chain: &[&'s ComplexType], any_attribute: bool) {
+        let mut declared: Vec<(&'s AttributeDecl, bool)> = Vec::new();
+        for complex in chain {
+            self.collect_attributes(&complex.attributes, &mut declared);
+        }
+
+        for attribute in node.attributes() {
+            if attribute.namespace() == Some(XSI_NAMESPACE) {
+                continue;
+            }
+            match declared.iter().find(|(decl, _)| decl.name == attribute.name()) {
+                Some((decl, _)) => {
+                    if let Err(message) = self.check_value(&decl.kind, attribute.value()) {
+                        self.issue(node, path, format!("Attribute '{}': {}", attribute.name(), message));
+                    }
+                }
+                None if any_attribute => {}
+                None => self.issue(node, path, format!("Unexpected attribute '{}'", attribute.name())),
+            }
+        }
+        for (decl, required) in declared {
+            if required && !node.attributes().any(|attribute| attribute.name() == decl.name) {
+                self.issue(node, path, format!("Missing required attribute '{}'", decl.name));
+            }
+        }
+    }
+
+    fn collect_attributes(&mut self, uses: &'s [AttributeUse], declared: &mut Vec<(&'s AttributeDecl, bool)>) {
+        for attribute in uses {
+            match attribute {
+                AttributeUse::Decl(decl) => declared.push((decl, decl.required)),
+                AttributeUse::Ref(name, required) => match self.schema.attributes.get(name) {
+                    Some(decl) => declared.push((decl, *required)),
+                    None => self.undeclared("attribute", name),
+                },
+                AttributeUse::GroupRef(name) => match self.schema.attribute_groups.get(name) {
+                    Some(group) => self.collect_attributes(group, declared),
+                    None => self.undeclared("attribute group", name),
+                },
+            }
+        }
+    }
+
+    // Element declarations in a content model can be found by name, since XSD requires
+    // same-named elements in one content model to share a type
+    fn collect_declarations(&mut self, particle: &'s Particle, declarations: &mut HashMap<&'s str, &'s TypeDef>) {
+        match particle {
+            Particle::Element(decl, _) => {
+                declarations.insert(&decl.name, &decl.kind);
+            }
+            Particle::ElementRef(name, _) => {
+                if let Some(decl) = self.schema.elements.get(name) {
+                    declarations.insert(&decl.name, &decl.kind);
+                }
+            }
+            Particle::Sequence(particles, _) | Particle::Choice(particles, _) | Particle::All(particles, _) => {
+                for particle in particles {
+                    self.collect_declarations(particle, declarations);
+                }
+            }
+            Particle::GroupRef(name, _) => {
+                if let Some(group) = self.schema.groups.get(name) {
+                    self.collect_declarations(group, declarations);
+                }
+            }
+            Particle::Any(_) => {}
+        }
+    }
+
+    fn expect(&mut self, pos: usize, name: &str) {
+        match &mut self.furthest {
+            Some((at, names)) if *at == pos => {
+                if !names.iter().any(|existing| existing == name) {
+                    names.push(name.to_string());
+                }
+            }
+            Some((at, _)) if *at > pos => {}
+            _ => self.furthest = Some((pos, vec![name.to_string()])),
+        }
+    }
+
+    // Position after the children matched by `particle` starting at `pos`, or None
+    fn match_particle(&mut self, particle: &'s Particle, elements: &[Node], pos: usize) -> Option<usize> {
+        match particle {
+            Particle::Element(decl, occurs) => self.repeat(*occurs, pos, |validator, pos| {
+                validator.match_name(&decl.name, elements, pos)
+            }),
+            Particle::ElementRef(name, occurs) => {
+                if !self.schema.elements.contains_key(name) {
+                    self.undeclared("element", name);
+                }
+                self.repeat(*occurs, pos, |validator, pos| validator.match_name(name, elements, pos))
+            }
+            Particle::Sequence(particles, occurs) => self.repeat(*occurs, pos, |validator, mut pos| {
+                for particle in particles {
+                    pos = validator.match_particle(particle, elements, pos)?;
+                }
+                Some(pos)
+            }),
+            Particle::Choice(particles, occurs) => self.repeat(*occurs, pos, |validator, pos| {
+                // An alternative that consumes children wins over one that matches nothing
+                let mut empty = None;
+                for particle in particles {
+                    match validator.match_particle(particle, elements, pos) {
+                        Some(next) if next > pos => return Some(next),
+                        Some(next) => empty = Some(next),
+                        None => {}
+                    }
+                }
+                empty
+            }),
+            Particle::All(particles, occurs) => self.repeat(*occurs, pos, |validator, mut pos| {
+                let mut seen = vec![false; particles.len()];
+                while let Some(child) = elements.get(pos) {
+                    let member = particles.iter().enumerate().position(|(i, particle)| {
+                        !seen[i] && validator.particle_name(particle) == Some(child.tag_name().name())
+                    });
+                    let Some(member) = member else { break };
+                    seen[member] = true;
+                    pos += 1;
+                }
+                let mut complete = true;
+                for (i, particle) in particles.iter().enumerate() {
+                    let required = match particle {
+                        Particle::Element(_, occurs) | Particle::ElementRef(_, occurs) => occurs.min > 0,
+                        _ => false,
+                    };
+                    if required && !seen[i]
+                        && let Some(name) = validator.particle_name(particle)
+                    {
+                        validator.expect(pos, name);
+                        complete = false;
+                    }
+                }
+                complete.then_some(pos)
+            }),
+            Particle::GroupRef(name, occurs) => match self.schema.groups.get(name) {
+                Some(group) => self.repeat(*occurs, pos, |validator, pos| validator.match_particle(group, elements, pos)),
+                None => {
+                    self.undeclared("group", name);
+                    Some(pos)
+                }
+            },
+            Particle::Any(occurs) => {
+                self.repeat(*occurs, pos, |_, pos| (pos < elements.len()).then_some(pos + 1))
+            }
+        }
+    }
+
+    fn particle_name(&self, particle: &'s Particle) -> Option<&'s str> {
+        match particle {
+            Particle::Element(decl, _) => Some(&decl.name),
+            Particle::ElementRef(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn match_name(&mut self, name: &str, elements: &[Node], pos: usize) -> Option<usize> {
+        match elements.get(pos) {
+            Some(child) if child.tag_name().name() == name => Some(pos + 1),
+            _ => {
+                self.expect(pos, name);
+                None
+            }
+        }
+    }
+
+    // Matches `once` greedily between `occurs.min` and `occurs.max` times
+    fn repeat(&mut self, occurs: Occurs, mut pos: usize, mut once: impl FnMut(&mut Self, usize) -> Option<usize>) -> Option<usize> {
+        let mut count = 0;
+        while occurs.max.is_none_or(|max| count < max) {
+            match once(self, pos) {
+                Some(next) if next > pos => {
+                    pos = next;
+                    count += 1;
+                }
+                // Matching nothing satisfies any remaining minimum
+                Some(_) => return Some(pos),
+                None if count >= occurs.min => break,
+                None => return None,
+            }
+        }
+        Some(pos)
+    }
+}
+
+fn text_of(node: Node) -> String {
+    node.children().filter(|child| child.is_text()).filter_map(|child| child.text()).collect()
+}
+
+// Index suffix only when siblings share the name, as in comparison diff paths
+fn child_path(parent: &str, siblings: &[Node], index: usize) -> String {
+    let name = siblings[index].tag_name().name();
+    let same_name: Vec<usize> = (0..siblings.len()).filter(|&i| siblings[i].tag_name().name() == name).collect();
+    if same_name.len() > 1 {
+        let position = same_name.iter().position(|&i| i == index).unwrap_or(0) + 1;
+        format!("{}/{}[{}]", parent, name, position)
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDER_XSD: &str = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:simpleType name="Currency">
+    <xs:restriction base="xs:string">
+      <xs:enumeration value="EUR"/>
+      <xs:enumeration value="USD"/>
+    </xs:restriction>
+  </xs:simpleType>
+  <xs:complexType name="Price">
+    <xs:simpleContent>
+      <xs:extension base="xs:decimal">
+        <xs:attribute name="currency" type="Currency" use="required"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+  <xs:element name="order">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="date" type="xs:date"/>
+        <xs:element name="item" maxOccurs="unbounded">
+          <xs:complexType>
+            <xs:sequence>
+              <xs:element name="sku">
+                <xs:simpleType>
+                  <xs:restriction base="xs:string"><xs:pattern value="[A-Z]{3}-\d+"/></xs:restriction>
+                </xs:simpleType>
+              </xs:element>
+              <xs:element name="quantity" type="xs:positiveInteger"/>
+              <xs:element name="price" type="Price"/>
+            </xs:sequence>
+          </xs:complexType>
+        </xs:element>
+        <xs:element name="note" type="xs:string" minOccurs="0"/>
+      </xs:sequence>
+      <xs:attribute name="id" type="xs:int" use="required"/>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#;
+
+    #[test]
+    fn test_valid_document() {
+        let xml = r#"<order id="7">
+  <date>2024-01-31</date>
+  <item><sku>ABC-1</sku><quantity>2</quantity><price currency="EUR">9.99</price></item>
+  <item><sku>XYZ-22</sku><quantity>1</quantity><price currency="USD">5</price></item>
+</order>"#;
+        assert_eq!(validate_against_xsd(xml, ORDER_XSD).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_reports_violations_with_positions() {
+        let xml = r#"<order id="x" extra="1">
+  <date>31/01/2024</date>
+  <item><sku>abc</sku><quantity>0</quantity><price>1</price></item>
+  <item><sku>ABC-1</sku><price currency="GBP">1</price></item>
+  <bogus/>
+</order>"#;
+        let issues = validate_against_xsd(xml, ORDER_XSD).unwrap();
+        let summary: Vec<(&str, u32, &str)> =
+            issues.iter().map(|issue| (issue.path.as_str(), issue.line, issue.message.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/order", 1, "Attribute 'id': 'x' is not a valid xs:int"),
+                ("/order", 1, "Unexpected attribute 'extra'"),
+                ("/order/bogus", 5, "Unexpected element 'bogus'; expected 'item' or 'note'"),
+                ("/order/date", 2, "Element 'date': '31/01/2024' is not a valid xs:date"),
+                ("/order/item[1]/sku", 3, "Element 'sku': 'abc' does not match pattern '[A-Z]{3}-\\d+'"),
+                ("/order/item[1]/quantity", 3, "Element 'quantity': '0' is not a valid xs:positiveInteger"),
+                ("/order/item[1]/price", 3, "Missing required attribute 'currency'"),
+                ("/order/item[2]/price", 4, "Unexpected element 'price'; expected 'quantity'"),
+                ("/order/item[2]/price", 4, "Attribute 'currency': 'GBP' is not one of: EUR, USD"),
+            ]
+        );
+        assert_eq!(issues[2].column, 3);
+    }
+
+    #[test]
+    fn test_choice_all_and_groups() {
+        let xsd = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:group name="contact">
+    <xs:choice><xs:element name="email"/><xs:element name="phone"/></xs:choice>
+  </xs:group>
+  <xs:complexType name="Base"><xs:sequence><xs:element name="name"/></xs:sequence></xs:complexType>
+  <xs:complexType name="Person">
+    <xs:complexContent>
+      <xs:extension base="Base"><xs:sequence><xs:group ref="contact"/></xs:sequence></xs:extension>
+    </xs:complexContent>
+  </xs:complexType>
+  <xs:element name="people">
+    <xs:complexType>
+      <xs:sequence>
+        <xs:element name="person" type="Person" maxOccurs="unbounded"/>
+        <xs:element name="meta">
+          <xs:complexType><xs:all><xs:element name="a"/><xs:element name="b" minOccurs="0"/></xs:all></xs:complexType>
+        </xs:element>
+      </xs:sequence>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#;
+        let valid = "<people><person><name/><phone/></person><person><name/><email/></person><meta><b/><a/></meta></people>";
+        assert_eq!(validate_against_xsd(valid, xsd).unwrap(), vec![]);
+
+        let invalid = "<people><person><name/></person><meta><b/></meta></people>";
+        let messages: Vec<String> = validate_against_xsd(invalid, xsd).unwrap().into_iter().map(|issue| issue.message).collect();
+        assert_eq!(
+            messages,
+            vec!["Element 'person' is incomplete; expected 'email' or 'phone'", "Element 'meta' is incomplete; expected 'a'"]
+        );
+    }
+
+    #[test]
+    fn test_malformed_inputs() {
+        let issues = validate_against_xsd("<order>\n<date></order>", ORDER_XSD).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, 2);
+
+        assert!(matches!(validate_against_xsd("<a/>", "<schema/>"), Err(AppError::ValidationError(_))));
+        assert!(matches!(validate_against_xsd("<a/>", "<xs:schema"), Err(AppError::XmlParseError(_))));
+        let undeclared = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"><xs:element name="a" type="Missing"/></xs:schema>"#;
+        assert!(matches!(validate_against_xsd("<a/>", undeclared), Err(AppError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_reference_cycles() {
+        let error = |declarations: &str| {
+            let xsd = format!(
+                r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"><xs:element name="a" type="A"/>{}</xs:schema>"#,
+                declarations
+            );
+            match validate_against_xsd("<a/>", &xsd) {
+                Err(AppError::ValidationError(message)) => message,
+                other => panic!("expected a schema error, got {:?}", other.map(|issues| issues.len())),
+            }
+        };
+        let complex = |name: &str, base: &str| {
+            format!(r#"<xs:complexType name="{}"><xs:complexContent><xs:extension base="{}"/></xs:complexContent></xs:complexType>"#, name, base)
+        };
+
+        let message = error(&format!("{}{}", complex("A", "B"), complex("B", "A")));
+        assert!(message == "Schema has a circular type reference: A -> B -> A" || message.ends_with("B -> A -> B"), "{}", message);
+        let restriction = r#"<xs:simpleType name="A"><xs:restriction base="A"/></xs:simpleType>"#;
+        assert_eq!(error(restriction), "Schema has a circular type reference: A -> A");
+        let list = r#"<xs:simpleType name="A"><xs:list><xs:simpleType><xs:restriction base="A"/></xs:simpleType></xs:list></xs:simpleType>"#;
+        assert_eq!(error(list), "Schema has a circular type reference: A -> A");
+        let union = r#"<xs:simpleType name="A"><xs:union memberTypes="xs:int B"/></xs:simpleType>
+            <xs:simpleType name="B"><xs:list itemType="A"/></xs:simpleType>"#;
+        assert!(error(union).starts_with("Schema has a circular type reference"));
+
+        let groups = r#"<xs:complexType name="A"><xs:group ref="g"/></xs:complexType>
+            <xs:group name="g"><xs:sequence><xs:element name="b"/><xs:group ref="h" minOccurs="0"/></xs:sequence></xs:group>
+            <xs:group name="h"><xs:choice><xs:group ref="g"/></xs:choice></xs:group>"#;
+        assert!(error(groups).starts_with("Schema has a circular group reference"));
+        let attribute_groups = r#"<xs:complexType name="A"><xs:attributeGroup ref="x"/></xs:complexType>
+            <xs:attributeGroup name="x"><xs:attributeGroup ref="x"/></xs:attributeGroup>"#;
+        assert_eq!(error(attribute_groups), "Schema has a circular attribute group reference: x -> x");
+
+        // Long chains are refused too, and recursive element nesting is not a cycle
+        let chain: String = (0..100).map(|i| complex(&format!("T{}", i), &format!("T{}", i + 1))).collect();
+        assert!(error(&format!("{}{}", complex("A", "T0"), chain)).contains("more than 64 deep"));
+        let nested = r#"<xs:complexType name="A"><xs:sequence><xs:element name="a" type="A" minOccurs="0"/></xs:sequence></xs:complexType>"#;
+        let xsd = format!(r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"><xs:element name="a" type="A"/>{}</xs:schema>"#, nested);
+        assert_eq!(validate_against_xsd("<a><a/></a>", &xsd).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{CompareError, CompareResult};
+use quick_xml::Reader;
+use crate::entities::EntityResolver;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::BTreeMap;
+
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Serializes a document in W3C Canonical XML 1.0 form (without comments): no XML declaration
+/// or DTD, `\n` line endings, entities and CDATA expanded into escaped text, empty elements
+/// written as start/end pairs, superfluous namespace declarations dropped, namespace
+/// declarations sorted by prefix and attributes sorted by namespace URI and local name.
+pub fn canonicalize(xml: &str) -> CompareResult<String> {
+    let mut reader = Reader::from_str(xml);
+    reader.expand_empty_elements(true);
+
+    let mut output = String::with_capacity(xml.len());
+    // In-scope namespace bindings (prefix, "" for the default namespace) of each open element
+    let mut scopes: Vec<BTreeMap<String, String>> = Vec::new();
+    let mut root_closed = false;
+    let mut entities = EntityResolver::default();
+    let mut buf = Vec::new();
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| CompareError::XmlParseError(e.to_string()))?;
+        match event {
+            Event::Start(start) => {
+                let parent = scopes.last().cloned().unwrap_or_default();
+                let scope = write_start(&mut output, &start, &parent, &entities)?;
+                scopes.push(scope);
+            }
+            Event::End(end) => {
+                scopes.pop();
+                output.push_str("</");
+                output.push_str(&String::from_utf8_lossy(end.name().as_ref()));
+                output.push('>');
+                root_closed = scopes.is_empty();
+            }
+            // Whitespace and text outside the document element are not part of the canonical form
+            Event::Text(text) if !scopes.is_empty() => {
+                let raw = normalize_line_endings(&String::from_utf8_lossy(&text));
+                let value = entities.unescape_strict(&raw)?;
+                escape_text(&mut output, &value);
+            }
+            Event::CData(text) if !scopes.is_empty() => {
+                escape_text(&mut output, &normalize_line_endings(&String::from_utf8_lossy(&text)));
+            }
+            Event::DocType(doctype) => {
+                entities = EntityResolver::from_doctype(&String::from_utf8_lossy(&doctype))?;
+            }
+            Event::PI(pi) => {
+                if root_closed {
+                    output.push('\n');
+                }
+                output.push_str("<?");
+                output.push_str(&normalize_line_endings(&String::from_utf8_lossy(&pi)));
+                output.push_str("?>");
+                if scopes.is_empty() && !root_closed {
+                    output.push('\n');
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(output)
+}
+
+fn write_start(
+    output: &mut String,
+    start: &BytesStart,
+    parent: &BTreeMap<String, String>,
+    entities: &EntityResolver,
+) -> CompareResult<BTreeMap<String, String>> {
+    let mut scope = parent.clone();
+    let mut declarations = BTreeMap::new();
+    let mut attributes = Vec::new();
+
+    for attr in start.attributes() {
+        let attr = attr.map_err(|e| CompareError::XmlParseError(e.to_string()))?;
+        // Literal whitespace in attribute values is normalized to spaces, as an XML parser would
+        let raw: String = normalize_line_endings(&String::from_utf8_lossy(&attr.value))
+            .chars()
+            .map(|c| if matches!(c, '\t' | '\n') { ' ' } else { c })
+            .collect();
+        let value = entities.unescape_strict(&raw)?.into_owned();
+        let key = String::from_utf8_lossy(attr.key.as_ref()).into_owned();
+
+        match key.strip_prefix("xmlns") {
+            Some("") => {
+                declarations.insert(String::new(), value);
+            }
+            Some(prefixed) if prefixed.starts_with(':') => {
+                declarations.insert(prefixed[1..].to_string(), value);
+            }
+            _ => attributes.push((key, value)),
+        }
+    }
+
+    // A declaration is superfluous when the parent already has the same binding in scope,
+    // which also drops `xmlns=""` unless it undeclares an inherited default namespace
+    declarations.retain(|prefix, uri| parent.get(prefix).map_or("", String::as_str) != uri.as_str());
+    scope.extend(declarations.iter().map(|(prefix, uri)| (prefix.clone(), uri.clone())));
+
+    let mut attributes: Vec<(String, String, String, String)> = attributes
+        .into_iter()
+        .map(|(key, value)| {
+            let (namespace, local) = match key.split_once(':') {
+                Some(("xml", local)) => (XML_NAMESPACE.to_string(), local.to_string()),
+                Some((prefix, local)) => (scope.get(prefix).cloned().unwrap_or_default(), local.to_string()),
+                None => (String::new(), key.clone()),
+            };
+            (namespace, local, key, value)
+        })
+        .collect();
+    attributes.sort();
+
+    output.push('<');
+    output.push_str(&String::from_utf8_lossy(start.name().as_ref()));
+    for (prefix, uri) in &declarations {
+        output.push_str(" xmlns");
+        if !prefix.is_empty() {
+            output.push(':');
+            output.push_str(prefix);
+        }
+        output.push_str("=\"");
+        escape_attribute(output, uri);
+        output.push('"');
+    }
+    for (_, _, key, value) in &attributes {
+        output.push(' ');
+        output.push_str(key);
+        output.push_str("=\"");
+        escape_attribute(output, value);
+        output.push('"');
+    }
+    output.push('>');
+
+    Ok(scope)
+}
+
+fn normalize_line_endings(value: &str) -> String {
+    value.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+fn escape_text(output: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '\r' => output.push_str("&#xD;"),
+            c => output.push(c),
+        }
+    }
+}
+
+fn escape_attribute(output: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '"' => output.push_str("&quot;"),
+            '\t' => output.push_str("&#x9;"),
+            '\n' => output.push_str("&#xA;"),
+            '\r' => output.push_str("&#xD;"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonical_form() {
+        let xml = "<?xml version=\"1.0\"?>\r\n<!-- comment -->\n<doc xmlns=\"urn:a\" b='2' a=\"1 &amp; &#x9;\">\r\n  <e1/><e2 xmlns=\"urn:a\"><![CDATA[x < y]]></e2><e3 xmlns=\"\">&#65;</e3></doc>\n";
+        assert_eq!(
+            canonicalize(xml).unwrap(),
+            "<doc xmlns=\"urn:a\" a=\"1 &amp; &#x9;\" b=\"2\">\n  <e1></e1><e2>x &lt; y</e2><e3 xmlns=\"\">A</e3></doc>"
+        );
+    }
+
+    #[test]
+    fn test_attributes_sorted_by_namespace() {
+        let xml = "<a xmlns:z=\"urn:1\" xmlns:b=\"urn:2\" z:x=\"1\" b:y=\"2\" c=\"3\"/>";
+        assert_eq!(
+            canonicalize(xml).unwrap(),
+            "<a xmlns:b=\"urn:2\" xmlns:z=\"urn:1\" c=\"3\" z:x=\"1\" b:y=\"2\"></a>"
+        );
+    }
+
+    #[test]
+    fn test_internal_entities_expanded() {
+        let xml = "<!DOCTYPE doc [<!ENTITY who \"world\">]><doc greeting=\"hello &who;\">&who;</doc>";
+        assert_eq!(canonicalize(xml).unwrap(), "<doc greeting=\"hello world\">world</doc>");
+        assert!(canonicalize("<doc>&unknown;</doc>").is_err());
+    }
+}
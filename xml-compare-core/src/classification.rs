@@ -0,0 +1,127 @@
+use crate::{ClassificationRule, CompareError, CompareResult, DiffType, XmlDiff};
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// The `classifications` rules, ready to tag diffs, with their regular expressions compiled
+#[derive(Default)]
+pub struct DiffClassifier {
+    rules: Vec<CompiledRule>,
+}
+
+struct CompiledRule {
+    tag: String,
+    path: Option<Regex>,
+    content: Option<Regex>,
+    diff_types: Option<Vec<DiffType>>,
+}
+
+impl CompiledRule {
+    fn matches(&self, diff: &XmlDiff) -> bool {
+        self.diff_types.as_ref().is_none_or(|types| types.contains(&diff.diff_type))
+            && self.path.as_ref().is_none_or(|path| path.is_match(&diff.path))
+            && self.content.as_ref().is_none_or(|content| {
+                [diff.expected.as_deref(), diff.actual.as_deref(), Some(diff.message.as_str())]
+                    .into_iter()
+                    .flatten()
+                    .any(|text| content.is_match(text))
+            })
+    }
+}
+
+impl DiffClassifier {
+    pub fn compile(rules: &[ClassificationRule]) -> CompareResult<Self> {
+        let regex = |pattern: &Option<String>, field: &str| {
+            pattern
+                .as_deref()
+                .map(|pattern| {
+                    Regex::new(pattern).map_err(|e| {
+                        CompareError::ValidationError(format!("Invalid classification {} pattern '{}': {}", field, pattern, e))
+                    })
+                })
+                .transpose()
+        };
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                if rule.tag.trim().is_empty() {
+                    return Err(CompareError::ValidationError("Classification rules need a tag".to_string()));
+                }
+                Ok(CompiledRule {
+                    tag: rule.tag.clone(),
+                    path: regex(&rule.path, "path")?,
+                    content: regex(&rule.content, "content")?,
+                    diff_types: rule.diff_types.clone(),
+                })
+            })
+            .collect::<CompareResult<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Tags each diff with the tags of the rules it matches, in rule order, and counts the diffs
+    /// carrying each tag
+    pub fn classify(&self, diffs: &mut [XmlDiff]) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        if self.rules.is_empty() {
+            return counts;
+        }
+        for diff in diffs {
+            let mut tags: Vec<String> = Vec::new();
+            for rule in self.rules.iter().filter(|rule| rule.matches(diff)) {
+                if !tags.contains(&rule.tag) {
+                    tags.push(rule.tag.clone());
+                    *counts.entry(rule.tag.clone()).or_default() += 1;
+                }
+            }
+            diff.tags = tags;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DiffSeverity;
+
+    fn rule(tag: &str, path: Option<&str>, content: Option<&str>, diff_types: Option<Vec<DiffType>>) -> ClassificationRule {
+        ClassificationRule { tag: tag.to_string(), path: path.map(str::to_string), content: content.map(str::to_string), diff_types }
+    }
+
+    fn diff(path: &str, diff_type: DiffType, expected: &str, actual: &str) -> XmlDiff {
+        XmlDiff {
+            path: path.to_string(),
+            diff_type,
+            expected: Some(expected.to_string()),
+            actual: Some(actual.to_string()),
+            message: "Content differs".to_string(),
+            similarity: None,
+            severity: DiffSeverity::Error,
+            tags: Vec::new(),
+            node: None,
+        }
+    }
+
+    #[test]
+    fn test_classify_diffs() {
+        let classifier = DiffClassifier::compile(&[
+            rule("pricing", Some(r"/(price|amount)$"), None, None),
+            rule("regulatory", None, Some(r"^LEI-"), None),
+            rule("cosmetic", None, None, Some(vec![DiffType::SelfClosingDifferent])),
+            rule("pricing", None, Some("USD"), None),
+        ])
+        .unwrap();
+        let mut diffs = vec![
+            diff("/trade/price", DiffType::ContentDifferent, "10", "USD 11"),
+            diff("/trade/party", DiffType::ContentDifferent, "LEI-1", "LEI-2"),
+            diff("/trade/note", DiffType::SelfClosingDifferent, "<note/>", "<note></note>"),
+            diff("/trade/book", DiffType::ContentDifferent, "FX", "EQ"),
+        ];
+        let counts = classifier.classify(&mut diffs);
+
+        let tags: Vec<_> = diffs.iter().map(|diff| diff.tags.join(",")).collect();
+        assert_eq!(tags, ["pricing", "regulatory", "cosmetic", ""]);
+        assert_eq!(counts, BTreeMap::from([("cosmetic".to_string(), 1), ("pricing".to_string(), 1), ("regulatory".to_string(), 1)]));
+        assert!(DiffClassifier::compile(&[rule("bad", Some("("), None, None)]).is_err());
+        assert!(DiffClassifier::compile(&[rule(" ", None, None, None)]).is_err());
+    }
+}
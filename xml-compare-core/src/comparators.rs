@@ -0,0 +1,27 @@
+/// A domain rule for when two values are equal, such as prices equal after currency rounding.
+/// Comparators are registered on the `XmlComparisonService` under a name, and the
+/// `value_comparators` option says which paths each one decides.
+pub trait ValueComparator: Send + Sync {
+    /// Called only for values that differ as written. `path` is the element path, with `/@name`
+    /// appended for attributes.
+    fn compare(&self, path: &str, expected: &str, actual: &str) -> ValueVerdict;
+}
+
+/// What a comparator decided about two values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueVerdict {
+    Equal,
+    /// Reported with this message instead of the generic one
+    Different(String),
+    /// Left to the other value rules, and to plain string comparison
+    Undecided,
+}
+
+impl<F> ValueComparator for F
+where
+    F: Fn(&str, &str, &str) -> ValueVerdict + Send + Sync,
+{
+    fn compare(&self, path: &str, expected: &str, actual: &str) -> ValueVerdict {
+        self(path, expected, actual)
+    }
+}
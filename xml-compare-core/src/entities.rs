@@ -0,0 +1,154 @@
+use crate::{CompareError, CompareResult};
+use quick_xml::escape::unescape_with;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+// Limits that keep entity expansion safe from "billion laughs" style documents
+pub const MAX_ENTITY_DEPTH: usize = 8;
+pub const MAX_ENTITY_EXPANSION: usize = 1024 * 1024;
+
+/// General entities declared in a document's internal DTD subset, with their replacement text
+/// fully expanded. External (`SYSTEM`/`PUBLIC`) and parameter entities are never resolved.
+#[derive(Debug, Default)]
+pub struct EntityResolver {
+    entities: HashMap<String, String>,
+    // Bytes of replacement text produced so far for the document
+    expanded: Cell<usize>,
+}
+
+impl EntityResolver {
+    pub fn from_doctype(doctype: &str) -> CompareResult<Self> {
+        let declarations = parse_entity_declarations(doctype);
+        let mut entities = HashMap::new();
+        for name in declarations.keys() {
+            expand_entity(name, &declarations, &mut entities, 0)?;
+        }
+        Ok(Self { entities, expanded: Cell::new(0) })
+    }
+
+    /// Replaces entity and character references in `raw`. References that cannot be resolved
+    /// are kept as written; exceeding the expansion budget is an error.
+    pub fn unescape<'a>(&self, raw: &'a str) -> CompareResult<Cow<'a, str>> {
+        Ok(self.expand(raw)?.unwrap_or(Cow::Borrowed(raw)))
+    }
+
+    /// Like [`EntityResolver::unescape`], but an unresolvable reference is a parse error
+    pub fn unescape_strict<'a>(&self, raw: &'a str) -> CompareResult<Cow<'a, str>> {
+        self.expand(raw)?
+            .ok_or_else(|| CompareError::XmlParseError(format!("Unresolvable entity reference in '{}'", raw)))
+    }
+
+    fn expand<'a>(&self, raw: &'a str) -> CompareResult<Option<Cow<'a, str>>> {
+        let mut exceeded = false;
+        let result = unescape_with(raw, |name| {
+            let value = self.entities.get(name)?;
+            let total = self.expanded.get() + value.len();
+            if total > MAX_ENTITY_EXPANSION {
+                exceeded = true;
+                return None;
+            }
+            self.expanded.set(total);
+            Some(value.as_str())
+        });
+        if exceeded {
+            return Err(expansion_limit_error());
+        }
+        Ok(result.ok())
+    }
+}
+
+fn expansion_limit_error() -> CompareError {
+    CompareError::ValidationError(format!("Entity expansion exceeds the limit of {} bytes", MAX_ENTITY_EXPANSION))
+}
+
+fn expand_entity(
+    name: &str,
+    declarations: &HashMap<String, String>,
+    entities: &mut HashMap<String, String>,
+    depth: usize,
+) -> CompareResult<()> {
+    if entities.contains_key(name) {
+        return Ok(());
+    }
+    if depth >= MAX_ENTITY_DEPTH {
+        return Err(CompareError::ValidationError(format!(
+            "Entity '{}' exceeds the maximum nesting depth of {}",
+            name, MAX_ENTITY_DEPTH
+        )));
+    }
+
+    let raw = &declarations[name];
+    let references = raw.split('&').skip(1).filter_map(|rest| rest.split_once(';').map(|(reference, _)| reference));
+    // Sized before expanding so an oversized value is never built
+    let mut size = raw.len();
+    for reference in references {
+        if declarations.contains_key(reference) {
+            expand_entity(reference, declarations, entities, depth + 1)?;
+            size += entities[reference].len();
+        }
+    }
+    if size > MAX_ENTITY_EXPANSION {
+        return Err(expansion_limit_error());
+    }
+
+    let value = unescape_with(raw, |reference| entities.get(reference).map(String::as_str))
+        .map(Cow::into_owned)
+        .unwrap_or_else(|_| raw.clone());
+    entities.insert(name.to_string(), value);
+    Ok(())
+}
+
+// `<!ENTITY name "value">` declarations of the internal subset; the first declaration of a
+// name wins, as in XML. External and parameter entities are skipped.
+fn parse_entity_declarations(doctype: &str) -> HashMap<String, String> {
+    let mut declarations = HashMap::new();
+    for declaration in doctype.split("<!ENTITY").skip(1) {
+        let declaration = declaration.trim_start();
+        if declaration.starts_with('%') {
+            continue;
+        }
+        let Some((name, rest)) = declaration.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        if let Some((value, _)) = rest[1..].split_once(quote) {
+            declarations.entry(name.to_string()).or_insert_with(|| value.to_string());
+        }
+    }
+    declarations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_entities_expand() {
+        let doctype = "root [ <!ENTITY company \"ACME &amp; Co\"> <!ENTITY signature 'Regards, &company;'> <!ENTITY ext SYSTEM \"file:///etc/passwd\"> ]";
+        let resolver = EntityResolver::from_doctype(doctype).unwrap();
+        assert_eq!(resolver.unescape("&signature; &lt;3").unwrap(), "Regards, ACME & Co <3");
+        assert_eq!(resolver.unescape("&ext;").unwrap(), "&ext;");
+    }
+
+    #[test]
+    fn test_expansion_limits() {
+        let recursive = "r [ <!ENTITY a \"&b;\"> <!ENTITY b \"&a;\"> ]";
+        assert!(EntityResolver::from_doctype(recursive).is_err());
+
+        let mut laughs = String::from("r [ <!ENTITY l0 \"lol\">");
+        for i in 1..10 {
+            let refs = format!("&l{};", i - 1).repeat(10);
+            laughs.push_str(&format!(" <!ENTITY l{} \"{}\">", i, refs));
+        }
+        laughs.push_str(" ]");
+        assert!(EntityResolver::from_doctype(&laughs).is_err());
+
+        let resolver = EntityResolver::from_doctype("r [ <!ENTITY big \"xxxxxxxxxxxxxxxx\"> ]").unwrap();
+        let raw = "&big;".repeat(MAX_ENTITY_EXPANSION / 16 + 1);
+        assert!(resolver.unescape(&raw).is_err());
+    }
+}
@@ -0,0 +1,13 @@
+use thiserror::Error;
+
+/// Why a comparison could not run: a document failed to parse, or an option is invalid
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum CompareError {
+    #[error("XML parsing error: {0}")]
+    XmlParseError(String),
+
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+}
+
+pub type CompareResult<T> = Result<T, CompareError>;
@@ -0,0 +1,133 @@
+use crate::{CompareError, CompareResult};
+use crate::canonicalization::canonicalize;
+use diffy::DiffOptions;
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+const INDENT: &str = "  ";
+
+/// Re-indents a document with one element per line. Whitespace-only text is dropped and text is
+/// kept on the line of its element when the element has no children; markup is otherwise
+/// written as it appears in the input.
+pub fn pretty_print(xml: &str) -> CompareResult<String> {
+    reformat(xml, Some(INDENT))
+}
+
+/// `pretty_print` indenting each level by `indent` spaces
+pub fn pretty_print_with_indent(xml: &str, indent: usize) -> CompareResult<String> {
+    reformat(xml, Some(&" ".repeat(indent)))
+}
+
+/// Drops whitespace between markup and writes the document on a single line
+pub fn minify(xml: &str) -> CompareResult<String> {
+    reformat(xml, None)
+}
+
+// Without an indent, nothing is written between markup
+fn reformat(xml: &str, indent: Option<&str>) -> CompareResult<String> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut output = String::with_capacity(xml.len());
+    let mut depth = 0;
+    // True while the last open element has had no child element yet, so text and the end tag
+    // stay on its line
+    let mut inline = false;
+    let mut buf = Vec::new();
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|e| CompareError::XmlParseError(e.to_string()))?;
+        let markup = match &event {
+            Event::Start(e) => format!("<{}>", String::from_utf8_lossy(e)),
+            Event::Empty(e) => format!("<{}/>", String::from_utf8_lossy(e)),
+            Event::End(e) => format!("</{}>", String::from_utf8_lossy(e.name().as_ref())),
+            Event::Text(e) => String::from_utf8_lossy(e).into_owned(),
+            Event::CData(e) => format!("<![CDATA[{}]]>", String::from_utf8_lossy(e)),
+            Event::Comment(e) => format!("<!--{}-->", String::from_utf8_lossy(e)),
+            Event::PI(e) => format!("<?{}?>", String::from_utf8_lossy(e)),
+            Event::Decl(e) => format!("<?{}?>", String::from_utf8_lossy(e)),
+            Event::DocType(e) => format!("<!DOCTYPE {}>", String::from_utf8_lossy(e)),
+            Event::Eof => break,
+        };
+
+        match event {
+            Event::Text(_) | Event::CData(_) if inline => output.push_str(&markup),
+            Event::End(_) => {
+                depth -= 1;
+                if !inline {
+                    new_line(&mut output, indent, depth);
+                }
+                output.push_str(&markup);
+                inline = false;
+            }
+            Event::Start(_) => {
+                new_line(&mut output, indent, depth);
+                output.push_str(&markup);
+                depth += 1;
+                inline = true;
+            }
+            _ => {
+                new_line(&mut output, indent, depth);
+                output.push_str(&markup);
+                inline = false;
+            }
+        }
+        buf.clear();
+    }
+
+    if indent.is_some() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Unified line diff between the canonical, pretty-printed forms of two documents
+pub fn unified_diff(xml1: &str, xml2: &str) -> CompareResult<String> {
+    let text1 = pretty_print(&canonicalize(xml1)?)?;
+    let text2 = pretty_print(&canonicalize(xml2)?)?;
+    let patch = DiffOptions::new()
+        .set_original_filename("xml1")
+        .set_modified_filename("xml2")
+        .create_patch(&text1, &text2);
+    Ok(patch.to_string())
+}
+
+fn new_line(output: &mut String, indent: Option<&str>, depth: usize) {
+    let Some(indent) = indent else { return };
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output.push_str(&indent.repeat(depth));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pretty_print() {
+        let xml = "<?xml version=\"1.0\"?><root a=\"1\"><item>x &amp; y</item><empty/>\n  <list><i>1</i></list></root>";
+        assert_eq!(
+            pretty_print(xml).unwrap(),
+            "<?xml version=\"1.0\"?>\n<root a=\"1\">\n  <item>x &amp; y</item>\n  <empty/>\n  <list>\n    <i>1</i>\n  </list>\n</root>\n"
+        );
+    }
+
+    #[test]
+    fn test_indent_and_minify() {
+        let xml = "<root>\n  <list>\n    <i> 1 </i>\n  </list>\n  <!-- note -->\n</root>";
+        assert_eq!(pretty_print_with_indent(xml, 4).unwrap(), "<root>\n    <list>\n        <i>1</i>\n    </list>\n    <!-- note -->\n</root>\n");
+        assert_eq!(minify(xml).unwrap(), "<root><list><i>1</i></list><!-- note --></root>");
+    }
+
+    #[test]
+    fn test_unified_diff() {
+        let diff = unified_diff("<root><a>1</a><b/></root>", "<root b=\"x\"><a>2</a><b/></root>").unwrap();
+        assert_eq!(
+            diff,
+            "--- xml1\n+++ xml2\n@@ -1,4 +1,4 @@\n-<root>\n-  <a>1</a>\n+<root b=\"x\">\n+  <a>2</a>\n   <b></b>\n </root>\n"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::XmlComparisonResponse;
+use quick_xml::escape::escape;
+use std::time::Duration;
+
+/// How one JUnit test case ended
+#[derive(Debug, Clone)]
+pub enum JunitOutcome<'a> {
+    /// Passes when the documents match and fails with the diffs otherwise
+    Compared(&'a XmlComparisonResponse),
+    /// Fails without a comparison, e.g. a file that only one side has
+    Failed(String),
+    /// The comparison could not run
+    Error(String),
+}
+
+/// A comparison, or a batch item, reported as a test case
+#[derive(Debug, Clone)]
+pub struct JunitCase<'a> {
+    pub name: String,
+    pub outcome: JunitOutcome<'a>,
+    pub time: Option<Duration>,
+}
+
+/// Renders a JUnit XML report with one test suite, in the shape Jenkins and GitLab read: a
+/// comparison that differs is a `<failure>` listing its diffs, one that could not run an `<error>`.
+pub fn render_junit(suite: &str, cases: &[JunitCase]) -> String {
+    let failures = cases
+        .iter()
+        .filter(|case| match &case.outcome {
+            JunitOutcome::Compared(result) => !result.matched,
+            JunitOutcome::Failed(_) => true,
+            JunitOutcome::Error(_) => false,
+        })
+        .count();
+    let errors = cases.iter().filter(|case| matches!(case.outcome, JunitOutcome::Error(_))).count();
+    let time = cases.iter().filter_map(|case| case.time).reduce(|total, time| total + time);
+    let suite = escape(suite);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let counts = format!("tests=\"{}\" failures=\"{}\" errors=\"{}\"{}", cases.len(), failures, errors, time_attribute(time));
+    xml.push_str(&format!("<testsuites name=\"xml-compare\" {}>\n", counts));
+    xml.push_str(&format!("  <testsuite name=\"{}\" {} skipped=\"0\">\n", suite, counts));
+    for case in cases {
+        xml.push_str(&format!(
+            "    <testcase classname=\"{}\" name=\"{}\"{}",
+            suite,
+            escape(&case.name),
+            time_attribute(case.time)
+        ));
+        match &case.outcome {
+            JunitOutcome::Compared(result) if result.matched => xml.push_str("/>\n"),
+            JunitOutcome::Compared(result) => {
+                let message = format!(
+                    "{} diff(s), {} of {} elements matched",
+                    result.diffs.len(),
+                    result.matched_elements,
+                    result.total_elements
+                );
+                let kind = result.diffs.first().map(|diff| format!("{:?}", diff.diff_type)).unwrap_or_default();
+                xml.push_str(&format!(
+                    ">\n      <failure message=\"{}\" type=\"{}\">{}</failure>\n    </testcase>\n",
+                    escape(&message),
+                    kind,
+                    escape(&failure_details(result))
+                ));
+            }
+            JunitOutcome::Failed(message) => xml.push_str(&format!(
+                ">\n      <failure message=\"{}\"/>\n    </testcase>\n",
+                escape(message)
+            )),
+            JunitOutcome::Error(message) => xml.push_str(&format!(
+                ">\n      <error message=\"{}\"/>\n    </testcase>\n",
+                escape(message)
+            )),
+        }
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+// One line per diff, as CI report pages show the body like a stack trace
+fn failure_details(result: &XmlComparisonResponse) -> String {
+    let mut details = String::new();
+    for diff in &result.diffs {
+        details.push_str(&format!("{}: {}", diff.path, diff.message));
+        match (&diff.expected, &diff.actual) {
+            (Some(expected), Some(actual)) => details.push_str(&format!(" (expected {:?}, actual {:?})", expected, actual)),
+            (Some(expected), None) => details.push_str(&format!(" (expected {:?})", expected)),
+            (None, Some(actual)) => details.push_str(&format!(" (actual {:?})", actual)),
+            (None, None) => {}
+        }
+        details.push('\n');
+    }
+    if result.truncated {
+        details.push_str("Comparison stopped at max_diffs\n");
+    }
+    details
+}
+
+fn time_attribute(time: Option<Duration>) -> String {
+    time.map(|time| format!(" time=\"{:.3}\"", time.as_secs_f64())).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComparisonOptions, XmlComparisonService};
+
+    #[test]
+    fn test_render_junit() {
+        let service = XmlComparisonService::new();
+        let options = ComparisonOptions::default();
+        let matched = service.compare_with_options("<a>1</a>", "<a>1</a>", &options).unwrap();
+        let differs = service.compare_with_options("<a>1 &amp; 2</a>", "<a>3</a>", &options).unwrap();
+        let cases = [
+            JunitCase { name: "same".to_string(), outcome: JunitOutcome::Compared(&matched), time: Some(Duration::from_millis(4)) },
+            JunitCase { name: "a<b".to_string(), outcome: JunitOutcome::Compared(&differs), time: Some(Duration::from_millis(6)) },
+            JunitCase { name: "only_left.xml".to_string(), outcome: JunitOutcome::Failed("Only in left".to_string()), time: None },
+            JunitCase { name: "broken".to_string(), outcome: JunitOutcome::Error("Invalid XML".to_string()), time: None },
+        ];
+        let xml = render_junit("job-1", &cases);
+
+        assert!(xml.contains("<testsuite name=\"job-1\" tests=\"4\" failures=\"2\" errors=\"1\" time=\"0.010\" skipped=\"0\">"));
+        assert!(xml.contains("<testcase classname=\"job-1\" name=\"same\" time=\"0.004\"/>"));
+        assert!(xml.contains("<testcase classname=\"job-1\" name=\"a&lt;b\" time=\"0.006\">"));
+        assert!(xml.contains(
+            "<failure message=\"1 diff(s), 0 of 1 elements matched\" type=\"ContentDifferent\">/a: "
+        ));
+        assert!(xml.contains("(expected &quot;1 &amp; 2&quot;, actual &quot;3&quot;)\n</failure>"));
+        assert!(xml.contains("<failure message=\"Only in left\"/>"));
+        assert!(xml.contains("<error message=\"Invalid XML\"/>"));
+        let mut reader = quick_xml::Reader::from_str(&xml);
+        while !matches!(reader.read_event().unwrap(), quick_xml::events::Event::Eof) {}
+    }
+}
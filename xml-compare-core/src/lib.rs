@@ -0,0 +1,39 @@
+// XML comparison engine: the element model, comparison options, diff types and the services
+// that parse, normalize and compare documents
+
+pub mod error;
+pub mod models;
+pub mod xml_comparison;
+pub mod canonicalization;
+pub mod classification;
+pub mod comparators;
+pub mod entities;
+pub mod formatting;
+pub mod junit;
+pub mod markdown;
+pub mod normalization;
+pub mod patch;
+pub mod report;
+pub mod sarif;
+pub mod scripts;
+pub mod transforms;
+pub mod xpath;
+mod parse_cache;
+
+pub use error::*;
+pub use models::*;
+pub use xml_comparison::*;
+pub use canonicalization::*;
+pub use classification::*;
+pub use comparators::*;
+pub use entities::*;
+pub use formatting::*;
+pub use junit::*;
+pub use markdown::*;
+pub use normalization::*;
+pub use patch::*;
+pub use report::*;
+pub use sarif::*;
+pub use scripts::*;
+pub use transforms::*;
+pub use xpath::*;
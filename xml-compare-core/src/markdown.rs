@@ -0,0 +1,140 @@
+use crate::{XmlComparisonResponse, XmlDiff};
+
+// Long tables are cut, as pull-request comments have a size limit
+const MAX_ROWS: usize = 100;
+
+/// Renders one comparison as Markdown for pull-request comments: the verdict, match ratio and
+/// the diffs in a table grouped by path
+pub fn render_markdown(title: &str, result: &XmlComparisonResponse) -> String {
+    let verdict = if result.matched { "✅ Documents match" } else { "❌ Documents differ" };
+    let mut markdown = format!("## {}: {}\n\n", title, verdict);
+    push_details(&mut markdown, result);
+    markdown
+}
+
+/// Renders a batch as Markdown: a table of every comparison, then the diffs of those that differ.
+/// Items are named, and hold the result or the reason they could not be compared.
+pub fn render_markdown_batch(title: &str, items: &[(String, Result<&XmlComparisonResponse, String>)]) -> String {
+    let differ = items.iter().filter(|(_, item)| item.as_ref().is_ok_and(|result| !result.matched)).count();
+    let failed = items.iter().filter(|(_, item)| item.is_err()).count();
+    let verdict = match (differ, failed) {
+        (0, 0) => format!("✅ All {} match", items.len()),
+        (differ, 0) => format!("❌ {} of {} differ", differ, items.len()),
+        (differ, failed) => format!("❌ {} of {} differ, {} failed", differ, items.len(), failed),
+    };
+    let mut markdown = format!("## {}: {}\n\n| Comparison | Verdict | Match ratio | Diffs |\n|---|---|---|---|\n", title, verdict);
+    for (name, item) in items {
+        match item {
+            Ok(result) => markdown.push_str(&format!(
+                "| {} | {} | {:.1}% | {} |\n",
+                cell(name),
+                if result.matched { "✅ match" } else { "❌ differ" },
+                result.match_ratio * 100.0,
+                result.diffs.len()
+            )),
+            Err(error) => markdown.push_str(&format!("| {} | ⚠️ error: {} | | |\n", cell(name), cell(error))),
+        }
+    }
+    for (name, item) in items {
+        if let Ok(result) = item
+            && !result.matched
+        {
+            markdown.push_str(&format!("\n### {}\n\n", name.replace('\n', " ")));
+            push_details(&mut markdown, result);
+        }
+    }
+    markdown
+}
+
+fn push_details(markdown: &mut String, result: &XmlComparisonResponse) {
+    markdown.push_str(&format!(
+        "**Match ratio:** {:.1}% · **Elements matched:** {} of {} · **Diffs:** {}{}\n",
+        result.match_ratio * 100.0,
+        result.matched_elements,
+        result.total_elements,
+        result.diffs.len(),
+        if result.truncated { " (stopped at `max_diffs`)" } else { "" }
+    ));
+    if result.diffs.is_empty() {
+        return;
+    }
+
+    // Sorting keeps each path's diffs together, in the order they were found
+    let mut diffs: Vec<&XmlDiff> = result.diffs.iter().collect();
+    diffs.sort_by(|a, b| a.path.cmp(&b.path));
+    markdown.push_str("\n| Path | Type | Expected | Actual | Message |\n|---|---|---|---|---|\n");
+    let mut previous = None;
+    for diff in diffs.iter().take(MAX_ROWS) {
+        let path = if previous == Some(&diff.path) { String::new() } else { code(&diff.path) };
+        previous = Some(&diff.path);
+        markdown.push_str(&format!(
+            "| {} | {:?} | {} | {} | {} |\n",
+            path,
+            diff.diff_type,
+            diff.expected.as_deref().map(code).unwrap_or_default(),
+            diff.actual.as_deref().map(code).unwrap_or_default(),
+            cell(&diff.message)
+        ));
+    }
+    if diffs.len() > MAX_ROWS {
+        markdown.push_str(&format!("\n…and {} more diffs\n", diffs.len() - MAX_ROWS));
+    }
+}
+
+// Table cells hold one line and no unescaped pipes
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+}
+
+// A code span long enough to hold the backticks inside it
+fn code(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    let mut longest = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    let fence = "`".repeat(longest + 1);
+    let padding = if longest > 0 { " " } else { "" };
+    format!("{}{}{}{}{}", fence, padding, cell(text), padding, fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComparisonOptions, XmlComparisonService};
+
+    #[test]
+    fn test_render_markdown() {
+        let service = XmlComparisonService::new();
+        let options = ComparisonOptions::default();
+        let differs = service
+            .compare_with_options(r#"<t><p a="1">x|y</p><q>`1`</q></t>"#, r#"<t><p a="2">z</p><q>2</q></t>"#, &options)
+            .unwrap();
+        let markdown = render_markdown("XML comparison", &differs);
+
+        assert!(markdown.starts_with("## XML comparison: ❌ Documents differ\n\n**Match ratio:** "));
+        assert!(markdown.contains("|---|\n| `/t/p` | ContentDifferent | `x\\|y` | `z` | Content differs |\n"));
+        // Diffs of the same element share its path cell
+        assert!(markdown.contains("\n|  | AttributeDifferent | `a=1` | `a=2` | Attribute 'a' differs |\n"));
+        assert!(markdown.contains("| `/t/q` | ContentDifferent | `` `1` `` | `2` | "));
+
+        let matched = service.compare_with_options("<a/>", "<a/>", &options).unwrap();
+        assert!(render_markdown("XML comparison", &matched).starts_with("## XML comparison: ✅ Documents match\n"));
+
+        let items = [
+            ("trade-1".to_string(), Ok(&matched)),
+            ("trade-2".to_string(), Ok(&differs)),
+            ("trade-3".to_string(), Err("Invalid XML".to_string())),
+        ];
+        let markdown = render_markdown_batch("nightly", &items);
+        assert!(markdown.starts_with("## nightly: ❌ 1 of 3 differ, 1 failed\n"));
+        assert!(markdown.contains("| trade-1 | ✅ match | 100.0% | 0 |\n"));
+        assert!(markdown.contains("| trade-3 | ⚠️ error: Invalid XML | | |\n"));
+        assert!(markdown.contains("\n### trade-2\n\n**Match ratio:**"));
+        assert!(!markdown.contains("### trade-1"));
+    }
+}
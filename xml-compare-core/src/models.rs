@@ -0,0 +1,574 @@
+use serde::ser::{SerializeMap, SerializeStruct};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+
+/// Everything that controls how two documents are compared and what the response contains
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ComparisonOptions {
+    // Normalization
+    pub namespace_mode: Option<NamespaceMode>,
+    pub normalize_prefixes: Option<bool>,
+    pub canonicalize: Option<bool>,
+    pub expand_entities: Option<bool>,
+    pub strict_cdata: Option<bool>,
+    pub strict_self_closing: Option<bool>,
+    pub xsi_nil: Option<NilMode>,
+    // Ordering and sibling matching
+    pub ignore_element_order: Option<bool>,
+    pub match_keys: Option<HashMap<String, String>>,
+    pub child_counts: Option<ChildCountMode>,
+    pub detect_duplicates: Option<bool>,
+    // Tolerances
+    pub date_paths: Option<Vec<String>>,
+    pub date_formats: Option<Vec<String>>,
+    /// Values selected here compare as booleans/numbers: `true` = `TRUE` = `1`, `0.50` = `.5`
+    pub literal_paths: Option<Vec<String>>,
+    pub transforms: Option<Vec<ValueTransform>>,
+    /// Paths each registered value comparator decides, by comparator name
+    pub value_comparators: Option<HashMap<String, Vec<String>>>,
+    /// Rhai scripts deciding the values their paths select
+    pub scripts: Option<Vec<ScriptRule>>,
+    /// Elements whose content is base64: the decoded payloads are compared recursively when both
+    /// are XML, and by SHA-256 digest otherwise
+    pub base64_paths: Option<Vec<String>>,
+    pub similarity_threshold: Option<f64>,
+    pub similarity_algorithm: Option<SimilarityAlgorithm>,
+    // Ignore rules and scope
+    pub ignore_paths: Option<Vec<String>>,
+    pub ignore_properties: Option<Vec<String>>,
+    /// Compare only the elements this path selects in xml1 (e.g. `/Envelope/Body/*`)
+    pub compare_root_path1: Option<String>,
+    /// Compare only the elements this path selects in xml2
+    pub compare_root_path2: Option<String>,
+    /// Compare only the Body payloads of SOAP 1.1/1.2 envelopes; documents that are not envelopes
+    /// are compared whole
+    pub soap: Option<bool>,
+    /// With `soap`, also compare the envelope Headers
+    pub soap_headers: Option<bool>,
+    // Output controls
+    /// `verdict` only decides whether the documents match, stopping at the first difference
+    pub mode: Option<ComparisonMode>,
+    pub max_diffs: Option<usize>,
+    pub generate_patch: Option<bool>,
+    pub output_format: Option<OutputFormat>,
+    /// `false` leaves `diffs` empty; the verdict, ratio and counts are still computed
+    pub include_diffs: Option<bool>,
+    /// Fields each diff keeps in the response, e.g. `["path", "diff_type"]`
+    pub diff_fields: Option<Vec<DiffField>>,
+    /// Matched results carry only the verdict, ratio and counts: no diffs, warnings, patch or
+    /// unified diff
+    pub exclude_matched_details: Option<bool>,
+    /// Count the instances of each element name that matched and differed, in `element_stats`
+    pub element_stats: Option<bool>,
+    /// Count `element_stats` per pattern instead of per element name, e.g. `["/trades/trade"]`
+    pub element_stats_paths: Option<Vec<String>>,
+    /// Rules tagging each diff for triage, e.g. `pricing` for diffs under a price
+    pub classifications: Option<Vec<ClassificationRule>>,
+}
+
+impl ComparisonOptions {
+    /// Fills every field left unset from `fallback`
+    pub fn or(self, fallback: ComparisonOptions) -> ComparisonOptions {
+        ComparisonOptions {
+            namespace_mode: self.namespace_mode.or(fallback.namespace_mode),
+            normalize_prefixes: self.normalize_prefixes.or(fallback.normalize_prefixes),
+            canonicalize: self.canonicalize.or(fallback.canonicalize),
+            expand_entities: self.expand_entities.or(fallback.expand_entities),
+            strict_cdata: self.strict_cdata.or(fallback.strict_cdata),
+            strict_self_closing: self.strict_self_closing.or(fallback.strict_self_closing),
+            xsi_nil: self.xsi_nil.or(fallback.xsi_nil),
+            ignore_element_order: self.ignore_element_order.or(fallback.ignore_element_order),
+            match_keys: self.match_keys.or(fallback.match_keys),
+            child_counts: self.child_counts.or(fallback.child_counts),
+            detect_duplicates: self.detect_duplicates.or(fallback.detect_duplicates),
+            date_paths: self.date_paths.or(fallback.date_paths),
+            date_formats: self.date_formats.or(fallback.date_formats),
+            literal_paths: self.literal_paths.or(fallback.literal_paths),
+            transforms: self.transforms.or(fallback.transforms),
+            value_comparators: self.value_comparators.or(fallback.value_comparators),
+            scripts: self.scripts.or(fallback.scripts),
+            base64_paths: self.base64_paths.or(fallback.base64_paths),
+            similarity_threshold: self.similarity_threshold.or(fallback.similarity_threshold),
+            similarity_algorithm: self.similarity_algorithm.or(fallback.similarity_algorithm),
+            ignore_paths: self.ignore_paths.or(fallback.ignore_paths),
+            ignore_properties: self.ignore_properties.or(fallback.ignore_properties),
+            compare_root_path1: self.compare_root_path1.or(fallback.compare_root_path1),
+            compare_root_path2: self.compare_root_path2.or(fallback.compare_root_path2),
+            soap: self.soap.or(fallback.soap),
+            soap_headers: self.soap_headers.or(fallback.soap_headers),
+            mode: self.mode.or(fallback.mode),
+            max_diffs: self.max_diffs.or(fallback.max_diffs),
+            generate_patch: self.generate_patch.or(fallback.generate_patch),
+            output_format: self.output_format.or(fallback.output_format),
+            include_diffs: self.include_diffs.or(fallback.include_diffs),
+            diff_fields: self.diff_fields.or(fallback.diff_fields),
+            exclude_matched_details: self.exclude_matched_details.or(fallback.exclude_matched_details),
+            element_stats: self.element_stats.or(fallback.element_stats),
+            element_stats_paths: self.element_stats_paths.or(fallback.element_stats_paths),
+            classifications: self.classifications.or(fallback.classifications),
+        }
+    }
+
+    /// These options without the response-shaping ones, for callers that shape the result
+    /// themselves
+    pub fn without_shaping(&self) -> ComparisonOptions {
+        ComparisonOptions {
+            include_diffs: None,
+            diff_fields: None,
+            exclude_matched_details: None,
+            ..self.clone()
+        }
+    }
+}
+
+/// How element and attribute names are matched when documents use XML namespaces
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum NamespaceMode {
+    /// Compare by (namespace URI, local name); prefixes are ignored
+    #[default]
+    Uri,
+    /// Compare by (namespace URI, local name) and also require matching prefixes
+    Strict,
+    /// Compare qualified names exactly as written, without namespace resolution
+    Qualified,
+}
+
+/// What an element marked `xsi:nil="true"` is equal to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum NilMode {
+    /// An empty element of the same name
+    Empty,
+    /// No element at all
+    Absent,
+}
+
+/// Reporting of parents whose children of one name differ in number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ChildCountMode {
+    /// Add a `ChildCountDifferent` diff next to the missing/extra element diffs
+    Report,
+    /// Report `ChildCountDifferent` instead of the missing/extra element diffs for that name
+    Collapse,
+}
+
+/// A normalization applied to the values selected by `paths` (same syntax as `ignore_paths`;
+/// use an attribute step such as `//trade/@currency` for attributes) before they are compared
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ValueTransform {
+    pub paths: Vec<String>,
+    #[serde(flatten)]
+    pub rule: TransformRule,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "transform", rename_all = "snake_case")]
+pub enum TransformRule {
+    /// Lower-case the value
+    Lowercase,
+    /// Remove currency symbols such as `$`, `€` or `£` and surrounding whitespace
+    StripCurrency,
+    /// Round a numeric value to `decimals` decimal places; other values are left unchanged
+    Round { decimals: u32 },
+    /// Replace every match of a regular expression; `replacement` may use `$1`-style groups
+    RegexReplace { pattern: String, replacement: String },
+}
+
+/// A Rhai script deciding the values selected by `paths` (same syntax as `transforms`). It sees
+/// `path`, `expected`, `actual`, and the owning elements' `attributes` and `actual_attributes`,
+/// and returns `true`/`false`, a difference message, a map such as
+/// `#{equal: false, severity: "info", message: "..."}`, or `()` to leave the value to other rules.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ScriptRule {
+    pub paths: Vec<String>,
+    pub script: String,
+}
+
+/// Tags the diffs matching every condition set: `path` and `content` are regular expressions over
+/// the diff path and over its expected value, actual value or message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ClassificationRule {
+    pub tag: String,
+    pub path: Option<String>,
+    pub content: Option<String>,
+    pub diff_types: Option<Vec<DiffType>>,
+}
+
+/// How text content is scored when `similarity_threshold` is set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityAlgorithm {
+    /// Normalized Levenshtein distance over characters
+    #[default]
+    Levenshtein,
+    /// Overlap of whitespace-separated words, ignoring case and word order
+    Token,
+}
+
+/// How much of the comparison result is worked out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonMode {
+    /// Diffs, counts and every rendering the options ask for
+    #[default]
+    Full,
+    /// Only `matched`, with a `match_ratio` of 1 or 0: the walk builds no diffs and stops at the
+    /// first difference. Options that need the diffs are rejected.
+    Verdict,
+}
+
+/// Extra renderings of the comparison returned next to the structured diffs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// Structured diffs only
+    #[default]
+    Structured,
+    /// Also return a unified text diff of the canonical, pretty-printed documents
+    Unified,
+    /// Answer with a JUnit XML report instead of JSON, one test case per comparison
+    Junit,
+    /// Answer with a SARIF log instead of JSON, one finding per diff
+    Sarif,
+    /// Answer with a Markdown summary for pull-request comments instead of JSON
+    Markdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DiffField {
+    Path,
+    DiffType,
+    Expected,
+    Actual,
+    Message,
+    Similarity,
+    Severity,
+    Tags,
+    Node,
+}
+
+impl DiffField {
+    const ALL: [DiffField; 9] = [
+        DiffField::Path,
+        DiffField::DiffType,
+        DiffField::Expected,
+        DiffField::Actual,
+        DiffField::Message,
+        DiffField::Similarity,
+        DiffField::Severity,
+        DiffField::Tags,
+        DiffField::Node,
+    ];
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct XmlComparisonResponse {
+    /// The `id` the client gave this comparison in a batch request
+    #[serde(default)]
+    pub id: Option<String>,
+    pub matched: bool,
+    pub match_ratio: f64,
+    pub diffs: Vec<XmlDiff>,
+    pub total_elements: usize,
+    pub matched_elements: usize,
+    /// True when comparison stopped early because `max_diffs` was reached
+    #[serde(default)]
+    pub truncated: bool,
+    /// RFC 5261 XML patch turning xml1 into xml2, when `generate_patch` is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub patch: Option<String>,
+    /// Unified diff of the canonical, pretty-printed documents, when `output_format` is `unified`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unified_diff: Option<String>,
+    /// Findings about the input documents themselves, such as duplicated siblings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<DocumentWarning>,
+    /// Where downloaded documents came from, in `url1`, `url2` order; empty for inline documents
+    #[serde(default)]
+    pub sources: Vec<DocumentSource>,
+    /// Matched and differing instances per element name or pattern, when `element_stats` or
+    /// `element_stats_paths` is set; the groups with the most differences come first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub element_stats: Vec<ElementStats>,
+    /// How many diffs carry each `classifications` tag, counted before `include_diffs` or
+    /// `diff_fields` trim the diffs
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tag_counts: BTreeMap<String, usize>,
+    /// Size and shape of xml1 and xml2, for checking that the intended documents were compared;
+    /// absent in verdict mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_stats: Option<DocumentPairStats>,
+    /// The `diff_fields` option, applied when the response is serialized
+    #[serde(skip)]
+    pub diff_fields: Option<Vec<DiffField>>,
+    /// The `output_format` option, for servers that answer `junit` with a report
+    #[serde(skip)]
+    pub output_format: Option<OutputFormat>,
+}
+
+/// Counts over each of the two input documents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentPairStats {
+    pub xml1: DocumentStats,
+    pub xml2: DocumentStats,
+}
+
+/// Counts over a whole input document, whatever the scoping options select
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentStats {
+    pub elements: usize,
+    /// Attributes other than namespace declarations
+    pub attributes: usize,
+    /// Nesting depth of the deepest element; the root is at depth 1
+    pub max_depth: usize,
+    /// Bytes of text and CDATA content, unescaped and trimmed
+    pub text_bytes: usize,
+}
+
+/// How many instances of an element, or of the elements a pattern selects, matched and differed.
+/// An instance differs when anything in its subtree does.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ElementStats {
+    /// The element name, or the `element_stats_paths` pattern
+    pub group: String,
+    pub matched: usize,
+    pub differed: usize,
+    /// Instances only in xml1, including those of a moved subtree
+    pub missing: usize,
+    /// Instances only in xml2, including those of a moved subtree
+    pub extra: usize,
+}
+
+/// A document fetched from a URL
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentSource {
+    pub url: String,
+    /// Where the document was found after following redirects
+    pub final_url: String,
+    pub cache: SourceCache,
+    /// Size of the document once decompressed
+    #[serde(default)]
+    pub bytes: u64,
+    /// Size as downloaded, when the document was compressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compressed_bytes: Option<u64>,
+    /// Compressions undone, in the order they were applied: `gzip` for a `.gz` file, then any
+    /// `Content-Encoding` (`gzip`, `deflate` or `br`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compression: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum SourceCache {
+    /// The server confirmed the cached copy is current, so the body was not downloaded again
+    Hit,
+    /// The body was downloaded
+    Miss,
+}
+
+impl XmlComparisonResponse {
+    /// Applies the response-shaping options: `exclude_matched_details`, `include_diffs`,
+    /// `diff_fields` and `output_format`
+    pub fn shape(&mut self, options: &ComparisonOptions) {
+        if self.matched && options.exclude_matched_details.unwrap_or(false) {
+            self.diffs.clear();
+            self.warnings.clear();
+            self.patch = None;
+            self.unified_diff = None;
+        }
+        if !options.include_diffs.unwrap_or(true) {
+            self.diffs.clear();
+        }
+        self.diff_fields = options.diff_fields.clone();
+        self.output_format = options.output_format;
+    }
+}
+
+// Written by hand so that `diff_fields` can trim each diff
+impl Serialize for XmlComparisonResponse {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("XmlComparisonResponse", 14)?;
+        match &self.id {
+            Some(id) => state.serialize_field("id", id)?,
+            None => state.skip_field("id")?,
+        }
+        state.serialize_field("matched", &self.matched)?;
+        state.serialize_field("match_ratio", &self.match_ratio)?;
+        state.serialize_field("diffs", &DiffsView(&self.diffs, self.diff_fields.as_deref()))?;
+        state.serialize_field("total_elements", &self.total_elements)?;
+        state.serialize_field("matched_elements", &self.matched_elements)?;
+        state.serialize_field("truncated", &self.truncated)?;
+        match &self.patch {
+            Some(patch) => state.serialize_field("patch", patch)?,
+            None => state.skip_field("patch")?,
+        }
+        match &self.unified_diff {
+            Some(unified_diff) => state.serialize_field("unified_diff", unified_diff)?,
+            None => state.skip_field("unified_diff")?,
+        }
+        if self.warnings.is_empty() {
+            state.skip_field("warnings")?;
+        } else {
+            state.serialize_field("warnings", &self.warnings)?;
+        }
+        if self.sources.is_empty() {
+            state.skip_field("sources")?;
+        } else {
+            state.serialize_field("sources", &self.sources)?;
+        }
+        if self.element_stats.is_empty() {
+            state.skip_field("element_stats")?;
+        } else {
+            state.serialize_field("element_stats", &self.element_stats)?;
+        }
+        if self.tag_counts.is_empty() {
+            state.skip_field("tag_counts")?;
+        } else {
+            state.serialize_field("tag_counts", &self.tag_counts)?;
+        }
+        match &self.document_stats {
+            Some(document_stats) => state.serialize_field("document_stats", document_stats)?,
+            None => state.skip_field("document_stats")?,
+        }
+        state.end()
+    }
+}
+
+/// Serializes diffs with only the given fields, or whole when there is no selection
+pub struct DiffsView<'a>(pub &'a [XmlDiff], pub Option<&'a [DiffField]>);
+
+impl Serialize for DiffsView<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Some(fields) = self.1 else {
+            return self.0.serialize(serializer);
+        };
+        serializer.collect_seq(self.0.iter().map(|diff| SelectedFields(diff, fields)))
+    }
+}
+
+struct SelectedFields<'a>(&'a XmlDiff, &'a [DiffField]);
+
+impl Serialize for SelectedFields<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let diff = self.0;
+        let mut map = serializer.serialize_map(None)?;
+        for field in DiffField::ALL.iter().filter(|field| self.1.contains(field)) {
+            match field {
+                DiffField::Path => map.serialize_entry("path", &diff.path)?,
+                DiffField::DiffType => map.serialize_entry("diff_type", &diff.diff_type)?,
+                DiffField::Expected => map.serialize_entry("expected", &diff.expected)?,
+                DiffField::Actual => map.serialize_entry("actual", &diff.actual)?,
+                DiffField::Message => map.serialize_entry("message", &diff.message)?,
+                DiffField::Similarity => {
+                    if let Some(similarity) = diff.similarity {
+                        map.serialize_entry("similarity", &similarity)?;
+                    }
+                }
+                DiffField::Severity => map.serialize_entry("severity", &diff.severity)?,
+                DiffField::Tags => map.serialize_entry("tags", &diff.tags)?,
+                DiffField::Node => {
+                    if let Some(node) = &diff.node {
+                        map.serialize_entry("node", node)?;
+                    }
+                }
+            }
+        }
+        map.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DocumentWarning {
+    /// `xml1` or `xml2`
+    pub document: String,
+    /// Path of the first occurrence
+    pub path: String,
+    pub occurrences: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct XmlDiff {
+    pub path: String,
+    pub diff_type: DiffType,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similarity: Option<f64>,
+    #[serde(default)]
+    pub severity: DiffSeverity,
+    /// Tags of the `classifications` rules the diff matches
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The missing or extra element, on `ElementMissing` and `ElementExtra` diffs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node: Option<XmlNodeSnapshot>,
+}
+
+/// An element as found in one document, without its descendants
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct XmlNodeSnapshot {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub attributes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Names of the element's children, in document order
+    #[serde(default)]
+    pub children: Vec<String>,
+}
+
+/// `info` marks formatting-level diffs that only strict options report
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum DiffSeverity {
+    #[default]
+    Error,
+    Info,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum DiffType {
+    ElementMissing,
+    ElementExtra,
+    AttributeDifferent,
+    ContentDifferent,
+    StructureDifferent,
+    NamespaceDifferent,
+    CdataDifferent,
+    ElementMoved,
+    ContentSimilar,
+    ElementReordered,
+    SelfClosingDifferent,
+    ChildCountDifferent,
+}
@@ -0,0 +1,170 @@
+use crate::SimilarityAlgorithm;
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+// Formats tried when a request enables date normalization without listing its own
+pub const DEFAULT_DATE_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%:z",
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M:%S%.f%:z",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y%m%dT%H%M%S",
+    "%Y-%m-%d",
+    "%Y%m%d",
+];
+
+// A parsed date value; values only compare equal within the same precision, so a plain
+// date never equals a timestamp
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NormalizedDate {
+    Instant(DateTime<Utc>),
+    Local(NaiveDateTime),
+    Date(NaiveDate),
+}
+
+pub fn parse_date(value: &str, formats: &[String]) -> Option<NormalizedDate> {
+    let value = value.trim();
+    if let Ok(instant) = DateTime::parse_from_rfc3339(value) {
+        return Some(NormalizedDate::Instant(instant.with_timezone(&Utc)));
+    }
+
+    let defaults: Vec<String>;
+    let formats = if formats.is_empty() {
+        defaults = DEFAULT_DATE_FORMATS.iter().map(|f| f.to_string()).collect();
+        &defaults
+    } else {
+        formats
+    };
+
+    formats.iter().find_map(|format| {
+        if let Ok(instant) = DateTime::parse_from_str(value, format) {
+            Some(NormalizedDate::Instant(instant.with_timezone(&Utc)))
+        } else if let Ok(local) = NaiveDateTime::parse_from_str(value, format) {
+            Some(NormalizedDate::Local(local))
+        } else {
+            NaiveDate::parse_from_str(value, format).ok().map(NormalizedDate::Date)
+        }
+    })
+}
+
+pub fn dates_equal(value1: &str, value2: &str, formats: &[String]) -> bool {
+    match (parse_date(value1, formats), parse_date(value2, formats)) {
+        (Some(date1), Some(date2)) => date1 == date2,
+        _ => false,
+    }
+}
+
+// A boolean or numeric value; `1` and `0` double as `true` and `false`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Literal {
+    Boolean(bool),
+    Number(f64),
+}
+
+pub fn parse_literal(value: &str) -> Option<Literal> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("true") {
+        Some(Literal::Boolean(true))
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(Literal::Boolean(false))
+    } else {
+        value.parse::<f64>().ok().filter(|number| number.is_finite()).map(Literal::Number)
+    }
+}
+
+pub fn literals_equal(value1: &str, value2: &str) -> bool {
+    match (parse_literal(value1), parse_literal(value2)) {
+        (Some(Literal::Boolean(flag)), Some(Literal::Number(number)))
+        | (Some(Literal::Number(number)), Some(Literal::Boolean(flag))) => number == if flag { 1.0 } else { 0.0 },
+        (Some(literal1), Some(literal2)) => literal1 == literal2,
+        _ => false,
+    }
+}
+
+// Decodes standard base64, ignoring the line breaks and indentation blobs are usually wrapped with
+pub fn decode_base64(value: &str) -> Option<Vec<u8>> {
+    let compact: String = value.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    general_purpose::STANDARD.decode(compact).ok()
+}
+
+// Short description of a binary payload used in place of its content in diffs
+pub fn payload_digest(bytes: &[u8]) -> String {
+    let hash = Sha256::digest(bytes);
+    let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256:{} ({} bytes)", hex, bytes.len())
+}
+
+// Similarity score between 0.0 (nothing in common) and 1.0 (identical)
+pub fn text_similarity(value1: &str, value2: &str, algorithm: SimilarityAlgorithm) -> f64 {
+    match algorithm {
+        SimilarityAlgorithm::Levenshtein => strsim::normalized_levenshtein(value1, value2),
+        SimilarityAlgorithm::Token => {
+            let tokens = |value: &str| value.split_whitespace().map(str::to_lowercase).collect::<HashSet<_>>();
+            let (tokens1, tokens2) = (tokens(value1), tokens(value2));
+            let union = tokens1.union(&tokens2).count();
+            if union == 0 {
+                return 1.0;
+            }
+            tokens1.intersection(&tokens2).count() as f64 / union as f64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dates_equal_across_formats() {
+        assert!(dates_equal("2025-08-19", "20250819", &[]));
+        assert!(dates_equal("2025-08-19T10:00:00+02:00", "2025-08-19T08:00:00Z", &[]));
+        assert!(dates_equal("2025-08-19T10:00:00.000", "2025-08-19 10:00:00", &[]));
+        assert!(!dates_equal("2025-08-19", "2025-08-20", &[]));
+        assert!(!dates_equal("2025-08-19", "2025-08-19T00:00:00Z", &[]));
+        assert!(!dates_equal("not a date", "not a date", &[]));
+    }
+
+    #[test]
+    fn test_custom_formats() {
+        let formats = vec!["%d/%m/%Y".to_string(), "%Y-%m-%d".to_string()];
+        assert!(dates_equal("19/08/2025", "2025-08-19", &formats));
+
+        let formats = vec!["%d/%m/%Y".to_string()];
+        assert_eq!(parse_date("19/08/2025", &formats), NaiveDate::from_ymd_opt(2025, 8, 19).map(NormalizedDate::Date));
+        assert_eq!(parse_date("2025-08-19", &formats), None);
+    }
+
+    #[test]
+    fn test_literals_equal() {
+        assert!(literals_equal("true", "TRUE"));
+        assert!(literals_equal("true", "1"));
+        assert!(literals_equal(" false ", "0.0"));
+        assert!(literals_equal("0.50", ".5"));
+        assert!(literals_equal("1e3", "1000"));
+        assert!(!literals_equal("true", "2"));
+        assert!(!literals_equal("0.5", "0.51"));
+        assert!(!literals_equal("yes", "true"));
+        assert!(!literals_equal("NaN", "NaN"));
+    }
+
+    #[test]
+    fn test_base64_payloads() {
+        assert_eq!(decode_base64("PGEv\n  Pg=="), Some(b"<a/>".to_vec()));
+        assert_eq!(decode_base64("not base64!"), None);
+        assert_eq!(
+            payload_digest(b"abc"),
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad (3 bytes)"
+        );
+    }
+
+    #[test]
+    fn test_text_similarity() {
+        assert_eq!(text_similarity("abc", "abc", SimilarityAlgorithm::Levenshtein), 1.0);
+        assert_eq!(text_similarity("abcd", "abcx", SimilarityAlgorithm::Levenshtein), 0.75);
+        assert_eq!(text_similarity("red green blue", "Blue red GREEN", SimilarityAlgorithm::Token), 1.0);
+        assert_eq!(text_similarity("red green", "green blue", SimilarityAlgorithm::Token), 1.0 / 3.0);
+    }
+}
@@ -0,0 +1,154 @@
+use crate::NamespaceMode;
+use crate::xml_comparison::{DocumentDigest, XmlElement, estimate_comparison_memory};
+use hashlink::LruCache;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+
+// Documents whose digests are remembered, least recently used dropped first
+const DIGEST_CACHE_CAPACITY: usize = 1024;
+
+/// SHA-256 of a document's text, which identifies it in the caches
+pub(crate) type ContentHash = [u8; 32];
+
+pub(crate) fn content_hash(xml: &str) -> ContentHash {
+    Sha256::digest(xml.as_bytes()).into()
+}
+
+// Digests of recently compared documents, so that a pair seen before is answered without parsing
+pub(crate) struct DigestCache(Mutex<LruCache<ContentHash, DocumentDigest>>);
+
+impl Default for DigestCache {
+    fn default() -> Self {
+        Self(Mutex::new(LruCache::new(DIGEST_CACHE_CAPACITY)))
+    }
+}
+
+impl DigestCache {
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.0.lock().map_or(0, |digests| digests.len())
+    }
+
+    pub(crate) fn get(&self, key: &ContentHash) -> Option<DocumentDigest> {
+        self.0.lock().ok()?.get(key).copied()
+    }
+
+    pub(crate) fn insert(&self, key: ContentHash, digest: DocumentDigest) {
+        if let Ok(mut digests) = self.0.lock() {
+            digests.insert(key, digest);
+        }
+    }
+}
+
+// Parsed documents keyed by their content and the settings they were parsed with, so that a
+// document compared over and over, such as the golden side of a batch, is parsed once. Holds
+// trees of at most `capacity_bytes` of estimated memory, evicting the least recently used first;
+// a zero capacity turns caching off.
+pub(crate) struct ParseCache {
+    entries: Mutex<CachedTrees>,
+    capacity_bytes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ParseKey {
+    hash: ContentHash,
+    length: usize,
+    namespace_mode: NamespaceMode,
+    expand_entities: bool,
+}
+
+impl ParseKey {
+    pub(crate) fn new(xml: &str, namespace_mode: NamespaceMode, expand_entities: bool) -> Self {
+        Self { hash: content_hash(xml), length: xml.len(), namespace_mode, expand_entities }
+    }
+
+    fn size(&self) -> usize {
+        estimate_comparison_memory(self.length)
+    }
+}
+
+// Least recently used first, with the estimated memory of them all
+struct CachedTrees {
+    trees: LruCache<ParseKey, CachedRoots>,
+    used_bytes: usize,
+}
+
+struct CachedRoots {
+    roots: Arc<Vec<XmlElement<'static>>>,
+    digest: DocumentDigest,
+}
+
+impl Default for ParseCache {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl ParseCache {
+    pub(crate) fn new(capacity_bytes: usize) -> Self {
+        let entries = CachedTrees { trees: LruCache::new_unbounded(), used_bytes: 0 };
+        Self { entries: Mutex::new(entries), capacity_bytes }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.capacity_bytes > 0
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().map_or(0, |entries| entries.trees.len())
+    }
+
+    pub(crate) fn get(&self, key: &ParseKey) -> Option<(Arc<Vec<XmlElement<'static>>>, DocumentDigest)> {
+        let mut entries = self.entries.lock().ok()?;
+        let entry = entries.trees.get(key)?;
+        Some((entry.roots.clone(), entry.digest))
+    }
+
+    // Documents larger than the whole cache are not kept
+    pub(crate) fn insert(&self, key: ParseKey, roots: Arc<Vec<XmlElement<'static>>>, digest: DocumentDigest) {
+        let size = key.size();
+        if size > self.capacity_bytes {
+            return;
+        }
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if entries.trees.remove(&key).is_some() {
+            entries.used_bytes -= size;
+        }
+        while entries.used_bytes + size > self.capacity_bytes {
+            let Some((evicted, _)) = entries.trees.remove_lru() else {
+                break;
+            };
+            entries.used_bytes -= evicted.size();
+        }
+        entries.trees.insert(key, CachedRoots { roots, digest });
+        entries.used_bytes += size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let key = |xml: &str| ParseKey::new(xml, NamespaceMode::default(), true);
+        // Room for two of the three documents
+        let cache = ParseCache::new(key("<a>1</a>").size() * 2);
+        let (roots, digest) = (Arc::new(Vec::new()), DocumentDigest::default());
+        cache.insert(key("<a>1</a>"), roots.clone(), digest);
+        cache.insert(key("<a>2</a>"), roots.clone(), digest);
+        assert!(cache.get(&key("<a>1</a>")).is_some());
+        cache.insert(key("<a>3</a>"), roots.clone(), digest);
+        assert!(cache.get(&key("<a>2</a>")).is_none());
+        assert!(cache.get(&key("<a>1</a>")).is_some());
+        assert!(cache.get(&key("<a>3</a>")).is_some());
+
+        cache.insert(key("<a>far too large</a>"), roots, digest);
+        assert_eq!(cache.len(), 2);
+        assert_ne!(key("<a/>"), ParseKey::new("<a/>", NamespaceMode::default(), false));
+        assert_ne!(key("<a>1</a>"), key("<a>2</a>"));
+    }
+}
@@ -0,0 +1,259 @@
+use crate::XmlElement;
+use crate::xpath::Lineage;
+use quick_xml::escape::escape;
+use std::collections::BTreeMap;
+
+/// Builds an RFC 5261 XML patch (`<diff>` document) that turns the first document into the
+/// second. Operations are applied in order, so every selector is computed against the document
+/// as it looks after the previous operations: sibling edits are simulated level by level, and
+/// elements below an already processed level are addressed by their position in the second
+/// document.
+#[derive(Debug, Default)]
+pub struct PatchBuilder {
+    operations: Vec<String>,
+    // Namespace URI -> prefix declared on the <diff> root for selectors and added content
+    prefixes: BTreeMap<String, String>,
+    // Selectors of elements replaced as a whole; operations inside them are already applied
+    replaced: Vec<String>,
+}
+
+// An element in the simulated child list of one parent while sibling edits are applied
+struct Slot<'a> {
+    element: &'a XmlElement<'a>,
+    // Index in the second document once the element is in its final place
+    target: Option<usize>,
+}
+
+impl PatchBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes, inserts and repositions the children of one parent. `matches[i]` is the child of
+    /// the second document paired with child `i` of the first; unpaired children are removed or
+    /// added when `removable`/`addable` allow it, and `moved` children are removed and re-added
+    /// at their new position.
+    #[allow(clippy::too_many_arguments)]
+    pub fn children(
+        &mut self,
+        parent: Option<&Lineage>,
+        children1: &[XmlElement],
+        children2: &[XmlElement],
+        matches: &[Option<usize>],
+        moved: &[bool],
+        removable: &[bool],
+        addable: &[bool],
+    ) {
+        let parent_selector = parent.map(|node| self.selector(node)).unwrap_or_default();
+
+        // The document element cannot be removed and re-added, so a changed root is replaced
+        if parent.is_none() && children1.len() == 1 && children2.len() == 1 && matches[0].is_none() {
+            if removable[0] && addable[0] {
+                let selector = format!("/{}", self.step_name(&children1[0]));
+                let content = self.serialize(&children2[0]);
+                self.push(&selector, format!("<replace sel=\"{}\">{}</replace>", escape(&selector), content));
+            }
+            return;
+        }
+
+        let mut slots: Vec<Slot> = children1
+            .iter()
+            .zip(matches)
+            .map(|(element, target)| Slot { element, target: *target })
+            .collect();
+
+        let mut index = 0;
+        for i in 0..children1.len() {
+            if (matches[i].is_none() && removable[i]) || moved[i] {
+                let selector = self.slot_selector(&parent_selector, &slots, index);
+                self.push(&selector, format!("<remove sel=\"{}\"/>", escape(&selector)));
+                slots.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+
+        let moved_targets: Vec<usize> = matches
+            .iter()
+            .zip(moved)
+            .filter_map(|(target, moved)| target.filter(|_| *moved))
+            .collect();
+        for (j, element) in children2.iter().enumerate() {
+            let is_added = !matches.contains(&Some(j)) && addable[j];
+            if !is_added && !moved_targets.contains(&j) {
+                continue;
+            }
+
+            let content = self.serialize(element);
+            let previous = (0..j).rev().find_map(|k| slots.iter().position(|slot| slot.target == Some(k)));
+            let position = match previous {
+                Some(previous) => {
+                    let selector = self.slot_selector(&parent_selector, &slots, previous);
+                    self.push(&selector, format!("<add sel=\"{}\" pos=\"after\">{}</add>", escape(&selector), content));
+                    previous + 1
+                }
+                None => {
+                    let selector = if parent_selector.is_empty() { "/".to_string() } else { parent_selector.clone() };
+                    self.push(&selector, format!("<add sel=\"{}\" pos=\"prepend\">{}</add>", escape(&selector), content));
+                    0
+                }
+            };
+            slots.insert(position, Slot { element, target: Some(j) });
+        }
+    }
+
+    pub fn replace_element(&mut self, node: &Lineage) {
+        let selector = self.selector(node);
+        let content = self.serialize(node.element);
+        self.push(&selector, format!("<replace sel=\"{}\">{}</replace>", escape(&selector), content));
+        self.replaced.push(selector);
+    }
+
+    pub fn text(&mut self, node: &Lineage, old: Option<&str>, new: Option<&str>) {
+        let selector = self.selector(node);
+        match (old, new) {
+            (Some(_), Some(new)) => {
+                let text = format!("{}/text()", selector);
+                self.push(&text, format!("<replace sel=\"{}\">{}</replace>", escape(&text), escape(new)));
+            }
+            (None, Some(new)) => self.push(&selector, format!("<add sel=\"{}\">{}</add>", escape(&selector), escape(new))),
+            (Some(_), None) => {
+                let text = format!("{}/text()", selector);
+                self.push(&text, format!("<remove sel=\"{}\"/>", escape(&text)));
+            }
+            (None, None) => {}
+        }
+    }
+
+    pub fn replace_attribute(&mut self, node: &Lineage, key: &str, prefix: Option<&str>, value: &str) {
+        let selector = format!("{}/@{}", self.selector(node), self.attribute_name(key, prefix));
+        self.push(&selector, format!("<replace sel=\"{}\">{}</replace>", escape(&selector), escape(value)));
+    }
+
+    pub fn remove_attribute(&mut self, node: &Lineage, key: &str, prefix: Option<&str>) {
+        let selector = format!("{}/@{}", self.selector(node), self.attribute_name(key, prefix));
+        self.push(&selector, format!("<remove sel=\"{}\"/>", escape(&selector)));
+    }
+
+    pub fn add_attribute(&mut self, node: &Lineage, key: &str, prefix: Option<&str>, value: &str) {
+        let selector = self.selector(node);
+        let name = self.attribute_name(key, prefix);
+        self.push(&selector, format!("<add sel=\"{}\" type=\"@{}\">{}</add>", escape(&selector), name, escape(value)));
+    }
+
+    pub fn finish(self) -> String {
+        let mut patch = String::from("<diff");
+        for (uri, prefix) in &self.prefixes {
+            patch.push_str(&format!(" xmlns:{}=\"{}\"", prefix, escape(uri)));
+        }
+        if self.operations.is_empty() {
+            patch.push_str("/>");
+            return patch;
+        }
+        patch.push('>');
+        for operation in &self.operations {
+            patch.push_str("\n  ");
+            patch.push_str(operation);
+        }
+        patch.push_str("\n</diff>");
+        patch
+    }
+
+    fn push(&mut self, selector: &str, operation: String) {
+        let inside_replaced = self
+            .replaced
+            .iter()
+            .any(|replaced| selector == replaced || selector.strip_prefix(replaced.as_str()).is_some_and(|rest| rest.starts_with('/')));
+        if !inside_replaced {
+            self.operations.push(operation);
+        }
+    }
+
+    // Selector of an element whose ancestors and siblings are already in their final place
+    fn selector(&mut self, node: &Lineage) -> String {
+        let mut steps = Vec::new();
+        for ancestor in node.ancestors() {
+            let name = self.step_name(ancestor.element);
+            let repeated = ancestor.parent.is_some_and(|parent| {
+                parent.element.children.iter().filter(|child| self.same_name(child, ancestor.element)).count() > 1
+            });
+            steps.push(if repeated { format!("{}[{}]", name, ancestor.position) } else { name });
+        }
+        steps.reverse();
+        format!("/{}", steps.join("/"))
+    }
+
+    fn slot_selector(&mut self, parent_selector: &str, slots: &[Slot], index: usize) -> String {
+        let element = slots[index].element;
+        let name = self.step_name(element);
+        let same_name = |slot: &&Slot| self.same_name(slot.element, element);
+        let position = slots[..index].iter().filter(same_name).count() + 1;
+        if slots.iter().filter(same_name).count() > 1 {
+            format!("{}/{}[{}]", parent_selector, name, position)
+        } else {
+            format!("{}/{}", parent_selector, name)
+        }
+    }
+
+    fn same_name(&self, a: &XmlElement, b: &XmlElement) -> bool {
+        a.name == b.name && a.namespace == b.namespace
+    }
+
+    fn step_name(&mut self, element: &XmlElement) -> String {
+        match &element.namespace {
+            Some(uri) => format!("{}:{}", self.prefix_for(uri, element.prefix.as_deref()), element.name),
+            None => element.name.to_string(),
+        }
+    }
+
+    // Namespaced attribute keys are stored as `{uri}local`
+    fn attribute_name(&mut self, key: &str, prefix: Option<&str>) -> String {
+        match key.strip_prefix('{').and_then(|rest| rest.split_once('}')) {
+            Some((uri, local)) => format!("{}:{}", self.prefix_for(uri, prefix), local),
+            None => key.to_string(),
+        }
+    }
+
+    fn prefix_for(&mut self, uri: &str, preferred: Option<&str>) -> String {
+        if let Some(prefix) = self.prefixes.get(uri) {
+            return prefix.clone();
+        }
+        let taken = |prefix: &str, prefixes: &BTreeMap<String, String>| {
+            prefix == "xml" || prefix == "xmlns" || prefixes.values().any(|p| p == prefix)
+        };
+        let prefix = match preferred {
+            Some(preferred) if !taken(preferred, &self.prefixes) => preferred.to_string(),
+            _ => (1..).map(|n| format!("ns{}", n)).find(|p| !taken(p, &self.prefixes)).expect("unbounded"),
+        };
+        self.prefixes.insert(uri.to_string(), prefix.clone());
+        prefix
+    }
+
+    // Namespaces are written with the prefixes declared on the <diff> root, which RFC 5261
+    // makes the namespace context of added content
+    fn serialize(&mut self, element: &XmlElement) -> String {
+        let name = self.step_name(element);
+        let mut output = format!("<{}", name);
+        let mut attributes: Vec<_> = element.attributes.iter().collect();
+        attributes.sort();
+        for (key, value) in attributes {
+            let prefix = element.attribute_prefix(key);
+            let key = self.attribute_name(key, prefix);
+            output.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+        if element.content.is_none() && element.children.is_empty() {
+            output.push_str("/>");
+            return output;
+        }
+        output.push('>');
+        if let Some(content) = &element.content {
+            output.push_str(&escape(content));
+        }
+        for child in &element.children {
+            let child = self.serialize(child);
+            output.push_str(&child);
+        }
+        output.push_str(&format!("</{}>", name));
+        output
+    }
+}
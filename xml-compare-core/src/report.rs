@@ -0,0 +1,239 @@
+use crate::{DiffType, XmlComparisonResponse};
+use crate::XmlElement;
+use quick_xml::escape::escape;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mark {
+    Removed,
+    Added,
+    Moved,
+    Changed,
+}
+
+impl Mark {
+    fn class(self) -> &'static str {
+        match self {
+            Mark::Removed => "removed",
+            Mark::Added => "added",
+            Mark::Moved => "moved",
+            Mark::Changed => "changed",
+        }
+    }
+}
+
+// Diffs that touch one element of one document, keyed by normalized element path
+#[derive(Default)]
+struct Marks(HashMap<String, (Mark, Vec<String>)>);
+
+impl Marks {
+    fn add(&mut self, path: &str, mark: Mark, message: &str) {
+        let entry = self.0.entry(normalize_path(path)).or_insert((mark, Vec::new()));
+        // A structural mark on the element wins over changes to its attributes or content
+        if entry.0 == Mark::Changed {
+            entry.0 = mark;
+        }
+        entry.1.push(message.to_string());
+    }
+
+    fn get(&self, path: &str) -> Option<&(Mark, Vec<String>)> {
+        self.0.get(&normalize_path(path))
+    }
+}
+
+/// Renders a standalone HTML page showing both documents side by side, with removed, added,
+/// moved and changed elements highlighted, followed by the list of diffs.
+pub fn render_report(roots1: &[XmlElement], roots2: &[XmlElement], result: &XmlComparisonResponse) -> String {
+    let mut marks1 = Marks::default();
+    let mut marks2 = Marks::default();
+    for diff in &result.diffs {
+        match diff.diff_type {
+            DiffType::ElementMissing => marks1.add(&diff.path, Mark::Removed, &diff.message),
+            DiffType::ElementExtra => marks2.add(&diff.path, Mark::Added, &diff.message),
+            DiffType::ElementMoved => {
+                marks1.add(diff.expected.as_deref().unwrap_or(&diff.path), Mark::Moved, &diff.message);
+                marks2.add(diff.actual.as_deref().unwrap_or(&diff.path), Mark::Moved, &diff.message);
+            }
+            DiffType::ElementReordered => {
+                marks1.add(&diff.path, Mark::Moved, &diff.message);
+                marks2.add(&reordered_path(&diff.path, diff.actual.as_deref()), Mark::Moved, &diff.message);
+            }
+            _ => {
+                marks1.add(&diff.path, Mark::Changed, &diff.message);
+                marks2.add(&diff.path, Mark::Changed, &diff.message);
+            }
+        }
+    }
+
+    let mut html = String::from(REPORT_HEAD);
+    html.push_str("<h1>XML comparison report</h1>\n<p class=\"summary\">");
+    html.push_str(if result.matched { "<strong class=\"match\">Documents match</strong>" } else { "<strong class=\"mismatch\">Documents differ</strong>" });
+    html.push_str(&format!(
+        " &middot; match ratio {:.1}% &middot; {} of {} elements matched &middot; {} diff(s){}</p>\n",
+        result.match_ratio * 100.0,
+        result.matched_elements,
+        result.total_elements,
+        result.diffs.len(),
+        if result.truncated { " (truncated)" } else { "" }
+    ));
+    html.push_str("<p class=\"legend\"><span class=\"removed\">removed</span> <span class=\"added\">added</span> <span class=\"moved\">moved</span> <span class=\"changed\">changed</span></p>\n");
+
+    html.push_str("<div class=\"documents\">\n<div class=\"document\"><h2>xml1</h2><pre>");
+    render_elements(&mut html, roots1, "", 0, &marks1);
+    html.push_str("</pre></div>\n<div class=\"document\"><h2>xml2</h2><pre>");
+    render_elements(&mut html, roots2, "", 0, &marks2);
+    html.push_str("</pre></div>\n</div>\n");
+
+    if !result.diffs.is_empty() {
+        html.push_str("<h2>Differences</h2>\n<table>\n<tr><th>Path</th><th>Type</th><th>Expected</th><th>Actual</th><th>Message</th></tr>\n");
+        for diff in &result.diffs {
+            html.push_str(&format!(
+                "<tr><td><code>{}</code></td><td>{:?}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&diff.path),
+                diff.diff_type,
+                escape(diff.expected.as_deref().unwrap_or("")),
+                escape(diff.actual.as_deref().unwrap_or("")),
+                escape(&diff.message)
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+// Removed, added and moved elements are highlighted with their whole subtree, changed elements
+// on their own lines
+fn render_elements(html: &mut String, elements: &[XmlElement], parent_path: &str, depth: usize, marks: &Marks) {
+    let paths = crate::xml_comparison::SiblingPaths::new(elements, &[]);
+    for (i, element) in elements.iter().enumerate() {
+        let path = paths.path(parent_path, i);
+        let indent = "  ".repeat(depth);
+        let (subtree_class, line_class, title) = match marks.get(&path) {
+            Some((Mark::Changed, messages)) => ("", Mark::Changed.class(), messages.join("\n")),
+            Some((mark, messages)) => (mark.class(), "", messages.join("\n")),
+            None => ("", "", String::new()),
+        };
+
+        html.push_str(&format!("<span class=\"node {}\" title=\"{}\">", subtree_class, escape(&title)));
+        let name = display_name(element.prefix.as_deref(), &element.name);
+        let open = format!("{}&lt;{}{}", indent, name, render_attributes(element));
+        let line = |content: &str| format!("<span class=\"line {}\">{}</span>\n", line_class, content);
+
+        if element.children.is_empty() {
+            match &element.content {
+                Some(content) => html.push_str(&line(&format!("{}&gt;{}&lt;/{}&gt;", open, escape(content), name))),
+                None => html.push_str(&line(&format!("{}/&gt;", open))),
+            }
+        } else {
+            html.push_str(&line(&format!("{}&gt;", open)));
+            if let Some(content) = &element.content {
+                html.push_str(&line(&format!("{}  {}", indent, escape(content))));
+            }
+            render_elements(html, &element.children, &path, depth + 1, marks);
+            html.push_str(&line(&format!("{}&lt;/{}&gt;", indent, name)));
+        }
+        html.push_str("</span>");
+    }
+}
+
+fn render_attributes(element: &XmlElement) -> String {
+    let mut attributes: Vec<_> = element.attributes.iter().collect();
+    attributes.sort();
+    attributes
+        .into_iter()
+        .map(|(key, value)| {
+            // Namespaced attribute keys are stored as `{uri}local`
+            let local = key.strip_prefix('{').and_then(|rest| rest.split_once('}')).map(|(_, local)| local);
+            let name = match (local, element.attribute_prefixes.get(key)) {
+                (Some(local), Some(prefix)) => format!("{}:{}", prefix, local),
+                _ => key.to_string(),
+            };
+            format!(" {}=\"{}\"", escape(&name), escape(value))
+        })
+        .collect()
+}
+
+fn display_name(prefix: Option<&str>, name: &str) -> String {
+    match prefix {
+        Some(prefix) if !name.contains(':') => format!("{}:{}", prefix, name),
+        _ => name.to_string(),
+    }
+}
+
+// `item[1]` and `item` name the same element: the index is only written when either document
+// repeats the name
+fn normalize_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| segment.strip_suffix("[1]").unwrap_or(segment))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// Reorder diffs carry the first document's path; the element sits at `position n` in the second
+fn reordered_path(path: &str, actual: Option<&str>) -> String {
+    let position = actual.and_then(|actual| actual.strip_prefix("position "));
+    match (path.rsplit_once('['), position) {
+        (Some((base, _)), Some(position)) => format!("{}[{}]", base, position),
+        _ => path.to_string(),
+    }
+}
+
+const REPORT_HEAD: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="UTF-8">
+<title>XML comparison report</title>
+<style>
+    body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; color: #333; margin: 20px; }
+    .summary .match { color: #28a745; }
+    .summary .mismatch { color: #dc3545; }
+    .documents { display: grid; grid-template-columns: 1fr 1fr; gap: 20px; }
+    .document { min-width: 0; }
+    pre { background: #f8f9fa; border-radius: 8px; padding: 15px; overflow-x: auto; font-family: 'Monaco', 'Menlo', monospace; font-size: 0.85rem; }
+    .node, .line { display: block; }
+    .removed { background: #f8d7da; }
+    .added { background: #d4edda; }
+    .moved { background: #d1ecf1; }
+    .changed { background: #fff3cd; }
+    .legend span { padding: 2px 8px; border-radius: 4px; }
+    table { border-collapse: collapse; width: 100%; font-size: 0.85rem; }
+    th, td { border: 1px solid #dee2e6; padding: 6px; text-align: left; vertical-align: top; word-break: break-word; }
+    th { background: #f8f9fa; }
+</style>
+</head>
+<body>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComparisonOptions, XmlComparisonService};
+
+    #[test]
+    fn test_report_highlights_diffs() {
+        let service = XmlComparisonService::new();
+        let html = service
+            .render_report(
+                "<root><a x=\"1\">old</a><b/></root>",
+                "<root><a x=\"1\">new &amp; improved</a><c/></root>",
+                &ComparisonOptions::default(),
+            )
+            .unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<span class=\"line changed\">  &lt;a x=\"1\"&gt;old&lt;/a&gt;</span>"));
+        assert!(html.contains("<span class=\"node removed\" title=\"Element missing in second XML\"><span class=\"line \">  &lt;b/&gt;</span>"));
+        assert!(html.contains("<span class=\"node added\" title=\"Extra element in second XML\"><span class=\"line \">  &lt;c/&gt;</span>"));
+        assert!(html.contains("&lt;a x=\"1\"&gt;new &amp; improved&lt;/a&gt;"));
+        assert!(html.contains("<td><code>/root/a</code></td><td>ContentDifferent</td>"));
+    }
+
+    #[test]
+    fn test_path_normalization() {
+        assert_eq!(normalize_path("/root/item[1]/name"), "/root/item/name");
+        assert_eq!(normalize_path("/root/item[2]"), "/root/item[2]");
+        assert_eq!(reordered_path("/list/item[1]", Some("position 3")), "/list/item[3]");
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{DiffSeverity, DiffType, XmlComparisonResponse};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+
+/// A compared pair of documents, as reported in a SARIF log
+#[derive(Debug, Clone)]
+pub struct SarifComparison<'a> {
+    pub result: &'a XmlComparisonResponse,
+    /// Where each document lives, as a file path or URL; `xml1` and `xml2` when unknown
+    pub uri1: Option<String>,
+    pub uri2: Option<String>,
+    /// The documents' text; without it findings have no line
+    pub xml1: Option<&'a str>,
+    pub xml2: Option<&'a str>,
+}
+
+/// Renders a SARIF 2.1.0 log, so that code-scanning pages list each diff as a finding: the rule
+/// is the diff type, and the location the document holding the element with its line when known.
+/// Missing elements point into the first document, every other diff into the second. `errors`
+/// are comparisons that could not run, reported as tool notifications.
+pub fn render_sarif(comparisons: &[SarifComparison], errors: &[String]) -> String {
+    let mut rules = BTreeSet::new();
+    let mut results = Vec::new();
+    for comparison in comparisons {
+        let lines1 = comparison.xml1.map(element_lines).unwrap_or_default();
+        let lines2 = comparison.xml2.map(element_lines).unwrap_or_default();
+        let uri1 = comparison.uri1.as_deref().unwrap_or("xml1");
+        let uri2 = comparison.uri2.as_deref().unwrap_or("xml2");
+        for diff in &comparison.result.diffs {
+            rules.insert(diff.diff_type);
+            let (uri, lines) = match diff.diff_type {
+                DiffType::ElementMissing => (uri1, &lines1),
+                _ => (uri2, &lines2),
+            };
+            let mut physical = json!({ "artifactLocation": { "uri": uri } });
+            if let Some(line) = find_line(lines, &diff.path) {
+                physical["region"] = json!({ "startLine": line });
+            }
+            let mut text = diff.message.clone();
+            match (&diff.expected, &diff.actual) {
+                (Some(expected), Some(actual)) => text.push_str(&format!(": expected {:?}, actual {:?}", expected, actual)),
+                (Some(expected), None) => text.push_str(&format!(": expected {:?}", expected)),
+                (None, Some(actual)) => text.push_str(&format!(": actual {:?}", actual)),
+                (None, None) => {}
+            }
+            let mut result = json!({
+                "ruleId": rule_id(diff.diff_type),
+                "level": match diff.severity {
+                    DiffSeverity::Error => "error",
+                    DiffSeverity::Info => "note",
+                },
+                "message": { "text": format!("{} at {}", text, diff.path) },
+                "locations": [{
+                    "physicalLocation": physical,
+                    "logicalLocations": [{ "fullyQualifiedName": diff.path, "kind": "element" }]
+                }]
+            });
+            if !diff.tags.is_empty() {
+                result["properties"] = json!({ "tags": diff.tags });
+            }
+            results.push(result);
+        }
+    }
+
+    let rules: Vec<Value> = rules
+        .into_iter()
+        .map(|diff_type| json!({ "id": rule_id(diff_type), "shortDescription": { "text": rule_description(diff_type) } }))
+        .collect();
+    let notifications: Vec<Value> =
+        errors.iter().map(|error| json!({ "level": "error", "message": { "text": error } })).collect();
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": "xml-compare", "version": env!("CARGO_PKG_VERSION"), "rules": rules } },
+            "invocations": [{ "executionSuccessful": errors.is_empty(), "toolExecutionNotifications": notifications }],
+            "results": results
+        }]
+    });
+    serde_json::to_string_pretty(&log).unwrap_or_default()
+}
+
+fn rule_id(diff_type: DiffType) -> String {
+    format!("{:?}", diff_type)
+}
+
+fn rule_description(diff_type: DiffType) -> &'static str {
+    match diff_type {
+        DiffType::ElementMissing => "Element missing from the second document",
+        DiffType::ElementExtra => "Element only in the second document",
+        DiffType::AttributeDifferent => "Attribute differs",
+        DiffType::ContentDifferent => "Text content differs",
+        DiffType::StructureDifferent => "Structure differs",
+        DiffType::NamespaceDifferent => "Namespace differs",
+        DiffType::CdataDifferent => "CDATA differs",
+        DiffType::ElementMoved => "Element moved",
+        DiffType::ContentSimilar => "Text content similar but not equal",
+        DiffType::ElementReordered => "Siblings reordered",
+        DiffType::SelfClosingDifferent => "Self-closing form differs",
+        DiffType::ChildCountDifferent => "Number of children differs",
+    }
+}
+
+// Line of each element's start tag, keyed by its path of local names with every position
+// written out (`/a[1]/b[2]`)
+fn element_lines(xml: &str) -> HashMap<String, usize> {
+    let mut reader = Reader::from_str(xml);
+    let mut lines = HashMap::new();
+    // Path of each open element, and how often each name has occurred among its children
+    let mut stack: Vec<(String, HashMap<String, usize>)> = vec![(String::new(), HashMap::new())];
+    let mut line = 1;
+    let mut counted = 0;
+    loop {
+        let start = reader.buffer_position();
+        let event = match reader.read_event() {
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(event) => event,
+        };
+        let (Event::Start(tag) | Event::Empty(tag)) = &event else {
+            if matches!(event, Event::End(_)) && stack.len() > 1 {
+                stack.pop();
+            }
+            continue;
+        };
+        line += xml.as_bytes()[counted..start].iter().filter(|byte| **byte == b'\n').count();
+        counted = start;
+        let name = String::from_utf8_lossy(tag.local_name().into_inner()).to_string();
+        let (parent, seen) = stack.last_mut().expect("the document level is never popped");
+        let position = seen.entry(name.clone()).or_default();
+        *position += 1;
+        let path = format!("{}/{}[{}]", parent, name, position);
+        lines.entry(path.clone()).or_insert(line);
+        if matches!(event, Event::Start(_)) {
+            stack.push((path, HashMap::new()));
+        }
+    }
+    lines
+}
+
+// The diff's element, or failing that its closest ancestor with a known line, such as the
+// element of an attribute or of a keyed match
+fn find_line(lines: &HashMap<String, usize>, path: &str) -> Option<usize> {
+    // Keyed segments such as `trade[@id='2']` say nothing about position, so the search stops there
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map_while(|segment| {
+            let (name, index) = match segment.split_once('[') {
+                Some((name, rest)) => (name, rest.strip_suffix(']').filter(|index| index.parse::<usize>().is_ok())?),
+                None => (segment, "1"),
+            };
+            let local = name.rsplit(':').next().unwrap_or(name);
+            Some(format!("/{}[{}]", local, index))
+        })
+        .collect();
+    (1..=segments.len()).rev().find_map(|length| lines.get(&segments[..length].concat()).copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ComparisonOptions, XmlComparisonService};
+
+    #[test]
+    fn test_render_sarif() {
+        let xml1 = "<trades>\n  <trade id=\"1\">\n    <price>10</price>\n  </trade>\n  <trade id=\"2\">\n    <price>20</price>\n    <book>FX</book>\n  </trade>\n</trades>";
+        let xml2 = "<trades>\n  <trade id=\"1\">\n    <price>10</price>\n  </trade>\n  <trade id=\"2\">\n    <price>21</price>\n  </trade>\n</trades>";
+        let result = XmlComparisonService::new().compare_with_options(xml1, xml2, &ComparisonOptions::default()).unwrap();
+        let comparison = SarifComparison {
+            result: &result,
+            uri1: Some("expected/trades.xml".to_string()),
+            uri2: Some("actual/trades.xml".to_string()),
+            xml1: Some(xml1),
+            xml2: Some(xml2),
+        };
+        let log: Value = serde_json::from_str(&render_sarif(&[comparison], &["b.xml: invalid XML".to_string()])).unwrap();
+
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        let rules: Vec<_> = run["tool"]["driver"]["rules"].as_array().unwrap().iter().map(|rule| rule["id"].as_str().unwrap()).collect();
+        assert_eq!(rules, ["ElementMissing", "ContentDifferent"]);
+        let locations: Vec<_> = run["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| {
+                let physical = &result["locations"][0]["physicalLocation"];
+                (result["ruleId"].as_str().unwrap(), physical["artifactLocation"]["uri"].as_str().unwrap(), physical["region"]["startLine"].as_u64())
+            })
+            .collect();
+        assert_eq!(
+            locations,
+            [("ContentDifferent", "actual/trades.xml", Some(6)), ("ElementMissing", "expected/trades.xml", Some(7))]
+        );
+        assert_eq!(run["results"][0]["level"], "error");
+        assert_eq!(run["invocations"][0]["executionSuccessful"], false);
+        assert_eq!(run["invocations"][0]["toolExecutionNotifications"][0]["message"]["text"], "b.xml: invalid XML");
+
+        let lines = element_lines(xml1);
+        assert_eq!(find_line(&lines, "/trades/trade[2]/@id"), Some(5));
+        assert_eq!(find_line(&lines, "/trades/trade[@id='2']/price"), Some(1));
+        assert_eq!(find_line(&lines, "/other"), None);
+    }
+}
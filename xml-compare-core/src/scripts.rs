@@ -0,0 +1,202 @@
+use crate::{CompareError, CompareResult, DiffSeverity};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// What a script decided about two values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptVerdict {
+    Equal,
+    Different { message: Option<String>, severity: DiffSeverity },
+    /// Left to the other value rules
+    Undecided,
+}
+
+/// The values a script sees
+pub struct ScriptInput<'a> {
+    pub path: &'a str,
+    pub expected: &'a str,
+    pub actual: &'a str,
+    pub attributes: &'a HashMap<Cow<'a, str>, Cow<'a, str>>,
+    pub actual_attributes: &'a HashMap<Cow<'a, str>, Cow<'a, str>>,
+}
+
+/// A [`crate::ScriptRule`] script, compiled once per comparison
+pub struct ValueScript {
+    #[cfg(feature = "scripting")]
+    ast: rhai::AST,
+}
+
+#[cfg(not(feature = "scripting"))]
+impl ValueScript {
+    pub fn compile(_source: &str) -> CompareResult<Self> {
+        Err(CompareError::ValidationError(
+            "Scripts are not supported by this build; enable the scripting feature".to_string(),
+        ))
+    }
+
+    pub fn evaluate(&self, _input: &ScriptInput) -> ScriptVerdict {
+        ScriptVerdict::Undecided
+    }
+}
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use super::*;
+    use chrono::{Datelike, NaiveDate};
+    use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope};
+    use std::sync::LazyLock;
+
+    // Scripts come with requests, so they get a bounded amount of work and no access to anything
+    // but their inputs
+    static ENGINE: LazyLock<Engine> = LazyLock::new(|| {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(100_000)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(1 << 20)
+            .set_max_array_size(10_000)
+            .set_max_map_size(10_000);
+        engine.register_fn("days_between", |from: &str, to: &str| -> Result<i64, Box<EvalAltResult>> {
+            Ok((parse_date(to)? - parse_date(from)?).num_days())
+        });
+        engine.register_fn("business_days_between", |from: &str, to: &str| -> Result<i64, Box<EvalAltResult>> {
+            Ok(business_days_between(parse_date(from)?, parse_date(to)?))
+        });
+        engine
+    });
+
+    impl ValueScript {
+        pub fn compile(source: &str) -> CompareResult<Self> {
+            let ast = ENGINE
+                .compile(source)
+                .map_err(|e| CompareError::ValidationError(format!("Invalid script: {}", e)))?;
+            Ok(Self { ast })
+        }
+
+        pub fn evaluate(&self, input: &ScriptInput) -> ScriptVerdict {
+            let map = |attributes: &HashMap<Cow<str>, Cow<str>>| -> Map {
+                attributes.iter().map(|(key, value)| (key.as_ref().into(), value.to_string().into())).collect()
+            };
+            let mut scope = Scope::new();
+            scope
+                .push_constant("path", input.path.to_string())
+                .push_constant("expected", input.expected.to_string())
+                .push_constant("actual", input.actual.to_string())
+                .push_constant("attributes", map(input.attributes))
+                .push_constant("actual_attributes", map(input.actual_attributes));
+            match ENGINE.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast) {
+                Ok(result) => verdict(result).unwrap_or_else(failed),
+                Err(e) => failed(e.to_string()),
+            }
+        }
+    }
+
+    fn verdict(result: Dynamic) -> Result<ScriptVerdict, String> {
+        if result.is_unit() {
+            return Ok(ScriptVerdict::Undecided);
+        }
+        if let Ok(equal) = result.as_bool() {
+            return Ok(if equal { ScriptVerdict::Equal } else { different(None, DiffSeverity::Error) });
+        }
+        if result.is_string() {
+            return Ok(different(result.into_string().ok(), DiffSeverity::Error));
+        }
+        let Some(map) = result.try_cast::<Map>() else {
+            return Err("a script must return a boolean, a message, a map or ()".to_string());
+        };
+        let equal = map
+            .get("equal")
+            .and_then(|equal| equal.as_bool().ok())
+            .ok_or("a script's result map needs a boolean 'equal'")?;
+        if equal {
+            return Ok(ScriptVerdict::Equal);
+        }
+        let message = map.get("message").map(|message| message.to_string());
+        let severity = match map.get("severity").map(|severity| severity.to_string()).as_deref() {
+            None | Some("error") => DiffSeverity::Error,
+            Some("info") => DiffSeverity::Info,
+            Some(other) => return Err(format!("unknown severity '{}'; use error or info", other)),
+        };
+        Ok(different(message, severity))
+    }
+
+    fn different(message: Option<String>, severity: DiffSeverity) -> ScriptVerdict {
+        ScriptVerdict::Different { message, severity }
+    }
+
+    // A script that fails cannot vouch for the values, so they are reported as different
+    fn failed(error: String) -> ScriptVerdict {
+        different(Some(format!("Script failed: {}", error)), DiffSeverity::Error)
+    }
+
+    // The date at the start of an ISO 8601 value; any time part is ignored
+    fn parse_date(value: &str) -> Result<NaiveDate, Box<EvalAltResult>> {
+        let value = value.trim();
+        value
+            .get(..10)
+            .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+            .ok_or_else(|| format!("'{}' is not an ISO 8601 date", value).into())
+    }
+
+    // Weekdays after `from` up to and including `to`; negative when `to` is earlier. Counted by
+    // whole weeks, so that a call costs the same whatever the span.
+    fn business_days_between(from: NaiveDate, to: NaiveDate) -> i64 {
+        let (start, end, sign) = if from <= to { (from, to, 1) } else { (to, from, -1) };
+        let days = (end - start).num_days();
+        let weekday = i64::from(start.weekday().num_days_from_monday());
+        let leftover = (1..=days % 7).filter(|day| (weekday + day) % 7 < 5).count() as i64;
+        sign * (days / 7 * 5 + leftover)
+    }
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use super::*;
+
+    fn evaluate(source: &str, expected: &str, actual: &str) -> ScriptVerdict {
+        let attributes = HashMap::from([(Cow::from("ccy"), Cow::from("EUR"))]);
+        let input = ScriptInput {
+            path: "/trade/settles",
+            expected,
+            actual,
+            attributes: &attributes,
+            actual_attributes: &HashMap::new(),
+        };
+        ValueScript::compile(source).unwrap().evaluate(&input)
+    }
+
+    fn different(message: Option<&str>, severity: DiffSeverity) -> ScriptVerdict {
+        ScriptVerdict::Different { message: message.map(str::to_string), severity }
+    }
+
+    #[test]
+    fn test_script_verdicts() {
+        // Friday to Monday is one business day
+        let tolerance = "business_days_between(expected, actual).abs() <= 1";
+        assert_eq!(evaluate(tolerance, "2024-03-01", "2024-03-04"), ScriptVerdict::Equal);
+        assert_eq!(evaluate(tolerance, "2024-03-04T09:00:00Z", "2024-03-01"), ScriptVerdict::Equal);
+        assert_eq!(evaluate(tolerance, "2024-03-01", "2024-03-05"), different(None, DiffSeverity::Error));
+        let whole_calendar = "business_days_between(expected, actual) == -2608614";
+        assert_eq!(evaluate(whole_calendar, "9999-12-31", "0001-01-01"), ScriptVerdict::Equal);
+
+        let by_currency = r#"
+            if attributes.ccy != "EUR" { return; }
+            if days_between(expected, actual) == 0 { true }
+            else { #{equal: false, severity: "info", message: `Moved to ${actual}`} }
+        "#;
+        assert_eq!(evaluate(by_currency, "2024-03-01", "2024-03-02"), different(Some("Moved to 2024-03-02"), DiffSeverity::Info));
+        assert_eq!(evaluate(r#"if path.ends_with("/other") { true }"#, "a", "b"), ScriptVerdict::Undecided);
+        assert_eq!(evaluate(r#""Not the same""#, "a", "b"), different(Some("Not the same"), DiffSeverity::Error));
+
+        let ScriptVerdict::Different { message: Some(message), .. } = evaluate("days_between(expected, actual)", "soon", "b") else {
+            panic!("a failing script should report a difference");
+        };
+        assert!(message.starts_with("Script failed"), "{}", message);
+        let ScriptVerdict::Different { message: Some(message), .. } = evaluate("loop {}", "a", "b") else {
+            panic!("a runaway script should be stopped");
+        };
+        assert!(message.starts_with("Script failed"), "{}", message);
+        assert!(ValueScript::compile("if {").is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{CompareError, CompareResult, TransformRule};
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+// f64 keeps about 15 significant decimal digits
+const MAX_ROUND_DECIMALS: u32 = 15;
+
+static CURRENCY_SYMBOLS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\p{Sc}").expect("valid regex"));
+
+/// A [`TransformRule`] ready to be applied, with its regular expression compiled
+pub enum ValueTransformer {
+    Lowercase,
+    StripCurrency,
+    Round(u32),
+    RegexReplace(Regex, String),
+}
+
+impl ValueTransformer {
+    pub fn compile(rule: &TransformRule) -> CompareResult<Self> {
+        Ok(match rule {
+            TransformRule::Lowercase => Self::Lowercase,
+            TransformRule::StripCurrency => Self::StripCurrency,
+            TransformRule::Round { decimals } if *decimals > MAX_ROUND_DECIMALS => {
+                return Err(CompareError::ValidationError(format!(
+                    "round transform supports at most {} decimals",
+                    MAX_ROUND_DECIMALS
+                )));
+            }
+            TransformRule::Round { decimals } => Self::Round(*decimals),
+            TransformRule::RegexReplace { pattern, replacement } => {
+                let regex = Regex::new(pattern)
+                    .map_err(|e| CompareError::ValidationError(format!("Invalid transform pattern '{}': {}", pattern, e)))?;
+                Self::RegexReplace(regex, replacement.clone())
+            }
+        })
+    }
+
+    pub fn apply<'a>(&self, value: Cow<'a, str>) -> Cow<'a, str> {
+        match self {
+            Self::Lowercase => Cow::Owned(value.to_lowercase()),
+            Self::StripCurrency => Cow::Owned(CURRENCY_SYMBOLS.replace_all(&value, "").trim().to_string()),
+            Self::Round(decimals) => match value.trim().parse::<f64>() {
+                Ok(number) if number.is_finite() => {
+                    let factor = 10f64.powi(*decimals as i32);
+                    let rounded = (number * factor).round() / factor;
+                    // Avoid "-0.00" for small negative values
+                    let rounded = if rounded == 0.0 { 0.0 } else { rounded };
+                    Cow::Owned(format!("{:.*}", *decimals as usize, rounded))
+                }
+                _ => value,
+            },
+            Self::RegexReplace(regex, replacement) => Cow::Owned(regex.replace_all(&value, replacement.as_str()).into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(rule: TransformRule, value: &str) -> String {
+        ValueTransformer::compile(&rule).unwrap().apply(Cow::Borrowed(value)).into_owned()
+    }
+
+    #[test]
+    fn test_transforms() {
+        assert_eq!(apply(TransformRule::Lowercase, "ACME Ltd"), "acme ltd");
+        assert_eq!(apply(TransformRule::StripCurrency, "$ 1,200.50"), "1,200.50");
+        assert_eq!(apply(TransformRule::StripCurrency, "99.90€"), "99.90");
+        assert_eq!(apply(TransformRule::Round { decimals: 2 }, "1.23456"), "1.23");
+        assert_eq!(apply(TransformRule::Round { decimals: 0 }, " 2.5 "), "3");
+        assert_eq!(apply(TransformRule::Round { decimals: 2 }, "-0.001"), "0.00");
+        assert_eq!(apply(TransformRule::Round { decimals: 2 }, "n/a"), "n/a");
+        let regex = TransformRule::RegexReplace { pattern: r"^(\d{4})(\d{2})$".to_string(), replacement: "$1-$2".to_string() };
+        assert_eq!(apply(regex, "202508"), "2025-08");
+    }
+
+    #[test]
+    fn test_invalid_transforms_rejected() {
+        assert!(ValueTransformer::compile(&TransformRule::Round { decimals: 40 }).is_err());
+        let regex = TransformRule::RegexReplace { pattern: "(".to_string(), replacement: String::new() };
+        assert!(ValueTransformer::compile(&regex).is_err());
+    }
+}
@@ -0,0 +1,4160 @@
+use crate::{
+    ComparisonOptions, ComparisonMode, XmlComparisonResponse, XmlDiff, DiffType, DiffSeverity, NamespaceMode, SimilarityAlgorithm, OutputFormat,
+    ChildCountMode, NilMode, DocumentWarning, DocumentPairStats, DocumentStats, ElementStats, XmlNodeSnapshot, CompareError, CompareResult,
+};
+use crate::canonicalization::canonicalize;
+use crate::classification::DiffClassifier;
+use crate::comparators::{ValueComparator, ValueVerdict};
+use crate::entities::EntityResolver;
+use crate::formatting::unified_diff;
+use crate::parse_cache::{ContentHash, DigestCache, ParseCache, ParseKey, content_hash};
+use crate::patch::PatchBuilder;
+use crate::transforms::ValueTransformer;
+use crate::report::render_report;
+use crate::scripts::{ScriptInput, ScriptVerdict, ValueScript};
+use crate::normalization::{dates_equal, decode_base64, literals_equal, payload_digest, text_similarity};
+use crate::xpath::{Lineage, XPathPattern};
+use quick_xml::NsReader;
+use quick_xml::escape::escape;
+use quick_xml::events::attributes::Attributes;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::{QName, ResolveResult};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Write as _};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::{Deref, Range};
+use std::sync::Arc;
+
+/// An element of a parsed document. Prefixes, attributes and text borrow from the document
+/// unless entities had to be expanded, so parsing allocates little beyond the tree itself.
+#[derive(Clone)]
+pub struct XmlElement<'a> {
+    /// Interned: elements with the same name share one allocation, across both documents
+    pub name: Arc<str>,
+    pub namespace: Option<Arc<str>>,
+    pub prefix: Option<Cow<'a, str>>,
+    pub attributes: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    pub attribute_prefixes: HashMap<Cow<'a, str>, Cow<'a, str>>,
+    pub content: Option<Cow<'a, str>>,
+    pub cdata: bool,
+    /// Written as `<a/>` rather than `<a></a>`
+    pub self_closing: bool,
+    pub children: Vec<XmlElement<'a>>,
+}
+
+impl<'a> XmlElement<'a> {
+    // Text and CDATA sections of the same element are joined into one content value; only
+    // elements with several sections need a copy
+    fn append_content(&mut self, text: Cow<'a, str>) {
+        match &mut self.content {
+            Some(content) if !text.is_empty() => content.to_mut().push_str(&text),
+            Some(_) => {}
+            None => self.content = Some(text),
+        }
+    }
+
+    fn count(&self) -> usize {
+        1 + self.children.iter().map(XmlElement::count).sum::<usize>()
+    }
+
+    // Order-independent fingerprint of the whole subtree, used to pair identical siblings
+    fn signature(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.name.hash(&mut hasher);
+        self.namespace.hash(&mut hasher);
+        self.content.hash(&mut hasher);
+        let mut attributes: Vec<_> = self.attributes.iter().collect();
+        attributes.sort();
+        attributes.hash(&mut hasher);
+        let mut children: Vec<u64> = self.children.iter().map(XmlElement::signature).collect();
+        children.sort_unstable();
+        children.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    // Evaluates a match_keys expression: `@attr`, `text()` or a relative child path such as `id` or `ids/primary`
+    fn key_value(&self, expression: &str) -> Option<String> {
+        if let Some(attribute) = expression.strip_prefix('@') {
+            return self
+                .attributes
+                .iter()
+                .find(|(key, _)| property_matches(key, self.attribute_prefix(key), attribute))
+                .map(|(_, value)| value.to_string());
+        }
+        if expression == "text()" || expression == "." {
+            return self.content.as_deref().map(str::to_string);
+        }
+
+        let mut element = self;
+        for segment in expression.split('/').filter(|segment| !segment.is_empty()) {
+            let name = segment.rsplit_once(':').map_or(segment, |(_, local)| local);
+            element = element.children.iter().find(|child| *child.name == *name || *child.name == *segment)?;
+        }
+        element.content.as_deref().map(str::to_string)
+    }
+
+    // A copy that no longer borrows from the document, so that it can outlive it in the parse cache
+    fn into_owned(self) -> XmlElement<'static> {
+        let owned = |text: Cow<str>| Cow::Owned(text.into_owned());
+        let owned_map = |map: HashMap<Cow<str>, Cow<str>>| map.into_iter().map(|(key, value)| (owned(key), owned(value))).collect();
+        XmlElement {
+            name: self.name,
+            namespace: self.namespace,
+            prefix: self.prefix.map(owned),
+            attributes: owned_map(self.attributes),
+            attribute_prefixes: owned_map(self.attribute_prefixes),
+            content: self.content.map(owned),
+            cdata: self.cdata,
+            self_closing: self.self_closing,
+            children: self.children.into_iter().map(XmlElement::into_owned).collect(),
+        }
+    }
+
+    pub(crate) fn attribute_prefix(&self, key: &str) -> Option<&str> {
+        self.attribute_prefixes.get(key).map(|prefix| &**prefix)
+    }
+
+    // The element without its descendants, as reported on missing and extra diffs
+    fn snapshot(&self) -> XmlNodeSnapshot {
+        XmlNodeSnapshot {
+            name: self.name.to_string(),
+            namespace: self.namespace.as_deref().map(str::to_string),
+            attributes: self.attributes.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            text: self.content.as_deref().map(str::to_string),
+            children: self.children.iter().map(|child| child.name.to_string()).collect(),
+        }
+    }
+
+    // Start tag with the attributes in name order, such as `<trade id="4">`
+    fn start_tag(&self) -> String {
+        let mut attributes: Vec<_> = self.attributes.iter().collect();
+        attributes.sort();
+        let mut tag = format!("<{}", self.name);
+        for (key, value) in attributes {
+            let _ = write!(tag, " {}=\"{}\"", key, escape(value));
+        }
+        tag.push('>');
+        tag
+    }
+
+    fn similarity(&self, other: &XmlElement) -> usize {
+        let attributes = self
+            .attributes
+            .iter()
+            .filter(|(key, value)| other.attributes.get(*key) == Some(*value))
+            .count();
+        let content = usize::from(self.content == other.content) * 2;
+        let children = self
+            .children
+            .iter()
+            .filter(|child| other.children.iter().any(|c| c.name == child.name && c.content == child.content))
+            .count();
+        attributes + content + children
+    }
+}
+
+// Hands out one shared copy of each element name and namespace URI, as large documents repeat
+// the same few over and over
+#[derive(Default)]
+struct NameInterner {
+    names: HashSet<Arc<str>>,
+}
+
+impl NameInterner {
+    fn intern(&mut self, name: &str) -> Arc<str> {
+        if let Some(interned) = self.names.get(name) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(name);
+        self.names.insert(interned.clone());
+        interned
+    }
+}
+
+// A document's top-level elements, parsed for this comparison or shared from the parse cache
+enum ParsedRoots<'a> {
+    Parsed(Vec<XmlElement<'a>>),
+    Cached(Arc<Vec<XmlElement<'static>>>),
+}
+
+impl<'a> ParsedRoots<'a> {
+    // Options that reshape the tree work on a copy of a cached one
+    fn into_vec(self) -> Vec<XmlElement<'a>> {
+        match self {
+            Self::Parsed(roots) => roots,
+            Self::Cached(roots) => roots.to_vec(),
+        }
+    }
+}
+
+impl<'a> Deref for ParsedRoots<'a> {
+    type Target = [XmlElement<'a>];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Parsed(roots) => roots,
+            Self::Cached(roots) => roots.as_slice(),
+        }
+    }
+}
+
+// Children are summarised rather than dumped so that diffs on large subtrees stay readable
+impl fmt::Debug for XmlElement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XmlElement")
+            .field("name", &self.name)
+            .field("namespace", &self.namespace)
+            .field("prefix", &self.prefix)
+            .field("attributes", &self.attributes)
+            .field("content", &self.content)
+            .field("cdata", &self.cdata)
+            .field("self_closing", &self.self_closing)
+            .field("children", &self.children.len())
+            .finish()
+    }
+}
+
+// A path pattern from the request: a legacy exact/prefix/star path or an XPath expression
+enum PathSelector {
+    Path(String),
+    XPath(XPathPattern),
+}
+
+impl PathSelector {
+    fn parse(pattern: &str, namespace_mode: NamespaceMode) -> CompareResult<Self> {
+        if XPathPattern::is_xpath(pattern) {
+            return Ok(Self::XPath(XPathPattern::parse(pattern)?));
+        }
+        // Paths written with prefixes still apply when paths are built from local names
+        Ok(Self::Path(match namespace_mode {
+            NamespaceMode::Qualified => pattern.to_string(),
+            _ => strip_path_prefixes(pattern),
+        }))
+    }
+
+    fn parse_all(patterns: Option<&Vec<String>>, namespace_mode: NamespaceMode) -> CompareResult<Vec<Self>> {
+        patterns
+            .into_iter()
+            .flatten()
+            .map(|pattern| Self::parse(pattern, namespace_mode))
+            .collect()
+    }
+
+    fn selects_element(&self, path: &str, node: &Lineage) -> bool {
+        match self {
+            // Indexed paths also match their unindexed form
+            Self::Path(pattern) => path_pattern_matches(path, pattern) || path_pattern_matches(&strip_path_indexes(path), pattern),
+            Self::XPath(xpath) => xpath.attribute().is_none() && xpath.matches(node),
+        }
+    }
+
+    fn selects_attribute(&self, node: &Lineage, key: &str) -> bool {
+        match self {
+            Self::Path(_) => false,
+            Self::XPath(xpath) => {
+                let prefix = node.element.attribute_prefix(key);
+                xpath.attribute().is_some_and(|attribute| property_matches(key, prefix, attribute)) && xpath.matches(node)
+            }
+        }
+    }
+}
+
+struct ComparisonContext {
+    ignore_paths: Vec<PathSelector>,
+    ignore_properties: Vec<String>,
+    ignore_attributes: Vec<(PathSelector, String)>,
+    namespace_mode: NamespaceMode,
+    normalize_prefixes: bool,
+    ignore_element_order: bool,
+    match_keys: Vec<(String, String)>,
+    child_counts: Option<ChildCountMode>,
+    date_paths: Vec<PathSelector>,
+    date_formats: Vec<String>,
+    literal_paths: Vec<PathSelector>,
+    transforms: Vec<(Vec<PathSelector>, ValueTransformer)>,
+    value_comparators: Vec<ComparatorRule>,
+    scripts: Vec<(Vec<PathSelector>, ValueScript)>,
+    base64_paths: Vec<PathSelector>,
+    // Options for XML documents found in base64 payloads
+    payload_options: ComparisonOptions,
+    strict_cdata: bool,
+    strict_self_closing: bool,
+    similarity: Option<(SimilarityAlgorithm, f64)>,
+    element_stats: Option<StatsGrouping>,
+    // Compare the top-level subtrees on the rayon pool
+    parallel: bool,
+}
+
+// What `element_stats` counts an element under: its name, or each pattern selecting it
+enum StatsGrouping {
+    Name,
+    Paths(Vec<(String, PathSelector)>),
+}
+
+// What makes two siblings duplicates: their match key value, or their whole subtree
+#[derive(PartialEq)]
+enum DuplicateKey {
+    Key(String),
+    Subtree(u64),
+}
+
+struct MatchKey {
+    expression: String,
+    value: String,
+}
+
+// Pairing state for one set of siblings, shared by the successive matching passes
+struct SiblingMatching {
+    matches: Vec<Option<usize>>,
+    used: Vec<bool>,
+    settled1: Vec<bool>,
+    settled2: Vec<bool>,
+    keys1: Vec<Option<MatchKey>>,
+    keys2: Vec<Option<MatchKey>>,
+    reordered: Vec<bool>,
+}
+
+impl SiblingMatching {
+    fn new(len1: usize, len2: usize) -> Self {
+        Self {
+            matches: vec![None; len1],
+            used: vec![false; len2],
+            settled1: vec![false; len1],
+            settled2: vec![false; len2],
+            keys1: (0..len1).map(|_| None).collect(),
+            keys2: (0..len2).map(|_| None).collect(),
+            reordered: vec![false; len1],
+        }
+    }
+
+    fn pair(&mut self, i: usize, j: usize) {
+        self.matches[i] = Some(j);
+        self.used[j] = true;
+    }
+
+    fn available1(&self, i: usize) -> bool {
+        self.matches[i].is_none() && !self.settled1[i]
+    }
+
+    fn available2(&self, j: usize) -> bool {
+        !self.used[j] && !self.settled2[j]
+    }
+
+    // Document order of the first input, followed by unmatched siblings of the second
+    fn pairs(&self) -> Vec<(Option<usize>, Option<usize>)> {
+        let mut pairs: Vec<_> = self.matches.iter().enumerate().map(|(i, j)| (Some(i), *j)).collect();
+        pairs.extend(self.used.iter().enumerate().filter(|(_, used)| !**used).map(|(j, _)| (None, Some(j))));
+        pairs
+    }
+}
+
+struct ChildCount<'a> {
+    name: &'a str,
+    expected: usize,
+    actual: usize,
+    reported: bool,
+}
+
+// An unmatched subtree root and the range of diffs reported for its subtree
+struct UnmatchedSubtree {
+    path: String,
+    signature: u64,
+    diffs: Range<usize>,
+}
+
+#[derive(Default)]
+struct ComparisonOutcome {
+    diffs: Vec<XmlDiff>,
+    matched_elements: usize,
+    missing: Vec<UnmatchedSubtree>,
+    extra: Vec<UnmatchedSubtree>,
+    max_diffs: Option<usize>,
+    patch: Option<PatchBuilder>,
+    // Set once a diff beyond max_diffs is found; the comparison stops there
+    truncated: bool,
+    element_stats: HashMap<String, ElementStats>,
+}
+
+impl ComparisonOutcome {
+    fn push(&mut self, diff: XmlDiff) {
+        if self.max_diffs.is_some_and(|max| self.diffs.len() >= max) {
+            self.truncated = true;
+        } else {
+            self.diffs.push(diff);
+        }
+    }
+
+    fn count_element(&mut self, groups: Vec<String>, count: fn(&mut ElementStats)) {
+        for group in groups {
+            let stats = self.element_stats.entry(group.clone()).or_insert_with(|| ElementStats { group, ..Default::default() });
+            count(stats);
+        }
+    }
+
+    // Appends the results of a subtree compared on its own, as if they had been found here
+    #[cfg(feature = "parallel")]
+    fn merge(&mut self, branch: ComparisonOutcome) {
+        let offset = self.diffs.len();
+        let shift = |subtree: UnmatchedSubtree| UnmatchedSubtree {
+            diffs: subtree.diffs.start + offset..subtree.diffs.end + offset,
+            ..subtree
+        };
+        self.diffs.extend(branch.diffs);
+        self.matched_elements += branch.matched_elements;
+        self.missing.extend(branch.missing.into_iter().map(shift));
+        self.extra.extend(branch.extra.into_iter().map(shift));
+        for (group, counts) in branch.element_stats {
+            let stats = self.element_stats.entry(group).or_insert_with(|| ElementStats { group: counts.group.clone(), ..Default::default() });
+            stats.matched += counts.matched;
+            stats.differed += counts.differed;
+            stats.missing += counts.missing;
+            stats.extra += counts.extra;
+        }
+    }
+
+    // Groups with the most differing instances first
+    fn element_stats(&mut self) -> Vec<ElementStats> {
+        let differing = |stats: &ElementStats| stats.differed + stats.missing + stats.extra;
+        let mut stats: Vec<_> = self.element_stats.drain().map(|(_, stats)| stats).collect();
+        stats.sort_by(|a, b| differing(b).cmp(&differing(a)).then_with(|| a.group.cmp(&b.group)));
+        stats
+    }
+
+    // Collapses each missing subtree that reappears unchanged elsewhere in the second document,
+    // together with its extra counterpart, into a single ElementMoved diff
+    fn resolve_moves(&mut self) {
+        let mut moved_to: HashMap<usize, XmlDiff> = HashMap::new();
+        let mut removed = vec![false; self.diffs.len()];
+        let mut used = vec![false; self.extra.len()];
+
+        for missing in &self.missing {
+            let Some(j) = (0..self.extra.len())
+                .find(|&j| !used[j] && self.extra[j].signature == missing.signature && self.extra[j].path != missing.path)
+            else {
+                continue;
+            };
+            used[j] = true;
+            let extra = &self.extra[j];
+            for index in missing.diffs.clone().chain(extra.diffs.clone()) {
+                removed[index] = true;
+            }
+            moved_to.insert(
+                missing.diffs.start,
+                XmlDiff {
+                    path: missing.path.clone(),
+                    diff_type: DiffType::ElementMoved,
+                    expected: Some(missing.path.clone()),
+                    actual: Some(extra.path.clone()),
+                    message: format!("Element moved from {} to {}", missing.path, extra.path),
+                    similarity: None,
+                    severity: DiffSeverity::Error,
+                    tags: Vec::new(),
+                    node: None,
+                },
+            );
+        }
+
+        let diffs = std::mem::take(&mut self.diffs);
+        for (index, diff) in diffs.into_iter().enumerate() {
+            if let Some(moved) = moved_to.remove(&index) {
+                self.diffs.push(moved);
+            }
+            if !removed[index] {
+                self.diffs.push(diff);
+            }
+        }
+    }
+}
+
+// Documents smaller than this are compared on the calling thread, as splitting them up costs more
+// than it saves
+const PARALLEL_MIN_ELEMENTS: usize = 10_000;
+
+// A dense document holds an element every few dozen bytes; each parsed element with its maps and
+// child list, and its share of the walk's working state, takes a few hundred
+const BYTES_PER_ELEMENT: usize = 32;
+const MEMORY_PER_ELEMENT: usize = 256;
+
+/// Rough peak memory, in bytes, of comparing two documents of `document_bytes` in total: the text
+/// itself plus the parsed trees, taking the documents to be densely tagged
+pub fn estimate_comparison_memory(document_bytes: usize) -> usize {
+    document_bytes.saturating_add((document_bytes / BYTES_PER_ELEMENT).saturating_mul(MEMORY_PER_ELEMENT))
+}
+
+const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+const SOAP_NAMESPACES: [&str; 2] = ["http://schemas.xmlsoap.org/soap/envelope/", "http://www.w3.org/2003/05/soap-envelope"];
+const XSI_NIL: &str = "{http://www.w3.org/2001/XMLSchema-instance}nil";
+
+// Canonical fingerprint of a document: equal hashes mean the documents parse to the same tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct DocumentDigest {
+    hash: [u8; 32],
+    stats: DocumentStats,
+}
+
+// Hashes the events the parser reads, with attributes sorted and text unescaped and trimmed, so
+// that formatting differences the comparison never reports do not change the hash. Every field is
+// tagged and length-prefixed, so that different event streams cannot be fed the same bytes.
+#[derive(Default)]
+struct DigestBuilder {
+    hasher: Sha256,
+    stats: DocumentStats,
+    depth: usize,
+}
+
+impl DigestBuilder {
+    fn field(&mut self, tag: u8, bytes: &[u8]) {
+        self.hasher.update([tag]);
+        self.hasher.update((bytes.len() as u64).to_le_bytes());
+        self.hasher.update(bytes);
+    }
+
+    fn event(&mut self, event: &Event) {
+        match event {
+            Event::Start(e) | Event::Empty(e) => {
+                self.stats.elements += 1;
+                self.stats.max_depth = self.stats.max_depth.max(self.depth + 1);
+                let tag = if matches!(event, Event::Start(_)) {
+                    self.depth += 1;
+                    0
+                } else {
+                    1
+                };
+                let mut attributes: Vec<(Vec<u8>, String)> = e
+                    .attributes()
+                    .flatten()
+                    .map(|attr| {
+                        let value = attr
+                            .unescape_value()
+                            .map(|v| v.into_owned())
+                            .unwrap_or_else(|_| String::from_utf8_lossy(&attr.value).into_owned());
+                        (attr.key.into_inner().to_vec(), value)
+                    })
+                    .collect();
+                self.stats.attributes += attributes.iter().filter(|(key, _)| QName(key).as_namespace_binding().is_none()).count();
+                attributes.sort();
+                self.field(tag, e.name().as_ref());
+                self.hasher.update((attributes.len() as u64).to_le_bytes());
+                for (key, value) in &attributes {
+                    self.field(2, key);
+                    self.field(3, value.as_bytes());
+                }
+            }
+            Event::End(_) => {
+                self.depth = self.depth.saturating_sub(1);
+                self.hasher.update([4]);
+            }
+            Event::Text(e) => {
+                let text = e.unescape().unwrap_or_else(|_| String::from_utf8_lossy(e));
+                self.stats.text_bytes += text.trim().len();
+                self.field(5, text.trim().as_bytes());
+            }
+            Event::CData(e) => {
+                let text = String::from_utf8_lossy(e);
+                self.stats.text_bytes += text.trim().len();
+                self.field(6, text.trim().as_bytes());
+            }
+            // Entity definitions change how references in the text expand
+            Event::DocType(e) => self.field(7, e),
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> DocumentDigest {
+        DocumentDigest { hash: self.hasher.finalize().into(), stats: self.stats }
+    }
+}
+
+/// Caps on the size of the documents a service parses, guarding it against maliciously deep or
+/// wide input; 0 leaves a dimension unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseLimits {
+    /// Deepest element nesting, counting the root as 1
+    pub max_depth: usize,
+    /// Most elements in one document
+    pub max_elements: usize,
+    /// Most attributes in one document, not counting namespace declarations
+    pub max_attributes: usize,
+}
+
+impl ParseLimits {
+    // `counts` may describe the part of a document read so far, so that parsing stops at the first
+    // element over a limit
+    fn check(&self, counts: &DocumentStats) -> CompareResult<()> {
+        let over = |count: usize, limit: usize| limit > 0 && count > limit;
+        let message = if over(counts.max_depth, self.max_depth) {
+            format!("Document is nested deeper than the limit of {} levels", self.max_depth)
+        } else if over(counts.elements, self.max_elements) {
+            format!("Document has more than the limit of {} elements", self.max_elements)
+        } else if over(counts.attributes, self.max_attributes) {
+            format!("Document has more than the limit of {} attributes", self.max_attributes)
+        } else {
+            return Ok(());
+        };
+        Err(CompareError::ValidationError(message))
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct XmlComparisonService {
+    digests: Arc<DigestCache>,
+    parsed: Arc<ParseCache>,
+    limits: ParseLimits,
+    comparators: HashMap<String, Arc<dyn ValueComparator>>,
+}
+
+impl XmlComparisonService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a comparator that the `value_comparators` option can then name
+    pub fn with_comparator(mut self, name: impl Into<String>, comparator: impl ValueComparator + 'static) -> Self {
+        self.comparators.insert(name.into(), Arc::new(comparator));
+        self
+    }
+
+    /// Keeps parsed documents, up to `capacity_bytes` of estimated memory, so that a document
+    /// compared repeatedly is parsed once; 0 (the default) turns the cache off
+    pub fn with_parse_cache(mut self, capacity_bytes: usize) -> Self {
+        self.parsed = Arc::new(ParseCache::new(capacity_bytes));
+        self
+    }
+
+    /// Rejects documents over the given limits with a validation error before they are compared
+    pub fn with_parse_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Names of the registered comparators, sorted
+    pub fn comparator_names(&self) -> Vec<&str> {
+        let mut names: Vec<_> = self.comparators.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn compare_with_options(
+        &self,
+        xml1: &str,
+        xml2: &str,
+        options: &ComparisonOptions,
+    ) -> CompareResult<XmlComparisonResponse> {
+        if options.mode == Some(ComparisonMode::Verdict) {
+            return self.compare_verdict(xml1, xml2, options);
+        }
+        let mut result = self.compare_unshaped(xml1, xml2, options)?;
+        result.shape(options);
+        Ok(result)
+    }
+
+    // Decides only whether the documents match. Identical documents short-circuit on their
+    // digests; otherwise a walk that builds no diffs stops at the first difference. The result
+    // carries `matched`, with a `match_ratio` of 1 or 0, and is then shaped like any other.
+    fn compare_verdict(&self, xml1: &str, xml2: &str, options: &ComparisonOptions) -> CompareResult<XmlComparisonResponse> {
+        let full_only = [
+            ("generate_patch", options.generate_patch == Some(true)),
+            ("output_format unified", options.output_format == Some(OutputFormat::Unified)),
+            ("element_stats", options.element_stats == Some(true) || options.element_stats_paths.is_some()),
+            ("classifications", options.classifications.as_ref().is_some_and(|rules| !rules.is_empty())),
+            ("detect_duplicates", options.detect_duplicates == Some(true)),
+        ];
+        if let Some((option, _)) = full_only.iter().find(|(_, requested)| *requested) {
+            return Err(CompareError::ValidationError(format!("{} needs mode full; mode verdict produces no diffs", option)));
+        }
+
+        let context = self.comparison_context(options)?;
+        let (xml1, xml2) = self.input_documents(xml1, xml2, options)?;
+        let scoped = is_scoped(options);
+        let key1 = content_hash(&xml1);
+        let key2 = if xml2 == xml1 { key1 } else { content_hash(&xml2) };
+        let matched = match self.known_digests((key1, key2), &xml1, !scoped)? {
+            Some((digest1, digest2)) if !scoped && digest1.hash == digest2.hash => true,
+            _ => {
+                let (xml1_roots, xml2_roots, (digest1, digest2)) = self.parse_documents(options, &xml1, &xml2)?;
+                self.digests.insert(key1, digest1);
+                self.digests.insert(key2, digest2);
+                (!scoped && digest1.hash == digest2.hash)
+                    || self.children_equal(&mut String::new(), (None, None), &xml1_roots, &xml2_roots, &context)
+            }
+        };
+
+        let mut result = XmlComparisonResponse { matched, match_ratio: if matched { 1.0 } else { 0.0 }, ..Default::default() };
+        result.shape(options);
+        Ok(result)
+    }
+
+    // The full result, before the response-shaping options drop any of it
+    fn compare_unshaped(
+        &self,
+        xml1: &str,
+        xml2: &str,
+        options: &ComparisonOptions,
+    ) -> CompareResult<XmlComparisonResponse> {
+        let mut context = self.comparison_context(options)?;
+        let classifier = DiffClassifier::compile(options.classifications.as_deref().unwrap_or_default())?;
+        let (xml1, xml2) = self.input_documents(xml1, xml2, options)?;
+        let unified_diff = match options.output_format.unwrap_or_default() {
+            OutputFormat::Unified => Some(unified_diff(&xml1, &xml2)?),
+            OutputFormat::Structured | OutputFormat::Junit | OutputFormat::Sarif | OutputFormat::Markdown => None,
+        };
+
+        // Identical documents cannot produce diffs under any option, so skip building and walking the trees.
+        // Scoped comparisons, duplicate detection and element statistics still parse, as only the
+        // selected subtrees count toward the totals and identical documents can both contain
+        // duplicates and still need their elements counted.
+        let detect_duplicates = options.detect_duplicates.unwrap_or(false);
+        let parse = is_scoped(options) || detect_duplicates || context.element_stats.is_some();
+        let key1 = content_hash(&xml1);
+        let key2 = if xml2 == xml1 { key1 } else { content_hash(&xml2) };
+        if let Some((digest1, digest2)) = self.known_digests((key1, key2), &xml1, !parse)?
+            && !parse
+            && digest1.hash == digest2.hash
+        {
+            return Ok(identical_documents(digest1, unified_diff, options));
+        }
+
+        // Pairs not seen before are digested while they are parsed, and identical ones still skip the walk
+        let (xml1_roots, xml2_roots, (digest1, digest2)) = self.parse_documents(options, &xml1, &xml2)?;
+        self.digests.insert(key1, digest1);
+        self.digests.insert(key2, digest2);
+        if !parse && digest1.hash == digest2.hash {
+            return Ok(identical_documents(digest1, unified_diff, options));
+        }
+        let document_stats = DocumentPairStats { xml1: digest1.stats, xml2: digest2.stats };
+
+        let mut warnings = Vec::new();
+        if detect_duplicates {
+            self.find_duplicates("xml1", "", None, &xml1_roots, &context, &mut warnings);
+            self.find_duplicates("xml2", "", None, &xml2_roots, &context, &mut warnings);
+        }
+
+        let mut outcome = ComparisonOutcome {
+            max_diffs: options.max_diffs,
+            patch: options.generate_patch.unwrap_or(false).then(PatchBuilder::new),
+            ..Default::default()
+        };
+        let count = |roots: &[XmlElement]| roots.iter().map(XmlElement::count).sum::<usize>();
+        let total_elements = count(&xml1_roots).max(count(&xml2_roots));
+        // max_diffs and patches depend on the order diffs are found in, so they keep the walk sequential
+        context.parallel = cfg!(feature = "parallel")
+            && total_elements >= PARALLEL_MIN_ELEMENTS
+            && outcome.max_diffs.is_none()
+            && outcome.patch.is_none();
+        self.compare_children(&mut String::new(), (None, None), &xml1_roots, &xml2_roots, &context, &mut outcome);
+        outcome.resolve_moves();
+
+        let matched_elements = outcome.matched_elements;
+        let truncated = outcome.truncated;
+        let element_stats = outcome.element_stats();
+        let patch = outcome.patch.map(PatchBuilder::finish);
+        let mut diffs = outcome.diffs;
+        let tag_counts = classifier.classify(&mut diffs);
+
+        let match_ratio = if total_elements > 0 {
+            matched_elements as f64 / total_elements as f64
+        } else {
+            1.0
+        };
+
+        Ok(XmlComparisonResponse {
+            id: None,
+            matched: diffs.is_empty() && !truncated,
+            match_ratio,
+            diffs,
+            total_elements,
+            matched_elements,
+            truncated,
+            patch,
+            unified_diff,
+            warnings,
+            sources: Vec::new(),
+            element_stats,
+            tag_counts,
+            document_stats: Some(document_stats),
+            diff_fields: None,
+            output_format: None,
+        })
+    }
+
+    fn comparison_context(&self, options: &ComparisonOptions) -> CompareResult<ComparisonContext> {
+        let namespace_mode = options.namespace_mode.unwrap_or_default();
+
+        if let Some(threshold) = options.similarity_threshold
+            && !(0.0..=1.0).contains(&threshold)
+        {
+            return Err(CompareError::ValidationError("similarity_threshold must be between 0 and 1".to_string()));
+        }
+
+        Ok(ComparisonContext {
+            ignore_paths: PathSelector::parse_all(options.ignore_paths.as_ref(), namespace_mode)?,
+            ignore_properties: options
+                .ignore_properties
+                .iter()
+                .flatten()
+                .filter(|prop| scoped_attribute(prop).is_none())
+                .cloned()
+                .collect(),
+            ignore_attributes: options
+                .ignore_properties
+                .iter()
+                .flatten()
+                .filter_map(|prop| scoped_attribute(prop))
+                .map(|(path, attribute)| Ok((PathSelector::parse(path, namespace_mode)?, attribute.to_string())))
+                .collect::<CompareResult<_>>()?,
+            namespace_mode,
+            normalize_prefixes: options.normalize_prefixes.unwrap_or(false),
+            ignore_element_order: options.ignore_element_order.unwrap_or(false),
+            match_keys: options
+                .match_keys
+                .iter()
+                .flatten()
+                .map(|(path, expression)| match namespace_mode {
+                    NamespaceMode::Qualified => (path.clone(), expression.clone()),
+                    _ => (strip_path_prefixes(path), expression.clone()),
+                })
+                .collect(),
+            child_counts: options.child_counts,
+            date_paths: PathSelector::parse_all(options.date_paths.as_ref(), namespace_mode)?,
+            date_formats: options.date_formats.clone().unwrap_or_default(),
+            literal_paths: PathSelector::parse_all(options.literal_paths.as_ref(), namespace_mode)?,
+            transforms: options
+                .transforms
+                .iter()
+                .flatten()
+                .map(|transform| {
+                    let selectors = PathSelector::parse_all(Some(&transform.paths), namespace_mode)?;
+                    Ok((selectors, ValueTransformer::compile(&transform.rule)?))
+                })
+                .collect::<CompareResult<_>>()?,
+            value_comparators: self.value_comparators(options, namespace_mode)?,
+            scripts: options
+                .scripts
+                .iter()
+                .flatten()
+                .map(|rule| {
+                    let selectors = PathSelector::parse_all(Some(&rule.paths), namespace_mode)?;
+                    Ok((selectors, ValueScript::compile(&rule.script)?))
+                })
+                .collect::<CompareResult<_>>()?,
+            base64_paths: PathSelector::parse_all(options.base64_paths.as_ref(), namespace_mode)?,
+            payload_options: match options.base64_paths {
+                Some(_) => ComparisonOptions {
+                    compare_root_path1: None,
+                    compare_root_path2: None,
+                    soap: None,
+                    max_diffs: None,
+                    generate_patch: None,
+                    output_format: None,
+                    include_diffs: None,
+                    diff_fields: None,
+                    exclude_matched_details: None,
+                    element_stats: None,
+                    element_stats_paths: None,
+                    classifications: None,
+                    ..options.clone()
+                },
+                None => ComparisonOptions::default(),
+            },
+            strict_cdata: options.strict_cdata.unwrap_or(false),
+            strict_self_closing: options.strict_self_closing.unwrap_or(false),
+            similarity: options
+                .similarity_threshold
+                .map(|threshold| (options.similarity_algorithm.unwrap_or_default(), threshold)),
+            element_stats: match &options.element_stats_paths {
+                Some(patterns) => Some(StatsGrouping::Paths(
+                    patterns
+                        .iter()
+                        .map(|pattern| Ok((pattern.clone(), PathSelector::parse(pattern, namespace_mode)?)))
+                        .collect::<CompareResult<_>>()?,
+                )),
+                None => options.element_stats.unwrap_or(false).then_some(StatsGrouping::Name),
+            },
+            parallel: false,
+        })
+    }
+
+    /// Runs the comparison and renders both documents with the result as a standalone HTML page
+    pub fn render_report(&self, xml1: &str, xml2: &str, options: &ComparisonOptions) -> CompareResult<String> {
+        let result = self.compare_unshaped(xml1, xml2, options)?;
+
+        let (xml1, xml2) = self.input_documents(xml1, xml2, options)?;
+        let (xml1_roots, xml2_roots, _) = self.parse_documents(options, &xml1, &xml2)?;
+
+        Ok(render_report(&xml1_roots, &xml2_roots, &result))
+    }
+
+    // Sorted by name, so that when two comparators select a value the same one always goes first
+    fn value_comparators(
+        &self,
+        options: &ComparisonOptions,
+        namespace_mode: NamespaceMode,
+    ) -> CompareResult<Vec<ComparatorRule>> {
+        let mut rules: Vec<_> = options.value_comparators.iter().flatten().collect();
+        rules.sort_by_key(|(name, _)| name.as_str());
+        rules
+            .into_iter()
+            .map(|(name, paths)| {
+                let comparator = self.comparators.get(name).ok_or_else(|| {
+                    CompareError::ValidationError(format!(
+                        "Unknown value comparator '{}'; registered: {}",
+                        name,
+                        self.comparator_names().join(", ")
+                    ))
+                })?;
+                Ok((PathSelector::parse_all(Some(paths), namespace_mode)?, comparator.clone()))
+            })
+            .collect()
+    }
+
+    fn input_documents<'a>(
+        &self,
+        xml1: &'a str,
+        xml2: &'a str,
+        options: &ComparisonOptions,
+    ) -> CompareResult<(Cow<'a, str>, Cow<'a, str>)> {
+        if options.canonicalize.unwrap_or(false) {
+            Ok((Cow::Owned(canonicalize(xml1)?), Cow::Owned(canonicalize(xml2)?)))
+        } else {
+            Ok((Cow::Borrowed(xml1), Cow::Borrowed(xml2)))
+        }
+    }
+
+    // Both documents' digests when they are known without parsing: remembered from earlier pairs,
+    // or read in one pass when `read_identical` and the documents are byte-identical
+    fn known_digests(
+        &self,
+        (key1, key2): (ContentHash, ContentHash),
+        xml: &str,
+        read_identical: bool,
+    ) -> CompareResult<Option<(DocumentDigest, DocumentDigest)>> {
+        let mut digests = self.digests.get(&key1).zip(self.digests.get(&key2));
+        if digests.is_none() && read_identical && key1 == key2 && let Some(digest) = canonical_digest(xml) {
+            self.digests.insert(key1, digest);
+            digests = Some((digest, digest));
+        }
+        if let Some((digest1, digest2)) = digests {
+            self.limits.check(&digest1.stats)?;
+            self.limits.check(&digest2.stats)?;
+        }
+        Ok(digests)
+    }
+
+    // Parses both documents and narrows them to the subtrees selected by the compare_root_path options
+    fn parse_documents<'a>(
+        &self,
+        options: &ComparisonOptions,
+        xml1: &'a str,
+        xml2: &'a str,
+    ) -> CompareResult<(ParsedRoots<'a>, ParsedRoots<'a>, (DocumentDigest, DocumentDigest))> {
+        let namespace_mode = options.namespace_mode.unwrap_or_default();
+        let expand_entities = options.expand_entities.unwrap_or(true);
+        // Both documents share one URI -> prefix map, so the second uses the first one's prefixes
+        let mut prefix_map = PrefixMap::new();
+        let mut prefixes = options.normalize_prefixes.unwrap_or(false).then_some(&mut prefix_map);
+        let mut names = NameInterner::default();
+        let (xml1_roots, digest1) = self.parse_cached(xml1, namespace_mode, expand_entities, prefixes.as_deref_mut(), &mut names)?;
+        let (xml2_roots, digest2) = self.parse_cached(xml2, namespace_mode, expand_entities, prefixes, &mut names)?;
+        let soap = options.soap.unwrap_or(false);
+        if options.xsi_nil.is_none() && !soap && options.compare_root_path1.is_none() && options.compare_root_path2.is_none() {
+            return Ok((xml1_roots, xml2_roots, (digest1, digest2)));
+        }
+
+        let (mut xml1_roots, mut xml2_roots) = (xml1_roots.into_vec(), xml2_roots.into_vec());
+        if let Some(mode) = options.xsi_nil {
+            apply_nil_mode(&mut xml1_roots, mode);
+            apply_nil_mode(&mut xml2_roots, mode);
+        }
+        if soap {
+            let headers = options.soap_headers.unwrap_or(false);
+            xml1_roots = soap_payloads(xml1_roots, headers, namespace_mode);
+            xml2_roots = soap_payloads(xml2_roots, headers, namespace_mode);
+        }
+        Ok((
+            ParsedRoots::Parsed(select_roots(xml1_roots, options.compare_root_path1.as_deref(), "compare_root_path1", namespace_mode)?),
+            ParsedRoots::Parsed(select_roots(xml2_roots, options.compare_root_path2.as_deref(), "compare_root_path2", namespace_mode)?),
+            (digest1, digest2),
+        ))
+    }
+
+    // Normalized prefixes depend on the other document of the pair, so those trees are not cached
+    fn parse_cached<'a>(
+        &self,
+        xml_content: &'a str,
+        namespace_mode: NamespaceMode,
+        expand_entities: bool,
+        prefixes: Option<&mut PrefixMap>,
+        names: &mut NameInterner,
+    ) -> CompareResult<(ParsedRoots<'a>, DocumentDigest)> {
+        if !self.parsed.enabled() || prefixes.is_some() {
+            let (roots, digest) = self.parse_xml(xml_content, namespace_mode, expand_entities, prefixes, names)?;
+            return Ok((ParsedRoots::Parsed(roots), digest));
+        }
+        let key = ParseKey::new(xml_content, namespace_mode, expand_entities);
+        if let Some((roots, digest)) = self.parsed.get(&key) {
+            // The tree may have been kept by a service with other limits
+            self.limits.check(&digest.stats)?;
+            return Ok((ParsedRoots::Cached(roots), digest));
+        }
+        let (roots, digest) = self.parse_xml(xml_content, namespace_mode, expand_entities, None, names)?;
+        let roots = Arc::new(roots.into_iter().map(XmlElement::into_owned).collect::<Vec<_>>());
+        self.parsed.insert(key, roots.clone(), digest);
+        Ok((ParsedRoots::Cached(roots), digest))
+    }
+
+    // `path` holds the parents' path; each child's segment is appended to it while the child is
+    // compared and removed afterwards, so that matched elements need no path of their own
+    fn compare_children(
+        &self,
+        path: &mut String,
+        parents: (Option<&Lineage>, Option<&Lineage>),
+        children1: &[XmlElement],
+        children2: &[XmlElement],
+        context: &ComparisonContext,
+        outcome: &mut ComparisonOutcome,
+    ) {
+        let matching = self.match_children(path, children1, children2, context);
+        let paths1 = SiblingPaths::new(children1, children2);
+        let paths2 = SiblingPaths::new(children2, children1);
+        let node1 = |i: usize| Lineage { element: &children1[i], position: paths1.position(i), parent: parents.0 };
+        let node2 = |j: usize| Lineage { element: &children2[j], position: paths2.position(j), parent: parents.1 };
+
+        if let Some(patch) = outcome.patch.as_mut() {
+            let removable: Vec<bool> =
+                (0..children1.len()).map(|i| !self.is_ignored(&paths1.path(path, i), &node1(i), context)).collect();
+            let addable: Vec<bool> =
+                (0..children2.len()).map(|j| !self.is_ignored(&paths2.path(path, j), &node2(j), context)).collect();
+            let moved: Vec<bool> = matching.reordered.iter().zip(&removable).map(|(reordered, removable)| *reordered && *removable).collect();
+            patch.children(parents.1, children1, children2, &matching.matches, &moved, &removable, &addable);
+        }
+
+        let count_differs = match context.child_counts {
+            Some(_) => self.report_child_counts(path, parents, children1, children2, context, outcome),
+            None => Vec::new(),
+        };
+        let collapsed = |element: &XmlElement| {
+            context.child_counts == Some(ChildCountMode::Collapse) && count_differs.contains(&&*element.name)
+        };
+        let push_path1 = |path: &mut String, i: usize| match &matching.keys1[i] {
+            Some(key) => push_keyed(path, &children1[i].name, key),
+            None => paths1.push(path, i),
+        };
+
+        let parent_length = path.len();
+        let compare_pair = |pair: (Option<usize>, Option<usize>), path: &mut String, outcome: &mut ComparisonOutcome| {
+            match pair {
+            (Some(i), Some(j)) => {
+                push_path1(path, i);
+                let (node1, node2) = (node1(i), node2(j));
+                let mut groups = self.stats_groups(path, &node1, context);
+                if !groups.is_empty() && self.is_ignored(path, &node2, context) {
+                    groups.clear();
+                }
+                let found = outcome.diffs.len();
+                if matching.reordered[i] && !self.is_ignored(path, &node1, context) {
+                    outcome.push(XmlDiff {
+                        path: path.clone(),
+                        diff_type: DiffType::ElementReordered,
+                        expected: Some(format!("position {}", node1.position)),
+                        actual: Some(format!("position {}", node2.position)),
+                        message: format!("Element moved from position {} to {}", node1.position, node2.position),
+                        similarity: None,
+                        severity: DiffSeverity::Error,
+                        tags: Vec::new(),
+                        node: None,
+                    });
+                }
+                let element_diffs = self.create_element_diffs(path, &node1, &node2, context, outcome.patch.as_mut());
+                if element_diffs.is_empty() {
+                    outcome.matched_elements += 1;
+                } else {
+                    element_diffs.into_iter().for_each(|diff| outcome.push(diff));
+                }
+                self.compare_children(
+                    path,
+                    (Some(&node1), Some(&node2)),
+                    &children1[i].children,
+                    &children2[j].children,
+                    context,
+                    outcome,
+                );
+                if outcome.diffs.len() > found || outcome.truncated {
+                    outcome.count_element(groups, |stats| stats.differed += 1);
+                } else {
+                    outcome.count_element(groups, |stats| stats.matched += 1);
+                }
+            }
+            (Some(i), None) if collapsed(&children1[i]) => {}
+            (None, Some(j)) if collapsed(&children2[j]) => {}
+            (Some(i), None) => {
+                push_path1(path, i);
+                self.report_unmatched(path, &node1(i), DiffType::ElementMissing, context, outcome);
+            }
+            (None, Some(j)) => {
+                match &matching.keys2[j] {
+                    Some(key) => push_keyed(path, &children2[j].name, key),
+                    None => paths2.push(path, j),
+                }
+                self.report_unmatched(path, &node2(j), DiffType::ElementExtra, context, outcome);
+            }
+            (None, None) => {}
+            }
+            path.truncate(parent_length);
+        };
+
+        // Each top-level subtree is compared into an outcome of its own; merging them in sibling
+        // order reports the diffs in the order of a sequential walk
+        #[cfg(feature = "parallel")]
+        if context.parallel && parents.0.is_some_and(|root| root.parent.is_none()) {
+            use rayon::prelude::*;
+            let branches: Vec<ComparisonOutcome> = matching
+                .pairs()
+                .into_par_iter()
+                .map(|pair| {
+                    let mut branch = ComparisonOutcome::default();
+                    compare_pair(pair, &mut path.clone(), &mut branch);
+                    branch
+                })
+                .collect();
+            branches.into_iter().for_each(|branch| outcome.merge(branch));
+            return;
+        }
+
+        for pair in matching.pairs() {
+            if outcome.truncated {
+                return;
+            }
+            compare_pair(pair, path, outcome);
+        }
+    }
+
+    fn match_children(
+        &self,
+        parent_path: &str,
+        children1: &[XmlElement],
+        children2: &[XmlElement],
+        context: &ComparisonContext,
+    ) -> SiblingMatching {
+        let mut matching = SiblingMatching::new(children1.len(), children2.len());
+        self.match_children_by_key(parent_path, children1, children2, context, &mut matching);
+        if context.ignore_element_order {
+            self.match_children_unordered(children1, children2, &mut matching);
+        } else {
+            self.match_children_reordered(children1, children2, &mut matching);
+            self.match_children_positional(children1, children2, &mut matching);
+        }
+        matching
+    }
+
+    // Whether compare_children would find no diff, decided without building any: siblings are
+    // paired the same way, and the walk stops at the first difference
+    fn children_equal(
+        &self,
+        path: &mut String,
+        parents: (Option<&Lineage>, Option<&Lineage>),
+        children1: &[XmlElement],
+        children2: &[XmlElement],
+        context: &ComparisonContext,
+    ) -> bool {
+        let matching = self.match_children(path, children1, children2, context);
+        let paths1 = SiblingPaths::new(children1, children2);
+        let paths2 = SiblingPaths::new(children2, children1);
+        let node1 = |i: usize| Lineage { element: &children1[i], position: paths1.position(i), parent: parents.0 };
+        let node2 = |j: usize| Lineage { element: &children2[j], position: paths2.position(j), parent: parents.1 };
+
+        let mut collapsed = Vec::new();
+        if context.child_counts.is_some() {
+            let counts = self.differing_child_counts(path, parents, children1, children2, context);
+            if counts.iter().any(|count| count.reported) {
+                return false;
+            }
+            if context.child_counts == Some(ChildCountMode::Collapse) {
+                collapsed = counts.into_iter().map(|count| count.name).collect();
+            }
+        }
+
+        let parent_length = path.len();
+        for pair in matching.pairs() {
+            let equal = match pair {
+                (Some(i), Some(j)) => {
+                    match &matching.keys1[i] {
+                        Some(key) => push_keyed(path, &children1[i].name, key),
+                        None => paths1.push(path, i),
+                    }
+                    let (node1, node2) = (node1(i), node2(j));
+                    (!matching.reordered[i] || self.is_ignored(path, &node1, context))
+                        && self.element_equal(path, &node1, &node2, context)
+                        && self.children_equal(path, (Some(&node1), Some(&node2)), &children1[i].children, &children2[j].children, context)
+                }
+                (Some(i), None) if collapsed.contains(&&*children1[i].name) => true,
+                (None, Some(j)) if collapsed.contains(&&*children2[j].name) => true,
+                (Some(i), None) => {
+                    match &matching.keys1[i] {
+                        Some(key) => push_keyed(path, &children1[i].name, key),
+                        None => paths1.push(path, i),
+                    }
+                    self.subtree_ignored(path, &node1(i), context)
+                }
+                (None, Some(j)) => {
+                    match &matching.keys2[j] {
+                        Some(key) => push_keyed(path, &children2[j].name, key),
+                        None => paths2.push(path, j),
+                    }
+                    self.subtree_ignored(path, &node2(j), context)
+                }
+                (None, None) => true,
+            };
+            path.truncate(parent_length);
+            if !equal {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Emits a ChildCountDifferent diff for each child name whose count differs between the two
+    // parents and returns those names
+    fn report_child_counts<'a>(
+        &self,
+        parent_path: &str,
+        parents: (Option<&Lineage>, Option<&Lineage>),
+        children1: &'a [XmlElement],
+        children2: &'a [XmlElement],
+        context: &ComparisonContext,
+        outcome: &mut ComparisonOutcome,
+    ) -> Vec<&'a str> {
+        let counts = self.differing_child_counts(parent_path, parents, children1, children2, context);
+        for count in counts.iter().filter(|count| count.reported) {
+            outcome.push(XmlDiff {
+                path: format!("{}/{}", parent_path, count.name),
+                diff_type: DiffType::ChildCountDifferent,
+                expected: Some(count.expected.to_string()),
+                actual: Some(count.actual.to_string()),
+                message: format!("Expected {} '{}' element(s) but found {}", count.expected, count.name, count.actual),
+                similarity: None,
+                severity: DiffSeverity::Error,
+                tags: Vec::new(),
+                node: None,
+            });
+        }
+        counts.into_iter().map(|count| count.name).collect()
+    }
+
+    // Each child name whose count differs between the two parents; the difference is reported
+    // unless every child of that name is ignored
+    fn differing_child_counts<'a>(
+        &self,
+        parent_path: &str,
+        parents: (Option<&Lineage>, Option<&Lineage>),
+        children1: &'a [XmlElement],
+        children2: &'a [XmlElement],
+        context: &ComparisonContext,
+    ) -> Vec<ChildCount<'a>> {
+        // Counts of ignored children are not reported
+        let all_ignored = |children: &[XmlElement], others: &[XmlElement], parent: Option<&Lineage>, indexes: &[usize]| {
+            let paths = SiblingPaths::new(children, others);
+            indexes.iter().all(|&i| {
+                let node = Lineage { element: &children[i], position: paths.position(i), parent };
+                self.is_ignored(&paths.path(parent_path, i), &node, context)
+            })
+        };
+
+        let mut names: Vec<&str> = Vec::new();
+        for element in children1.iter().chain(children2) {
+            if !names.contains(&element.name.as_ref()) {
+                names.push(&element.name);
+            }
+        }
+
+        let mut differing = Vec::new();
+        for name in names {
+            let indexes = |children: &[XmlElement]| (0..children.len()).filter(|&i| *children[i].name == *name).collect::<Vec<_>>();
+            let (indexes1, indexes2) = (indexes(children1), indexes(children2));
+            if indexes1.len() == indexes2.len() {
+                continue;
+            }
+            differing.push(ChildCount {
+                name,
+                expected: indexes1.len(),
+                actual: indexes2.len(),
+                reported: !all_ignored(children1, children2, parents.0, &indexes1)
+                    || !all_ignored(children2, children1, parents.1, &indexes2),
+            });
+        }
+        differing
+    }
+
+    // Groups the element counts toward in `element_stats`: none when the statistics are off or
+    // the element is ignored
+    fn stats_groups(&self, path: &str, node: &Lineage, context: &ComparisonContext) -> Vec<String> {
+        let groups = match &context.element_stats {
+            None => return Vec::new(),
+            Some(StatsGrouping::Name) => vec![node.element.name.to_string()],
+            Some(StatsGrouping::Paths(selectors)) => selectors
+                .iter()
+                .filter(|(_, selector)| selector.selects_element(path, node))
+                .map(|(pattern, _)| pattern.clone())
+                .collect(),
+        };
+        if self.is_ignored(path, node, context) {
+            return Vec::new();
+        }
+        groups
+    }
+
+    // Legacy patterns match the element path; XPath patterns select the element or any ancestor,
+    // so ignoring a node ignores its whole subtree
+    fn is_ignored(&self, path: &str, node: &Lineage, context: &ComparisonContext) -> bool {
+        context.ignore_paths.iter().any(|selector| match selector {
+            PathSelector::Path(_) => selector.selects_element(path, node),
+            PathSelector::XPath(_) => node.ancestors().any(|ancestor| selector.selects_element(path, ancestor)),
+        })
+    }
+
+    // Decides whether two element (attribute = None) or attribute values are equal, applying
+    // the value rules configured for the path. A difference may come with a value comparator's
+    // or script's message and severity.
+    fn values_equal(
+        &self,
+        path: &str,
+        nodes: (&Lineage, &Lineage),
+        attribute: Option<&str>,
+        value1: Option<&str>,
+        value2: Option<&str>,
+        context: &ComparisonContext,
+    ) -> Result<(), ValueDifference> {
+        if value1 == value2 {
+            return Ok(());
+        }
+        let (Some(value1), Some(value2)) = (value1, value2) else {
+            return Err(ValueDifference::default());
+        };
+
+        let selected = |selectors: &[PathSelector]| {
+            selectors.iter().any(|selector| match attribute {
+                Some(key) => selector.selects_attribute(nodes.0, key) || selector.selects_attribute(nodes.1, key),
+                None => selector.selects_element(path, nodes.0) || selector.selects_element(path, nodes.1),
+            })
+        };
+
+        // Transforms apply in the order given, each to the values its paths select
+        let (value1, value2) = context
+            .transforms
+            .iter()
+            .filter(|(selectors, _)| selected(selectors))
+            .fold((Cow::Borrowed(value1), Cow::Borrowed(value2)), |(value1, value2), (_, transformer)| {
+                (transformer.apply(value1), transformer.apply(value2))
+            });
+        if value1 == value2 {
+            return Ok(());
+        }
+
+        // The first comparator that decides settles it
+        let value_path = match attribute {
+            Some(key) => Cow::Owned(format!("{}/@{}", path, key)),
+            None => Cow::Borrowed(path),
+        };
+        for (_, comparator) in context.value_comparators.iter().filter(|(selectors, _)| selected(selectors)) {
+            match comparator.compare(&value_path, &value1, &value2) {
+                ValueVerdict::Equal => return Ok(()),
+                ValueVerdict::Different(message) => {
+                    return Err(ValueDifference { message: Some(message), ..Default::default() });
+                }
+                ValueVerdict::Undecided => {}
+            }
+        }
+        let input = ScriptInput {
+            path: &value_path,
+            expected: &value1,
+            actual: &value2,
+            attributes: &nodes.0.element.attributes,
+            actual_attributes: &nodes.1.element.attributes,
+        };
+        for (_, script) in context.scripts.iter().filter(|(selectors, _)| selected(selectors)) {
+            match script.evaluate(&input) {
+                ScriptVerdict::Equal => return Ok(()),
+                ScriptVerdict::Different { message, severity } => return Err(ValueDifference { message, severity }),
+                ScriptVerdict::Undecided => {}
+            }
+        }
+
+        let equal = (selected(&context.date_paths) && dates_equal(&value1, &value2, &context.date_formats))
+            || (selected(&context.literal_paths) && literals_equal(&value1, &value2));
+        if equal { Ok(()) } else { Err(ValueDifference::default()) }
+    }
+
+    // Compares the decoded content of elements selected by base64_paths. Payloads that are both XML
+    // are compared as documents, with diff paths continuing below the element; anything else is
+    // compared by digest. None when the rule does not apply or either side is not valid base64.
+    fn payload_diffs(&self, path: &str, nodes: (&Lineage, &Lineage), context: &ComparisonContext) -> Option<Vec<XmlDiff>> {
+        let (payload1, payload2) = self.payloads(path, nodes, context)?;
+        if payload1 == payload2 {
+            return Some(Vec::new());
+        }
+
+        if let Some((xml1, xml2)) = payload_documents(&payload1, &payload2)
+            && let Ok(result) = self.compare_with_options(xml1, xml2, &context.payload_options)
+        {
+            return Some(
+                result
+                    .diffs
+                    .into_iter()
+                    .map(|diff| XmlDiff { path: format!("{}{}", path, diff.path), ..diff })
+                    .collect(),
+            );
+        }
+
+        Some(vec![XmlDiff {
+            path: path.to_string(),
+            diff_type: DiffType::ContentDifferent,
+            expected: Some(payload_digest(&payload1)),
+            actual: Some(payload_digest(&payload2)),
+            message: "Decoded base64 payload differs".to_string(),
+            similarity: None,
+            severity: DiffSeverity::Error,
+            tags: Vec::new(),
+            node: None,
+        }])
+    }
+
+    // Whether payload_diffs would find no diff; None when the rule does not apply
+    fn payload_equal(&self, path: &str, nodes: (&Lineage, &Lineage), context: &ComparisonContext) -> Option<bool> {
+        let (payload1, payload2) = self.payloads(path, nodes, context)?;
+        Some(
+            payload1 == payload2
+                || payload_documents(&payload1, &payload2).is_some_and(|(xml1, xml2)| {
+                    self.compare_with_options(xml1, xml2, &context.payload_options).is_ok_and(|result| result.matched)
+                }),
+        )
+    }
+
+    // The decoded content of both elements when base64_paths selects them and both are valid base64
+    fn payloads(&self, path: &str, nodes: (&Lineage, &Lineage), context: &ComparisonContext) -> Option<(Vec<u8>, Vec<u8>)> {
+        let selected = context
+            .base64_paths
+            .iter()
+            .any(|selector| selector.selects_element(path, nodes.0) || selector.selects_element(path, nodes.1));
+        if !selected {
+            return None;
+        }
+        Some((decode_base64(nodes.0.element.content.as_deref()?)?, decode_base64(nodes.1.element.content.as_deref()?)?))
+    }
+
+    // Pairs siblings covered by a match_keys rule on their key value; siblings without a
+    // key value are left to the positional or unordered pass
+    fn match_children_by_key(
+        &self,
+        parent_path: &str,
+        children1: &[XmlElement],
+        children2: &[XmlElement],
+        context: &ComparisonContext,
+        matching: &mut SiblingMatching,
+    ) {
+        if context.match_keys.is_empty() {
+            return;
+        }
+
+        let parent_path = strip_path_indexes(parent_path);
+        let mut expressions: HashMap<Arc<str>, Option<&str>> = HashMap::new();
+        let mut key_of = |element: &XmlElement| {
+            let expression = *expressions
+                .entry(element.name.clone())
+                .or_insert_with(|| self.key_expression(&parent_path, &element.name, context));
+            expression.and_then(|expression| {
+                element.key_value(expression).map(|value| MatchKey {
+                    expression: expression.to_string(),
+                    value,
+                })
+            })
+        };
+
+        let keys1: Vec<Option<MatchKey>> = children1.iter().map(&mut key_of).collect();
+        let keys2: Vec<Option<MatchKey>> = children2.iter().map(&mut key_of).collect();
+
+        let mut by_key: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+        for (j, key) in keys2.iter().enumerate().rev() {
+            if let Some(key) = key {
+                by_key.entry((children2[j].name.as_ref(), key.value.as_str())).or_default().push(j);
+            }
+        }
+        for (i, key) in keys1.iter().enumerate() {
+            if let Some(key) = key
+                && let Some(j) = by_key.get_mut(&(children1[i].name.as_ref(), key.value.as_str())).and_then(Vec::pop)
+            {
+                matching.pair(i, j);
+            }
+        }
+
+        // Keyed items are never paired by position, so unmatched ones are reported by key
+        matching.settled1 = keys1.iter().map(Option::is_some).collect();
+        matching.settled2 = keys2.iter().map(Option::is_some).collect();
+        matching.keys1 = keys1;
+        matching.keys2 = keys2;
+    }
+
+    // The match_keys expression that applies to `name` children of an (unindexed) parent path
+    fn key_expression<'c>(&self, parent_path: &str, name: &str, context: &'c ComparisonContext) -> Option<&'c str> {
+        let path = format!("{}/{}", parent_path, name);
+        context
+            .match_keys
+            .iter()
+            .find(|(rule_path, _)| self.path_matches(&path, rule_path))
+            .map(|(_, expression)| expression.as_str())
+    }
+
+    // Reports siblings that share a match key, or are identical subtrees when no key applies
+    fn find_duplicates(
+        &self,
+        document: &str,
+        parent_path: &str,
+        parent: Option<&Lineage>,
+        elements: &[XmlElement],
+        context: &ComparisonContext,
+        warnings: &mut Vec<DocumentWarning>,
+    ) {
+        let paths = SiblingPaths::new(elements, &[]);
+        let unindexed_parent = strip_path_indexes(parent_path);
+        let mut groups: Vec<(&str, DuplicateKey, Vec<usize>)> = Vec::new();
+        for (i, element) in elements.iter().enumerate() {
+            let path = paths.path(parent_path, i);
+            let node = Lineage { element, position: paths.position(i), parent };
+            if self.is_ignored(&path, &node, context) {
+                continue;
+            }
+
+            let key = self
+                .key_expression(&unindexed_parent, &element.name, context)
+                .and_then(|expression| element.key_value(expression).map(|value| format!("{}='{}'", expression, value)))
+                .map_or_else(|| DuplicateKey::Subtree(element.signature()), DuplicateKey::Key);
+            match groups.iter_mut().find(|(name, existing, _)| **name == *element.name && *existing == key) {
+                Some((_, _, indexes)) => indexes.push(i),
+                None => groups.push((&element.name, key, vec![i])),
+            }
+
+            self.find_duplicates(document, &path, Some(&node), &element.children, context, warnings);
+        }
+
+        for (name, key, indexes) in groups.into_iter().filter(|(_, _, indexes)| indexes.len() > 1) {
+            let message = match key {
+                DuplicateKey::Key(key) => format!("{} '{}' elements share the key {}", indexes.len(), name, key),
+                DuplicateKey::Subtree(_) => format!("{} identical '{}' elements", indexes.len(), name),
+            };
+            warnings.push(DocumentWarning {
+                document: document.to_string(),
+                path: paths.path(parent_path, indexes[0]),
+                occurrences: indexes.len(),
+                message,
+            });
+        }
+    }
+
+    // When same-name siblings hold the same subtrees in a different order, pairs them by content
+    // and flags the ones whose position changed, instead of diffing them position by position
+    fn match_children_reordered(&self, children1: &[XmlElement], children2: &[XmlElement], matching: &mut SiblingMatching) {
+        let mut groups: HashMap<&str, (Vec<usize>, Vec<usize>)> = HashMap::new();
+        for i in (0..children1.len()).filter(|&i| matching.available1(i)) {
+            groups.entry(children1[i].name.as_ref()).or_default().0.push(i);
+        }
+        for j in (0..children2.len()).filter(|&j| matching.available2(j)) {
+            groups.entry(children2[j].name.as_ref()).or_default().1.push(j);
+        }
+
+        for (indexes1, indexes2) in groups.values() {
+            if indexes1.len() < 2 || indexes1.len() != indexes2.len() {
+                continue;
+            }
+            let signatures1: Vec<u64> = indexes1.iter().map(|&i| children1[i].signature()).collect();
+            let signatures2: Vec<u64> = indexes2.iter().map(|&j| children2[j].signature()).collect();
+            let (mut sorted1, mut sorted2) = (signatures1.clone(), signatures2.clone());
+            sorted1.sort_unstable();
+            sorted2.sort_unstable();
+            if signatures1 == signatures2 || sorted1 != sorted2 {
+                continue;
+            }
+
+            let mut used = vec![false; indexes2.len()];
+            for (position1, signature) in signatures1.iter().enumerate() {
+                let position2 = (0..indexes2.len())
+                    .find(|&k| !used[k] && signatures2[k] == *signature)
+                    .expect("signature multisets are equal");
+                used[position2] = true;
+                matching.pair(indexes1[position1], indexes2[position2]);
+                matching.reordered[indexes1[position1]] = position1 != position2;
+            }
+        }
+    }
+
+    // Pairs the n-th occurrence of a name in the first document with the n-th occurrence in the second
+    fn match_children_positional(&self, children1: &[XmlElement], children2: &[XmlElement], matching: &mut SiblingMatching) {
+        let mut by_name: HashMap<&str, Vec<usize>> = HashMap::new();
+        for j in (0..children2.len()).rev().filter(|&j| matching.available2(j)) {
+            by_name.entry(children2[j].name.as_ref()).or_default().push(j);
+        }
+
+        for (i, child) in children1.iter().enumerate() {
+            if matching.available1(i)
+                && let Some(j) = by_name.get_mut(child.name.as_ref()).and_then(Vec::pop)
+            {
+                matching.pair(i, j);
+            }
+        }
+    }
+
+    // Pairs identical subtrees first, then the most similar remaining siblings with the same name
+    fn match_children_unordered(&self, children1: &[XmlElement], children2: &[XmlElement], matching: &mut SiblingMatching) {
+        let signatures2: Vec<u64> = children2.iter().map(XmlElement::signature).collect();
+
+        for (i, child) in children1.iter().enumerate() {
+            if !matching.available1(i) {
+                continue;
+            }
+            let signature = child.signature();
+            if let Some(j) = (0..children2.len()).find(|&j| matching.available2(j) && signatures2[j] == signature) {
+                matching.pair(i, j);
+            }
+        }
+
+        for (i, child) in children1.iter().enumerate() {
+            if !matching.available1(i) {
+                continue;
+            }
+            let best = (0..children2.len())
+                .filter(|&j| matching.available2(j) && children2[j].name == child.name)
+                .max_by_key(|&j| (child.similarity(&children2[j]), std::cmp::Reverse(j)));
+            if let Some(j) = best {
+                matching.pair(i, j);
+            }
+        }
+    }
+
+    // Reports an unmatched subtree and remembers it as a candidate for move detection
+    fn report_unmatched(
+        &self,
+        path: &mut String,
+        node: &Lineage,
+        diff_type: DiffType,
+        context: &ComparisonContext,
+        outcome: &mut ComparisonOutcome,
+    ) {
+        let start = outcome.diffs.len();
+        self.report_subtree(path, node, diff_type, context, outcome);
+        if self.is_ignored(path, node, context) {
+            return;
+        }
+
+        let subtree = UnmatchedSubtree {
+            path: path.clone(),
+            signature: node.element.signature(),
+            diffs: start..outcome.diffs.len(),
+        };
+        match diff_type {
+            DiffType::ElementMissing => outcome.missing.push(subtree),
+            _ => outcome.extra.push(subtree),
+        }
+    }
+
+    fn report_subtree(
+        &self,
+        path: &mut String,
+        node: &Lineage,
+        diff_type: DiffType,
+        context: &ComparisonContext,
+        outcome: &mut ComparisonOutcome,
+    ) {
+        let element = node.element;
+        match diff_type {
+            DiffType::ElementMissing => outcome.count_element(self.stats_groups(path, node, context), |stats| stats.missing += 1),
+            _ => outcome.count_element(self.stats_groups(path, node, context), |stats| stats.extra += 1),
+        }
+        if !self.is_ignored(path, node, context) {
+            let (expected, actual, message) = match diff_type {
+                DiffType::ElementMissing => (Some(element.start_tag()), None, "Element missing in second XML"),
+                _ => (None, Some(element.start_tag()), "Extra element in second XML"),
+            };
+            outcome.push(XmlDiff {
+                path: path.to_string(),
+                diff_type,
+                expected,
+                actual,
+                message: message.to_string(),
+                similarity: None,
+                severity: DiffSeverity::Error,
+                tags: Vec::new(),
+                node: Some(element.snapshot()),
+            });
+        }
+
+        let paths = SiblingPaths::new(&element.children, &[]);
+        let parent_length = path.len();
+        for (i, child) in element.children.iter().enumerate() {
+            if outcome.truncated {
+                return;
+            }
+            paths.push(path, i);
+            let child_node = Lineage { element: child, position: paths.position(i), parent: Some(node) };
+            self.report_subtree(path, &child_node, diff_type, context, outcome);
+            path.truncate(parent_length);
+        }
+    }
+
+    // Whether report_subtree would report nothing, every element of the subtree being ignored
+    fn subtree_ignored(&self, path: &mut String, node: &Lineage, context: &ComparisonContext) -> bool {
+        if !self.is_ignored(path, node, context) {
+            return false;
+        }
+        let paths = SiblingPaths::new(&node.element.children, &[]);
+        let parent_length = path.len();
+        node.element.children.iter().enumerate().all(|(i, child)| {
+            paths.push(path, i);
+            let child_node = Lineage { element: child, position: paths.position(i), parent: Some(node) };
+            let ignored = self.subtree_ignored(path, &child_node, context);
+            path.truncate(parent_length);
+            ignored
+        })
+    }
+
+    fn parse_xml<'a>(
+        &self,
+        xml_content: &'a str,
+        namespace_mode: NamespaceMode,
+        expand_entities: bool,
+        mut prefixes: Option<&mut PrefixMap>,
+        names: &mut NameInterner,
+    ) -> CompareResult<(Vec<XmlElement<'a>>, DocumentDigest)> {
+        // Reading from the string rather than through a buffer lets events borrow from it
+        let mut reader = NsReader::from_str(xml_content);
+        reader.trim_text(true);
+
+        let mut roots = Vec::new();
+        let mut stack: Vec<XmlElement> = Vec::new();
+        let mut entities = EntityResolver::default();
+        // The digest is taken from the same events, and its counts are checked against the limits
+        // before each element is built
+        let mut digest = DigestBuilder::default();
+
+        loop {
+            let event = reader.read_event();
+            if let Ok(event) = &event {
+                digest.event(event);
+                if matches!(event, Event::Start(_) | Event::Empty(_)) {
+                    self.limits.check(&digest.stats)?;
+                }
+            }
+            match event {
+                Ok(Event::Start(ref e)) => {
+                    let element = self.build_element(&reader, xml_content, e, namespace_mode, &entities, prefixes.as_deref_mut(), names)?;
+                    stack.push(element);
+                }
+                Ok(Event::Empty(ref e)) => {
+                    let mut element =
+                        self.build_element(&reader, xml_content, e, namespace_mode, &entities, prefixes.as_deref_mut(), names)?;
+                    element.self_closing = true;
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(element),
+                        None => roots.push(element),
+                    }
+                }
+                Ok(Event::Text(e)) => {
+                    if let Some(element) = stack.last_mut() {
+                        // Unknown entities are kept as written rather than failing the comparison
+                        let text = unescape_text(&entities, document_text(e.into_inner()))?;
+                        element.append_content(trim_text(text));
+                    }
+                }
+                Ok(Event::DocType(e)) if expand_entities => {
+                    entities = EntityResolver::from_doctype(&String::from_utf8_lossy(&e))?;
+                }
+                Ok(Event::CData(e)) => {
+                    if let Some(element) = stack.last_mut() {
+                        element.append_content(trim_text(document_text(e.into_inner())));
+                        element.cdata = true;
+                    }
+                }
+                Ok(Event::End(_)) => {
+                    if let Some(element) = stack.pop() {
+                        match stack.last_mut() {
+                            Some(parent) => parent.children.push(element),
+                            None => roots.push(element),
+                        }
+                    }
+                }
+                Ok(Event::Eof) => break,
+                Err(e) => return Err(CompareError::XmlParseError(e.to_string())),
+                _ => {}
+            }
+        }
+
+        Ok((roots, digest.finish()))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_element<'a>(
+        &self,
+        reader: &NsReader<&[u8]>,
+        xml_content: &'a str,
+        start: &BytesStart,
+        namespace_mode: NamespaceMode,
+        entities: &EntityResolver,
+        mut prefixes: Option<&mut PrefixMap>,
+        names: &mut NameInterner,
+    ) -> CompareResult<XmlElement<'a>> {
+        // The tag is read back from the document, so that prefixes, attribute names and values
+        // borrow from it rather than from the event
+        let tag = tag_source(xml_content, start)
+            .ok_or_else(|| CompareError::XmlParseError("Start tag is not part of the document".to_string()))?;
+        let qname = QName(&tag.as_bytes()[..start.name().as_ref().len()]);
+        let mut prefix = qname.prefix().map(|p| String::from_utf8_lossy(p.into_inner()));
+
+        let (name, namespace) = match namespace_mode {
+            // Normalized prefixes make qualified names of the same namespace comparable
+            NamespaceMode::Qualified if let Some(prefixes) = prefixes.as_deref_mut() => match reader.resolve_element(qname) {
+                (ResolveResult::Bound(ns), local) => {
+                    let uri = String::from_utf8_lossy(ns.into_inner()).to_string();
+                    let canonical = prefixes.entry(uri).or_insert_with(|| prefix.as_deref().map(str::to_string));
+                    prefix = canonical.clone().map(Cow::Owned);
+                    (names.intern(&qualified_name(prefix.as_deref(), &String::from_utf8_lossy(local.into_inner()))), None)
+                }
+                _ => (names.intern(&String::from_utf8_lossy(qname.into_inner())), None),
+            },
+            NamespaceMode::Qualified => (names.intern(&String::from_utf8_lossy(qname.into_inner())), None),
+            _ => {
+                let (resolved, local) = reader.resolve_element(qname);
+                match resolved {
+                    ResolveResult::Bound(ns) => (
+                        names.intern(&String::from_utf8_lossy(local.into_inner())),
+                        Some(names.intern(&String::from_utf8_lossy(ns.into_inner()))),
+                    ),
+                    ResolveResult::Unbound => (names.intern(&String::from_utf8_lossy(local.into_inner())), None),
+                    // Undeclared prefix: keep the name as written rather than rejecting the document
+                    ResolveResult::Unknown(_) => (names.intern(&String::from_utf8_lossy(qname.into_inner())), None),
+                }
+            }
+        };
+
+        let mut attributes = HashMap::new();
+        let mut attribute_prefixes = HashMap::new();
+        for attr in Attributes::new(tag, qname.as_ref().len()).flatten() {
+            let value = unescape_text(entities, document_text(attr.value))?;
+
+            if namespace_mode == NamespaceMode::Qualified {
+                let mut key = String::from_utf8_lossy(attr.key.into_inner());
+                if let Some(prefixes) = prefixes.as_deref_mut() {
+                    if attr.key.as_namespace_binding().is_some() {
+                        continue;
+                    }
+                    if let (ResolveResult::Bound(ns), local) = reader.resolve_attribute(attr.key) {
+                        let uri = String::from_utf8_lossy(ns.into_inner()).to_string();
+                        let attr_prefix = attr.key.prefix().map(|p| String::from_utf8_lossy(p.into_inner()).to_string());
+                        // Attributes cannot use a default namespace, so they keep their prefix when the
+                        // namespace is unprefixed in the first document
+                        if let Some(canonical) = prefixes.entry(uri).or_insert(attr_prefix).as_deref() {
+                            key = Cow::Owned(qualified_name(Some(canonical), &String::from_utf8_lossy(local.into_inner())));
+                        }
+                    }
+                }
+                attributes.insert(key, value);
+                continue;
+            }
+
+            // Namespace declarations are compared through the resolved element/attribute names
+            if attr.key.as_namespace_binding().is_some() {
+                continue;
+            }
+
+            let (resolved, local) = reader.resolve_attribute(attr.key);
+            let local = String::from_utf8_lossy(local.into_inner());
+            // xsi:type names a type by QName, so its value is compared by namespace URI like element names
+            let xsi = matches!(&resolved, ResolveResult::Bound(ns) if ns.into_inner() == XSI_NAMESPACE.as_bytes());
+            let value = if xsi && local == "type" { resolve_qname_value(reader, value) } else { value };
+            let key = match resolved {
+                ResolveResult::Bound(ns) => Cow::Owned(format!("{{{}}}{}", String::from_utf8_lossy(ns.into_inner()), local)),
+                ResolveResult::Unbound => local,
+                ResolveResult::Unknown(_) => String::from_utf8_lossy(attr.key.into_inner()),
+            };
+            if let Some(attr_prefix) = attr.key.prefix() {
+                attribute_prefixes.insert(key.clone(), String::from_utf8_lossy(attr_prefix.into_inner()));
+            }
+            attributes.insert(key, value);
+        }
+
+        Ok(XmlElement {
+            name,
+            namespace,
+            prefix,
+            attributes,
+            attribute_prefixes,
+            content: None,
+            cdata: false,
+            self_closing: false,
+            children: Vec::new(),
+        })
+    }
+
+    fn create_element_diffs(
+        &self,
+        path: &str,
+        node1: &Lineage,
+        node2: &Lineage,
+        context: &ComparisonContext,
+        mut patch: Option<&mut PatchBuilder>,
+    ) -> Vec<XmlDiff> {
+        let mut diffs = Vec::new();
+        let (element1, element2) = (node1.element, node2.element);
+        let ignore_properties = &context.ignore_properties;
+        let namespace_mode = context.namespace_mode;
+
+        // Check if this path should be ignored
+        if self.is_ignored(path, node1, context) || self.is_ignored(path, node2, context) {
+            return diffs;
+        }
+
+        // Check if this element name should be ignored
+        if ignore_properties.iter().any(|prop| property_matches(&element1.name, element1.prefix.as_deref(), prop)) {
+            return diffs;
+        }
+
+        // Check namespace differences; with normalized prefixes only the URI matters, so strict mode
+        // reports prefix changes for information
+        let prefix_severity = if context.normalize_prefixes { DiffSeverity::Info } else { DiffSeverity::Error };
+        let renamed = element1.namespace != element2.namespace
+            || (namespace_mode == NamespaceMode::Strict && element1.prefix != element2.prefix);
+        if renamed && let Some(patch) = patch.as_deref_mut() {
+            patch.replace_element(node2);
+        }
+        if element1.namespace != element2.namespace {
+            diffs.push(XmlDiff {
+                path: path.to_string(),
+                diff_type: DiffType::NamespaceDifferent,
+                expected: element1.namespace.as_deref().map(str::to_string),
+                actual: element2.namespace.as_deref().map(str::to_string),
+                message: "Namespace URI differs".to_string(),
+                similarity: None,
+                severity: DiffSeverity::Error,
+                tags: Vec::new(),
+                node: None,
+            });
+        } else if namespace_mode == NamespaceMode::Strict && element1.prefix != element2.prefix {
+            diffs.push(XmlDiff {
+                path: path.to_string(),
+                diff_type: DiffType::NamespaceDifferent,
+                expected: element1.prefix.as_deref().map(str::to_string),
+                actual: element2.prefix.as_deref().map(str::to_string),
+                message: "Namespace prefix differs".to_string(),
+                similarity: None,
+                severity: prefix_severity,
+                tags: Vec::new(),
+                node: None,
+            });
+        }
+
+        // Check content differences
+        let content1 = element1.content.as_deref();
+        let content2 = element2.content.as_deref();
+        if let Some(payload_diffs) = self.payload_diffs(path, (node1, node2), context) {
+            if !payload_diffs.is_empty() && let Some(patch) = patch.as_deref_mut() {
+                patch.text(node2, content1, content2);
+            }
+            diffs.extend(payload_diffs);
+        } else if let Err(difference) = self.values_equal(path, (node1, node2), None, content1, content2, context) {
+            let similarity = context
+                .similarity
+                .map(|(algorithm, _)| text_similarity(content1.unwrap_or(""), content2.unwrap_or(""), algorithm));
+            let (diff_type, message) = match (similarity, context.similarity) {
+                (Some(score), Some((_, threshold))) if score >= threshold => (DiffType::ContentSimilar, "Content differs slightly"),
+                _ => (DiffType::ContentDifferent, "Content differs"),
+            };
+            diffs.push(XmlDiff {
+                path: path.to_string(),
+                diff_type,
+                expected: content1.map(str::to_string),
+                actual: content2.map(str::to_string),
+                message: difference.message.unwrap_or_else(|| message.to_string()),
+                similarity,
+                severity: difference.severity,
+                tags: Vec::new(),
+                node: None,
+            });
+            if let Some(patch) = patch.as_deref_mut() {
+                patch.text(node2, content1, content2);
+            }
+        } else if context.strict_cdata && element1.cdata != element2.cdata {
+            let form = |cdata: bool| if cdata { "CDATA" } else { "text" };
+            diffs.push(XmlDiff {
+                path: path.to_string(),
+                diff_type: DiffType::CdataDifferent,
+                expected: Some(form(element1.cdata).to_string()),
+                actual: Some(form(element2.cdata).to_string()),
+                message: format!("Content is {} in first XML but {} in second XML", form(element1.cdata), form(element2.cdata)),
+                similarity: None,
+                severity: DiffSeverity::Error,
+                tags: Vec::new(),
+                node: None,
+            });
+        }
+
+        // Both forms are the same empty element; the difference is only reported in strict mode
+        if context.strict_self_closing
+            && element1.self_closing != element2.self_closing
+            && element1.content.is_none()
+            && element2.content.is_none()
+            && element1.children.is_empty()
+            && element2.children.is_empty()
+        {
+            let form = |self_closing: bool| if self_closing { "self-closing" } else { "expanded" };
+            diffs.push(XmlDiff {
+                path: path.to_string(),
+                diff_type: DiffType::SelfClosingDifferent,
+                expected: Some(form(element1.self_closing).to_string()),
+                actual: Some(form(element2.self_closing).to_string()),
+                message: format!(
+                    "Empty element is {} in first XML but {} in second XML",
+                    form(element1.self_closing),
+                    form(element2.self_closing)
+                ),
+                similarity: None,
+                severity: DiffSeverity::Info,
+                tags: Vec::new(),
+                node: None,
+            });
+        }
+
+        let attr_ignored = |key: &str, element: &XmlElement| self.attribute_ignored(path, (node1, node2), key, element, context);
+
+        // Check attribute differences
+        for (key, value1) in &element1.attributes {
+            if attr_ignored(key, element1) {
+                continue;
+            }
+
+            if let Some(value2) = element2.attributes.get(key) {
+                if let Err(difference) = self.values_equal(path, (node1, node2), Some(key), Some(value1), Some(value2), context) {
+                    diffs.push(XmlDiff {
+                        path: path.to_string(),
+                        diff_type: DiffType::AttributeDifferent,
+                        expected: Some(format!("{}={}", key, value1)),
+                        actual: Some(format!("{}={}", key, value2)),
+                        message: difference.message.unwrap_or_else(|| format!("Attribute '{}' differs", key)),
+                        similarity: None,
+                        severity: difference.severity,
+                        tags: Vec::new(),
+                        node: None,
+                    });
+                    if let Some(patch) = patch.as_deref_mut() {
+                        patch.replace_attribute(node2, key, element2.attribute_prefix(key), value2);
+                    }
+                } else if namespace_mode == NamespaceMode::Strict
+                    && element1.attribute_prefixes.get(key) != element2.attribute_prefixes.get(key)
+                {
+                    diffs.push(XmlDiff {
+                        path: path.to_string(),
+                        diff_type: DiffType::NamespaceDifferent,
+                        expected: element1.attribute_prefix(key).map(str::to_string),
+                        actual: element2.attribute_prefix(key).map(str::to_string),
+                        message: format!("Namespace prefix of attribute '{}' differs", key),
+                        similarity: None,
+                        severity: prefix_severity,
+                        tags: Vec::new(),
+                        node: None,
+                    });
+                }
+            } else {
+                diffs.push(XmlDiff {
+                    path: path.to_string(),
+                    diff_type: DiffType::AttributeDifferent,
+                    expected: Some(format!("{}={}", key, value1)),
+                    actual: None,
+                    message: format!("Attribute '{}' missing in second XML", key),
+                    similarity: None,
+                    severity: DiffSeverity::Error,
+                    tags: Vec::new(),
+                    node: None,
+                });
+                if let Some(patch) = patch.as_deref_mut() {
+                    patch.remove_attribute(node2, key, element1.attribute_prefix(key));
+                }
+            }
+        }
+
+        // Check for extra attributes in element2
+        for (key, value2) in &element2.attributes {
+            if !attr_ignored(key, element2) && !element1.attributes.contains_key(key) {
+                diffs.push(XmlDiff {
+                    path: path.to_string(),
+                    diff_type: DiffType::AttributeDifferent,
+                    expected: None,
+                    actual: Some(format!("{}={}", key, value2)),
+                    message: format!("Extra attribute '{}' in second XML", key),
+                    similarity: None,
+                    severity: DiffSeverity::Error,
+                    tags: Vec::new(),
+                    node: None,
+                });
+                if let Some(patch) = patch.as_deref_mut() {
+                    patch.add_attribute(node2, key, element2.attribute_prefix(key), value2);
+                }
+            }
+        }
+
+        diffs
+    }
+
+    // Whether create_element_diffs would find no diff
+    fn element_equal(&self, path: &str, node1: &Lineage, node2: &Lineage, context: &ComparisonContext) -> bool {
+        let (element1, element2) = (node1.element, node2.element);
+        if self.is_ignored(path, node1, context)
+            || self.is_ignored(path, node2, context)
+            || context.ignore_properties.iter().any(|prop| property_matches(&element1.name, element1.prefix.as_deref(), prop))
+        {
+            return true;
+        }
+
+        let strict = context.namespace_mode == NamespaceMode::Strict;
+        if element1.namespace != element2.namespace || (strict && element1.prefix != element2.prefix) {
+            return false;
+        }
+        let (content1, content2) = (element1.content.as_deref(), element2.content.as_deref());
+        let content_equal = match self.payload_equal(path, (node1, node2), context) {
+            Some(equal) => equal,
+            None => {
+                self.values_equal(path, (node1, node2), None, content1, content2, context).is_ok()
+                    && !(context.strict_cdata && element1.cdata != element2.cdata)
+            }
+        };
+        if !content_equal {
+            return false;
+        }
+        if context.strict_self_closing
+            && element1.self_closing != element2.self_closing
+            && content1.is_none()
+            && content2.is_none()
+            && element1.children.is_empty()
+            && element2.children.is_empty()
+        {
+            return false;
+        }
+
+        let attr_ignored = |key: &str, element: &XmlElement| self.attribute_ignored(path, (node1, node2), key, element, context);
+        let attributes_equal = element1.attributes.iter().filter(|(key, _)| !attr_ignored(key, element1)).all(|(key, value1)| {
+            element2.attributes.get(key).is_some_and(|value2| {
+                self.values_equal(path, (node1, node2), Some(key), Some(value1), Some(value2), context).is_ok()
+                    && !(strict && element1.attribute_prefixes.get(key) != element2.attribute_prefixes.get(key))
+            })
+        });
+        attributes_equal && element2.attributes.keys().all(|key| attr_ignored(key, element2) || element1.attributes.contains_key(key))
+    }
+
+    fn attribute_ignored(
+        &self,
+        path: &str,
+        (node1, node2): (&Lineage, &Lineage),
+        key: &str,
+        element: &XmlElement,
+        context: &ComparisonContext,
+    ) -> bool {
+        let prefix = element.attribute_prefix(key);
+        context.ignore_properties.iter().any(|prop| property_matches(key, prefix, prop))
+            || context.ignore_attributes.iter().any(|(selector, attribute)| {
+                property_matches(key, prefix, attribute)
+                    && (selector.selects_element(path, node1) || selector.selects_element(path, node2))
+            })
+            || context
+                .ignore_paths
+                .iter()
+                .any(|selector| selector.selects_attribute(node1, key) || selector.selects_attribute(node2, key))
+    }
+
+    fn path_matches(&self, actual_path: &str, ignore_pattern: &str) -> bool {
+        path_pattern_matches(actual_path, ignore_pattern)
+    }
+}
+
+/// Whether an element path matches an `ignore_paths` pattern: exact, prefix (`/root/`), trailing
+/// `/*`, or a glob with `**`, `*`, `?` and `[...]`
+pub fn path_pattern_matches(actual_path: &str, ignore_pattern: &str) -> bool {
+    if ignore_pattern == actual_path {
+        return true;
+    }
+
+    // Support path prefix matching (if pattern ends with /)
+    if ignore_pattern.ends_with('/') {
+        return actual_path.starts_with(ignore_pattern) || format!("{}/", actual_path).starts_with(ignore_pattern);
+    }
+
+    // Glob: `**` spans any number of segments, `*`, `?` and `[...]` stay within one segment.
+    // A trailing `*` keeps its original meaning of also matching everything below.
+    let mut pattern: Vec<&str> = ignore_pattern.split('/').collect();
+    if ignore_pattern.ends_with('*') && !ignore_pattern.ends_with("**") {
+        pattern.push("**");
+    }
+    let actual: Vec<&str> = actual_path.split('/').collect();
+    glob_segments_match(&pattern, &actual)
+}
+
+fn glob_segments_match(pattern: &[&str], actual: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => actual.is_empty(),
+        Some((&"**", rest)) => (0..=actual.len()).any(|skip| glob_segments_match(rest, &actual[skip..])),
+        Some((segment, rest)) => actual
+            .split_first()
+            .is_some_and(|(name, remaining)| glob_segment_matches(segment, name) && glob_segments_match(rest, remaining)),
+    }
+}
+
+fn glob_segment_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_chars_match(&pattern, &name)
+}
+
+fn glob_chars_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob_chars_match(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_chars_match(&pattern[1..], &name[1..]),
+        Some('[') => match (glob_class_len(pattern), name.first()) {
+            (Some(len), Some(c)) => glob_class_matches(&pattern[1..len - 1], *c) && glob_chars_match(&pattern[len..], &name[1..]),
+            (Some(_), None) => false,
+            // An unclosed bracket is matched literally
+            (None, _) => name.first() == Some(&'[') && glob_chars_match(&pattern[1..], &name[1..]),
+        },
+        Some(c) => name.first() == Some(c) && glob_chars_match(&pattern[1..], &name[1..]),
+    }
+}
+
+// Length of the `[...]` class at the start of the pattern, including both brackets
+fn glob_class_len(pattern: &[char]) -> Option<usize> {
+    // A `]` right after the opening bracket (or its negation) is part of the class
+    let start = match pattern.get(1) {
+        Some('!' | '^') => 3,
+        _ => 2,
+    };
+    pattern.iter().skip(start).position(|c| *c == ']').map(|end| start + end + 1)
+}
+
+fn glob_class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!' | '^') => (true, &class[1..]),
+        _ => (false, class),
+    };
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= (class[i]..=class[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+    matched != negated
+}
+
+// Digest of a document read without building its tree, or None when it fails to parse, leaving
+// the error to the full comparison
+fn canonical_digest(xml: &str) -> Option<DocumentDigest> {
+    let mut reader = NsReader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut digest = DigestBuilder::default();
+    loop {
+        match reader.read_event().ok()? {
+            Event::Eof => break,
+            event => digest.event(&event),
+        }
+    }
+    Some(digest.finish())
+}
+
+// Scoped comparisons count only the selected subtrees, so they always parse
+fn is_scoped(options: &ComparisonOptions) -> bool {
+    options.compare_root_path1.is_some() || options.compare_root_path2.is_some() || options.soap.unwrap_or(false)
+}
+
+// Both payloads as text when both look like XML documents
+fn payload_documents<'a>(payload1: &'a [u8], payload2: &'a [u8]) -> Option<(&'a str, &'a str)> {
+    let (xml1, xml2) = (std::str::from_utf8(payload1).ok()?, std::str::from_utf8(payload2).ok()?);
+    (xml1.trim_start().starts_with('<') && xml2.trim_start().starts_with('<')).then_some((xml1, xml2))
+}
+
+// The response for documents with equal digests, which match without being walked
+fn identical_documents(digest: DocumentDigest, unified_diff: Option<String>, options: &ComparisonOptions) -> XmlComparisonResponse {
+    let elements = digest.stats.elements;
+    XmlComparisonResponse {
+        id: None,
+        matched: true,
+        match_ratio: 1.0,
+        diffs: Vec::new(),
+        total_elements: elements,
+        matched_elements: elements,
+        truncated: false,
+        patch: options.generate_patch.unwrap_or(false).then(|| PatchBuilder::new().finish()),
+        unified_diff,
+        warnings: Vec::new(),
+        sources: Vec::new(),
+        element_stats: Vec::new(),
+        tag_counts: BTreeMap::new(),
+        document_stats: Some(DocumentPairStats { xml1: digest.stats, xml2: digest.stats }),
+        diff_fields: None,
+        output_format: None,
+    }
+}
+
+// Splits a path-scoped ignore_properties entry such as `/root/item@id` or `//item[@type='x']@id`
+// into its element path and attribute name; plain names apply everywhere and yield None
+fn scoped_attribute(prop: &str) -> Option<(&str, &str)> {
+    let (path, attribute) = prop.rsplit_once('@')?;
+    let is_name = !attribute.is_empty() && attribute.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':' | '*' | '?'));
+    (prop.starts_with('/') && path.len() > 1 && is_name).then_some((path, attribute))
+}
+
+// `prefix:Type` as `{uri}Type`, resolved against the namespaces in scope; unprefixed names take the
+// default namespace. Values with undeclared prefixes are kept as written.
+fn resolve_qname_value<'a>(reader: &NsReader<&[u8]>, value: Cow<'a, str>) -> Cow<'a, str> {
+    match reader.resolve_element(QName(value.trim().as_bytes())) {
+        (ResolveResult::Bound(ns), local) => Cow::Owned(format!(
+            "{{{}}}{}",
+            String::from_utf8_lossy(ns.into_inner()),
+            String::from_utf8_lossy(local.into_inner())
+        )),
+        _ => value,
+    }
+}
+
+// The start tag's name and attributes as a slice of the document. Events read from a string
+// borrow from it, so the tag is found by its address.
+fn tag_source<'a>(xml_content: &'a str, start: &BytesStart) -> Option<&'a str> {
+    let offset = (start.as_ptr() as usize).checked_sub(xml_content.as_ptr() as usize)?;
+    xml_content.get(offset..offset + start.len())
+}
+
+// Event bytes as text, still borrowing from the document when the event did
+fn document_text(bytes: Cow<'_, [u8]>) -> Cow<'_, str> {
+    match bytes {
+        Cow::Borrowed(bytes) => String::from_utf8_lossy(bytes),
+        Cow::Owned(bytes) => Cow::Owned(String::from_utf8_lossy(&bytes).into_owned()),
+    }
+}
+
+// Expands entities, keeping the borrow when the text has none
+fn unescape_text<'a>(entities: &EntityResolver, text: Cow<'a, str>) -> CompareResult<Cow<'a, str>> {
+    match text {
+        Cow::Borrowed(text) => entities.unescape(text),
+        Cow::Owned(text) => Ok(Cow::Owned(entities.unescape(&text)?.into_owned())),
+    }
+}
+
+fn trim_text(text: Cow<'_, str>) -> Cow<'_, str> {
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(text.trim()),
+        Cow::Owned(text) if text.trim().len() == text.len() => Cow::Owned(text),
+        Cow::Owned(text) => Cow::Owned(text.trim().to_string()),
+    }
+}
+
+// Removes xsi:nil attributes so nil elements compare as empty ones; with `absent` the elements
+// they mark as nil are removed as well
+fn apply_nil_mode(elements: &mut Vec<XmlElement>, mode: NilMode) {
+    elements.retain_mut(|element| {
+        // Qualified mode keeps attribute names as written
+        let nil = element.attributes.remove(XSI_NIL).or_else(|| element.attributes.remove("xsi:nil"));
+        element.attribute_prefixes.remove(XSI_NIL);
+        apply_nil_mode(&mut element.children, mode);
+        let nil = nil.is_some_and(|value| matches!(value.trim(), "true" | "1"));
+        !(nil && mode == NilMode::Absent)
+    });
+}
+
+/// Matches an ignore_properties entry against an element or attribute name. Namespaced
+/// attribute keys are stored as `{uri}local`, so the local name and the `prefix:local`
+/// form written in the document are accepted as well. Entries with `*`, `?` or `[...]` are
+/// globs over any of these forms (`xsi:*`, `data-*`, `*-timestamp`).
+pub fn property_matches(key: &str, prefix: Option<&str>, prop: &str) -> bool {
+    if key == prop {
+        return true;
+    }
+    let local = key.rsplit_once('}').map_or(key, |(_, local)| local);
+    let qualified = prefix.map(|prefix| format!("{}:{}", prefix, local));
+    if prop.contains(['*', '?', '[']) {
+        return [Some(key), Some(local), qualified.as_deref()]
+            .into_iter()
+            .flatten()
+            .any(|name| glob_segment_matches(prop, name));
+    }
+    local == prop || qualified.as_deref() == Some(prop)
+}
+
+// Namespace URI -> prefix (None for a default namespace) first used for it in either document
+type PrefixMap = HashMap<String, Option<String>>;
+
+// The paths a value comparator decides, and the comparator
+type ComparatorRule = (Vec<PathSelector>, Arc<dyn ValueComparator>);
+
+// Why two values differ, when a comparator or script said more than the generic diff does
+#[derive(Default)]
+struct ValueDifference {
+    message: Option<String>,
+    severity: DiffSeverity,
+}
+
+fn qualified_name(prefix: Option<&str>, local: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}:{}", prefix, local),
+        None => local.to_string(),
+    }
+}
+
+// Replaces each SOAP envelope by the payloads of its Body, preceded by its Header when headers
+// are compared; other elements are kept as they are
+fn soap_payloads(roots: Vec<XmlElement>, headers: bool, namespace_mode: NamespaceMode) -> Vec<XmlElement> {
+    let mut payloads = Vec::with_capacity(roots.len());
+    for root in roots {
+        if !is_soap_part(&root, "Envelope", namespace_mode) {
+            payloads.push(root);
+            continue;
+        }
+        for part in root.children {
+            if is_soap_part(&part, "Body", namespace_mode) {
+                payloads.extend(part.children);
+            } else if headers && is_soap_part(&part, "Header", namespace_mode) {
+                // SOAP versions and prefixes differ in the Header's name only, so it is compared
+                // by its local name
+                payloads.push(XmlElement { name: Arc::from("Header"), namespace: None, prefix: None, ..part });
+            }
+        }
+    }
+    payloads
+}
+
+// Qualified names carry no namespace URI, so there the local name alone identifies SOAP parts
+fn is_soap_part(element: &XmlElement, local_name: &str, namespace_mode: NamespaceMode) -> bool {
+    match namespace_mode {
+        NamespaceMode::Qualified => element.name.rsplit(':').next() == Some(local_name),
+        _ => *element.name == *local_name && element.namespace.as_deref().is_some_and(|ns| SOAP_NAMESPACES.contains(&ns)),
+    }
+}
+
+// The outermost elements selected by `pattern`, which become the roots of the comparison
+fn select_roots<'a>(
+    roots: Vec<XmlElement<'a>>,
+    pattern: Option<&str>,
+    option: &str,
+    namespace_mode: NamespaceMode,
+) -> CompareResult<Vec<XmlElement<'a>>> {
+    let Some(pattern) = pattern else {
+        return Ok(roots);
+    };
+    let selector = PathSelector::parse(pattern, namespace_mode)?;
+
+    let mut selected = Vec::new();
+    collect_selected("", None, &roots, &selector, &mut selected);
+    if selected.is_empty() {
+        return Err(CompareError::ValidationError(format!("{} '{}' does not select any element", option, pattern)));
+    }
+    Ok(selected)
+}
+
+fn collect_selected<'a>(
+    parent_path: &str,
+    parent: Option<&Lineage>,
+    elements: &[XmlElement<'a>],
+    selector: &PathSelector,
+    selected: &mut Vec<XmlElement<'a>>,
+) {
+    let paths = SiblingPaths::new(elements, &[]);
+    for (i, element) in elements.iter().enumerate() {
+        let path = paths.path(parent_path, i);
+        let node = Lineage { element, position: paths.position(i), parent };
+        if selector.selects_element(&path, &node) {
+            selected.push(element.clone());
+        } else {
+            collect_selected(&path, Some(&node), &element.children, selector, selected);
+        }
+    }
+}
+
+// Path segments of one set of siblings, worked out once for all of them: names that repeat among
+// the siblings of either document get a 1-based XPath-style position such as `/root/item[2]`
+pub(crate) struct SiblingPaths<'a> {
+    siblings: &'a [XmlElement<'a>],
+    positions: Vec<usize>,
+    repeated: HashSet<&'a str>,
+}
+
+impl<'a> SiblingPaths<'a> {
+    pub(crate) fn new(siblings: &'a [XmlElement<'a>], other_siblings: &[XmlElement]) -> Self {
+        fn count<'e>(elements: &'e [XmlElement]) -> HashMap<&'e str, usize> {
+            let mut counts = HashMap::new();
+            for element in elements {
+                *counts.entry(element.name.as_ref()).or_default() += 1;
+            }
+            counts
+        }
+        let (counts, other_counts) = (count(siblings), count(other_siblings));
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        let positions = siblings
+            .iter()
+            .map(|element| {
+                let position = seen.entry(&element.name).or_default();
+                *position += 1;
+                *position
+            })
+            .collect();
+        let repeated = siblings
+            .iter()
+            .map(|element| &*element.name)
+            .filter(|name| counts[name] > 1 || other_counts.get(name).is_some_and(|count| *count > 1))
+            .collect();
+        Self { siblings, positions, repeated }
+    }
+
+    // 1-based position of the sibling among those with its name
+    fn position(&self, index: usize) -> usize {
+        self.positions[index]
+    }
+
+    // Appends the sibling's segment to its parent's path
+    fn push(&self, path: &mut String, index: usize) {
+        let name = &self.siblings[index].name;
+        path.push('/');
+        path.push_str(name);
+        if self.repeated.contains(&**name) {
+            let _ = write!(path, "[{}]", self.positions[index]);
+        }
+    }
+
+    pub(crate) fn path(&self, parent_path: &str, index: usize) -> String {
+        let mut path = parent_path.to_string();
+        self.push(&mut path, index);
+        path
+    }
+}
+
+fn push_keyed(path: &mut String, name: &str, key: &MatchKey) {
+    let _ = write!(path, "/{}[{}='{}']", name, key.expression, key.value);
+}
+
+/// The path with positions and match keys removed, so that every `/trades/trade[n]/amount` reads
+/// `/trades/trade/amount`
+pub fn strip_path_indexes(path: &str) -> String {
+    path.split('/')
+        .map(|segment| segment.split_once('[').map_or(segment, |(name, _)| name))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn strip_path_prefixes(path: &str) -> String {
+    path.split('/')
+        .map(|segment| segment.rsplit_once(':').map_or(segment, |(_, local)| local))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClassificationRule, DiffField, OutputFormat, TransformRule, ValueTransform};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct XmlComparisonRequest {
+        xml1: String,
+        xml2: String,
+        options: ComparisonOptions,
+    }
+
+    fn compare(service: &XmlComparisonService, request: &XmlComparisonRequest) -> CompareResult<XmlComparisonResponse> {
+        service.compare_with_options(&request.xml1, &request.xml2, &request.options)
+    }
+
+    #[test]
+    fn test_identical_xmls() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<a c=\"C\"><child>hey</child></a>".to_string(),
+            xml2: "<a c=\"C\"><child>hey</child></a>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.match_ratio, 1.0);
+        assert!(result.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_ignore_property() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<a c=\"C\"><child>hey</child></a>".to_string(),
+            xml2: "<a c=\"D\"><child>hey</child></a>".to_string(),
+            options: ComparisonOptions {
+                ignore_properties: Some(vec!["c".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.match_ratio, 1.0);
+        assert!(result.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_ignore_tag() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<a c=\"C\"><child>hey</child></a>".to_string(),
+            xml2: "<a c=\"C\"><child>yo</child></a>".to_string(),
+            options: ComparisonOptions {
+                ignore_properties: Some(vec!["child".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.match_ratio, 1.0);
+        assert!(result.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_different_xmls() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<a c=\"C\"><child>hey</child></a>".to_string(),
+            xml2: "<a c=\"D\"><child>yo</child></a>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert!(result.match_ratio < 1.0);
+        assert!(!result.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_attribute_and_content_differences() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<CVAMapping date=\"20250819\">test</CVAMapping>".to_string(),
+            xml2: "<CVAMapping date=\"20250818\">test2</CVAMapping>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec![]),
+                ignore_properties: Some(vec![]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 2); // Should have both attribute and content diffs
+        
+        // Check we have both types of diffs
+        let has_content_diff = result.diffs.iter().any(|d| matches!(d.diff_type, DiffType::ContentDifferent));
+        let has_attr_diff = result.diffs.iter().any(|d| matches!(d.diff_type, DiffType::AttributeDifferent));
+        
+        assert!(has_content_diff, "Should have content difference");
+        assert!(has_attr_diff, "Should have attribute difference");
+    }
+
+    #[test]
+    fn test_attribute_only_difference() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<CVAMapping date=\"20250819\">test</CVAMapping>".to_string(),
+            xml2: "<CVAMapping date=\"20250818\">test</CVAMapping>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 1);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::AttributeDifferent));
+        assert_eq!(result.diffs[0].path, "/CVAMapping");
+        assert!(result.diffs[0].message.contains("date"));
+    }
+
+    #[test]
+    fn test_ignore_attribute_property() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<CVAMapping date=\"20250819\">test</CVAMapping>".to_string(),
+            xml2: "<CVAMapping date=\"20250818\">test</CVAMapping>".to_string(),
+            options: ComparisonOptions {
+                ignore_properties: Some(vec!["date".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.diffs.len(), 0);
+    }
+
+    #[test]
+    fn test_content_only_difference() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<CVAMapping date=\"20250819\">test</CVAMapping>".to_string(),
+            xml2: "<CVAMapping date=\"20250819\">test2</CVAMapping>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 1);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::ContentDifferent));
+        assert_eq!(result.diffs[0].path, "/CVAMapping");
+    }
+
+    #[test]
+    fn test_path_matching_exact() {
+        let service = XmlComparisonService::new();
+        assert!(service.path_matches("/root/child", "/root/child"));
+        assert!(!service.path_matches("/root/child", "/root/other"));
+    }
+
+    #[test]
+    fn test_path_matching_wildcard() {
+        let service = XmlComparisonService::new();
+        assert!(service.path_matches("/root/items/x", "/root/item*"));
+        assert!(service.path_matches("/root/child/grandchild", "/root/*"));
+        assert!(service.path_matches("/root/child", "/root/*"));
+        assert!(!service.path_matches("/other/child", "/root/*"));
+    }
+
+    #[test]
+    fn test_path_matching_glob() {
+        let service = XmlComparisonService::new();
+        assert!(service.path_matches("/root/a/b/timestamp", "/root/**/timestamp"));
+        assert!(service.path_matches("/root/timestamp", "/root/**/timestamp"));
+        assert!(service.path_matches("/root/a/b", "/**"));
+        assert!(!service.path_matches("/root/a/b/other", "/root/**/timestamp"));
+
+        assert!(service.path_matches("/root/item/timestamp", "/root/*/timestamp"));
+        assert!(!service.path_matches("/root/a/b/timestamp", "/root/*/timestamp"));
+        assert!(service.path_matches("/root/item_created", "/root/item_*"));
+        assert!(service.path_matches("/root/item1/name", "/root/item?/name"));
+        assert!(!service.path_matches("/root/item12/name", "/root/item?/name"));
+    }
+
+    #[test]
+    fn test_path_matching_character_classes() {
+        let service = XmlComparisonService::new();
+        assert!(service.path_matches("/root/line2", "/root/line[0-9]"));
+        assert!(!service.path_matches("/root/lineX", "/root/line[0-9]"));
+        assert!(service.path_matches("/root/lineX", "/root/line[!0-9]"));
+        assert!(service.path_matches("/root/b", "/root/[abc]"));
+        assert!(!service.path_matches("/root/d", "/root/[abc]"));
+        assert!(service.path_matches("/root/[x", "/root/[x"));
+    }
+
+    #[test]
+    fn test_path_matching_prefix() {
+        let service = XmlComparisonService::new();
+        assert!(service.path_matches("/root/child/grandchild", "/root/"));
+        assert!(service.path_matches("/root", "/root/"));
+        assert!(!service.path_matches("/other", "/root/"));
+    }
+
+    #[test]
+    fn test_ignore_paths_exact_match() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><child>test1</child><other>test2</other></root>".to_string(),
+            xml2: "<root><child>different</child><other>test2</other></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["/root/child".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.diffs.len(), 0);
+    }
+
+    #[test]
+    fn test_ignore_paths_wildcard() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><child><deep>test1</deep></child><other>test2</other></root>".to_string(),
+            xml2: "<root><child><deep>different</deep></child><other>test2</other></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["/root/child/*".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.diffs.len(), 0);
+    }
+
+    #[test]
+    fn test_namespace_prefix_differences_ignored_by_default() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<ns:Trade xmlns:ns=\"urn:trades\"><ns:id>1</ns:id></ns:Trade>".to_string(),
+            xml2: "<Trade xmlns=\"urn:trades\"><id>1</id></Trade>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert!(result.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_namespace_uri_difference() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<Trade xmlns=\"urn:trades:v1\"><id>1</id></Trade>".to_string(),
+            xml2: "<Trade xmlns=\"urn:trades:v2\"><id>1</id></Trade>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 2);
+        assert!(result.diffs.iter().all(|d| matches!(d.diff_type, DiffType::NamespaceDifferent)));
+    }
+
+    #[test]
+    fn test_namespace_strict_mode_reports_prefix() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<a:Trade xmlns:a=\"urn:trades\">1</a:Trade>".to_string(),
+            xml2: "<b:Trade xmlns:b=\"urn:trades\">1</b:Trade>".to_string(),
+            options: ComparisonOptions { namespace_mode: Some(NamespaceMode::Strict), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 1);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::NamespaceDifferent));
+        assert_eq!(result.diffs[0].path, "/Trade");
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("a"));
+        assert_eq!(result.diffs[0].actual.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_namespace_qualified_mode_compares_names_as_written() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<ns:Trade xmlns:ns=\"urn:trades\">1</ns:Trade>".to_string(),
+            xml2: "<Trade xmlns=\"urn:trades\">1</Trade>".to_string(),
+            options: ComparisonOptions {
+                namespace_mode: Some(NamespaceMode::Qualified),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert!(result.diffs.iter().any(|d| matches!(d.diff_type, DiffType::ElementMissing) && d.path == "/ns:Trade"));
+        assert!(result.diffs.iter().any(|d| matches!(d.diff_type, DiffType::ElementExtra) && d.path == "/Trade"));
+    }
+
+    #[test]
+    fn test_namespaced_attribute_resolution_and_ignore() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<r xmlns:x=\"urn:meta\" x:stamp=\"1\"></r>".to_string(),
+            xml2: "<r xmlns:y=\"urn:meta\" y:stamp=\"2\"></r>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::AttributeDifferent));
+        assert!(result.diffs[0].message.contains("{urn:meta}stamp"));
+
+        request.options.ignore_properties = Some(vec!["x:stamp".to_string()]);
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+    }
+
+    #[test]
+    fn test_ignore_paths_with_prefixes_apply_to_local_names() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<ns:root xmlns:ns=\"urn:r\"><ns:stamp>1</ns:stamp></ns:root>".to_string(),
+            xml2: "<ns:root xmlns:ns=\"urn:r\"><ns:stamp>2</ns:stamp></ns:root>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["/ns:root/ns:stamp".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+    }
+
+    #[test]
+    fn test_repeated_siblings_compared_by_position() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<list><item>a</item><item>b</item></list>".to_string(),
+            xml2: "<list><item>a</item><item>c</item><item>d</item></list>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.total_elements, 4);
+        assert_eq!(result.diffs.len(), 2);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::ContentDifferent));
+        assert_eq!(result.diffs[0].path, "/list/item[2]");
+        assert!(matches!(result.diffs[1].diff_type, DiffType::ElementExtra));
+        assert_eq!(result.diffs[1].path, "/list/item[3]");
+    }
+
+    #[test]
+    fn test_reordered_siblings_differ_by_default() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<list><item id=\"1\">a</item><item id=\"2\">b</item></list>".to_string(),
+            xml2: "<list><item id=\"2\">b</item><item id=\"1\">a</item></list>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+    }
+
+    #[test]
+    fn test_ignore_element_order_matches_reordered_siblings() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<list><item id=\"1\">a</item><other/><item id=\"2\">b</item></list>".to_string(),
+            xml2: "<list><item id=\"2\">b</item><item id=\"1\">a</item><other/></list>".to_string(),
+            options: ComparisonOptions { ignore_element_order: Some(true), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.match_ratio, 1.0);
+        assert_eq!(result.matched_elements, 4);
+    }
+
+    #[test]
+    fn test_ignore_element_order_reports_only_real_differences() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<list><item id=\"1\"><price>10</price></item><item id=\"2\"><price>20</price></item></list>".to_string(),
+            xml2: "<list><item id=\"2\"><price>25</price></item><item id=\"1\"><price>10</price></item></list>".to_string(),
+            options: ComparisonOptions { ignore_element_order: Some(true), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 1);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::ContentDifferent));
+        assert_eq!(result.diffs[0].path, "/list/item[2]/price");
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("20"));
+        assert_eq!(result.diffs[0].actual.as_deref(), Some("25"));
+    }
+
+    #[test]
+    fn test_match_keys_by_attribute() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<trades><trade id=\"A\"><qty>1</qty></trade><trade id=\"B\"><qty>2</qty></trade></trades>".to_string(),
+            xml2: "<trades><trade id=\"C\"><qty>3</qty></trade><trade id=\"B\"><qty>5</qty></trade><trade id=\"A\"><qty>1</qty></trade></trades>".to_string(),
+            options: ComparisonOptions {
+                match_keys: Some(HashMap::from([("/trades/trade".to_string(), "@id".to_string())])),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+
+        let content_diff = result.diffs.iter().find(|d| matches!(d.diff_type, DiffType::ContentDifferent)).unwrap();
+        assert_eq!(content_diff.path, "/trades/trade[@id='B']/qty");
+        assert_eq!(content_diff.expected.as_deref(), Some("2"));
+        assert_eq!(content_diff.actual.as_deref(), Some("5"));
+
+        assert!(result.diffs.iter().any(|d| matches!(d.diff_type, DiffType::ElementExtra) && d.path == "/trades/trade[@id='C']"));
+        assert!(!result.diffs.iter().any(|d| matches!(d.diff_type, DiffType::ElementMissing)));
+    }
+
+    #[test]
+    fn test_match_keys_by_child_element() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<book><entry><ref>x1</ref><v>1</v></entry><entry><ref>x2</ref><v>2</v></entry></book>".to_string(),
+            xml2: "<book><entry><ref>x2</ref><v>2</v></entry></book>".to_string(),
+            options: ComparisonOptions {
+                match_keys: Some(HashMap::from([("/book/entry".to_string(), "ref".to_string())])),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert!(result.diffs.iter().all(|d| matches!(d.diff_type, DiffType::ElementMissing)));
+        assert_eq!(result.diffs[0].path, "/book/entry[ref='x1']");
+        assert_eq!(result.diffs.len(), 3);
+    }
+
+    #[test]
+    fn test_ignore_paths_apply_to_keyed_items() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<trades><trade id=\"A\"><stamp>1</stamp></trade></trades>".to_string(),
+            xml2: "<trades><trade id=\"A\"><stamp>2</stamp></trade></trades>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["/trades/trade/stamp".to_string()]),
+                match_keys: Some(HashMap::from([("/trades/trade".to_string(), "@id".to_string())])),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+    }
+
+    #[test]
+    fn test_ignore_xpath_descendant_anywhere() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><a><timestamp>1</timestamp></a><b><c><timestamp>2</timestamp></c></b></root>".to_string(),
+            xml2: "<root><a><timestamp>3</timestamp></a><b><c><timestamp>4</timestamp></c></b></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["//timestamp".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+    }
+
+    #[test]
+    fn test_ignore_xpath_predicate_ignores_subtree() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><field name=\"audit\"><v>1</v></field><field name=\"price\"><v>1</v></field></root>".to_string(),
+            xml2: "<root><field name=\"audit\"><v>2</v></field><field name=\"price\"><v>2</v></field></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["//field[@name='audit']".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/root/field[2]/v");
+    }
+
+    #[test]
+    fn test_ignore_xpath_attribute_selector() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><item id=\"1\" v=\"a\"></item><other id=\"1\"></other></root>".to_string(),
+            xml2: "<root><item id=\"2\" v=\"a\"></item><other id=\"2\"></other></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["//item/@id".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/root/other");
+    }
+
+    #[test]
+    fn test_ignore_xpath_suppresses_missing_elements() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><meta><generated>now</generated></meta><v>1</v></root>".to_string(),
+            xml2: "<root><v>1</v></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["//meta".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_xpath_is_rejected() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root/>".to_string(),
+            xml2: "<root/>".to_string(),
+            options: ComparisonOptions {
+                ignore_paths: Some(vec!["//item[@id='1'".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        assert!(matches!(compare(&service, &request), Err(CompareError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_date_paths_compare_semantically() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<trade date=\"2025-08-19\"><booked>2025-08-19T10:00:00+02:00</booked><ref>20250819</ref></trade>".to_string(),
+            xml2: "<trade date=\"20250819\"><booked>2025-08-19T08:00:00Z</booked><ref>2025-08-19</ref></trade>".to_string(),
+            options: ComparisonOptions {
+                date_paths: Some(vec!["/trade/booked".to_string(), "//trade/@date".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/trade/ref");
+    }
+
+    #[test]
+    fn test_date_paths_report_real_date_changes() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<trade><booked>19/08/2025</booked></trade>".to_string(),
+            xml2: "<trade><booked>2025-08-20</booked></trade>".to_string(),
+            options: ComparisonOptions {
+                date_paths: Some(vec!["//booked".to_string()]),
+                date_formats: Some(vec!["%d/%m/%Y".to_string(), "%Y-%m-%d".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::ContentDifferent));
+    }
+
+    #[test]
+    fn test_base64_paths_compare_decoded_payloads() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<msg><body>PGRvYz48dj4xPC92PjwvZG9jPg==</body><pdf>JVBERi0x</pdf></msg>".to_string(),
+            xml2: "<msg><body>PGRvYz48dj4yPC92PjwvZG9jPg==</body><pdf>JVBERi0y</pdf></msg>".to_string(),
+            options: ComparisonOptions {
+                base64_paths: Some(vec!["/msg/body".to_string(), "//pdf".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 2);
+        assert_eq!(result.diffs[0].path, "/msg/body/doc/v");
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("1"));
+        assert_eq!(result.diffs[1].path, "/msg/pdf");
+        assert!(result.diffs[1].expected.as_deref().unwrap().starts_with("sha256:"));
+
+        // Differently formatted but equal payload documents match
+        request.xml2 = "<msg><body>PGRvYz4gIDx2PjE8L3Y+\n  PC9kb2M+</body><pdf>JVBERi0x</pdf></msg>".to_string();
+        assert!(compare(&service, &request).unwrap().matched);
+    }
+
+    #[test]
+    fn test_literal_paths_compare_booleans_and_numbers() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<trade active=\"true\"><rate>0.50</rate><flag>TRUE</flag><qty>1</qty></trade>".to_string(),
+            xml2: "<trade active=\"1\"><rate>.5</rate><flag>true</flag><qty>1.0</qty></trade>".to_string(),
+            options: ComparisonOptions {
+                literal_paths: Some(vec!["//trade/@active".to_string(), "/trade/rate".to_string(), "//flag".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/trade/qty");
+    }
+
+    #[test]
+    fn test_value_comparators_decide_selected_values() {
+        // Amounts equal to the cent, and currency codes that only differ in case
+        let cents = |_: &str, expected: &str, actual: &str| match (expected.parse::<f64>(), actual.parse::<f64>()) {
+            (Ok(a), Ok(b)) if (a * 100.0).round() == (b * 100.0).round() => ValueVerdict::Equal,
+            (Ok(_), Ok(_)) => ValueVerdict::Different("Amounts differ by more than a cent".to_string()),
+            _ => ValueVerdict::Undecided,
+        };
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let seen = paths.clone();
+        let currency = move |path: &str, expected: &str, actual: &str| {
+            seen.lock().unwrap().push(path.to_string());
+            if expected.eq_ignore_ascii_case(actual) { ValueVerdict::Equal } else { ValueVerdict::Undecided }
+        };
+        let service = XmlComparisonService::new().with_comparator("cents", cents).with_comparator("currency", currency);
+        assert_eq!(service.comparator_names(), vec!["cents", "currency"]);
+
+        let mut request = XmlComparisonRequest {
+            xml1: "<trade ccy=\"usd\"><amount>10.001</amount><fee>1.00</fee><note>n/a</note></trade>".to_string(),
+            xml2: "<trade ccy=\"USD\"><amount>10.004</amount><fee>1.50</fee><note>none</note></trade>".to_string(),
+            options: ComparisonOptions {
+                value_comparators: Some(HashMap::from([
+                    ("cents".to_string(), vec!["//amount".to_string(), "//fee".to_string(), "//note".to_string()]),
+                    ("currency".to_string(), vec!["//trade/@ccy".to_string()]),
+                ])),
+                ..Default::default()
+            },
+        };
+        let result = compare(&service, &request).unwrap();
+        let diffs: Vec<_> = result.diffs.iter().map(|diff| (diff.path.as_str(), diff.message.as_str())).collect();
+        // An undecided comparator leaves the value to plain comparison
+        assert_eq!(diffs, vec![("/trade/fee", "Amounts differ by more than a cent"), ("/trade/note", "Content differs")]);
+        assert_eq!(*paths.lock().unwrap(), vec!["/trade/@ccy"]);
+
+        request.options.value_comparators = Some(HashMap::from([("isin".to_string(), vec!["//isin".to_string()])]));
+        let error = compare(&service, &request).unwrap_err().to_string();
+        assert!(error.contains("Unknown value comparator 'isin'; registered: cents, currency"), "{}", error);
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn test_scripts_decide_selected_values() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: r#"<trade><settles>2024-03-01</settles><leg settles="2024-03-01"/><ref>A</ref></trade>"#.to_string(),
+            xml2: r#"<trade><settles>2024-03-04</settles><leg settles="2024-03-06"/><ref>B</ref></trade>"#.to_string(),
+            options: ComparisonOptions {
+                scripts: Some(vec![crate::ScriptRule {
+                    paths: vec!["//settles".to_string(), "//leg/@settles".to_string(), "//ref".to_string()],
+                    script: r#"
+                        if !path.contains("settles") { return; }
+                        let days = business_days_between(expected, actual);
+                        if days.abs() <= 1 { true } else { #{equal: false, severity: "info", message: `${days} business days apart`} }
+                    "#
+                    .to_string(),
+                }]),
+                ..Default::default()
+            },
+        };
+        let result = compare(&service, &request).unwrap();
+        let diffs: Vec<_> = result.diffs.iter().map(|diff| (diff.path.as_str(), diff.message.as_str(), diff.severity)).collect();
+        assert_eq!(
+            diffs,
+            vec![("/trade/leg", "3 business days apart", DiffSeverity::Info), ("/trade/ref", "Content differs", DiffSeverity::Error)]
+        );
+
+        request.options.scripts.as_mut().unwrap()[0].script = "expected ==".to_string();
+        let error = compare(&service, &request).unwrap_err().to_string();
+        assert!(error.contains("Invalid script"), "{}", error);
+    }
+
+    #[test]
+    fn test_cdata_captured_as_content() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<a><![CDATA[x < y]]></a>".to_string(),
+            xml2: "<a><![CDATA[x > y]]></a>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("x < y"));
+        assert_eq!(result.diffs[0].actual.as_deref(), Some("x > y"));
+    }
+
+    #[test]
+    fn test_cdata_equals_escaped_text() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<a><b><![CDATA[x < y & z]]></b></a>".to_string(),
+            xml2: "<a><b>x &lt; y &amp; z</b></a>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+
+        request.options.strict_cdata = Some(true);
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/a/b");
+        assert!(matches!(result.diffs[0].diff_type, DiffType::CdataDifferent));
+    }
+
+    #[test]
+    fn test_moved_subtree_reported_once() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><a><item id=\"1\"><name>x</name></item></a><b/></root>".to_string(),
+            xml2: "<root><a/><b><item id=\"1\"><name>x</name></item></b></root>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::ElementMoved));
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("/root/a/item"));
+        assert_eq!(result.diffs[0].actual.as_deref(), Some("/root/b/item"));
+    }
+
+    #[test]
+    fn test_changed_subtree_is_not_a_move() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root><a><item id=\"1\"/></a><b/></root>".to_string(),
+            xml2: "<root><a/><b><item id=\"2\"/></b></root>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        let types: Vec<DiffType> = result.diffs.iter().map(|diff| diff.diff_type).collect();
+        assert_eq!(types, vec![DiffType::ElementMissing, DiffType::ElementExtra]);
+    }
+
+    #[test]
+    fn test_similarity_threshold_flags_near_matches() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<item><desc>A small red wooden box</desc><code>ABC</code></item>".to_string(),
+            xml2: "<item><desc>A small red wooden fox</desc><code>XYZ</code></item>".to_string(),
+            options: ComparisonOptions { similarity_threshold: Some(0.8), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 2);
+        assert!(matches!(result.diffs[0].diff_type, DiffType::ContentSimilar));
+        assert!(result.diffs[0].similarity.unwrap() > 0.9);
+        assert!(matches!(result.diffs[1].diff_type, DiffType::ContentDifferent));
+        assert_eq!(result.diffs[1].similarity, Some(0.0));
+
+        request.options.similarity_threshold = Some(1.5);
+        assert!(compare(&service, &request).is_err());
+    }
+
+    #[test]
+    fn test_canonically_identical_documents_short_circuit() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root a=\"1\" b=\"2\"><item>x &amp; y</item><empty/></root>".to_string(),
+            xml2: "<root b='2' a='1'>\n  <item>x &amp; y</item>\n  <empty></empty>\n</root>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.total_elements, 3);
+        assert_eq!(result.matched_elements, 3);
+        assert_eq!(service.digests.len(), 2);
+        // Seen before, so answered from the digests without parsing
+        assert!(compare(&service, &request).unwrap().matched);
+
+        assert_ne!(canonical_digest("<root a=\"1\"/>"), canonical_digest("<root a=\"2\"/>"));
+        assert_ne!(canonical_digest("<a><![CDATA[x]]></a>"), canonical_digest("<a>x</a>"));
+        assert_ne!(canonical_digest("<a/>"), canonical_digest("<a>"));
+        assert_ne!(canonical_digest("<a b=\"c\" d=\"\"/>"), canonical_digest("<a b=\"\" c=\"d\"/>"));
+    }
+
+    #[test]
+    fn test_ignore_attribute_at_specific_path() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root id=\"1\"><item id=\"a\" v=\"1\"/><item id=\"b\"/><other id=\"x\"/></root>".to_string(),
+            xml2: "<root id=\"2\"><item id=\"c\" v=\"2\"/><item id=\"d\"/><other id=\"y\"/></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_properties: Some(vec!["/root/item@id".to_string(), "//other[@id='x']@id".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        let paths: Vec<(&str, DiffType)> = result.diffs.iter().map(|diff| (diff.path.as_str(), diff.diff_type)).collect();
+        assert_eq!(paths, vec![("/root", DiffType::AttributeDifferent), ("/root/item[1]", DiffType::AttributeDifferent)]);
+        assert_eq!(result.diffs[1].expected.as_deref(), Some("v=1"));
+    }
+
+    #[test]
+    fn test_canonicalize_option() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<root xmlns=\"urn:a\"><item xmlns=\"urn:a\" b=\"2\" a=\"1\"/></root>".to_string(),
+            xml2: "<root xmlns=\"urn:a\"><item a='1' b='2'></item></root>".to_string(),
+            options: ComparisonOptions {
+                namespace_mode: Some(NamespaceMode::Qualified),
+                canonicalize: Some(true),
+                ..Default::default()
+            },
+        };
+        assert!(compare(&service, &request).unwrap().matched);
+
+        request.options.canonicalize = None;
+        assert!(!compare(&service, &request).unwrap().matched);
+    }
+
+    #[test]
+    fn test_internal_entities_expanded() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<!DOCTYPE doc [<!ENTITY co \"ACME Ltd\">]><doc owner=\"&co;\"><name>&co;</name></doc>".to_string(),
+            xml2: "<doc owner=\"ACME Ltd\"><name>ACME Ltd</name></doc>".to_string(),
+            ..Default::default()
+        };
+        assert!(compare(&service, &request).unwrap().matched);
+
+        request.options.expand_entities = Some(false);
+        assert_eq!(compare(&service, &request).unwrap().diffs.len(), 2);
+    }
+
+    #[test]
+    fn test_reordered_siblings_reported_with_positions() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<r><a>1</a><a>2</a><a>3</a><b/></r>".to_string(),
+            xml2: "<r><b/><a>1</a><a>3</a><a>2</a></r>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        let diffs: Vec<(&str, DiffType, Option<&str>)> = result
+            .diffs
+            .iter()
+            .map(|diff| (diff.path.as_str(), diff.diff_type, diff.actual.as_deref()))
+            .collect();
+        assert_eq!(
+            diffs,
+            vec![
+                ("/r/a[2]", DiffType::ElementReordered, Some("position 3")),
+                ("/r/a[3]", DiffType::ElementReordered, Some("position 2")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_changed_siblings_are_not_reorders() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<r><a>1</a><a>2</a></r>".to_string(),
+            xml2: "<r><a>2</a><a>3</a></r>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 2);
+        assert!(result.diffs.iter().all(|diff| diff.diff_type == DiffType::ContentDifferent));
+    }
+
+    #[test]
+    fn test_max_diffs_stops_comparison() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<r><a>1</a><b>1</b><c>1</c><d>1</d></r>".to_string(),
+            xml2: "<r><a>2</a><b>2</b><c>2</c><d>2</d></r>".to_string(),
+            options: ComparisonOptions { max_diffs: Some(2), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.truncated);
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 2);
+        assert_eq!(result.total_elements, 5);
+
+        request.options.max_diffs = Some(4);
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.truncated);
+        assert_eq!(result.diffs.len(), 4);
+
+        request.options.max_diffs = Some(0);
+        let result = compare(&service, &request).unwrap();
+        assert!(result.truncated && !result.matched && result.diffs.is_empty());
+    }
+
+    #[test]
+    fn test_verdict_mode() {
+        let service = XmlComparisonService::new();
+        let verdict = ComparisonOptions { mode: Some(ComparisonMode::Verdict), ..Default::default() };
+
+        let result = service.compare_with_options("<r><a>1</a><b>1</b></r>", "<r>\n  <a>1</a>\n  <b>1</b>\n</r>", &verdict).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.match_ratio, 1.0);
+
+        let result = service.compare_with_options("<r><a>1</a><b>1</b></r>", "<r><a>2</a><b>2</b></r>", &verdict).unwrap();
+        assert!(!result.matched && !result.truncated && result.diffs.is_empty());
+        assert_eq!((result.match_ratio, result.total_elements, result.matched_elements), (0.0, 0, 0));
+
+        // The verdict agrees with the full comparison under the options that decide it
+        let cases = [
+            ("<r><a>1</a><b x=\"1\"/></r>", "<r><a>1</a><b x=\"2\"/></r>", ComparisonOptions::default()),
+            ("<r><a>1</a><b/></r>", "<r><b/><a>1</a></r>", ComparisonOptions::default()),
+            ("<r><a>1</a><b/></r>", "<r><b/><a>1</a></r>", ComparisonOptions { ignore_element_order: Some(true), ..Default::default() }),
+            ("<r><a>1</a><b>2</b></r>", "<r><a>1</a></r>", ComparisonOptions::default()),
+            (
+                "<r><a>1</a><b><c>2</c></b></r>",
+                "<r><a>1</a></r>",
+                ComparisonOptions { ignore_paths: Some(vec!["/r/b".to_string()]), ..Default::default() },
+            ),
+            (
+                "<r><a>1</a><b><c>2</c></b></r>",
+                "<r><a>1</a></r>",
+                ComparisonOptions { ignore_paths: Some(vec!["//b".to_string()]), ..Default::default() },
+            ),
+            (
+                "<r><i id=\"1\">a</i><i id=\"2\">b</i></r>",
+                "<r><i id=\"2\">b</i><i id=\"1\">a</i></r>",
+                ComparisonOptions {
+                    match_keys: Some([("/r/i".to_string(), "@id".to_string())].into()),
+                    ..Default::default()
+                },
+            ),
+            (
+                "<r><i>1</i><i>2</i></r>",
+                "<r><i>1</i></r>",
+                ComparisonOptions { child_counts: Some(ChildCountMode::Collapse), ..Default::default() },
+            ),
+            ("<r><a/></r>", "<r><a></a></r>", ComparisonOptions { strict_self_closing: Some(true), ..Default::default() }),
+            (
+                "<r><a>x</a></r>",
+                "<r><a><![CDATA[x]]></a></r>",
+                ComparisonOptions { strict_cdata: Some(true), ..Default::default() },
+            ),
+            (
+                "<r xmlns:p=\"urn:x\"><p:a/></r>",
+                "<r xmlns:q=\"urn:x\"><q:a/></r>",
+                ComparisonOptions { namespace_mode: Some(NamespaceMode::Strict), normalize_prefixes: Some(true), ..Default::default() },
+            ),
+            (
+                "<r><a id=\"1\" t=\"x\">1</a></r>",
+                "<r><a id=\"2\" t=\"x\">1</a></r>",
+                ComparisonOptions { ignore_properties: Some(vec!["id".to_string()]), ..Default::default() },
+            ),
+            (
+                "<r><p>PGE+MTwvYT4=</p></r>",
+                "<r><p>IDxhPjE8L2E+</p></r>",
+                ComparisonOptions { base64_paths: Some(vec!["/r/p".to_string()]), ..Default::default() },
+            ),
+            (
+                "<r><p>PGE+MTwvYT4=</p></r>",
+                "<r><p>PGE+MjwvYT4=</p></r>",
+                ComparisonOptions { base64_paths: Some(vec!["/r/p".to_string()]), ..Default::default() },
+            ),
+        ];
+        for (xml1, xml2, options) in cases {
+            let full = service.compare_with_options(xml1, xml2, &options).unwrap();
+            let options = ComparisonOptions { mode: Some(ComparisonMode::Verdict), ..options };
+            let result = service.compare_with_options(xml1, xml2, &options).unwrap();
+            assert_eq!(result.matched, full.matched, "{} vs {}", xml1, xml2);
+        }
+
+        // Options that need the diffs are refused rather than ignored, and shaping still applies
+        let options = ComparisonOptions { generate_patch: Some(true), ..verdict.clone() };
+        assert!(matches!(service.compare_with_options("<a/>", "<a/>", &options), Err(CompareError::ValidationError(_))));
+        let options = ComparisonOptions { output_format: Some(OutputFormat::Junit), ..verdict };
+        let result = service.compare_with_options("<a/>", "<b/>", &options).unwrap();
+        assert_eq!(result.output_format, Some(OutputFormat::Junit));
+    }
+
+    #[test]
+    fn test_document_stats() {
+        let service = XmlComparisonService::new();
+        let full = r#"<t:trades xmlns:t="urn:t" n="2"><trade id="1"><amount>10</amount></trade><trade id="2"><![CDATA[ x&y ]]></trade></t:trades>"#;
+        let cut = r#"<t:trades xmlns:t="urn:t" n="2"><trade id="1"><amount>10</amount></trade></t:trades>"#;
+        let stats = |elements, attributes, max_depth, text_bytes| DocumentStats { elements, attributes, max_depth, text_bytes };
+        let pair = |xml1, xml2| Some(DocumentPairStats { xml1, xml2 });
+
+        let result = service.compare_with_options(full, cut, &ComparisonOptions::default()).unwrap();
+        assert_eq!(result.document_stats, pair(stats(4, 3, 3, 5), stats(3, 2, 3, 2)));
+
+        // Identical documents skip the tree walk, and scoping does not narrow the counts
+        let result = service.compare_with_options(full, full, &ComparisonOptions::default()).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.document_stats, pair(stats(4, 3, 3, 5), stats(4, 3, 3, 5)));
+        let options = ComparisonOptions { compare_root_path1: Some("/trades/trade".to_string()), ..Default::default() };
+        let result = service.compare_with_options(full, full, &options).unwrap();
+        assert_eq!(result.document_stats, pair(stats(4, 3, 3, 5), stats(4, 3, 3, 5)));
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        let limits = ParseLimits { max_depth: 3, max_elements: 5, max_attributes: 2 };
+        let service = XmlComparisonService::new().with_parse_limits(limits);
+        let compare = |xml1: &str, xml2: &str| service.compare_with_options(xml1, xml2, &ComparisonOptions::default());
+        let rejected = |result: CompareResult<XmlComparisonResponse>, message: &str| match result {
+            Err(CompareError::ValidationError(error)) => assert!(error.contains(message), "{}", error),
+            other => panic!("expected a validation error, got {:?}", other.map(|r| r.matched)),
+        };
+
+        // At the limits, namespace declarations not counting as attributes
+        let fits = r#"<r xmlns="urn:r" a="1"><b c="2"><d/></b><e/><e/></r>"#;
+        assert!(compare(fits, fits).unwrap().matched);
+        rejected(compare(fits, "<r><b><d><f/></d></b></r>"), "limit of 3 levels");
+        rejected(compare("<r><e/><e/><e/><e/><e/></r>", fits), "limit of 5 elements");
+        rejected(compare(fits, r#"<r a="1" b="2" c="3"/>"#), "limit of 2 attributes");
+
+        // Documents that are not well-formed stop at the limit rather than at the syntax error
+        rejected(compare("<r><b><d><f><g></r>", fits), "limit of 3 levels");
+        assert!(matches!(compare("<r><b></r>", fits), Err(CompareError::XmlParseError(_))));
+        assert!(XmlComparisonService::new().compare_with_options("<r><b><d><f/></d></b></r>", fits, &ComparisonOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_generate_patch() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root v=\"1\"><a>x</a><b/><c old=\"1\">t</c><d>1</d><d>2</d></root>".to_string(),
+            xml2: "<root v=\"2\"><a>y</a><n>new &amp; shiny</n><c new=\"2\"/><d>2</d><d>1</d></root>".to_string(),
+            options: ComparisonOptions { generate_patch: Some(true), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        let expected = [
+            "<diff>",
+            "  <replace sel=\"/root/@v\">2</replace>",
+            "  <remove sel=\"/root/b\"/>",
+            "  <remove sel=\"/root/d[1]\"/>",
+            "  <remove sel=\"/root/d\"/>",
+            "  <add sel=\"/root/a\" pos=\"after\"><n>new &amp; shiny</n></add>",
+            "  <add sel=\"/root/c\" pos=\"after\"><d>2</d></add>",
+            "  <add sel=\"/root/d\" pos=\"after\"><d>1</d></add>",
+            "  <replace sel=\"/root/a/text()\">y</replace>",
+            "  <remove sel=\"/root/c/text()\"/>",
+            "  <remove sel=\"/root/c/@old\"/>",
+            "  <add sel=\"/root/c\" type=\"@new\">2</add>",
+            "</diff>",
+        ];
+        assert_eq!(result.patch.as_deref(), Some(expected.join("\n").as_str()));
+    }
+
+    #[test]
+    fn test_generate_patch_namespaces() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<r xmlns=\"urn:r\" xmlns:x=\"urn:x\"><x:item x:id=\"1\"/></r>".to_string(),
+            xml2: "<r xmlns=\"urn:r\" xmlns:x=\"urn:x\"><x:item x:id=\"2\"/><other/></r>".to_string(),
+            options: ComparisonOptions { generate_patch: Some(true), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        let expected = [
+            "<diff xmlns:ns1=\"urn:r\" xmlns:x=\"urn:x\">",
+            "  <add sel=\"/ns1:r/x:item\" pos=\"after\"><ns1:other/></add>",
+            "  <replace sel=\"/ns1:r/x:item/@x:id\">2</replace>",
+            "</diff>",
+        ];
+        assert_eq!(result.patch.as_deref(), Some(expected.join("\n").as_str()));
+
+        let identical = XmlComparisonRequest { xml2: request.xml1.clone(), ..request };
+        assert_eq!(compare(&service, &identical).unwrap().patch.as_deref(), Some("<diff/>"));
+    }
+
+    #[test]
+    fn test_unified_output_format() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<root><a>1</a></root>".to_string(),
+            xml2: "<root><a>2</a></root>".to_string(),
+            ..Default::default()
+        };
+        assert!(compare(&service, &request).unwrap().unified_diff.is_none());
+
+        request.options.output_format = Some(OutputFormat::Unified);
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(
+            result.unified_diff.as_deref(),
+            Some("--- xml1\n+++ xml2\n@@ -1,3 +1,3 @@\n <root>\n-  <a>1</a>\n+  <a>2</a>\n </root>\n")
+        );
+
+        request.xml2 = "<root>\n  <a>1</a>\n</root>".to_string();
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.unified_diff.as_deref(), Some("--- xml1\n+++ xml2\n"));
+    }
+
+    #[test]
+    fn test_value_transforms_normalize_before_comparing() {
+        let service = XmlComparisonService::new();
+        let transforms: Vec<ValueTransform> = serde_json::from_str(
+            r#"[
+                {"paths": ["//price"], "transform": "strip_currency"},
+                {"paths": ["//price"], "transform": "round", "decimals": 2},
+                {"paths": ["//trade/@ccy"], "transform": "lowercase"}
+            ]"#,
+        )
+        .unwrap();
+        let request = XmlComparisonRequest {
+            xml1: "<trades><trade ccy=\"USD\"><price>$100.004</price><qty>5</qty></trade></trades>".to_string(),
+            xml2: "<trades><trade ccy=\"usd\"><price>100.00</price><qty>5.0</qty></trade></trades>".to_string(),
+            options: ComparisonOptions { transforms: Some(transforms), ..Default::default() },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/trades/trade/qty");
+
+        let invalid = XmlComparisonRequest {
+            options: ComparisonOptions {
+                transforms: Some(vec![ValueTransform {
+                    paths: vec!["//price".to_string()],
+                    rule: TransformRule::RegexReplace { pattern: "[".to_string(), replacement: String::new() },
+                }]),
+                ..Default::default()
+            },
+            ..request
+        };
+        assert!(matches!(compare(&service, &invalid), Err(CompareError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_self_closing_strictness() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<root><a/><b x=\"1\"/><c/></root>".to_string(),
+            xml2: "<root><a></a><b x=\"1\"></b><c/></root>".to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+
+        request.options.strict_self_closing = Some(true);
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched);
+        assert_eq!(result.diffs.len(), 2);
+        assert!(result.diffs.iter().all(|diff| diff.diff_type == DiffType::SelfClosingDifferent && diff.severity == DiffSeverity::Info));
+        assert_eq!(result.diffs[0].path, "/root/a");
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("self-closing"));
+        assert_eq!(result.diffs[0].actual.as_deref(), Some("expanded"));
+    }
+
+    #[test]
+    fn test_compare_root_paths_scope_the_comparison() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<soap:Envelope xmlns:soap=\"urn:soap\"><soap:Header><id>1</id></soap:Header><soap:Body><Trade><price>5</price></Trade></soap:Body></soap:Envelope>".to_string(),
+            xml2: "<Response><meta>x</meta><Trade><price>6</price></Trade></Response>".to_string(),
+            options: ComparisonOptions {
+                compare_root_path1: Some("/Envelope/Body/*".to_string()),
+                compare_root_path2: Some("//Trade".to_string()),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.total_elements, 2);
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/Trade/price");
+
+        request.options.compare_root_path2 = Some("/Response/missing".to_string());
+        assert!(matches!(compare(&service, &request), Err(CompareError::ValidationError(_))));
+
+        request.xml2 = request.xml1.clone();
+        request.options.compare_root_path2 = request.options.compare_root_path1.clone();
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.total_elements, 2);
+    }
+
+    #[test]
+    fn test_soap_mode_compares_body_payloads() {
+        let service = XmlComparisonService::new();
+        let envelope = |header: &str, trade: &str| {
+            format!(
+                "<s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\">\n  <s:Header><msgId>{}</msgId></s:Header>\n  <s:Body>{}</s:Body>\n</s:Envelope>",
+                header, trade
+            )
+        };
+        let mut request = XmlComparisonRequest {
+            xml1: envelope("1", "<Trade xmlns=\"urn:trades\"><price>5</price></Trade>"),
+            xml2: "<env:Envelope xmlns:env=\"http://www.w3.org/2003/05/soap-envelope\"><env:Header><msgId>2</msgId></env:Header><env:Body><Trade xmlns=\"urn:trades\"><price>6</price></Trade></env:Body></env:Envelope>".to_string(),
+            options: ComparisonOptions { soap: Some(true), ..Default::default() },
+        };
+
+        // SOAP 1.1 against 1.2, with different prefixes: only the payloads count
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.total_elements, 2);
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/Trade/price");
+
+        request.options.soap_headers = Some(true);
+        let paths: Vec<_> = compare(&service, &request).unwrap().diffs.into_iter().map(|diff| diff.path).collect();
+        assert_eq!(paths, vec!["/Header/msgId", "/Trade/price"]);
+
+        // A bare payload compares against an envelope's, and an Envelope in another namespace is a payload
+        request.options.soap_headers = None;
+        request.xml2 = "<Trade xmlns=\"urn:trades\"><price>5</price></Trade>".to_string();
+        assert!(compare(&service, &request).unwrap().matched);
+        request.xml2 = "<Envelope xmlns=\"urn:other\"><Body><Trade xmlns=\"urn:trades\"><price>5</price></Trade></Body></Envelope>".to_string();
+        assert!(!compare(&service, &request).unwrap().matched);
+
+        request.xml2 = envelope("2", "<Trade xmlns=\"urn:trades\"><price>5</price></Trade>");
+        request.options.namespace_mode = Some(NamespaceMode::Qualified);
+        assert!(compare(&service, &request).unwrap().matched);
+    }
+
+    #[test]
+    fn test_child_count_differences() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<list><item>a</item><item>b</item><item>c</item><total>3</total></list>".to_string(),
+            xml2: "<list><item>a</item><total>3</total></list>".to_string(),
+            ..Default::default()
+        };
+        let default_diffs = compare(&service, &request).unwrap().diffs;
+        assert!(default_diffs.iter().all(|diff| diff.diff_type != DiffType::ChildCountDifferent));
+
+        request.options.child_counts = Some(ChildCountMode::Report);
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), default_diffs.len() + 1);
+        let count_diff = &result.diffs[0];
+        assert_eq!(count_diff.diff_type, DiffType::ChildCountDifferent);
+        assert_eq!(count_diff.path, "/list/item");
+        assert_eq!(count_diff.expected.as_deref(), Some("3"));
+        assert_eq!(count_diff.actual.as_deref(), Some("1"));
+
+        request.options.child_counts = Some(ChildCountMode::Collapse);
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].diff_type, DiffType::ChildCountDifferent);
+
+        request.options.ignore_paths = Some(vec!["/list/item".to_string()]);
+        assert!(compare(&service, &request).unwrap().matched);
+    }
+
+    #[test]
+    fn test_detect_duplicate_siblings() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<list><item>a</item><item>a</item><item>b</item><trade id=\"T1\"><p>1</p></trade><trade id=\"T1\"><p>2</p></trade></list>".to_string(),
+            ..Default::default()
+        };
+        request.xml2 = request.xml1.clone();
+        assert!(compare(&service, &request).unwrap().warnings.is_empty());
+
+        request.options.detect_duplicates = Some(true);
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.warnings.len(), 2);
+        assert_eq!(result.warnings[0].document, "xml1");
+        assert_eq!(result.warnings[0].path, "/list/item[1]");
+        assert_eq!(result.warnings[0].occurrences, 2);
+
+        request.options.match_keys = Some(HashMap::from([("/list/trade".to_string(), "@id".to_string())]));
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.warnings.len(), 4);
+        assert!(result.warnings.iter().any(|warning| warning.path == "/list/trade[1]" && warning.message.contains("@id='T1'")));
+    }
+
+    #[test]
+    fn test_normalize_prefixes() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<ns1:Trade xmlns:ns1=\"urn:t\" ns1:v=\"1\"><ns1:id>1</ns1:id></ns1:Trade>".to_string(),
+            xml2: "<t:Trade xmlns:t=\"urn:t\" t:v=\"1\"><t:id>1</t:id></t:Trade>".to_string(),
+            options: ComparisonOptions {
+                namespace_mode: Some(NamespaceMode::Qualified),
+                ..Default::default()
+            },
+        };
+        assert!(!compare(&service, &request).unwrap().matched);
+
+        request.options.normalize_prefixes = Some(true);
+        assert!(compare(&service, &request).unwrap().matched);
+
+        request.xml2 = "<Trade xmlns=\"urn:t\" xmlns:t=\"urn:t\" t:v=\"1\"><id>2</id></Trade>".to_string();
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/ns1:Trade/ns1:id");
+
+        request.options.namespace_mode = Some(NamespaceMode::Strict);
+        let result = compare(&service, &request).unwrap();
+        let prefix_diffs: Vec<_> = result.diffs.iter().filter(|diff| diff.diff_type == DiffType::NamespaceDifferent).collect();
+        assert_eq!(prefix_diffs.len(), 3);
+        assert!(prefix_diffs.iter().all(|diff| diff.severity == DiffSeverity::Info));
+    }
+
+    #[test]
+    fn test_wildcard_ignore_properties() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: "<root xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"A\" data-id=\"1\" created-timestamp=\"t1\" v=\"1\"><item x=\"1\"/></root>".to_string(),
+            xml2: "<root xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" xsi:type=\"B\" data-id=\"2\" created-timestamp=\"t2\" v=\"2\"><item x=\"2\"/></root>".to_string(),
+            options: ComparisonOptions {
+                ignore_properties: Some(vec!["xsi:*".to_string(), "data-*".to_string(), "*-timestamp".to_string(), "/root/item@?".to_string()]),
+                ..Default::default()
+            },
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].expected.as_deref(), Some("v=1"));
+    }
+
+    #[test]
+    fn test_xsi_type_compared_by_namespace() {
+        let service = XmlComparisonService::new();
+        let request = XmlComparisonRequest {
+            xml1: r#"<root xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:a="urn:types"><v xsi:type="a:Fixed"/><w xsi:type="a:Fixed"/></root>"#.to_string(),
+            xml2: r#"<root xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xmlns:b="urn:types" xmlns:c="urn:other"><v xsi:type="b:Fixed"/><w xsi:type="c:Fixed"/></root>"#.to_string(),
+            ..Default::default()
+        };
+
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.diffs.len(), 1);
+        assert_eq!(result.diffs[0].path, "/root/w");
+        assert!(result.diffs[0].actual.as_deref().unwrap().contains("{urn:other}Fixed"));
+    }
+
+    #[test]
+    fn test_xsi_nil_modes() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: r#"<root xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><a xsi:nil="true"/><b>1</b></root>"#.to_string(),
+            xml2: "<root><a/><b>1</b></root>".to_string(),
+            ..Default::default()
+        };
+        assert!(!compare(&service, &request).unwrap().matched);
+
+        request.options.xsi_nil = Some(NilMode::Empty);
+        assert!(compare(&service, &request).unwrap().matched);
+
+        request.xml2 = "<root><b>1</b></root>".to_string();
+        assert!(!compare(&service, &request).unwrap().matched);
+        request.options.xsi_nil = Some(NilMode::Absent);
+        assert!(compare(&service, &request).unwrap().matched);
+    }
+
+    #[test]
+    fn test_response_shaping() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<root><a>1</a><b>1</b></root>".to_string(),
+            xml2: "<root><a>2</a><b>1</b></root>".to_string(),
+            ..Default::default()
+        };
+        request.options.diff_fields = Some(vec![DiffField::DiffType, DiffField::Path]);
+        let json = serde_json::to_value(compare(&service, &request).unwrap()).unwrap();
+        assert_eq!(json["diffs"], serde_json::json!([{ "path": "/root/a", "diff_type": "ContentDifferent" }]));
+
+        request.options.include_diffs = Some(false);
+        let result = compare(&service, &request).unwrap();
+        assert!(!result.matched && result.diffs.is_empty());
+        assert_eq!(result.matched_elements, 2);
+
+        request.options = ComparisonOptions {
+            exclude_matched_details: Some(true),
+            output_format: Some(OutputFormat::Unified),
+            ..Default::default()
+        };
+        assert!(compare(&service, &request).unwrap().unified_diff.is_some());
+        request.xml2 = request.xml1.clone();
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched && result.unified_diff.is_none());
+    }
+
+    #[test]
+    fn test_unmatched_element_snapshot() {
+        let service = XmlComparisonService::new();
+        let result = service
+            .compare_with_options(
+                "<a/>",
+                r#"<a><b z="2" id="&quot;7&quot;">x<c/><d/></b></a>"#,
+                &ComparisonOptions::default(),
+            )
+            .unwrap();
+        let extra = &result.diffs[0];
+        assert_eq!(extra.actual.as_deref(), Some(r#"<b id="&quot;7&quot;" z="2">"#));
+        assert_eq!(
+            extra.node,
+            Some(XmlNodeSnapshot {
+                name: "b".to_string(),
+                namespace: None,
+                attributes: BTreeMap::from([("id".to_string(), "\"7\"".to_string()), ("z".to_string(), "2".to_string())]),
+                text: Some("x".to_string()),
+                children: vec!["c".to_string(), "d".to_string()],
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(extra).unwrap()["node"],
+            serde_json::json!({ "name": "b", "attributes": { "id": "\"7\"", "z": "2" }, "text": "x", "children": ["c", "d"] })
+        );
+        // Descendants get their own diffs and snapshots; other diffs carry none
+        assert_eq!(result.diffs[1].node.as_ref().map(|node| node.name.as_str()), Some("c"));
+        let changed = service.compare_with_options("<a>1</a>", "<a>2</a>", &ComparisonOptions::default()).unwrap();
+        assert!(changed.diffs[0].node.is_none());
+    }
+
+    #[test]
+    fn test_large_documents_compare_like_a_sequential_walk() {
+        let document = |changed: bool| {
+            let mut xml = String::from("<book>");
+            for section in 0..12 {
+                xml.push_str(&format!("<section id=\"{}\">", section));
+                for row in 0..1000 {
+                    let value = if changed && row % 250 == 0 { section * row + 1 } else { section * row };
+                    xml.push_str(&format!("<row n=\"{}\">{}</row>", row, value));
+                }
+                // The note moves from the fourth section to the sixth
+                if section == if changed { 5 } else { 3 } {
+                    xml.push_str("<note>moved</note>");
+                }
+                xml.push_str("</section>");
+            }
+            xml.push_str("</book>");
+            xml
+        };
+        let (xml1, xml2) = (document(false), document(true));
+        let service = XmlComparisonService::new();
+        let mut options = ComparisonOptions { element_stats: Some(true), ..Default::default() };
+        let parallel = service.compare_with_options(&xml1, &xml2, &options).unwrap();
+        // A limit the comparison never reaches keeps it on one thread
+        options.max_diffs = Some(usize::MAX);
+        let sequential = service.compare_with_options(&xml1, &xml2, &options).unwrap();
+
+        let diffs = |result: &XmlComparisonResponse| {
+            result.diffs.iter().map(|diff| format!("{} {:?} {:?}", diff.path, diff.diff_type, diff.actual)).collect::<Vec<_>>()
+        };
+        assert_eq!(diffs(&parallel), diffs(&sequential));
+        assert_eq!(parallel.matched_elements, sequential.matched_elements);
+        assert_eq!(parallel.element_stats, sequential.element_stats);
+        assert!(parallel.diffs.iter().any(|diff| diff.diff_type == DiffType::ElementMoved));
+        assert_eq!(parallel.diffs.len(), 12 * 4 + 1);
+    }
+
+    #[test]
+    fn test_parse_borrows_from_document() {
+        let service = XmlComparisonService::new();
+        let xml = r#"<a:t xmlns:a="urn:a" id="1" note="x &amp; y"><p>plain</p><q>one<![CDATA[two]]></q><r>&lt;</r></a:t>"#;
+        let (roots, _, _) = service.parse_documents(&ComparisonOptions::default(), xml, "<t/>").unwrap();
+        let root = &roots[0];
+        let borrowed = |text: Option<&Cow<str>>| matches!(text, Some(Cow::Borrowed(_)));
+        assert!(borrowed(root.attributes.get("id")));
+        assert!(borrowed(root.prefix.as_ref()));
+        assert!(borrowed(root.children[0].content.as_ref()));
+        // Entities and joined sections need copies of their own
+        assert_eq!(root.attributes.get("note").map(|note| &**note), Some("x & y"));
+        assert!(!borrowed(root.attributes.get("note")));
+        assert_eq!(root.children[1].content.as_deref(), Some("onetwo"));
+        assert!(!borrowed(root.children[2].content.as_ref()));
+    }
+
+    #[test]
+    fn test_repeated_documents_parsed_once() {
+        let service = XmlComparisonService::new().with_parse_cache(1024 * 1024);
+        let uncached = XmlComparisonService::new();
+        let golden = "<trades><trade id=\"1\"><amount>10</amount></trade><trade id=\"2\" xsi:nil=\"true\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"/></trades>";
+        let candidates = ["<trades><trade id=\"1\"><amount>11</amount></trade></trades>", "<trades><trade id=\"1\"/></trades>"];
+        let scoped = ComparisonOptions {
+            compare_root_path1: Some("/trades/trade".to_string()),
+            compare_root_path2: Some("/trades/trade".to_string()),
+            xsi_nil: Some(NilMode::Empty),
+            ..Default::default()
+        };
+
+        for options in [ComparisonOptions::default(), scoped] {
+            for candidate in candidates {
+                let result = service.compare_with_options(golden, candidate, &options).unwrap();
+                let expected = uncached.compare_with_options(golden, candidate, &options).unwrap();
+                assert_eq!(serde_json::to_value(&result).unwrap(), serde_json::to_value(&expected).unwrap());
+            }
+        }
+        assert_eq!(service.parsed.len(), 3);
+
+        // Trees shaped by the other document's prefixes are not kept
+        let options = ComparisonOptions { normalize_prefixes: Some(true), ..Default::default() };
+        service.compare_with_options("<p:a xmlns:p=\"urn:a\"/>", "<q:a xmlns:q=\"urn:a\"/>", &options).unwrap();
+        assert_eq!(service.parsed.len(), 3);
+    }
+
+    #[test]
+    fn test_names_shared_and_paths_built_in_place() {
+        let service = XmlComparisonService::new();
+        let options = ComparisonOptions::default();
+        let (roots1, roots2, _) = service
+            .parse_documents(&options, "<a xmlns=\"urn:x\"><b/><b/><c/></a>", "<a xmlns=\"urn:x\"><b/><c/><c/></a>")
+            .unwrap();
+        // Both documents hold the same allocation for each name and namespace
+        assert!(Arc::ptr_eq(&roots1[0].name, &roots2[0].name));
+        assert!(Arc::ptr_eq(&roots1[0].children[0].name, &roots1[0].children[1].name));
+        assert!(Arc::ptr_eq(roots1[0].namespace.as_ref().unwrap(), roots2[0].namespace.as_ref().unwrap()));
+
+        // A name repeated in either document is indexed in both
+        let result = service
+            .compare_with_options("<a><b/><b/><c>1</c><d><e>1</e></d></a>", "<a><b/><c>2</c><c/><d><e>2</e></d></a>", &options)
+            .unwrap();
+        let paths: Vec<_> = result.diffs.iter().map(|diff| diff.path.as_str()).collect();
+        assert_eq!(paths, ["/a/b[2]", "/a/c[1]", "/a/d/e", "/a/c[2]"]);
+    }
+
+    #[test]
+    fn test_element_stats() {
+        let service = XmlComparisonService::new();
+        let stats = |group: &str, counts: [usize; 4]| ElementStats {
+            group: group.to_string(),
+            matched: counts[0],
+            differed: counts[1],
+            missing: counts[2],
+            extra: counts[3],
+        };
+        let mut request = XmlComparisonRequest {
+            xml1: r#"<trades><trade id="1"><price>10</price></trade><trade id="2"><price>20</price></trade><trade id="3"><price>30</price><stamp>1</stamp></trade></trades>"#.to_string(),
+            xml2: r#"<trades><trade id="1"><price>10</price></trade><trade id="2"><price>21</price></trade><trade id="3"><price>30</price><stamp>2</stamp></trade><trade id="4"><price>40</price></trade></trades>"#.to_string(),
+            ..Default::default()
+        };
+        request.options.element_stats = Some(true);
+        request.options.ignore_paths = Some(vec!["//stamp".to_string()]);
+        let result = compare(&service, &request).unwrap();
+        // A trade differs when anything below it does; ignored elements are not counted
+        assert_eq!(
+            result.element_stats,
+            [stats("price", [2, 1, 0, 1]), stats("trade", [2, 1, 0, 1]), stats("trades", [0, 1, 0, 0])]
+        );
+
+        request.options.element_stats_paths = Some(vec!["/trades/trade".to_string(), "//trade[@id='2']/price".to_string()]);
+        let result = compare(&service, &request).unwrap();
+        assert_eq!(result.element_stats, [stats("/trades/trade", [2, 1, 0, 1]), stats("//trade[@id='2']/price", [0, 1, 0, 0])]);
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["element_stats"][0], serde_json::json!({ "group": "/trades/trade", "matched": 2, "differed": 1, "missing": 0, "extra": 1 }));
+
+        // Identical documents are still walked to count their elements
+        request.xml2 = request.xml1.clone();
+        request.options.element_stats_paths = None;
+        let result = compare(&service, &request).unwrap();
+        assert!(result.matched);
+        assert_eq!(result.element_stats[0], stats("price", [3, 0, 0, 0]));
+        request.options.element_stats = None;
+        assert!(compare(&service, &request).unwrap().element_stats.is_empty());
+    }
+
+    #[test]
+    fn test_classifications_tag_diffs() {
+        let service = XmlComparisonService::new();
+        let mut request = XmlComparisonRequest {
+            xml1: "<trade><price>10</price><note/><book>FX</book></trade>".to_string(),
+            xml2: "<trade><price>11</price><note></note><book>EQ</book></trade>".to_string(),
+            ..Default::default()
+        };
+        request.options.strict_self_closing = Some(true);
+        request.options.classifications = Some(vec![
+            ClassificationRule { tag: "pricing".to_string(), path: Some("/price$".to_string()), content: None, diff_types: None },
+            ClassificationRule {
+                tag: "cosmetic".to_string(),
+                path: None,
+                content: None,
+                diff_types: Some(vec![DiffType::SelfClosingDifferent]),
+            },
+        ]);
+        request.options.include_diffs = Some(false);
+        let result = compare(&service, &request).unwrap();
+        // Counts survive the response shaping that drops the diffs
+        assert!(result.diffs.is_empty());
+        assert_eq!(result.tag_counts, BTreeMap::from([("cosmetic".to_string(), 1), ("pricing".to_string(), 1)]));
+
+        request.options.include_diffs = None;
+        let json = serde_json::to_value(compare(&service, &request).unwrap()).unwrap();
+        let tags: Vec<_> = json["diffs"].as_array().unwrap().iter().map(|diff| diff["tags"].clone()).collect();
+        // Untagged diffs have no `tags` field
+        assert_eq!(tags, [serde_json::json!(["pricing"]), serde_json::json!(["cosmetic"]), serde_json::Value::Null]);
+        assert_eq!(json["tag_counts"], serde_json::json!({ "cosmetic": 1, "pricing": 1 }));
+
+        request.options.classifications = Some(vec![ClassificationRule { tag: "x".to_string(), path: None, content: Some("[".to_string()), diff_types: None }]);
+        assert!(matches!(compare(&service, &request), Err(CompareError::ValidationError(_))));
+    }
+}
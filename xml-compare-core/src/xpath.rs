@@ -0,0 +1,301 @@
+use crate::{CompareError, CompareResult};
+use crate::XmlElement;
+
+// Position of an element in the document: the element itself, its 1-based position among
+// same-name siblings and the chain of ancestors above it
+pub struct Lineage<'a> {
+    pub element: &'a XmlElement<'a>,
+    pub position: usize,
+    pub parent: Option<&'a Lineage<'a>>,
+}
+
+impl<'a> Lineage<'a> {
+    pub fn ancestors(&'a self) -> impl Iterator<Item = &'a Lineage<'a>> {
+        std::iter::successors(Some(self), |node| node.parent)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Predicate {
+    Position(usize),
+    HasAttribute(String),
+    AttributeEquals(String, String),
+    HasChild(String),
+    ChildEquals(String, String),
+    TextEquals(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Step {
+    descendant: bool,
+    name: Option<String>,
+    predicates: Vec<Predicate>,
+}
+
+/// Compiled subset of XPath used by ignore rules: absolute location paths with `/` and `//`
+/// axes, name and `*` tests, predicates (`[2]`, `[@a]`, `[@a='v']`, `[child='v']`,
+/// `[text()='v']`) and an optional trailing attribute step (`/@id`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct XPathPattern {
+    steps: Vec<Step>,
+    attribute: Option<String>,
+}
+
+impl XPathPattern {
+    // Patterns without XPath syntax are matched as globs. Brackets are predicates only when they
+    // hold a position or a comparison; `[abc]` or `[0-9]` is a glob character class.
+    pub fn is_xpath(pattern: &str) -> bool {
+        pattern.contains("//")
+            || pattern.contains('@')
+            || pattern.split('[').skip(1).any(|rest| {
+                let body = rest.split_once(']').map_or(rest, |(body, _)| body);
+                body.parse::<usize>().is_ok() || body.contains('=') || body.contains('(')
+            })
+    }
+
+    pub fn parse(pattern: &str) -> CompareResult<Self> {
+        let invalid = |reason: &str| CompareError::ValidationError(format!("Invalid XPath '{}': {}", pattern, reason));
+        if !pattern.starts_with('/') {
+            return Err(invalid("only absolute paths are supported"));
+        }
+
+        let mut steps = Vec::new();
+        let mut attribute = None;
+        let mut rest = pattern;
+        while !rest.is_empty() {
+            let descendant = rest.starts_with("//");
+            rest = rest.trim_start_matches('/');
+            if rest.is_empty() {
+                return Err(invalid("missing step after '/'"));
+            }
+            if attribute.is_some() {
+                return Err(invalid("attribute step must be last"));
+            }
+
+            let (step, remaining) = split_step(rest).ok_or_else(|| invalid("unbalanced brackets"))?;
+            rest = remaining;
+
+            if let Some(name) = step.strip_prefix('@') {
+                if descendant || name.is_empty() {
+                    return Err(invalid("attribute step must follow an element step"));
+                }
+                attribute = Some(name.to_string());
+                continue;
+            }
+
+            let (name, predicates) = match step.find('[') {
+                Some(index) => (&step[..index], &step[index..]),
+                None => (step, ""),
+            };
+            if name.is_empty() {
+                return Err(invalid("missing element name"));
+            }
+            steps.push(Step {
+                descendant,
+                name: (name != "*").then(|| name.to_string()),
+                predicates: parse_predicates(predicates).ok_or_else(|| invalid("unsupported predicate"))?,
+            });
+        }
+
+        if steps.is_empty() {
+            return Err(invalid("no element steps"));
+        }
+        Ok(Self { steps, attribute })
+    }
+
+    pub fn attribute(&self) -> Option<&str> {
+        self.attribute.as_deref()
+    }
+
+    pub fn matches(&self, node: &Lineage) -> bool {
+        matches_steps(&self.steps, Some(node))
+    }
+}
+
+fn matches_steps(steps: &[Step], node: Option<&Lineage>) -> bool {
+    let (Some((step, rest)), Some(node)) = (steps.split_last(), node) else {
+        return false;
+    };
+    if !step_matches(step, node) {
+        return false;
+    }
+    if rest.is_empty() {
+        return step.descendant || node.parent.is_none();
+    }
+    match node.parent {
+        Some(parent) if step.descendant => parent.ancestors().any(|ancestor| matches_steps(rest, Some(ancestor))),
+        parent => matches_steps(rest, parent),
+    }
+}
+
+fn step_matches(step: &Step, node: &Lineage) -> bool {
+    let element = node.element;
+    if let Some(name) = &step.name
+        && !name_matches(&element.name, name)
+    {
+        return false;
+    }
+
+    step.predicates.iter().all(|predicate| match predicate {
+        Predicate::Position(position) => node.position == *position,
+        Predicate::HasAttribute(name) => attribute_value(element, name).is_some(),
+        Predicate::AttributeEquals(name, value) => attribute_value(element, name) == Some(value.as_str()),
+        Predicate::HasChild(name) => element.children.iter().any(|child| name_matches(&child.name, name)),
+        Predicate::ChildEquals(name, value) => element
+            .children
+            .iter()
+            .any(|child| name_matches(&child.name, name) && child.content.as_deref() == Some(value.as_str())),
+        Predicate::TextEquals(value) => element.content.as_deref() == Some(value.as_str()),
+    })
+}
+
+// Element names are stored as local names unless namespace_mode is `qualified`,
+// so a prefixed name test also matches on its local part
+fn name_matches(element_name: &str, test: &str) -> bool {
+    element_name == test || test.rsplit_once(':').is_some_and(|(_, local)| local == element_name)
+}
+
+fn attribute_value<'e>(element: &'e XmlElement, name: &str) -> Option<&'e str> {
+    element
+        .attributes
+        .iter()
+        .find(|(key, _)| {
+            let key: &str = key;
+            let local = key.rsplit_once('}').map_or(key, |(_, local)| local);
+            let prefixed = element.attribute_prefix(key).map(|prefix| format!("{}:{}", prefix, local));
+            key == name || local == name || prefixed.as_deref() == Some(name)
+        })
+        .map(|(_, value)| &**value)
+}
+
+// Splits off the next step, keeping `/` inside predicates (e.g. `[a='x/y']`) intact
+fn split_step(input: &str) -> Option<(&str, &str)> {
+    let mut depth = 0usize;
+    let mut quote = None;
+    for (index, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth = depth.checked_sub(1)?,
+            (None, '/') if depth == 0 => return Some((&input[..index], &input[index..])),
+            _ => {}
+        }
+    }
+    (depth == 0 && quote.is_none()).then_some((input, ""))
+}
+
+fn parse_predicates(mut input: &str) -> Option<Vec<Predicate>> {
+    let mut predicates = Vec::new();
+    while !input.is_empty() {
+        let body = input.strip_prefix('[')?;
+        let end = body.find(']')?;
+        predicates.push(parse_predicate(body[..end].trim())?);
+        input = &body[end + 1..];
+    }
+    Some(predicates)
+}
+
+fn parse_predicate(predicate: &str) -> Option<Predicate> {
+    if let Ok(position) = predicate.parse::<usize>() {
+        return (position > 0).then_some(Predicate::Position(position));
+    }
+
+    match predicate.split_once('=') {
+        Some((left, right)) => {
+            let (left, value) = (left.trim(), unquote(right.trim())?);
+            if let Some(name) = left.strip_prefix('@') {
+                Some(Predicate::AttributeEquals(name.to_string(), value))
+            } else if left == "text()" || left == "." {
+                Some(Predicate::TextEquals(value))
+            } else {
+                is_name(left).then(|| Predicate::ChildEquals(left.to_string(), value))
+            }
+        }
+        None => match predicate.strip_prefix('@') {
+            Some(name) => is_name(name).then(|| Predicate::HasAttribute(name.to_string())),
+            None => is_name(predicate).then(|| Predicate::HasChild(predicate.to_string())),
+        },
+    }
+}
+
+fn unquote(value: &str) -> Option<String> {
+    let quote = value.chars().next().filter(|c| *c == '\'' || *c == '"')?;
+    value
+        .strip_prefix(quote)
+        .and_then(|v| v.strip_suffix(quote))
+        .map(str::to_string)
+}
+
+fn is_name(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    fn element(name: &str, attributes: &[(&'static str, &'static str)], content: Option<&'static str>) -> XmlElement<'static> {
+        XmlElement {
+            name: name.into(),
+            namespace: None,
+            prefix: None,
+            attributes: attributes.iter().map(|(k, v)| (Cow::Borrowed(*k), Cow::Borrowed(*v))).collect(),
+            attribute_prefixes: HashMap::new(),
+            content: content.map(Cow::Borrowed),
+            cdata: false,
+            self_closing: false,
+            children: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_is_xpath() {
+        assert!(XPathPattern::is_xpath("//timestamp"));
+        assert!(XPathPattern::is_xpath("/root/item[@id='1']"));
+        assert!(XPathPattern::is_xpath("/root/item/@id"));
+        assert!(XPathPattern::is_xpath("/root/item[2]"));
+        assert!(XPathPattern::is_xpath("/root/item[code='x']"));
+        assert!(!XPathPattern::is_xpath("/root/item/*"));
+        assert!(!XPathPattern::is_xpath("/root/**/item[0-9]"));
+        assert!(!XPathPattern::is_xpath("/root/"));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_patterns() {
+        assert!(XPathPattern::parse("item[@id='1']").is_err());
+        assert!(XPathPattern::parse("/root/item[@id='1'").is_err());
+        assert!(XPathPattern::parse("/root/@id/child").is_err());
+        assert!(XPathPattern::parse("//@id").is_err());
+        assert!(XPathPattern::parse("/root/item[contains(., 'x')]").is_err());
+    }
+
+    #[test]
+    fn test_descendant_and_predicates() {
+        let root = element("root", &[], None);
+        let mut item = element("item", &[("name", "x")], None);
+        item.children.push(element("code", &[], Some("A/1")));
+        let stamp = element("timestamp", &[], Some("now"));
+
+        let root_node = Lineage { element: &root, position: 1, parent: None };
+        let item_node = Lineage { element: &item, position: 2, parent: Some(&root_node) };
+        let stamp_node = Lineage { element: &stamp, position: 1, parent: Some(&item_node) };
+
+        assert!(XPathPattern::parse("//timestamp").unwrap().matches(&stamp_node));
+        assert!(XPathPattern::parse("/root//timestamp").unwrap().matches(&stamp_node));
+        assert!(XPathPattern::parse("/root/*/timestamp").unwrap().matches(&stamp_node));
+        assert!(!XPathPattern::parse("/timestamp").unwrap().matches(&stamp_node));
+        assert!(XPathPattern::parse("//item[@name='x']").unwrap().matches(&item_node));
+        assert!(!XPathPattern::parse("//item[@name='y']").unwrap().matches(&item_node));
+        assert!(XPathPattern::parse("//item[2]").unwrap().matches(&item_node));
+        assert!(XPathPattern::parse("//item[code='A/1']/timestamp").unwrap().matches(&stamp_node));
+        assert!(XPathPattern::parse("//timestamp[text()='now']").unwrap().matches(&stamp_node));
+
+        let attribute = XPathPattern::parse("//item[@name]/@id").unwrap();
+        assert_eq!(attribute.attribute(), Some("id"));
+        assert!(attribute.matches(&item_node));
+    }
+}